@@ -23,7 +23,7 @@ use hyper::{Body, Client, Request};
 use hyper_rustls;
 use hyper_timeout::TimeoutConnector;
 use serde::{Deserialize, Serialize};
-use serde_json;
+use serde_json::{self, json, Value};
 use std::time::Duration;
 use tokio::runtime::Builder;
 
@@ -116,6 +116,52 @@ where
 	Ok(())
 }
 
+/// Helper function to issue a JSON-RPC 2.0 call against one of the v2 APIs
+/// (`/v2/owner` or `/v2/foreign`) and unwrap its result. An error returned by
+/// the called method is reported as a response error.
+pub fn rpc<IN, OUT>(
+	url: &str,
+	api_secret: Option<String>,
+	method: &str,
+	params: &IN,
+) -> Result<OUT, Error>
+where
+	IN: Serialize,
+	for<'de> OUT: Deserialize<'de>,
+{
+	let req = json!({
+		"jsonrpc": "2.0",
+		"method": method,
+		"params": params,
+		"id": 1
+	});
+	let res: Value = post(url, api_secret, &req)?;
+	parse_rpc_response(method, res)
+}
+
+fn parse_rpc_response<OUT>(method: &str, res: Value) -> Result<OUT, Error>
+where
+	for<'de> OUT: Deserialize<'de>,
+{
+	if let Some(err) = res.get("error") {
+		return Err(ErrorKind::ResponseError(format!("{} failed: {}", method, err)).into());
+	}
+	let result = res.get("result").ok_or_else(|| {
+		ErrorKind::ResponseError(format!("{} returned no result: {}", method, res))
+	})?;
+	if let Some(err) = result.get("Err") {
+		return Err(ErrorKind::ResponseError(format!("{} failed: {}", method, err)).into());
+	}
+	let ok = result.get("Ok").cloned().unwrap_or(Value::Null);
+	serde_json::from_value(ok).map_err(|e| {
+		e.context(ErrorKind::ResponseError(format!(
+			"Cannot parse {} result",
+			method
+		)))
+		.into()
+	})
+}
+
 fn build_request(
 	url: &str,
 	method: &str,
@@ -215,3 +261,24 @@ pub fn send_request(req: Request<Body>) -> Result<String, Error> {
 		.map_err(|e| ErrorKind::RequestError(format!("{}", e)))?;
 	rt.block_on(send_request_async(req))
 }
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	#[test]
+	fn test_parse_rpc_response() {
+		let res = json!({"id": 1, "jsonrpc": "2.0", "result": {"Ok": [1, 2]}});
+		let ok: Vec<u64> = parse_rpc_response("get", res).unwrap();
+		assert_eq!(ok, vec![1, 2]);
+
+		let res = json!({"id": 1, "jsonrpc": "2.0", "result": {"Ok": null}});
+		let _: () = parse_rpc_response("ban_peer", res).unwrap();
+
+		let res = json!({"id": 1, "jsonrpc": "2.0", "result": {"Err": {"Argument": "bad"}}});
+		assert!(parse_rpc_response::<()>("ban_peer", res).is_err());
+
+		let res = json!({"id": 1, "jsonrpc": "2.0", "error": {"code": -32601, "message": "Method not found"}});
+		assert!(parse_rpc_response::<()>("unknown", res).is_err());
+	}
+}
@@ -20,13 +20,14 @@ use crate::core::core::transaction::Transaction;
 use crate::handlers::blocks_api::{BlockHandler, HeaderHandler};
 use crate::handlers::chain_api::{ChainHandler, KernelHandler, OutputHandler};
 use crate::handlers::pool_api::PoolHandler;
+use crate::handlers::proofs_api::MerkleProofsHandler;
 use crate::handlers::transactions_api::TxHashSetHandler;
 use crate::handlers::version_api::VersionHandler;
 use crate::pool::{self, PoolEntry};
 use crate::rest::*;
 use crate::types::{
-	BlockHeaderPrintable, BlockPrintable, LocatedTxKernel, OutputListing, OutputPrintable, Tip,
-	Version,
+	BlockHeaderPrintable, BlockPrintable, KernelMerkleProofPrintable, LocatedTxKernel,
+	MerkleProofs, OutputListing, OutputMerkleProofPrintable, OutputPrintable, Tip, Version,
 };
 use crate::util::RwLock;
 use std::sync::Weak;
@@ -42,6 +43,7 @@ pub struct Foreign {
 	pub chain: Weak<Chain>,
 	pub tx_pool: Weak<RwLock<pool::TransactionPool>>,
 	pub sync_state: Weak<SyncState>,
+	pub merkle_proofs: Option<Weak<dyn MerkleProofs>>,
 }
 
 impl Foreign {
@@ -53,6 +55,7 @@ impl Foreign {
 	/// * `tx_pool` - A non-owning reference of the transaction pool.
 	/// * `peers` - A non-owning reference of the peers.
 	/// * `sync_state` - A non-owning reference of the `sync_state`.
+	/// * `merkle_proofs` - A non-owning reference of the merkle proofs fetched from peers, when running header only.
 	///
 	/// # Returns
 	/// * An instance of the Node holding references to the current chain, transaction pool, peers and sync_state.
@@ -62,11 +65,13 @@ impl Foreign {
 		chain: Weak<Chain>,
 		tx_pool: Weak<RwLock<pool::TransactionPool>>,
 		sync_state: Weak<SyncState>,
+		merkle_proofs: Option<Weak<dyn MerkleProofs>>,
 	) -> Self {
 		Foreign {
 			chain,
 			tx_pool,
 			sync_state,
+			merkle_proofs,
 		}
 	}
 
@@ -183,6 +188,56 @@ impl Foreign {
 		kernel_handler.get_kernel_v2(excess, min_height, max_height)
 	}
 
+	/// Returns a merkle proof of the kernel with the given excess against the
+	/// kernel root of the block header including it, letting a counterparty
+	/// holding the header chain check the kernel is on chain. The `min_height`
+	/// and `max_height` parameters bound the lookup as for `get_kernel`.
+	///
+	/// # Arguments
+	/// * `excess` - kernel excess to look for.
+	/// * `min_height` - minimum height to stop the lookup.
+	/// * `max_height` - maximum height to start the lookup.
+	///
+	/// # Returns
+	/// * Result Containing:
+	/// * A [`KernelMerkleProofPrintable`](types/struct.KernelMerkleProofPrintable.html)
+	/// * or [`Error`](struct.Error.html) if an error is encountered.
+	///
+
+	pub fn get_kernel_merkle_proof(
+		&self,
+		excess: String,
+		min_height: Option<u64>,
+		max_height: Option<u64>,
+	) -> Result<KernelMerkleProofPrintable, Error> {
+		let kernel_handler = KernelHandler {
+			chain: self.chain.clone(),
+		};
+		kernel_handler.get_kernel_merkle_proof(excess, min_height, max_height)
+	}
+
+	/// Verifies a kernel merkle proof against the block header it was built
+	/// for. The header has to be on our chain for the proof to hold.
+	///
+	/// # Arguments
+	/// * `proof` - the [`KernelMerkleProofPrintable`](types/struct.KernelMerkleProofPrintable.html) to verify.
+	///
+	/// # Returns
+	/// * Result Containing:
+	/// * `true` if the proof is valid, `false` otherwise
+	/// * or [`Error`](struct.Error.html) if an error is encountered.
+	///
+
+	pub fn verify_kernel_merkle_proof(
+		&self,
+		proof: KernelMerkleProofPrintable,
+	) -> Result<bool, Error> {
+		let kernel_handler = KernelHandler {
+			chain: self.chain.clone(),
+		};
+		kernel_handler.verify_kernel_merkle_proof(proof)
+	}
+
 	/// Retrieves details about specifics outputs. Supports retrieval of multiple outputs in a single request.
 	/// Support retrieval by both commitment string and block height.
 	///
@@ -269,6 +324,54 @@ impl Foreign {
 		txhashset_handler.block_height_range_to_pmmr_indices(start_block_height, end_block_height)
 	}
 
+	/// Fetches a merkle proof of an output from our peers, only available
+	/// when the node runs header only. The proof is checked against our header
+	/// chain before being returned. It proves the output was included in the
+	/// output MMR at the returned block, not that it is still unspent: spent
+	/// outputs stay in the MMR and no bitmap proof is provided.
+	///
+	/// # Arguments
+	/// * `commit` - output commitment.
+	///
+	/// # Returns
+	/// * Result Containing:
+	/// * An [`OutputMerkleProofPrintable`](types/struct.OutputMerkleProofPrintable.html)
+	/// * or [`Error`](struct.Error.html) if an error is encountered.
+	///
+
+	pub fn fetch_output_merkle_proof(
+		&self,
+		commit: String,
+	) -> Result<OutputMerkleProofPrintable, Error> {
+		let proofs_handler = MerkleProofsHandler {
+			proofs: self.merkle_proofs.clone(),
+		};
+		proofs_handler.fetch_output_merkle_proof(commit)
+	}
+
+	/// Fetches a merkle proof of a kernel from our peers, only available when
+	/// the node runs header only. The proof is checked against our header
+	/// chain before being returned.
+	///
+	/// # Arguments
+	/// * `excess` - kernel excess.
+	///
+	/// # Returns
+	/// * Result Containing:
+	/// * A [`KernelMerkleProofPrintable`](types/struct.KernelMerkleProofPrintable.html)
+	/// * or [`Error`](struct.Error.html) if an error is encountered.
+	///
+
+	pub fn fetch_kernel_merkle_proof(
+		&self,
+		excess: String,
+	) -> Result<KernelMerkleProofPrintable, Error> {
+		let proofs_handler = MerkleProofsHandler {
+			proofs: self.merkle_proofs.clone(),
+		};
+		proofs_handler.fetch_kernel_merkle_proof(excess)
+	}
+
 	/// Returns the number of transaction in the transaction pool.
 	///
 	/// # Returns
@@ -315,7 +418,8 @@ impl Foreign {
 		pool_handler.get_unconfirmed_transactions()
 	}
 
-	/// Push new transaction to our local transaction pool.
+	/// Push new transaction to our local transaction pool. Refused when the
+	/// node runs header only, as it can't validate nor relay transactions.
 	///
 	/// # Arguments
 	/// * `tx` - the Kepler transaction to push.
@@ -327,6 +431,13 @@ impl Foreign {
 	/// * or [`Error`](struct.Error.html) if an error is encountered.
 	///
 	pub fn push_transaction(&self, tx: Transaction, fluff: Option<bool>) -> Result<(), Error> {
+		// Merkle proofs are only fetched from peers when running header only
+		if self.merkle_proofs.is_some() {
+			return Err(ErrorKind::Internal(
+				"node is running header only, transactions aren't accepted".to_owned(),
+			)
+			.into());
+		}
 		let pool_handler = PoolHandler {
 			tx_pool: self.tx_pool.clone(),
 		};
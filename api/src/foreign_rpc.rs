@@ -20,8 +20,8 @@ use crate::foreign::Foreign;
 use crate::pool::PoolEntry;
 use crate::rest::ErrorKind;
 use crate::types::{
-	BlockHeaderPrintable, BlockPrintable, LocatedTxKernel, OutputListing, OutputPrintable, Tip,
-	Version,
+	BlockHeaderPrintable, BlockPrintable, KernelMerkleProofPrintable, LocatedTxKernel,
+	OutputListing, OutputMerkleProofPrintable, OutputPrintable, Tip, Version,
 };
 use crate::util;
 
@@ -354,6 +354,94 @@ pub trait ForeignRpc: Sync + Send {
 		max_height: Option<u64>,
 	) -> Result<LocatedTxKernel, ErrorKind>;
 
+	/**
+	Networked version of [Foreign::get_kernel_merkle_proof](struct.Node.html#method.get_kernel_merkle_proof).
+
+	# Json rpc example
+
+	```
+	# kepler_api::doctest_helper_json_rpc_foreign_assert_response!(
+	# r#"
+	{
+		"jsonrpc": "2.0",
+		"method": "get_kernel_merkle_proof",
+		"params": ["09c868a2fed619580f296e91d2819b6b3ae61ab734bf3d9c3eafa6d9700f00361b", null, null],
+		"id": 1
+	}
+	# "#
+	# ,
+	# r#"
+	{
+		"id": 1,
+		"jsonrpc": "2.0",
+		"result": {
+			"Ok": {
+				"block_hash": "000000543c69a0306b5463b92939643442a44a6d9be5bef72bea9fc1d718d310",
+				"merkle_proof": "000000000021bf5200000000000000020f2a4d3b3c8e5d1b6c9e0a7f4b2d8c1e3f5a6b7c8d9e0f1a2b3c4d5e6f7a8b9c7e8f9a0b1c2d3e4f5a6b7c8d9e0f1a2b3c4d5e6f7a8b9c0d1e2f3a4b5c6d7e8f",
+				"mmr_index": 2211662,
+				"tx_kernel": {
+					"excess": "09c868a2fed619580f296e91d2819b6b3ae61ab734bf3d9c3eafa6d9700f00361b",
+					"excess_sig": "1720ec1b94aa5d6ba4d567f7446314f9a6d064eea69c5675cc5659f65f290d80b0e9e3a48d818cadba0a4e894bbc6eb6754b56f53813e2ee0b1447969894ca4a",
+					"features": "Coinbase"
+				}
+			}
+		}
+	}
+	# "#
+	# );
+	```
+	 */
+	fn get_kernel_merkle_proof(
+		&self,
+		excess: String,
+		min_height: Option<u64>,
+		max_height: Option<u64>,
+	) -> Result<KernelMerkleProofPrintable, ErrorKind>;
+
+	/**
+	Networked version of [Foreign::verify_kernel_merkle_proof](struct.Node.html#method.verify_kernel_merkle_proof).
+
+	# Json rpc example
+
+	```
+	# kepler_api::doctest_helper_json_rpc_foreign_assert_response!(
+	# r#"
+	{
+		"jsonrpc": "2.0",
+		"method": "verify_kernel_merkle_proof",
+		"params": [
+			{
+				"block_hash": "000000543c69a0306b5463b92939643442a44a6d9be5bef72bea9fc1d718d310",
+				"merkle_proof": "000000000021bf5200000000000000020f2a4d3b3c8e5d1b6c9e0a7f4b2d8c1e3f5a6b7c8d9e0f1a2b3c4d5e6f7a8b9c7e8f9a0b1c2d3e4f5a6b7c8d9e0f1a2b3c4d5e6f7a8b9c0d1e2f3a4b5c6d7e8f",
+				"mmr_index": 2211662,
+				"tx_kernel": {
+					"excess": "09c868a2fed619580f296e91d2819b6b3ae61ab734bf3d9c3eafa6d9700f00361b",
+					"excess_sig": "1720ec1b94aa5d6ba4d567f7446314f9a6d064eea69c5675cc5659f65f290d80b0e9e3a48d818cadba0a4e894bbc6eb6754b56f53813e2ee0b1447969894ca4a",
+					"features": "Coinbase"
+				}
+			}
+		],
+		"id": 1
+	}
+	# "#
+	# ,
+	# r#"
+	{
+		"id": 1,
+		"jsonrpc": "2.0",
+		"result": {
+			"Ok": true
+		}
+	}
+	# "#
+	# );
+	```
+	 */
+	fn verify_kernel_merkle_proof(
+		&self,
+		proof: KernelMerkleProofPrintable,
+	) -> Result<bool, ErrorKind>;
+
 	/**
 	Networked version of [Foreign::get_outputs](struct.Node.html#method.get_outputs).
 
@@ -539,6 +627,91 @@ pub trait ForeignRpc: Sync + Send {
 		end_block_height: Option<u64>,
 	) -> Result<OutputListing, ErrorKind>;
 
+	/**
+	Networked version of [Foreign::fetch_output_merkle_proof](struct.Node.html#method.fetch_output_merkle_proof).
+	The proof only shows the output was included in the output MMR at `block_hash`;
+	it does not show the output is unspent.
+
+	# Json rpc example
+
+	```
+	# kepler_api::doctest_helper_json_rpc_foreign_assert_response!(
+	# r#"
+	{
+		"jsonrpc": "2.0",
+		"method": "fetch_output_merkle_proof",
+		"params": ["095c12db5e57e4a1ead0870219bda4ebfb1419f6ab1501386b9dd8dc9811a8c5ff"],
+		"id": 1
+	}
+	# "#
+	# ,
+	# r#"
+	{
+		"id": 1,
+		"jsonrpc": "2.0",
+		"result": {
+			"Ok": {
+				"bitmap_root": "5e3bc6ab2b9d25b02aeef1cc3b2b5cb45d7349fd95dc7cc1b5cc1b2c6da52b01",
+				"block_hash": "00000c3a9c4f5e0cb8a5c9b7e1fdf4b32c0f5d3b9c5d0ad7c8e1c4dd1f7c2b3a",
+				"commit": "095c12db5e57e4a1ead0870219bda4ebfb1419f6ab1501386b9dd8dc9811a8c5ff",
+				"merkle_proof": "00000000003eadc6000000000000000e13c509a17cbb0d81634215cd2482ab6d9eb58b332fcbe6b2c4fa458a63d3cb0dfe3614ebe6e52657870df225d132179fa1ea0fdc2105f0e51d03bc3765a9cd059c60d434a7cae0a3d669b37588c25410f57405c841312cfa50cf514678877a3f4ce8bd3e57723ba75a2b7d61027b2088fbabebdb7336b97ea88b00a7e809a6245def980eba18d987601f4cbd6c3cc9f12a5684fe7a1bc2565a9f8ab63c2db1afa8304f5e23d4754cd97f29c8b06dcb3de4f6d3a83079676b6e9941afe5553a7195384b564ecd6d37522cb5e452cc930d2b549af22698a8fd9bf6cad05a06b09e3f6e672b94e82c0255394b5c187ab76fda653a2491378997ba3d49f9d9c34ca93bc627fe5d98b327c03d429b5473f62672e9d73c4eafd9cb8f62e5158a1ec7eb56653696b10fb9ec205f5e4d1c7a1f3e2dd2994b12eeed93e84776d8dcd8a5d78aecd4f96ae95c0b090d104adf2aa84f0a1fbd8d319fea5476d1a306b2800716e60b00115a5cca678617361c5a89660b4536c56254bc8dd7035d96f05de62b042d16acaeff57c111fdf243b859984063e3fcfdf40c4c4a52889706857a7c3e90e264f30f40cc87bd20e74689f14284bc5ea0a540950dfcc8d33c503477eb1c60",
+				"mmr_index": 4107717,
+				"output_pmmr_root": "8cbc33bd7a65c9ea1b1fd2b3c6d7f4e2a1c6b5d4e3f2a1b0c9d8e7f6a5b4c3d2",
+				"output_type": "Coinbase"
+			}
+		}
+	}
+	# "#
+	# );
+	```
+	 */
+	fn fetch_output_merkle_proof(
+		&self,
+		commit: String,
+	) -> Result<OutputMerkleProofPrintable, ErrorKind>;
+
+	/**
+	Networked version of [Foreign::fetch_kernel_merkle_proof](struct.Node.html#method.fetch_kernel_merkle_proof).
+
+	# Json rpc example
+
+	```
+	# kepler_api::doctest_helper_json_rpc_foreign_assert_response!(
+	# r#"
+	{
+		"jsonrpc": "2.0",
+		"method": "fetch_kernel_merkle_proof",
+		"params": ["09c868a2fed619580f296e91d2819b6b3ae61ab734bf3d9c3eafa6d9700f00361b"],
+		"id": 1
+	}
+	# "#
+	# ,
+	# r#"
+	{
+		"id": 1,
+		"jsonrpc": "2.0",
+		"result": {
+			"Ok": {
+				"block_hash": "00000c3a9c4f5e0cb8a5c9b7e1fdf4b32c0f5d3b9c5d0ad7c8e1c4dd1f7c2b3a",
+				"merkle_proof": "0000000000437b5a0000000000000002e1c4dd1f7c2b3a00000c3a9c4f5e0cb8a5c9b7e1fdf4b32c0f5d3b9c5d0ad7c8b5c9b7e1fdf4b32c0f5d3b9c5d0ad7c8e1c4dd1f7c2b3a00000c3a9c4f5e0ca8",
+				"mmr_index": 2211662,
+				"tx_kernel": {
+					"excess": "09c868a2fed619580f296e91d2819b6b3ae61ab734bf3d9c3eafa6d9700f00361b",
+					"excess_sig": "1720ec1b94aa5d6ba4d567f7446314f9a6d064eea69c5675cc5659f65f290d80b0e9e3a48d818cadba0a4e894bbc6eb6754b56f53813e2ee0b1447969894ca4a",
+					"features": "Coinbase"
+				}
+			}
+		}
+	}
+	# "#
+	# );
+	```
+	 */
+	fn fetch_kernel_merkle_proof(
+		&self,
+		excess: String,
+	) -> Result<KernelMerkleProofPrintable, ErrorKind>;
+
 	/**
 	Networked version of [Foreign::get_pool_size](struct.Node.html#method.get_pool_size).
 
@@ -785,6 +958,23 @@ impl ForeignRpc for Foreign {
 		Foreign::get_kernel(self, excess, min_height, max_height).map_err(|e| e.kind().clone())
 	}
 
+	fn get_kernel_merkle_proof(
+		&self,
+		excess: String,
+		min_height: Option<u64>,
+		max_height: Option<u64>,
+	) -> Result<KernelMerkleProofPrintable, ErrorKind> {
+		Foreign::get_kernel_merkle_proof(self, excess, min_height, max_height)
+			.map_err(|e| e.kind().clone())
+	}
+
+	fn verify_kernel_merkle_proof(
+		&self,
+		proof: KernelMerkleProofPrintable,
+	) -> Result<bool, ErrorKind> {
+		Foreign::verify_kernel_merkle_proof(self, proof).map_err(|e| e.kind().clone())
+	}
+
 	fn get_outputs(
 		&self,
 		commits: Option<Vec<String>>,
@@ -824,6 +1014,20 @@ impl ForeignRpc for Foreign {
 			.map_err(|e| e.kind().clone())
 	}
 
+	fn fetch_output_merkle_proof(
+		&self,
+		commit: String,
+	) -> Result<OutputMerkleProofPrintable, ErrorKind> {
+		Foreign::fetch_output_merkle_proof(self, commit).map_err(|e| e.kind().clone())
+	}
+
+	fn fetch_kernel_merkle_proof(
+		&self,
+		excess: String,
+	) -> Result<KernelMerkleProofPrintable, ErrorKind> {
+		Foreign::fetch_kernel_merkle_proof(self, excess).map_err(|e| e.kind().clone())
+	}
+
 	fn get_pool_size(&self) -> Result<usize, ErrorKind> {
 		Foreign::get_pool_size(self).map_err(|e| e.kind().clone())
 	}
@@ -847,7 +1051,7 @@ macro_rules! doctest_helper_json_rpc_foreign_assert_response {
 		// create temporary kepler server, run jsonrpc request on node api, delete server, return
 		// json response.
 
-			{
+		{
 			/*use kepler_servers::test_framework::framework::run_doctest;
 			use kepler_util as util;
 			use serde_json;
@@ -881,6 +1085,6 @@ macro_rules! doctest_helper_json_rpc_foreign_assert_response {
 					serde_json::to_string_pretty(&expected_response).unwrap()
 				);
 				}*/
-			}
+		}
 	};
 }
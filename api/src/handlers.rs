@@ -12,11 +12,15 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+pub mod blockgen_api;
 pub mod blocks_api;
 pub mod chain_api;
+pub mod coinbase_api;
 pub mod peers_api;
 pub mod pool_api;
+pub mod proofs_api;
 pub mod server_api;
+pub mod stratum_api;
 pub mod transactions_api;
 pub mod utils;
 pub mod version_api;
@@ -52,6 +56,7 @@ use crate::pool;
 use crate::rest::{ApiServer, Error, TLSConfig};
 use crate::router::ResponseFuture;
 use crate::router::{Router, RouterError};
+use crate::types::{BlockGeneration, CoinbaseKeys, MerkleProofs, StratumAccounts};
 use crate::util::to_base64;
 use crate::util::RwLock;
 use crate::web::*;
@@ -67,12 +72,18 @@ pub fn node_apis(
 	addr: &str,
 	chain: Arc<chain::Chain>,
 	tx_pool: Arc<RwLock<pool::TransactionPool>>,
-	peers: Arc<p2p::Peers>,
+	p2p_server: Arc<p2p::Server>,
 	sync_state: Arc<chain::SyncState>,
 	api_secret: Option<String>,
 	foreign_api_secret: Option<String>,
 	tls_config: Option<TLSConfig>,
+	stratum_accounts: Option<Arc<dyn StratumAccounts>>,
+	coinbase_keys: Option<Arc<dyn CoinbaseKeys>>,
+	merkle_proofs: Option<Arc<dyn MerkleProofs>>,
+	block_generation: Option<Arc<dyn BlockGeneration>>,
 ) -> Result<(), Error> {
+	let peers = p2p_server.peers.clone();
+
 	// Manually build router when getting rid of v1
 	//let mut router = Router::new();
 	let mut router = build_router(
@@ -98,7 +109,11 @@ pub fn node_apis(
 	let api_handler_v2 = OwnerAPIHandlerV2::new(
 		Arc::downgrade(&chain),
 		Arc::downgrade(&peers),
+		Arc::downgrade(&p2p_server),
 		Arc::downgrade(&sync_state),
+		stratum_accounts.as_ref().map(|a| Arc::downgrade(a)),
+		coinbase_keys.as_ref().map(|k| Arc::downgrade(k)),
+		block_generation.as_ref().map(|g| Arc::downgrade(g)),
 	);
 	router.add_route("/v2/owner", Arc::new(api_handler_v2))?;
 
@@ -118,6 +133,7 @@ pub fn node_apis(
 		Arc::downgrade(&chain),
 		Arc::downgrade(&tx_pool),
 		Arc::downgrade(&sync_state),
+		merkle_proofs.as_ref().map(|p| Arc::downgrade(p)),
 	);
 	router.add_route("/v2/foreign", Arc::new(api_handler_v2))?;
 
@@ -141,16 +157,32 @@ pub fn node_apis(
 pub struct OwnerAPIHandlerV2 {
 	pub chain: Weak<Chain>,
 	pub peers: Weak<p2p::Peers>,
+	pub p2p_server: Weak<p2p::Server>,
 	pub sync_state: Weak<SyncState>,
+	pub stratum_accounts: Option<Weak<dyn StratumAccounts>>,
+	pub coinbase_keys: Option<Weak<dyn CoinbaseKeys>>,
+	pub block_generation: Option<Weak<dyn BlockGeneration>>,
 }
 
 impl OwnerAPIHandlerV2 {
 	/// Create a new owner API handler for GET methods
-	pub fn new(chain: Weak<Chain>, peers: Weak<p2p::Peers>, sync_state: Weak<SyncState>) -> Self {
+	pub fn new(
+		chain: Weak<Chain>,
+		peers: Weak<p2p::Peers>,
+		p2p_server: Weak<p2p::Server>,
+		sync_state: Weak<SyncState>,
+		stratum_accounts: Option<Weak<dyn StratumAccounts>>,
+		coinbase_keys: Option<Weak<dyn CoinbaseKeys>>,
+		block_generation: Option<Weak<dyn BlockGeneration>>,
+	) -> Self {
 		OwnerAPIHandlerV2 {
 			chain,
 			peers,
+			p2p_server,
 			sync_state,
+			stratum_accounts,
+			coinbase_keys,
+			block_generation,
 		}
 	}
 }
@@ -160,7 +192,11 @@ impl crate::router::Handler for OwnerAPIHandlerV2 {
 		let api = Owner::new(
 			self.chain.clone(),
 			self.peers.clone(),
+			self.p2p_server.clone(),
 			self.sync_state.clone(),
+			self.stratum_accounts.clone(),
+			self.coinbase_keys.clone(),
+			self.block_generation.clone(),
 		);
 
 		Box::pin(async move {
@@ -195,6 +231,7 @@ pub struct ForeignAPIHandlerV2 {
 	pub chain: Weak<Chain>,
 	pub tx_pool: Weak<RwLock<pool::TransactionPool>>,
 	pub sync_state: Weak<SyncState>,
+	pub merkle_proofs: Option<Weak<dyn MerkleProofs>>,
 }
 
 impl ForeignAPIHandlerV2 {
@@ -203,11 +240,13 @@ impl ForeignAPIHandlerV2 {
 		chain: Weak<Chain>,
 		tx_pool: Weak<RwLock<pool::TransactionPool>>,
 		sync_state: Weak<SyncState>,
+		merkle_proofs: Option<Weak<dyn MerkleProofs>>,
 	) -> Self {
 		ForeignAPIHandlerV2 {
 			chain,
 			tx_pool,
 			sync_state,
+			merkle_proofs,
 		}
 	}
 }
@@ -218,6 +257,7 @@ impl crate::router::Handler for ForeignAPIHandlerV2 {
 			self.chain.clone(),
 			self.tx_pool.clone(),
 			self.sync_state.clone(),
+			self.merkle_proofs.clone(),
 		);
 
 		Box::pin(async move {
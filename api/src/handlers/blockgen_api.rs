@@ -0,0 +1,46 @@
+// Copyright 2020 The Kepler Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use super::utils::w;
+use crate::rest::*;
+use crate::types::*;
+use std::sync::Weak;
+
+/// On demand block generation, on testing and custom chains
+pub struct BlockGenerationHandler {
+	pub generator: Option<Weak<dyn BlockGeneration>>,
+}
+
+impl BlockGenerationHandler {
+	pub fn generate_blocks(
+		&self,
+		n: u64,
+		coinbase_destination: Option<String>,
+	) -> Result<Vec<String>, Error> {
+		w(self.generator()?)?.generate_blocks(n, coinbase_destination)
+	}
+
+	pub fn set_mock_time(&self, timestamp: Option<i64>) -> Result<(), Error> {
+		w(self.generator()?)?.set_mock_time(timestamp)
+	}
+
+	fn generator(&self) -> Result<&Weak<dyn BlockGeneration>, Error> {
+		self.generator.as_ref().ok_or_else(|| {
+			ErrorKind::Internal(
+				"block generation is only available on testing and custom chains".to_owned(),
+			)
+			.into()
+		})
+	}
+}
@@ -14,7 +14,8 @@
 
 use super::utils::{get_output, get_output_v2, w};
 use crate::chain;
-use crate::core::core::hash::Hashed;
+use crate::core::core::hash::{Hash, Hashed};
+use crate::core::core::merkle_proof::MerkleProof;
 use crate::rest::*;
 use crate::router::{Handler, ResponseFuture};
 use crate::types::*;
@@ -465,6 +466,55 @@ impl KernelHandler {
 			});
 		kernel.ok_or_else(|| ErrorKind::NotFound.into())
 	}
+
+	pub fn get_kernel_merkle_proof(
+		&self,
+		excess: String,
+		min_height: Option<u64>,
+		max_height: Option<u64>,
+	) -> Result<KernelMerkleProofPrintable, Error> {
+		let excess = util::from_hex(excess)
+			.map_err(|_| ErrorKind::RequestError("invalid excess hex".into()))?;
+		if excess.len() != 33 {
+			return Err(ErrorKind::RequestError("invalid excess length".into()).into());
+		}
+		let excess = Commitment::from_vec(excess);
+
+		let chain = w(&self.chain)?;
+		let proof = chain
+			.get_kernel_merkle_proof(&excess, min_height, max_height)
+			.map_err(|e| ErrorKind::Internal(format!("{}", e)))?
+			.ok_or(ErrorKind::NotFound)?;
+		Ok(KernelMerkleProofPrintable::from_proof(&proof))
+	}
+
+	/// Checks a kernel merkle proof against the header it was built for,
+	/// which has to be on our chain.
+	pub fn verify_kernel_merkle_proof(
+		&self,
+		proof: KernelMerkleProofPrintable,
+	) -> Result<bool, Error> {
+		let block_hash = Hash::from_hex(&proof.block_hash)
+			.map_err(|_| ErrorKind::RequestError("invalid block hash".into()))?;
+		let merkle_proof = MerkleProof::from_hex(&proof.merkle_proof)
+			.map_err(|_| ErrorKind::RequestError("invalid merkle proof".into()))?;
+		let proof = chain::KernelMerkleProof {
+			block_hash,
+			kernel: proof.tx_kernel,
+			mmr_index: proof.mmr_index,
+			proof: merkle_proof,
+		};
+
+		let chain = w(&self.chain)?;
+		let header = match chain.get_block_header(&block_hash) {
+			Ok(header) => header,
+			Err(_) => return Ok(false),
+		};
+		if chain.is_on_current_chain(&header).is_err() {
+			return Ok(false);
+		}
+		Ok(proof.verify(&header).is_ok())
+	}
 }
 
 impl Handler for KernelHandler {
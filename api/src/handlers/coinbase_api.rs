@@ -0,0 +1,32 @@
+// Copyright 2020 The Kepler Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use super::utils::w;
+use crate::rest::*;
+use crate::types::*;
+use std::sync::Weak;
+
+/// Coinbase key ids derived by the node
+pub struct CoinbaseKeysHandler {
+	pub keys: Option<Weak<dyn CoinbaseKeys>>,
+}
+
+impl CoinbaseKeysHandler {
+	pub fn get_coinbase_keys(&self, min_height: Option<u64>) -> Result<Vec<CoinbaseKey>, Error> {
+		match &self.keys {
+			Some(keys) => w(keys)?.get_coinbase_keys(min_height),
+			None => Err(ErrorKind::Internal("local coinbase is not enabled".to_owned()).into()),
+		}
+	}
+}
@@ -94,6 +94,39 @@ impl PeerHandler {
 			.unban_peer(peer_addr)
 			.map_err(|e| ErrorKind::Internal(format!("unban peer error: {:?}", e)).into())
 	}
+
+	pub fn disconnect_peer(&self, addr: SocketAddr) -> Result<(), Error> {
+		let peer_addr = PeerAddr(addr);
+		w(&self.peers)?
+			.disconnect_peer(peer_addr)
+			.map_err(|e| ErrorKind::Internal(format!("disconnect peer error: {:?}", e)).into())
+	}
+}
+
+/// Opens outgoing connections, only available through the owner v2 API
+pub struct PeerConnectHandler {
+	pub p2p_server: Weak<p2p::Server>,
+}
+
+impl PeerConnectHandler {
+	pub fn connect_peer(&self, addr: SocketAddr, preferred: bool) -> Result<(), Error> {
+		let peer_addr = PeerAddr(addr);
+		let p2p_server = w(&self.p2p_server)?;
+		p2p_server.connect(peer_addr).map_err(|e| {
+			let e: Error = match e {
+				p2p::Error::PeerDenied => {
+					ErrorKind::Argument(format!("peer {} is denied by the config", addr))
+				}
+				e => ErrorKind::Internal(format!("connect peer error: {:?}", e)),
+			}
+			.into();
+			e
+		})?;
+		if preferred {
+			p2p_server.peers.add_preferred(peer_addr);
+		}
+		Ok(())
+	}
 }
 
 impl Handler for PeerHandler {
@@ -0,0 +1,61 @@
+// Copyright 2020 The Kepler Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use super::utils::w;
+use crate::rest::*;
+use crate::types::*;
+use crate::util;
+use crate::util::secp::pedersen::Commitment;
+use std::sync::{Arc, Weak};
+
+/// Merkle proofs fetched from our peers, when running header only
+pub struct MerkleProofsHandler {
+	pub proofs: Option<Weak<dyn MerkleProofs>>,
+}
+
+impl MerkleProofsHandler {
+	fn proofs(&self) -> Result<Arc<dyn MerkleProofs>, Error> {
+		match &self.proofs {
+			Some(proofs) => w(proofs),
+			None => Err(ErrorKind::Internal("node isn't running header only".to_owned()).into()),
+		}
+	}
+
+	pub fn fetch_output_merkle_proof(
+		&self,
+		commit: String,
+	) -> Result<OutputMerkleProofPrintable, Error> {
+		let commit = parse_commitment(commit, "commit")?;
+		let proof = self.proofs()?.output_merkle_proof(commit)?;
+		Ok(OutputMerkleProofPrintable::from_proof(&proof))
+	}
+
+	pub fn fetch_kernel_merkle_proof(
+		&self,
+		excess: String,
+	) -> Result<KernelMerkleProofPrintable, Error> {
+		let excess = parse_commitment(excess, "excess")?;
+		let proof = self.proofs()?.kernel_merkle_proof(excess)?;
+		Ok(KernelMerkleProofPrintable::from_proof(&proof))
+	}
+}
+
+fn parse_commitment(hex: String, name: &str) -> Result<Commitment, Error> {
+	let bytes = util::from_hex(hex)
+		.map_err(|_| ErrorKind::RequestError(format!("invalid {} hex", name)))?;
+	if bytes.len() != 33 {
+		return Err(ErrorKind::RequestError(format!("invalid {} length", name)).into());
+	}
+	Ok(Commitment::from_vec(bytes))
+}
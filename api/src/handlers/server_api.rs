@@ -113,6 +113,13 @@ fn sync_status_to_api(sync_status: SyncStatus) -> (String, Option<serde_json::Va
 			"txhashset_download".to_string(),
 			Some(json!({ "downloaded_size": downloaded_size, "total_size": total_size })),
 		),
+		SyncStatus::TxHashsetPibd {
+			recv_segments,
+			total_segments,
+		} => (
+			"txhashset_pibd".to_string(),
+			Some(json!({ "recv_segments": recv_segments, "total_segments": total_segments })),
+		),
 		SyncStatus::TxHashsetRangeProofsValidation {
 			rproofs,
 			rproofs_total,
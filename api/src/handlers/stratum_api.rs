@@ -0,0 +1,32 @@
+// Copyright 2020 The Kepler Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use super::utils::w;
+use crate::rest::*;
+use crate::types::*;
+use std::sync::Weak;
+
+/// Per-login share accounting of the stratum server
+pub struct StratumLoginsHandler {
+	pub accounts: Option<Weak<dyn StratumAccounts>>,
+}
+
+impl StratumLoginsHandler {
+	pub fn get_login_stats(&self, login: Option<String>) -> Result<Vec<StratumLoginStats>, Error> {
+		match &self.accounts {
+			Some(accounts) => w(accounts)?.get_login_stats(login),
+			None => Err(ErrorKind::Internal("stratum server is not enabled".to_owned()).into()),
+		}
+	}
+}
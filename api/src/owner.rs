@@ -15,13 +15,19 @@
 //! Owner API External Definition
 
 use crate::chain::{Chain, SyncState};
+use crate::handlers::blockgen_api::BlockGenerationHandler;
 use crate::handlers::chain_api::{ChainCompactHandler, ChainValidationHandler};
-use crate::handlers::peers_api::{PeerHandler, PeersConnectedHandler};
+use crate::handlers::coinbase_api::CoinbaseKeysHandler;
+use crate::handlers::peers_api::{PeerConnectHandler, PeerHandler, PeersConnectedHandler};
 use crate::handlers::server_api::StatusHandler;
+use crate::handlers::stratum_api::StratumLoginsHandler;
 use crate::p2p::types::PeerInfoDisplay;
 use crate::p2p::{self, PeerData};
 use crate::rest::*;
-use crate::types::Status;
+use crate::types::{
+	BlockGeneration, CoinbaseKey, CoinbaseKeys, Logs, Status, StratumAccounts, StratumLoginStats,
+};
+use crate::util::logger;
 use std::net::SocketAddr;
 use std::sync::Weak;
 
@@ -35,7 +41,11 @@ use std::sync::Weak;
 pub struct Owner {
 	pub chain: Weak<Chain>,
 	pub peers: Weak<p2p::Peers>,
+	pub p2p_server: Weak<p2p::Server>,
 	pub sync_state: Weak<SyncState>,
+	pub stratum_accounts: Option<Weak<dyn StratumAccounts>>,
+	pub coinbase_keys: Option<Weak<dyn CoinbaseKeys>>,
+	pub block_generation: Option<Weak<dyn BlockGeneration>>,
 }
 
 impl Owner {
@@ -46,17 +56,36 @@ impl Owner {
 	/// * `chain` - A non-owning reference of the chain.
 	/// * `tx_pool` - A non-owning reference of the transaction pool.
 	/// * `peers` - A non-owning reference of the peers.
+	/// * `p2p_server` - A non-owning reference of the p2p server, used to open new connections.
 	/// * `sync_state` - A non-owning reference of the `sync_state`.
+	/// * `stratum_accounts` - A non-owning reference of the stratum share accounting, if the
+	/// stratum server is enabled.
+	/// * `coinbase_keys` - A non-owning reference of the coinbase key ids derived by the node, if
+	/// it builds coinbase outputs itself.
+	/// * `block_generation` - A non-owning reference of the block generator, on testing and
+	/// custom chains.
 	///
 	/// # Returns
 	/// * An instance of the Node holding references to the current chain, transaction pool, peers and sync_state.
 	///
 
-	pub fn new(chain: Weak<Chain>, peers: Weak<p2p::Peers>, sync_state: Weak<SyncState>) -> Self {
+	pub fn new(
+		chain: Weak<Chain>,
+		peers: Weak<p2p::Peers>,
+		p2p_server: Weak<p2p::Server>,
+		sync_state: Weak<SyncState>,
+		stratum_accounts: Option<Weak<dyn StratumAccounts>>,
+		coinbase_keys: Option<Weak<dyn CoinbaseKeys>>,
+		block_generation: Option<Weak<dyn BlockGeneration>>,
+	) -> Self {
 		Owner {
 			chain,
 			peers,
+			p2p_server,
 			sync_state,
+			stratum_accounts,
+			coinbase_keys,
+			block_generation,
 		}
 	}
 
@@ -176,4 +205,154 @@ impl Owner {
 		};
 		peer_handler.unban_peer(addr)
 	}
+
+	/// Connects to a specific peer, unless it's denied by `peers_deny` or
+	/// missing from `peers_allow`.
+	///
+	/// # Arguments
+	/// * `addr` - the ip:port of the peer to connect to.
+	/// * `preferred` - if `Some(true)`, the peer is also marked preferred until the node is
+	/// restarted, the node then reconnects to it like to the peers listed in `peers_preferred`.
+	///
+	/// # Returns
+	/// * Result Containing:
+	/// * `Ok(())` if the connection was established
+	/// * or [`Error`](struct.Error.html) if an error is encountered.
+	///
+
+	pub fn connect_peer(&self, addr: SocketAddr, preferred: Option<bool>) -> Result<(), Error> {
+		let peer_connect_handler = PeerConnectHandler {
+			p2p_server: self.p2p_server.clone(),
+		};
+		peer_connect_handler.connect_peer(addr, preferred.unwrap_or(false))
+	}
+
+	/// Disconnects from a specific peer, without banning it.
+	///
+	/// # Arguments
+	/// * `addr` - the ip:port of the peer to disconnect from.
+	///
+	/// # Returns
+	/// * Result Containing:
+	/// * `Ok(())` if the peer was disconnected
+	/// * or [`Error`](struct.Error.html) if an error is encountered.
+	///
+
+	pub fn disconnect_peer(&self, addr: SocketAddr) -> Result<(), Error> {
+		let peer_handler = PeerHandler {
+			peers: self.peers.clone(),
+		};
+		peer_handler.disconnect_peer(addr)
+	}
+
+	/// Retrieves the share accounting of the stratum server logins.
+	/// If `None` is provided, will list all known logins.
+	///
+	/// # Arguments
+	/// * `login` - the login to get the share accounting of.
+	///
+	/// # Returns
+	/// * Result Containing:
+	/// * A vector of [`StratumLoginStats`](types/struct.StratumLoginStats.html)
+	/// * or [`Error`](struct.Error.html) if an error is encountered.
+	///
+
+	pub fn get_stratum_logins(
+		&self,
+		login: Option<String>,
+	) -> Result<Vec<StratumLoginStats>, Error> {
+		let stratum_logins_handler = StratumLoginsHandler {
+			accounts: self.stratum_accounts.clone(),
+		};
+		stratum_logins_handler.get_login_stats(login)
+	}
+
+	/// Retrieves the key ids the node derived to build coinbase outputs
+	/// itself, so the wallet can recover those outputs.
+	///
+	/// # Arguments
+	/// * `min_height` - only list the key ids used for blocks at or above this height.
+	///
+	/// # Returns
+	/// * Result Containing:
+	/// * A vector of [`CoinbaseKey`](types/struct.CoinbaseKey.html)
+	/// * or [`Error`](struct.Error.html) if an error is encountered.
+	///
+
+	pub fn get_coinbase_keys(&self, min_height: Option<u64>) -> Result<Vec<CoinbaseKey>, Error> {
+		let coinbase_keys_handler = CoinbaseKeysHandler {
+			keys: self.coinbase_keys.clone(),
+		};
+		coinbase_keys_handler.get_coinbase_keys(min_height)
+	}
+
+	/// Mines blocks on top of the chain head with the transactions of the pool,
+	/// solving them at the chain minimum graph size, and processes them before
+	/// returning. Only available on the AutomatedTesting, UserTesting and
+	/// custom chains.
+	///
+	/// # Arguments
+	/// * `n` - the number of blocks to mine, at most 1000.
+	/// * `coinbase_destination` - base address of the wallet listener the coinbase outputs are
+	/// built by. If not provided, they're built with the coinbase key of the node if configured,
+	/// otherwise the rewards are burnt.
+	///
+	/// # Returns
+	/// * Result Containing:
+	/// * A vector of the hashes of the mined blocks, in chain order
+	/// * or [`Error`](struct.Error.html) if an error is encountered, including if the wallet
+	/// listener can't be reached (the blocks mined until then are kept).
+	///
+
+	pub fn generate_blocks(
+		&self,
+		n: u64,
+		coinbase_destination: Option<String>,
+	) -> Result<Vec<String>, Error> {
+		let block_generation_handler = BlockGenerationHandler {
+			generator: self.block_generation.clone(),
+		};
+		block_generation_handler.generate_blocks(n, coinbase_destination)
+	}
+
+	/// Sets the time the blocks mined by `generate_blocks` are timestamped at,
+	/// so that tests can drive the timestamps deterministically. Each block is
+	/// still timestamped at least one second after the previous one. Blocks
+	/// more than 12 block intervals ahead of the system time aren't generated,
+	/// as our peers would reject them.
+	///
+	/// # Arguments
+	/// * `timestamp` - the mock time, in seconds since the epoch, or `None` to go back to the
+	/// system time.
+	///
+	/// # Returns
+	/// * Result Containing:
+	/// * `Ok(())` if the mock time was set
+	/// * or [`Error`](struct.Error.html) if an error is encountered.
+	///
+
+	pub fn set_mock_time(&self, timestamp: Option<i64>) -> Result<(), Error> {
+		let block_generation_handler = BlockGenerationHandler {
+			generator: self.block_generation.clone(),
+		};
+		block_generation_handler.set_mock_time(timestamp)
+	}
+
+	/// Retrieves the most recent log entries of the node, at or above its
+	/// stdout log level. Only the last thousand entries are kept.
+	///
+	/// # Arguments
+	/// * `after` - only list the entries logged after the one with this sequence number,
+	/// usually the `last_id` of the previous call.
+	///
+	/// # Returns
+	/// * Result Containing:
+	/// * The [`Logs`](types/struct.Logs.html)
+	/// * or [`Error`](struct.Error.html) if an error is encountered.
+	///
+
+	pub fn get_logs(&self, after: Option<u64>) -> Result<Logs, Error> {
+		let (last_id, entries) = logger::recent_logs(after);
+		Ok(Logs { last_id, entries })
+	}
 }
@@ -18,7 +18,7 @@ use crate::owner::Owner;
 use crate::p2p::types::PeerInfoDisplay;
 use crate::p2p::PeerData;
 use crate::rest::ErrorKind;
-use crate::types::Status;
+use crate::types::{CoinbaseKey, Logs, Status, StratumLoginStats};
 use std::net::SocketAddr;
 
 /// Public definition used to generate Node jsonrpc api.
@@ -352,6 +352,253 @@ pub trait OwnerRpc: Sync + Send {
 	```
 	 */
 	fn unban_peer(&self, peer_addr: SocketAddr) -> Result<(), ErrorKind>;
+
+	/**
+	Networked version of [Owner::connect_peer](struct.Node.html#method.connect_peer).
+
+	# Json rpc example
+
+	```
+	# kepler_api::doctest_helper_json_rpc_owner_assert_response!(
+	# r#"
+	{
+		"jsonrpc": "2.0",
+		"method": "connect_peer",
+		"params": ["70.50.33.130:7414", true],
+		"id": 1
+	}
+	# "#
+	# ,
+	# r#"
+	{
+		"id": 1,
+		"jsonrpc": "2.0",
+		"result": {
+			"Ok": null
+		}
+	}
+	# "#
+	# );
+	```
+	 */
+	fn connect_peer(&self, peer_addr: SocketAddr, preferred: Option<bool>)
+		-> Result<(), ErrorKind>;
+
+	/**
+	Networked version of [Owner::disconnect_peer](struct.Node.html#method.disconnect_peer).
+
+	# Json rpc example
+
+	```
+	# kepler_api::doctest_helper_json_rpc_owner_assert_response!(
+	# r#"
+	{
+		"jsonrpc": "2.0",
+		"method": "disconnect_peer",
+		"params": ["70.50.33.130:7414"],
+		"id": 1
+	}
+	# "#
+	# ,
+	# r#"
+	{
+		"id": 1,
+		"jsonrpc": "2.0",
+		"result": {
+			"Ok": null
+		}
+	}
+	# "#
+	# );
+	```
+	 */
+	fn disconnect_peer(&self, peer_addr: SocketAddr) -> Result<(), ErrorKind>;
+
+	/**
+	Networked version of [Owner::get_stratum_logins](struct.Node.html#method.get_stratum_logins).
+
+	# Json rpc example
+
+	```
+	# kepler_api::doctest_helper_json_rpc_owner_assert_response!(
+	# r#"
+	{
+		"jsonrpc": "2.0",
+		"method": "get_stratum_logins",
+		"params": ["alice"],
+		"id": 1
+	}
+	# "#
+	# ,
+	# r#"
+	{
+		"id": 1,
+		"jsonrpc": "2.0",
+		"result": {
+			"Ok": [
+			{
+				"login": "alice",
+				"num_accepted": 1254,
+				"num_rejected": 3,
+				"num_stale": 12,
+				"num_blocks_found": 2,
+				"last_share": 1584355920
+			}
+			]
+		}
+	}
+	# "#
+	# );
+	```
+	 */
+	fn get_stratum_logins(
+		&self,
+		login: Option<String>,
+	) -> Result<Vec<StratumLoginStats>, ErrorKind>;
+
+	/**
+	Networked version of [Owner::get_coinbase_keys](struct.Node.html#method.get_coinbase_keys).
+
+	# Json rpc example
+
+	```
+	# kepler_api::doctest_helper_json_rpc_owner_assert_response!(
+	# r#"
+	{
+		"jsonrpc": "2.0",
+		"method": "get_coinbase_keys",
+		"params": [1200],
+		"id": 1
+	}
+	# "#
+	# ,
+	# r#"
+	{
+		"id": 1,
+		"jsonrpc": "2.0",
+		"result": {
+			"Ok": [
+			{
+				"key_id": "0200000000000000030000000000000000",
+				"height": 1204,
+				"commit": "08b7e57c448db5ef25aa119dde2312c64d7ff1b890c416c6dda5ec73cbfed2edea",
+				"value": 60000000000,
+				"timestamp": 1584356040
+			}
+			]
+		}
+	}
+	# "#
+	# );
+	```
+	 */
+	fn get_coinbase_keys(&self, min_height: Option<u64>) -> Result<Vec<CoinbaseKey>, ErrorKind>;
+
+	/**
+	Networked version of [Owner::generate_blocks](struct.Node.html#method.generate_blocks).
+
+	# Json rpc example
+
+	```
+	# kepler_api::doctest_helper_json_rpc_owner_assert_response!(
+	# r#"
+	{
+		"jsonrpc": "2.0",
+		"method": "generate_blocks",
+		"params": [2, "http://127.0.0.1:13415"],
+		"id": 1
+	}
+	# "#
+	# ,
+	# r#"
+	{
+		"id": 1,
+		"jsonrpc": "2.0",
+		"result": {
+			"Ok": [
+				"0018f8b5c8a30c6a8a4c1ec8a1eb7c2f4f0e2c6f4e1a33e9b4d1cc6a1c2f8e7d",
+				"002a4c5e39e1f1b0c3a8e5d26fb1f0c9e47d8ab3f5e2c1d0b9a8f7e6d5c4b3a2"
+			]
+		}
+	}
+	# "#
+	# );
+	```
+	 */
+	fn generate_blocks(
+		&self,
+		n: u64,
+		coinbase_destination: Option<String>,
+	) -> Result<Vec<String>, ErrorKind>;
+
+	/**
+	Networked version of [Owner::set_mock_time](struct.Node.html#method.set_mock_time).
+
+	# Json rpc example
+
+	```
+	# kepler_api::doctest_helper_json_rpc_owner_assert_response!(
+	# r#"
+	{
+		"jsonrpc": "2.0",
+		"method": "set_mock_time",
+		"params": [1590969600],
+		"id": 1
+	}
+	# "#
+	# ,
+	# r#"
+	{
+		"id": 1,
+		"jsonrpc": "2.0",
+		"result": {
+			"Ok": null
+		}
+	}
+	# "#
+	# );
+	```
+	 */
+	fn set_mock_time(&self, timestamp: Option<i64>) -> Result<(), ErrorKind>;
+
+	/**
+	Networked version of [Owner::get_logs](struct.Node.html#method.get_logs).
+
+	# Json rpc example
+
+	```
+	# kepler_api::doctest_helper_json_rpc_owner_assert_response!(
+	# r#"
+	{
+		"jsonrpc": "2.0",
+		"method": "get_logs",
+		"params": [1041],
+		"id": 1
+	}
+	# "#
+	# ,
+	# r#"
+	{
+		"id": 1,
+		"jsonrpc": "2.0",
+		"result": {
+			"Ok": {
+				"last_id": 1042,
+				"entries": [
+				{
+					"log": "20200316 10:54:00.123 INFO kepler_servers::common::adapters - Received block 0000a3b6 at 1204 from 70.50.33.130:7414 [in/out/kern: 1/2/2] going to process.\n",
+					"level": "INFO",
+					"module": "kepler_servers::common::adapters"
+				}
+				]
+			}
+		}
+	}
+	# "#
+	# );
+	```
+	 */
+	fn get_logs(&self, after: Option<u64>) -> Result<Logs, ErrorKind>;
 }
 
 impl OwnerRpc for Owner {
@@ -382,6 +629,41 @@ impl OwnerRpc for Owner {
 	fn unban_peer(&self, addr: SocketAddr) -> Result<(), ErrorKind> {
 		Owner::unban_peer(self, addr).map_err(|e| e.kind().clone())
 	}
+
+	fn connect_peer(&self, addr: SocketAddr, preferred: Option<bool>) -> Result<(), ErrorKind> {
+		Owner::connect_peer(self, addr, preferred).map_err(|e| e.kind().clone())
+	}
+
+	fn disconnect_peer(&self, addr: SocketAddr) -> Result<(), ErrorKind> {
+		Owner::disconnect_peer(self, addr).map_err(|e| e.kind().clone())
+	}
+
+	fn get_stratum_logins(
+		&self,
+		login: Option<String>,
+	) -> Result<Vec<StratumLoginStats>, ErrorKind> {
+		Owner::get_stratum_logins(self, login).map_err(|e| e.kind().clone())
+	}
+
+	fn get_coinbase_keys(&self, min_height: Option<u64>) -> Result<Vec<CoinbaseKey>, ErrorKind> {
+		Owner::get_coinbase_keys(self, min_height).map_err(|e| e.kind().clone())
+	}
+
+	fn generate_blocks(
+		&self,
+		n: u64,
+		coinbase_destination: Option<String>,
+	) -> Result<Vec<String>, ErrorKind> {
+		Owner::generate_blocks(self, n, coinbase_destination).map_err(|e| e.kind().clone())
+	}
+
+	fn set_mock_time(&self, timestamp: Option<i64>) -> Result<(), ErrorKind> {
+		Owner::set_mock_time(self, timestamp).map_err(|e| e.kind().clone())
+	}
+
+	fn get_logs(&self, after: Option<u64>) -> Result<Logs, ErrorKind> {
+		Owner::get_logs(self, after).map_err(|e| e.kind().clone())
+	}
 }
 
 #[doc(hidden)]
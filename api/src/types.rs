@@ -20,7 +20,9 @@ use crate::core::core::merkle_proof::MerkleProof;
 use crate::core::core::{KernelFeatures, TxKernel};
 use crate::core::{core, ser};
 use crate::p2p;
+use crate::rest::Error;
 use crate::util;
+use crate::util::logger::LogEntry;
 use crate::util::secp::pedersen;
 use serde;
 use serde::de::MapAccess;
@@ -719,12 +721,154 @@ pub struct LocatedTxKernel {
 	pub mmr_index: u64,
 }
 
+/// Merkle proof of an output's inclusion, checked against our header chain.
+/// Says nothing about whether the output is still unspent.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct OutputMerkleProofPrintable {
+	/// Hash of the header the proof is for
+	pub block_hash: String,
+	/// The output commitment
+	pub commit: String,
+	/// The type of output Coinbase|Transaction
+	pub output_type: OutputType,
+	/// Position of the output in the output MMR
+	pub mmr_index: u64,
+	/// Root of the output MMR
+	pub output_pmmr_root: String,
+	/// Root of the bitmap accumulator
+	pub bitmap_root: String,
+	/// Merkle proof, hex encoded
+	pub merkle_proof: String,
+}
+
+impl OutputMerkleProofPrintable {
+	pub fn from_proof(proof: &chain::OutputMerkleProof) -> OutputMerkleProofPrintable {
+		let output_type = if proof.output.features.is_coinbase() {
+			OutputType::Coinbase
+		} else {
+			OutputType::Transaction
+		};
+		OutputMerkleProofPrintable {
+			block_hash: proof.block_hash.to_hex(),
+			commit: util::to_hex(proof.output.commit.0.to_vec()),
+			output_type,
+			mmr_index: proof.mmr_index,
+			output_pmmr_root: proof.output_pmmr_root.to_hex(),
+			bitmap_root: proof.bitmap_root.to_hex(),
+			merkle_proof: proof.proof.to_hex(),
+		}
+	}
+}
+
+/// Merkle proof of a kernel, checked against our header chain
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct KernelMerkleProofPrintable {
+	/// Hash of the header the proof is for
+	pub block_hash: String,
+	/// The kernel
+	pub tx_kernel: TxKernel,
+	/// Position of the kernel in the kernel MMR
+	pub mmr_index: u64,
+	/// Merkle proof, hex encoded
+	pub merkle_proof: String,
+}
+
+impl KernelMerkleProofPrintable {
+	pub fn from_proof(proof: &chain::KernelMerkleProof) -> KernelMerkleProofPrintable {
+		KernelMerkleProofPrintable {
+			block_hash: proof.block_hash.to_hex(),
+			tx_kernel: proof.kernel.clone(),
+			mmr_index: proof.mmr_index,
+			merkle_proof: proof.proof.to_hex(),
+		}
+	}
+}
+
 #[derive(Serialize, Deserialize)]
 pub struct PoolInfo {
 	/// Size of the pool
 	pub pool_size: usize,
 }
 
+/// Share accounting of a stratum server login
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct StratumLoginStats {
+	/// Login used by the workers
+	pub login: String,
+	/// Number of valid shares submitted
+	pub num_accepted: u64,
+	/// Number of invalid shares submitted
+	pub num_rejected: u64,
+	/// Number of shares submitted too late
+	pub num_stale: u64,
+	/// Number of valid blocks found
+	pub num_blocks_found: u64,
+	/// Timestamp of the last share submitted
+	pub last_share: i64,
+}
+
+/// Access to the per-login share accounting of the stratum server
+pub trait StratumAccounts: Send + Sync {
+	/// Share accounting of the provided login, or of all known logins if
+	/// `None` is provided.
+	fn get_login_stats(&self, login: Option<String>) -> Result<Vec<StratumLoginStats>, Error>;
+}
+
+/// Coinbase key id derived by the node when it builds coinbase outputs itself
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct CoinbaseKey {
+	/// Key id, relative to the configured extended key
+	pub key_id: String,
+	/// Height of the block the output was built for
+	pub height: u64,
+	/// Output commitment
+	pub commit: String,
+	/// Output value
+	pub value: u64,
+	/// Timestamp of the derivation
+	pub timestamp: i64,
+}
+
+/// Access to the coinbase key ids derived by the node
+pub trait CoinbaseKeys: Send + Sync {
+	/// Key ids used for blocks at or above the provided height, or all of
+	/// them if `None` is provided.
+	fn get_coinbase_keys(&self, min_height: Option<u64>) -> Result<Vec<CoinbaseKey>, Error>;
+}
+
+/// On demand block generation, only available on testing and custom chains
+pub trait BlockGeneration: Send + Sync {
+	/// Mines `n` blocks on top of the chain head with transactions of the
+	/// pool and returns their hashes once they're all processed.
+	fn generate_blocks(
+		&self,
+		n: u64,
+		coinbase_destination: Option<String>,
+	) -> Result<Vec<String>, Error>;
+
+	/// Sets the time generated blocks are timestamped at, or goes back to the
+	/// system time if `None` is provided.
+	fn set_mock_time(&self, timestamp: Option<i64>) -> Result<(), Error>;
+}
+
+/// Merkle proofs of outputs and kernels fetched from full peers, for a node
+/// only holding the header chain
+pub trait MerkleProofs: Send + Sync {
+	/// Proof of the unspent output with the provided commitment, checked
+	/// against our header chain.
+	fn output_merkle_proof(
+		&self,
+		commit: pedersen::Commitment,
+	) -> Result<chain::OutputMerkleProof, Error>;
+
+	/// Proof of the kernel with the provided excess, checked against our
+	/// header chain.
+	fn kernel_merkle_proof(
+		&self,
+		excess: pedersen::Commitment,
+	) -> Result<chain::KernelMerkleProof, Error>;
+}
+
 #[cfg(test)]
 mod test {
 	use super::*;
@@ -759,3 +903,13 @@ mod test {
 		assert_eq!(serialized, hex_commit);
 	}
 }
+
+/// Most recent log entries of the node
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Logs {
+	/// Sequence number of the last entry, to be provided to get the entries
+	/// logged after this batch
+	pub last_id: u64,
+	/// Log entries, oldest first
+	pub entries: Vec<LogEntry>,
+}
@@ -23,21 +23,22 @@ use crate::core::core::{
 };
 use crate::core::global;
 use crate::core::pow;
-use crate::core::ser::{ProtocolVersion, Readable, StreamingReader};
+use crate::core::ser::{self, ProtocolVersion, Readable, StreamingReader};
 use crate::error::{Error, ErrorKind};
 use crate::pipe;
 use crate::store;
 use crate::txhashset;
-use crate::txhashset::{PMMRHandle, TxHashSet};
+use crate::txhashset::{Desegmenter, PMMRHandle, Segmenter, TxHashSet};
 use crate::types::{
-	BlockStatus, ChainAdapter, CommitPos, NoStatus, Options, Tip, TxHashsetWriteStatus,
+	BlockStatus, ChainAdapter, CommitPos, KernelMerkleProof, NoStatus, Options, OutputMerkleProof,
+	Tip, TxHashsetWriteStatus,
 };
 use crate::util::secp::pedersen::{Commitment, RangeProof};
 use crate::util::RwLock;
 use kepler_store::Error::NotFoundErr;
 use std::collections::HashMap;
 use std::fs::{self, File};
-use std::io::Read;
+use std::io::{Read, Write};
 use std::path::PathBuf;
 use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
@@ -49,6 +50,9 @@ pub const MAX_ORPHAN_SIZE: usize = 200;
 /// When evicting, very old orphans are evicted first
 const MAX_ORPHAN_AGE_SECS: u64 = 300;
 
+/// Headers processed at once when importing a header file
+const HEADERS_FILE_CHUNK: usize = 512;
+
 #[derive(Debug, Clone)]
 struct Orphan {
 	block: Block,
@@ -155,6 +159,10 @@ pub struct Chain {
 	pow_verifier: fn(&BlockHeader) -> Result<(), pow::Error>,
 	archive_mode: bool,
 	genesis: BlockHeader,
+	// segmenter serving our archive header txhashset to peers
+	pibd_segmenter: Arc<RwLock<Option<Segmenter>>>,
+	// desegmenter validating the segments of the txhashset we're syncing
+	pibd_desegmenter: Arc<RwLock<Option<Desegmenter>>>,
 }
 
 impl Chain {
@@ -220,6 +228,8 @@ impl Chain {
 			verifier_cache,
 			archive_mode,
 			genesis: genesis.header,
+			pibd_segmenter: Arc::new(RwLock::new(None)),
+			pibd_desegmenter: Arc::new(RwLock::new(None)),
 		};
 
 		// DB migrations to be run prior to the chain being used.
@@ -296,6 +306,34 @@ impl Chain {
 		}
 	}
 
+	/// Headers of the blocks rewound from the previous head (most recent first)
+	/// and applied up to the new head (oldest first) when the head moved to
+	/// another fork.
+	fn reorg_headers(
+		&self,
+		prev_head: &Tip,
+		head: &Tip,
+	) -> Result<(Vec<BlockHeader>, Vec<BlockHeader>), Error> {
+		let mut disconnected = vec![];
+		let mut connected = vec![];
+		let mut old = self.get_block_header(&prev_head.last_block_h)?;
+		let mut new = self.get_block_header(&head.last_block_h)?;
+		while old.hash() != new.hash() {
+			if old.height >= new.height {
+				let prev = self.get_previous_header(&old)?;
+				disconnected.push(old);
+				old = prev;
+			}
+			if new.height > old.height {
+				let prev = self.get_previous_header(&new)?;
+				connected.push(new);
+				new = prev;
+			}
+		}
+		connected.reverse();
+		Ok((disconnected, connected))
+	}
+
 	/// Attempt to add a new block to the chain.
 	/// Returns true if it has been added to the longest chain
 	/// or false if it has added to a fork (or orphan?).
@@ -324,9 +362,17 @@ impl Chain {
 
 		match maybe_new_head {
 			Ok(head) => {
-				let status = self.determine_status(head.clone(), prev_head);
+				let status = self.determine_status(head.clone(), prev_head.clone());
 
 				// notifying other parts of the system of the update
+				if let (BlockStatus::Reorg(_), Some(head)) = (&status, &head) {
+					match self.reorg_headers(&prev_head, head) {
+						Ok((disconnected, connected)) => {
+							self.adapter.reorg(&disconnected, &connected, opts)
+						}
+						Err(e) => warn!("process_block: failed to walk reorg headers: {:?}", e),
+					}
+				}
 				self.adapter.block_accepted(&b, status, opts);
 
 				Ok(head)
@@ -416,6 +462,45 @@ impl Chain {
 		Ok(())
 	}
 
+	/// Imports the headers of a header file, as written by `export_headers`,
+	/// into the header chain. They go through the same validation as headers
+	/// received during sync, proof of work included. Returns the number of
+	/// headers read.
+	pub fn import_headers(&self, reader: &mut dyn Read) -> Result<u64, Error> {
+		let mut stream = StreamingReader::new(reader, ProtocolVersion::local());
+		let count = u64::read(&mut stream).map_err(ErrorKind::SerErr)?;
+
+		let mut imported = 0;
+		let mut headers = Vec::with_capacity(HEADERS_FILE_CHUNK);
+		while imported < count {
+			headers.clear();
+			while headers.len() < HEADERS_FILE_CHUNK && imported + (headers.len() as u64) < count {
+				headers.push(BlockHeader::read(&mut stream).map_err(ErrorKind::SerErr)?);
+			}
+			self.sync_block_headers(&headers, Options::SYNC)?;
+			imported += headers.len() as u64;
+			if imported % (HEADERS_FILE_CHUNK as u64 * 100) == 0 || imported == count {
+				info!("import_headers: {}/{} headers imported", imported, count);
+			}
+		}
+		Ok(count)
+	}
+
+	/// Writes the headers of our header chain, from the one right after the
+	/// genesis up to the header head, in a header file another node can
+	/// import. The file is the number of headers followed by the headers.
+	/// Returns the number of headers written.
+	pub fn export_headers(&self, writer: &mut dyn Write) -> Result<u64, Error> {
+		let header_head = self.header_head()?;
+		let version = ProtocolVersion::local();
+		ser::serialize(writer, version, &header_head.height).map_err(ErrorKind::SerErr)?;
+		for height in 1..=header_head.height {
+			let header = self.get_header_by_height(height)?;
+			ser::serialize(writer, version, &header).map_err(ErrorKind::SerErr)?;
+		}
+		Ok(header_head.height)
+	}
+
 	fn new_ctx<'a>(
 		&self,
 		opts: Options,
@@ -639,6 +724,33 @@ impl Chain {
 		Ok(merkle_proof)
 	}
 
+	/// Return a Merkle proof of the kernel with the given excess, against the
+	/// kernel root of the header including it. Searches the same height range
+	/// as `get_kernel_height`.
+	pub fn get_kernel_merkle_proof(
+		&self,
+		excess: &Commitment,
+		min_height: Option<u64>,
+		max_height: Option<u64>,
+	) -> Result<Option<KernelMerkleProof>, Error> {
+		let (kernel, height, mmr_index) =
+			match self.get_kernel_height(excess, min_height, max_height)? {
+				Some(k) => k,
+				None => return Ok(None),
+			};
+		let header = self.get_header_by_height(height)?;
+		let proof = self
+			.txhashset
+			.write()
+			.kernel_merkle_proof(&header, mmr_index)?;
+		Ok(Some(KernelMerkleProof {
+			block_hash: header.hash(),
+			kernel,
+			mmr_index,
+			proof,
+		}))
+	}
+
 	/// Return a merkle proof valid for the current output pmmr state at the
 	/// given pos
 	pub fn get_merkle_proof_for_pos(&self, commit: Commitment) -> Result<MerkleProof, Error> {
@@ -646,6 +758,45 @@ impl Chain {
 		txhashset.merkle_proof(commit)
 	}
 
+	/// Proof of an unspent output against the output root of our head, for
+	/// nodes only holding the header chain.
+	pub fn output_merkle_proof(&self, commit: &Commitment) -> Result<OutputMerkleProof, Error> {
+		let mut txhashset = self.txhashset.write();
+		let header = self.head_header()?;
+		let pos = txhashset.get_output_pos(commit)?;
+		let output = txhashset
+			.output_pmmr_at(&header)
+			.get_data(pos)
+			.ok_or(ErrorKind::OutputNotFound)?;
+		let roots = txhashset.roots();
+		let proof = txhashset.merkle_proof(*commit)?;
+		Ok(OutputMerkleProof {
+			block_hash: header.hash(),
+			output,
+			mmr_index: pos,
+			output_pmmr_root: roots.output_roots.pmmr_root,
+			bitmap_root: roots.output_roots.bitmap_root,
+			proof,
+		})
+	}
+
+	/// Proof of the kernel with the provided excess against the kernel root
+	/// of our head, for nodes only holding the header chain.
+	pub fn kernel_merkle_proof(&self, excess: &Commitment) -> Result<KernelMerkleProof, Error> {
+		let mut txhashset = self.txhashset.write();
+		let header = self.head_header()?;
+		let (kernel, pos) = txhashset
+			.find_kernel(excess, None, None)
+			.ok_or_else(|| ErrorKind::Other("kernel not found".to_owned()))?;
+		let proof = txhashset.kernel_merkle_proof(&header, pos)?;
+		Ok(KernelMerkleProof {
+			block_hash: header.hash(),
+			kernel,
+			mmr_index: pos,
+			proof,
+		})
+	}
+
 	/// Provides a reading view into the current kernel state.
 	pub fn kernel_data_read(&self) -> Result<File, Error> {
 		let txhashset = self.txhashset.read();
@@ -691,6 +842,89 @@ impl Chain {
 		})
 	}
 
+	/// Hash of the txhashset archive provided for the block, to be called
+	/// after `txhashset_read` prepared the archive.
+	pub fn txhashset_archive_hash(&self, h: &Hash) -> Result<Hash, Error> {
+		let header = self.get_block_header(h)?;
+		txhashset::zip_hash(self.db_root.clone(), &header)
+	}
+
+	/// The segmenter serving the segments of the txhashset at our archive
+	/// header, built again whenever the archive header moves.
+	pub fn segmenter(&self) -> Result<Segmenter, Error> {
+		let header = self.txhashset_archive_header()?;
+		if let Some(segmenter) = self.pibd_segmenter.read().as_ref() {
+			if segmenter.header().hash() == header.hash() {
+				return Ok(segmenter.clone());
+			}
+		}
+
+		let segmenter = self.init_segmenter(&header)?;
+		*self.pibd_segmenter.write() = Some(segmenter.clone());
+		Ok(segmenter)
+	}
+
+	// Snapshots the bitmap accumulator and the unspent outputs at the header
+	// by rewinding a readonly extension.
+	fn init_segmenter(&self, header: &BlockHeader) -> Result<Segmenter, Error> {
+		let now = Instant::now();
+		let mut header_pmmr = self.header_pmmr.write();
+		let mut txhashset = self.txhashset.write();
+		let (bitmap_snapshot, unspent_snapshot) =
+			txhashset::extending_readonly(&mut header_pmmr, &mut txhashset, |ext, batch| {
+				let extension = &mut ext.extension;
+				extension.rewind(header, batch)?;
+				Ok((
+					extension.bitmap_accumulator().clone(),
+					extension.unspent_output_bitmap(),
+				))
+			})?;
+
+		debug!(
+			"init_segmenter: snapshot at {} at {} took {}ms",
+			header.hash(),
+			header.height,
+			now.elapsed().as_millis(),
+		);
+		Ok(Segmenter::new(
+			self.txhashset.clone(),
+			bitmap_snapshot,
+			unspent_snapshot,
+			header.clone(),
+		))
+	}
+
+	/// The desegmenter validating the segments of the txhashset at the
+	/// provided header, created again (dropping the segments received so far)
+	/// if it was for another header.
+	pub fn desegmenter(
+		&self,
+		header: &BlockHeader,
+	) -> Result<Arc<RwLock<Option<Desegmenter>>>, Error> {
+		let current = self
+			.pibd_desegmenter
+			.read()
+			.as_ref()
+			.map(|x| x.header().hash());
+		if current != Some(header.hash()) {
+			let dir = self.get_tmp_dir().join("txhashset_segments");
+			*self.pibd_desegmenter.write() = Some(Desegmenter::new(header.clone(), dir)?);
+		}
+		Ok(self.pibd_desegmenter.clone())
+	}
+
+	/// The desegmenter of the segmented sync in progress, if any.
+	pub fn current_desegmenter(&self) -> Arc<RwLock<Option<Desegmenter>>> {
+		self.pibd_desegmenter.clone()
+	}
+
+	/// Drops the desegmenter and the segments it stored.
+	pub fn reset_desegmenter(&self) {
+		if let Some(desegmenter) = self.pibd_desegmenter.write().take() {
+			let _ = fs::remove_dir_all(desegmenter.dir());
+		}
+	}
+
 	/// To support the ability to download the txhashset from multiple peers in parallel,
 	/// the peers must all agree on the exact binary representation of the txhashset.
 	/// This means compacting and rewinding to the exact same header.
@@ -924,17 +1158,56 @@ impl Chain {
 		txhashset::clean_txhashset_folder(&sandbox_dir);
 		txhashset::zip_write(sandbox_dir.clone(), txhashset_data.try_clone()?, &header)?;
 
+		self.txhashset_install(&header, sandbox_dir, status)?;
+		Ok(false)
+	}
+
+	/// Builds the txhashset from the segments of the segmented sync once all
+	/// of them have been received, then validates and installs it just like
+	/// a txhashset archive.
+	pub fn txhashset_segments_write(&self, status: &dyn TxHashsetWriteStatus) -> Result<(), Error> {
+		status.on_setup();
+
+		let mut hashes: Option<Vec<Hash>> = None;
+		if !self.check_txhashset_needed("txhashset_segments_write".to_owned(), &mut hashes)? {
+			return Err(ErrorKind::InvalidTxHashSet("not needed".to_owned()).into());
+		}
+
+		let sandbox_dir = self.get_tmp_dir();
+		txhashset::clean_txhashset_folder(&sandbox_dir);
+		let header = {
+			let desegmenter = self.pibd_desegmenter.read();
+			let desegmenter = desegmenter
+				.as_ref()
+				.ok_or_else(|| ErrorKind::SyncError("no segmented sync".to_owned()))?;
+			desegmenter.write_txhashset(&sandbox_dir)?;
+			desegmenter.header().clone()
+		};
+
+		self.txhashset_install(&header, sandbox_dir, status)?;
+		self.reset_desegmenter();
+		Ok(())
+	}
+
+	// Opens the txhashset written to the sandbox dir, fully validates it at
+	// the provided header and replaces ours with it.
+	fn txhashset_install(
+		&self,
+		header: &BlockHeader,
+		sandbox_dir: PathBuf,
+		status: &dyn TxHashsetWriteStatus,
+	) -> Result<(), Error> {
 		let mut txhashset = txhashset::TxHashSet::open(
 			sandbox_dir
 				.to_str()
 				.expect("invalid sandbox folder")
 				.to_owned(),
 			self.store.clone(),
-			Some(&header),
+			Some(header),
 		)?;
 
 		// Validate the full kernel history (kernel MMR root for every block header).
-		self.validate_kernel_history(&header, &txhashset)?;
+		self.validate_kernel_history(header, &txhashset)?;
 
 		// all good, prepare a new batch and update all the required records
 		debug!("txhashset_write: rewinding a 2nd time (writeable)");
@@ -947,12 +1220,12 @@ impl Chain {
 			&mut batch,
 			|ext, batch| {
 				let extension = &mut ext.extension;
-				extension.rewind(&header, batch)?;
+				extension.rewind(header, batch)?;
 
 				// Validate the extension, generating the utxo_sum and kernel_sum.
 				// Full validation, including rangeproofs and kernel signature verification.
 				let (utxo_sum, kernel_sum) =
-					extension.validate(&self.genesis, false, status, &header)?;
+					extension.validate(&self.genesis, false, status, header)?;
 
 				// Save the block_sums (utxo_sum, kernel_sum) to the db for use later.
 				batch.save_block_sums(
@@ -973,7 +1246,7 @@ impl Chain {
 
 		// Save the new head to the db and rebuild the header by height index.
 		{
-			let tip = Tip::from_header(&header);
+			let tip = Tip::from_header(header);
 			batch.save_body_head(&tip)?;
 
 			// Reset the body tail to the body head after a txhashset write
@@ -1000,11 +1273,8 @@ impl Chain {
 			txhashset::txhashset_replace(sandbox_dir, PathBuf::from(self.db_root.clone()))?;
 
 			// Re-open on db root dir
-			txhashset = txhashset::TxHashSet::open(
-				self.db_root.clone(),
-				self.store.clone(),
-				Some(&header),
-			)?;
+			txhashset =
+				txhashset::TxHashSet::open(self.db_root.clone(), self.store.clone(), Some(header))?;
 
 			// Replace the chain txhashset with the newly built one.
 			*txhashset_ref = txhashset;
@@ -1014,7 +1284,7 @@ impl Chain {
 
 		status.on_done();
 
-		Ok(false)
+		Ok(())
 	}
 
 	/// Cleanup old blocks from the db.
@@ -13,6 +13,7 @@
 // limitations under the License.
 
 //! Error types for chain
+use crate::core::core::pmmr::SegmentError;
 use crate::core::core::{block, committed, transaction};
 use crate::core::ser;
 use crate::keychain;
@@ -143,6 +144,12 @@ pub enum ErrorKind {
 	/// Error during chain sync
 	#[fail(display = "Sync error")]
 	SyncError(String),
+	/// A txhashset segment that couldn't be built or validated
+	#[fail(display = "Invalid segment: {:?}", _0)]
+	InvalidSegment(SegmentError),
+	/// Segments requested for a header we're not serving or syncing
+	#[fail(display = "Segment header mismatch")]
+	SegmentHeaderMismatch,
 }
 
 impl Display for Error {
@@ -187,6 +194,7 @@ impl Error {
 			| ErrorKind::SerErr(_)
 			| ErrorKind::TxHashSetErr(_)
 			| ErrorKind::GenesisBlockRequired
+			| ErrorKind::SegmentHeaderMismatch
 			| ErrorKind::Other(_) => false,
 			_ => true,
 		}
@@ -227,6 +235,14 @@ impl From<store::Error> for Error {
 	}
 }
 
+impl From<SegmentError> for Error {
+	fn from(error: SegmentError) -> Error {
+		Error {
+			inner: Context::new(ErrorKind::InvalidSegment(error)),
+		}
+	}
+}
+
 impl From<keychain::Error> for Error {
 	fn from(error: keychain::Error) -> Error {
 		Error {
@@ -46,5 +46,6 @@ pub use crate::chain::{Chain, MAX_ORPHAN_SIZE};
 pub use crate::error::{Error, ErrorKind};
 pub use crate::store::ChainStore;
 pub use crate::types::{
-	BlockStatus, ChainAdapter, Options, SyncState, SyncStatus, Tip, TxHashsetWriteStatus,
+	BlockStatus, ChainAdapter, KernelMerkleProof, Options, OutputMerkleProof, SegmentType,
+	SegmentTypeIdentifier, SyncState, SyncStatus, SyncStatusAdapter, Tip, TxHashsetWriteStatus,
 };
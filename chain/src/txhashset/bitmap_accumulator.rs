@@ -38,8 +38,8 @@ use crate::error::{Error, ErrorKind};
 /// relevant peaks and the overall MMR root.
 ///
 /// It is also possible to generate Merkle proofs for these 1024 bit fragments, proving
-/// both inclusion and location in the overall "accumulator" MMR. We plan to take advantage of
-/// this during fast sync, allowing for validation of partial data.
+/// both inclusion and location in the overall "accumulator" MMR. Segmented state sync takes
+/// advantage of this, serving the chunks in segments validated independently.
 ///
 #[derive(Clone)]
 pub struct BitmapAccumulator {
@@ -50,7 +50,7 @@ impl BitmapAccumulator {
 	/// Crate a new empty bitmap accumulator.
 	pub fn new() -> BitmapAccumulator {
 		BitmapAccumulator {
-			backend: VecBackend::new_hash_only(),
+			backend: VecBackend::new(),
 		}
 	}
 
@@ -176,13 +176,18 @@ impl BitmapAccumulator {
 
 	/// The root hash of the bitmap accumulator MMR.
 	pub fn root(&self) -> Hash {
-		ReadonlyPMMR::at(&self.backend, self.backend.size()).root()
+		self.readonly_pmmr().root()
+	}
+
+	/// Readonly view of the underlying MMR of bitmap chunks.
+	pub fn readonly_pmmr(&self) -> ReadonlyPMMR<'_, BitmapChunk, VecBackend<BitmapChunk>> {
+		ReadonlyPMMR::at(&self.backend, self.backend.size())
 	}
 }
 
 /// A bitmap "chunk" representing 1024 contiguous bits of the overall bitmap.
 /// The first 1024 bits belong in one chunk. The next 1024 bits in the next chunk, etc.
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub struct BitmapChunk(BitVec);
 
 impl BitmapChunk {
@@ -203,6 +208,13 @@ impl BitmapChunk {
 		self.0.set(idx, value)
 	}
 
+	/// Value of a single bit in this chunk.
+	/// 0-indexed from start of chunk.
+	pub fn get(&self, idx: u64) -> bool {
+		let idx = usize::try_from(idx).expect("usize from u64");
+		self.0.get(idx).unwrap_or(false)
+	}
+
 	/// Does this bitmap chunk have any bits set to 1?
 	pub fn any(&self) -> bool {
 		self.0.any()
@@ -230,10 +242,8 @@ impl Writeable for BitmapChunk {
 }
 
 impl Readable for BitmapChunk {
-	/// Reading is not currently supported, just return an empty one for now.
-	/// We store the underlying roaring bitmap externally for the bitmap accumulator
-	/// and the "hash only" backend means we never actually read these chunks.
-	fn read(_reader: &mut dyn Reader) -> Result<BitmapChunk, ser::Error> {
-		Ok(BitmapChunk::new())
+	fn read(reader: &mut dyn Reader) -> Result<BitmapChunk, ser::Error> {
+		let bytes = reader.read_fixed_bytes(Self::LEN_BYTES)?;
+		Ok(BitmapChunk(BitVec::from_bytes(&bytes)))
 	}
 }
@@ -0,0 +1,478 @@
+// Copyright 2020 The Kepler Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Validation of the txhashset segments received from peers during a
+//! segmented state sync. Each segment is checked against the roots of the
+//! archive header as soon as it's received, then stored until the whole
+//! txhashset has been downloaded and can be built from them.
+
+use std::collections::{BTreeSet, HashMap};
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+
+use crate::core::core::hash::{Hash, Hashed};
+use crate::core::core::pmmr::{self, Segment, SegmentIdentifier};
+use crate::core::core::{
+	BlockHeader, HeaderVersion, Output, OutputFeatures, OutputIdentifier, TxKernel,
+};
+use crate::core::ser::{self, PMMRIndexHashable, PMMRable, ProtocolVersion, Readable, Writeable};
+use crate::error::{Error, ErrorKind};
+use crate::txhashset::{
+	BitmapChunk, KERNEL_SUBDIR, OUTPUT_SUBDIR, RANGE_PROOF_SUBDIR, TXHASHSET_SUBDIR,
+};
+use crate::types::{SegmentType, SegmentTypeIdentifier};
+use crate::util::secp::pedersen::{Commitment, RangeProof};
+use kepler_store::pmmr::PMMRBackend;
+
+/// Bits in a bitmap chunk
+const CHUNK_BITS: u64 = 1024;
+
+/// Keeps track of the segments received for the txhashset at a given
+/// header, validating and storing each of them.
+pub struct Desegmenter {
+	header: BlockHeader,
+	dir: PathBuf,
+	/// Output and bitmap roots, known once they've been checked against the
+	/// merged output root of the header
+	output_root: Option<Hash>,
+	bitmap_root: Option<Hash>,
+	bitmap_mmr_size: Option<u64>,
+	bitmap_chunks: HashMap<u64, BitmapChunk>,
+	received: HashMap<SegmentType, BTreeSet<u64>>,
+}
+
+impl Desegmenter {
+	/// Create a new desegmenter for the txhashset at the provided header,
+	/// storing the validated segments in the provided directory.
+	pub fn new(header: BlockHeader, dir: PathBuf) -> Result<Desegmenter, Error> {
+		// the bitmap root is only committed to in the header from version 3
+		if header.version < HeaderVersion(3) {
+			return Err(ErrorKind::InvalidBlockVersion(header.version).into());
+		}
+		if dir.exists() {
+			fs::remove_dir_all(&dir)?;
+		}
+		fs::create_dir_all(&dir)?;
+
+		Ok(Desegmenter {
+			header,
+			dir,
+			output_root: None,
+			bitmap_root: None,
+			bitmap_mmr_size: None,
+			bitmap_chunks: HashMap::new(),
+			received: HashMap::new(),
+		})
+	}
+
+	/// The header of the txhashset being downloaded.
+	pub fn header(&self) -> &BlockHeader {
+		&self.header
+	}
+
+	/// Directory the validated segments are stored in.
+	pub fn dir(&self) -> &PathBuf {
+		&self.dir
+	}
+
+	/// Number of segments in each tree, the bitmap one being only known once
+	/// we've received the first bitmap segment.
+	fn segment_count(&self, segment_type: SegmentType) -> Option<u64> {
+		let mmr_size = match segment_type {
+			SegmentType::Bitmap => self.bitmap_mmr_size?,
+			SegmentType::Output | SegmentType::RangeProof => self.header.output_mmr_size,
+			SegmentType::Kernel => self.header.kernel_mmr_size,
+		};
+		Some(SegmentIdentifier::count_segments_required(
+			mmr_size,
+			segment_type.segment_height(),
+		))
+	}
+
+	fn received_count(&self, segment_type: SegmentType) -> u64 {
+		self.received
+			.get(&segment_type)
+			.map(|x| x.len() as u64)
+			.unwrap_or(0)
+	}
+
+	fn is_tree_complete(&self, segment_type: SegmentType) -> bool {
+		self.segment_count(segment_type)
+			.map_or(false, |count| self.received_count(segment_type) == count)
+	}
+
+	/// Whether every segment of every tree has been received.
+	pub fn is_complete(&self) -> bool {
+		[
+			SegmentType::Bitmap,
+			SegmentType::Output,
+			SegmentType::RangeProof,
+			SegmentType::Kernel,
+		]
+		.iter()
+		.all(|x| self.is_tree_complete(*x))
+	}
+
+	/// Number of segments received so far and total number of segments,
+	/// counting a single bitmap segment until we know how many there are.
+	pub fn progress(&self) -> (u64, u64) {
+		let types = [
+			SegmentType::Bitmap,
+			SegmentType::Output,
+			SegmentType::RangeProof,
+			SegmentType::Kernel,
+		];
+		let received = types.iter().map(|x| self.received_count(*x)).sum();
+		let total = types
+			.iter()
+			.map(|x| self.segment_count(*x).unwrap_or(1))
+			.sum();
+		(received, total)
+	}
+
+	/// The segments we're still missing, in the order they should be
+	/// requested. Outputs and rangeproofs are checked against the bitmap so
+	/// they're only asked for once we have all of it.
+	pub fn missing_segments(&self) -> Vec<SegmentTypeIdentifier> {
+		let mut missing = vec![];
+		let mut add_missing = |segment_type: SegmentType, count: u64| {
+			let received = self.received.get(&segment_type);
+			for idx in 0..count {
+				if !received.map_or(false, |x| x.contains(&idx)) {
+					missing.push(SegmentTypeIdentifier::new(segment_type, idx));
+				}
+			}
+		};
+
+		add_missing(
+			SegmentType::Bitmap,
+			self.segment_count(SegmentType::Bitmap).unwrap_or(1),
+		);
+		if self.is_tree_complete(SegmentType::Bitmap) {
+			for segment_type in &[SegmentType::Output, SegmentType::RangeProof] {
+				add_missing(
+					*segment_type,
+					self.segment_count(*segment_type).unwrap_or(0),
+				);
+			}
+		}
+		add_missing(
+			SegmentType::Kernel,
+			self.segment_count(SegmentType::Kernel).unwrap_or(0),
+		);
+		missing
+	}
+
+	fn check_height(segment_type: SegmentType, id: SegmentIdentifier) -> Result<(), Error> {
+		if id.height != segment_type.segment_height() {
+			return Err(ErrorKind::InvalidTxHashSet(format!(
+				"unexpected {:?} segment height {}",
+				segment_type, id.height
+			))
+			.into());
+		}
+		Ok(())
+	}
+
+	/// Checks the output and bitmap roots provided with a segment hash to the
+	/// output root of the header, then pins them for the next segments.
+	fn check_output_roots(&mut self, output_root: Hash, bitmap_root: Hash) -> Result<(), Error> {
+		if self.output_root.map_or(false, |x| x != output_root)
+			|| self.bitmap_root.map_or(false, |x| x != bitmap_root)
+		{
+			return Err(ErrorKind::InvalidRoot.into());
+		}
+		let merged_root = (output_root, bitmap_root).hash_with_index(self.header.output_mmr_size);
+		if merged_root != self.header.output_root {
+			return Err(ErrorKind::InvalidRoot.into());
+		}
+		self.output_root = Some(output_root);
+		self.bitmap_root = Some(bitmap_root);
+		Ok(())
+	}
+
+	/// Validates and stores a segment of the bitmap accumulator, provided
+	/// along with the root of the output MMR and the size of the bitmap MMR.
+	pub fn add_bitmap_segment(
+		&mut self,
+		segment: Segment<BitmapChunk>,
+		output_root: Hash,
+		bitmap_mmr_size: u64,
+	) -> Result<(), Error> {
+		Desegmenter::check_height(SegmentType::Bitmap, segment.identifier())?;
+		if let Some(size) = self.bitmap_mmr_size {
+			if size != bitmap_mmr_size {
+				return Err(ErrorKind::InvalidRoot.into());
+			}
+		}
+		if !segment.is_unpruned() {
+			return Err(ErrorKind::InvalidTxHashSet("pruned bitmap segment".to_owned()).into());
+		}
+		let bitmap_root = segment.root(bitmap_mmr_size)?;
+		self.check_output_roots(output_root, bitmap_root)?;
+		self.bitmap_mmr_size = Some(bitmap_mmr_size);
+
+		for (pos, chunk) in segment.leaf_iter() {
+			self.bitmap_chunks
+				.insert(pmmr::n_leaves(pos) - 1, chunk.clone());
+		}
+		self.store(SegmentType::Bitmap, &segment)
+	}
+
+	/// Validates and stores a segment of the output MMR, provided along with
+	/// the root of the bitmap accumulator.
+	pub fn add_output_segment(
+		&mut self,
+		segment: Segment<OutputIdentifier>,
+		bitmap_root: Hash,
+	) -> Result<(), Error> {
+		Desegmenter::check_height(SegmentType::Output, segment.identifier())?;
+		let output_root = segment.root(self.header.output_mmr_size)?;
+		self.check_output_roots(output_root, bitmap_root)?;
+		self.check_unspent(&segment)?;
+		self.store(SegmentType::Output, &segment)
+	}
+
+	/// Validates and stores a segment of the rangeproof MMR.
+	pub fn add_rangeproof_segment(&mut self, segment: Segment<RangeProof>) -> Result<(), Error> {
+		Desegmenter::check_height(SegmentType::RangeProof, segment.identifier())?;
+		segment.validate(self.header.output_mmr_size, self.header.range_proof_root)?;
+		self.check_unspent(&segment)?;
+		self.store(SegmentType::RangeProof, &segment)
+	}
+
+	/// Validates and stores a segment of the kernel MMR.
+	pub fn add_kernel_segment(&mut self, segment: Segment<TxKernel>) -> Result<(), Error> {
+		Desegmenter::check_height(SegmentType::Kernel, segment.identifier())?;
+		if !segment.is_unpruned() {
+			return Err(ErrorKind::InvalidTxHashSet("pruned kernel segment".to_owned()).into());
+		}
+		segment.validate(self.header.kernel_mmr_size, self.header.kernel_root)?;
+		self.store(SegmentType::Kernel, &segment)
+	}
+
+	/// Whether the output at the provided position was unspent, according
+	/// to the bitmap. Chunks missing on the right of the bitmap are empty.
+	fn is_unspent(&self, pos: u64) -> bool {
+		let idx = pmmr::n_leaves(pos) - 1;
+		self.bitmap_chunks
+			.get(&(idx / CHUNK_BITS))
+			.map_or(false, |x| x.get(idx % CHUNK_BITS))
+	}
+
+	/// Checks a segment holds exactly the leaves of the outputs unspent, as
+	/// per the bitmap, so no peer can withhold any of them.
+	fn check_unspent<T>(&self, segment: &Segment<T>) -> Result<(), Error> {
+		if !self.is_tree_complete(SegmentType::Bitmap) {
+			return Err(ErrorKind::SyncError("bitmap not downloaded yet".to_owned()).into());
+		}
+		let (first, last) = segment
+			.identifier()
+			.pmmr_range(self.header.output_mmr_size)
+			.ok_or_else(|| ErrorKind::InvalidTxHashSet("segment out of range".to_owned()))?;
+		let mut leaves = segment.leaf_iter().map(|(pos, _)| pos).peekable();
+		for pos in (first..=last).filter(|x| pmmr::is_leaf(*x)) {
+			let included = leaves.peek() == Some(&pos);
+			if included {
+				leaves.next();
+			}
+			if included != self.is_unspent(pos) {
+				return Err(ErrorKind::InvalidTxHashSet(format!(
+					"segment {:?} doesn't match the bitmap at {}",
+					segment.identifier(),
+					pos
+				))
+				.into());
+			}
+		}
+		Ok(())
+	}
+
+	/// Builds the output, rangeproof and kernel MMRs from the segments once
+	/// all of them have been received, under the txhashset dir of the
+	/// provided root dir. Spent outputs are pruned and compacted, so it can
+	/// then be opened and fully validated like a txhashset archive.
+	pub fn write_txhashset(&self, root_dir: &Path) -> Result<(), Error> {
+		if !self.is_complete() {
+			return Err(ErrorKind::SyncError("missing txhashset segments".to_owned()).into());
+		}
+		let dir = root_dir.join(TXHASHSET_SUBDIR);
+
+		// the data of a spent output is only kept when its sibling is
+		// unspent, it's never read back so we don't need the actual one
+		let output_placeholder =
+			OutputIdentifier::new(OutputFeatures::Plain, &Commitment::from_vec(vec![0; 33]));
+		self.write_pmmr::<Output>(
+			&dir.join(OUTPUT_SUBDIR),
+			SegmentType::Output,
+			self.header.output_mmr_size,
+			ProtocolVersion(1),
+			Some(output_placeholder),
+		)?;
+		self.write_pmmr::<RangeProof>(
+			&dir.join(RANGE_PROOF_SUBDIR),
+			SegmentType::RangeProof,
+			self.header.output_mmr_size,
+			ProtocolVersion(1),
+			Some(RangeProof::zero()),
+		)?;
+		self.write_pmmr::<TxKernel>(
+			&dir.join(KERNEL_SUBDIR),
+			SegmentType::Kernel,
+			self.header.kernel_mmr_size,
+			ProtocolVersion(2),
+			None,
+		)?;
+
+		debug!(
+			"desegmenter: txhashset for {} built from segments",
+			self.header.hash()
+		);
+		Ok(())
+	}
+
+	/// Writes a PMMR position by position, hashing up the segments in turn.
+	/// Only the kernel MMR has no placeholder, none of its leaves being
+	/// removed.
+	fn write_pmmr<T>(
+		&self,
+		dir: &Path,
+		segment_type: SegmentType,
+		mmr_size: u64,
+		version: ProtocolVersion,
+		placeholder: Option<T::E>,
+	) -> Result<(), Error>
+	where
+		T: PMMRable,
+		T::E: Readable + PMMRIndexHashable,
+	{
+		if dir.exists() {
+			fs::remove_dir_all(dir)?;
+		}
+		fs::create_dir_all(dir)?;
+		let prunable = placeholder.is_some();
+		let mut backend = PMMRBackend::<T>::new(dir, prunable, version, None)?;
+		if prunable {
+			let removed = (1..=pmmr::n_leaves(mmr_size))
+				.map(pmmr::insertion_to_pmmr_index)
+				.filter(|x| !self.is_unspent(*x));
+			backend.init_prune_list(removed)?;
+		}
+
+		let missing = |pos: u64| -> Error {
+			ErrorKind::InvalidTxHashSet(format!(
+				"{:?} segments missing the hash at {}",
+				segment_type, pos
+			))
+			.into()
+		};
+
+		// hashes of the subtrees on the left still waiting for their sibling
+		let mut stack: Vec<Option<Hash>> = vec![];
+		let mut next_pos = 1;
+		let count =
+			SegmentIdentifier::count_segments_required(mmr_size, segment_type.segment_height());
+		for idx in 0..count {
+			let segment: Segment<T::E> = self.load(segment_type, idx)?;
+			let (_, last) = segment
+				.identifier()
+				.pmmr_range(mmr_size)
+				.ok_or_else(|| ErrorKind::InvalidTxHashSet("segment out of range".to_owned()))?;
+			let mut leaves = segment.leaf_iter().peekable();
+			let mut hashes = segment.hash_iter().peekable();
+
+			// the parents above the previous segments come first
+			for pos in next_pos..=last {
+				let height = pmmr::bintree_postorder_height(pos);
+				let mut data = None;
+				let hash = if hashes.peek().map(|x| x.0) == Some(pos) {
+					if height > 0 {
+						stack.truncate(stack.len().saturating_sub(2));
+					}
+					hashes.next().map(|x| x.1)
+				} else if height == 0 {
+					match leaves.peek() {
+						Some((leaf_pos, _)) if *leaf_pos == pos => {
+							let (_, leaf) = leaves.next().unwrap();
+							data = Some(leaf);
+							Some(leaf.hash_with_index(pos - 1))
+						}
+						_ => None,
+					}
+				} else {
+					let rhash = stack.pop().ok_or_else(|| missing(pos))?;
+					let lhash = stack.pop().ok_or_else(|| missing(pos))?;
+					match (lhash, rhash) {
+						(Some(lhash), Some(rhash)) => Some((lhash, rhash).hash_with_index(pos - 1)),
+						_ => None,
+					}
+				};
+				stack.push(hash);
+
+				if backend.is_compacted(pos) {
+					continue;
+				}
+				backend.append_hash(&hash.ok_or_else(|| missing(pos))?)?;
+				if height == 0 {
+					match (data, &placeholder) {
+						(Some(data), _) => backend.append_leaf_data(pos, data, false)?,
+						(None, Some(placeholder)) => {
+							backend.append_leaf_data(pos, placeholder, true)?
+						}
+						(None, None) => return Err(missing(pos)),
+					}
+				}
+			}
+			next_pos = last + 1;
+		}
+
+		backend.sync()?;
+		if backend.unpruned_size() != mmr_size {
+			return Err(missing(mmr_size));
+		}
+		Ok(())
+	}
+
+	fn load<T: Readable>(&self, segment_type: SegmentType, idx: u64) -> Result<Segment<T>, Error> {
+		let mut file = File::open(self.segment_path(segment_type, idx))?;
+		Ok(ser::deserialize_default(&mut file).map_err(ErrorKind::SerErr)?)
+	}
+
+	fn segment_path(&self, segment_type: SegmentType, idx: u64) -> PathBuf {
+		self.dir
+			.join(format!("{:?}-{}.seg", segment_type, idx).to_lowercase())
+	}
+
+	fn store<T: Writeable>(
+		&mut self,
+		segment_type: SegmentType,
+		segment: &Segment<T>,
+	) -> Result<(), Error> {
+		let id = segment.identifier();
+		let mut file = File::create(self.segment_path(segment_type, id.idx))?;
+		ser::serialize_default(&mut file, segment).map_err(ErrorKind::SerErr)?;
+		self.received
+			.entry(segment_type)
+			.or_default()
+			.insert(id.idx);
+
+		trace!(
+			"desegmenter: {:?} segment {} for {} stored",
+			segment_type,
+			id.idx,
+			self.header.hash(),
+		);
+		Ok(())
+	}
+}
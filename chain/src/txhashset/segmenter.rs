@@ -0,0 +1,138 @@
+// Copyright 2020 The Kepler Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Generation of the txhashset segments we serve to peers doing a segmented
+//! state sync.
+
+use std::sync::Arc;
+use std::time::Instant;
+
+use croaring::Bitmap;
+
+use crate::core::core::hash::{Hash, Hashed};
+use crate::core::core::pmmr::{Segment, SegmentIdentifier};
+use crate::core::core::{BlockHeader, OutputIdentifier, TxKernel};
+use crate::error::Error;
+use crate::txhashset::{BitmapAccumulator, BitmapChunk, TxHashSet};
+use crate::util::secp::pedersen::RangeProof;
+use crate::util::RwLock;
+
+/// Serves segments of the txhashset as it was at a given header, usually our
+/// archive header. Outputs and rangeproofs spent since are sent as hashes,
+/// per the snapshot of the unspent outputs taken when building it.
+#[derive(Clone)]
+pub struct Segmenter {
+	txhashset: Arc<RwLock<TxHashSet>>,
+	bitmap_snapshot: Arc<BitmapAccumulator>,
+	unspent_snapshot: Arc<Bitmap>,
+	header: BlockHeader,
+}
+
+impl Segmenter {
+	/// Create a new segmenter given the txhashset and the snapshots of the
+	/// bitmap accumulator and the unspent outputs at the header.
+	pub fn new(
+		txhashset: Arc<RwLock<TxHashSet>>,
+		bitmap_snapshot: BitmapAccumulator,
+		unspent_snapshot: Bitmap,
+		header: BlockHeader,
+	) -> Segmenter {
+		Segmenter {
+			txhashset,
+			bitmap_snapshot: Arc::new(bitmap_snapshot),
+			unspent_snapshot: Arc::new(unspent_snapshot),
+			header,
+		}
+	}
+
+	/// The header our segments are taken at.
+	pub fn header(&self) -> &BlockHeader {
+		&self.header
+	}
+
+	/// Root of the bitmap accumulator, needed by peers to check our output
+	/// segments against the merged output root of the header.
+	pub fn bitmap_root(&self) -> Hash {
+		self.bitmap_snapshot.root()
+	}
+
+	/// Size of the bitmap accumulator MMR, which depends on the unspent
+	/// outputs as trailing empty chunks are left out.
+	pub fn bitmap_mmr_size(&self) -> u64 {
+		self.bitmap_snapshot.readonly_pmmr().unpruned_size()
+	}
+
+	/// Root of the output MMR, needed by peers to check our bitmap segments
+	/// against the merged output root of the header.
+	pub fn output_root(&self) -> Hash {
+		self.txhashset.read().output_pmmr_at(&self.header).root()
+	}
+
+	/// A segment of the kernel MMR.
+	pub fn kernel_segment(&self, id: SegmentIdentifier) -> Result<Segment<TxKernel>, Error> {
+		let now = Instant::now();
+		let txhashset = self.txhashset.read();
+		let segment = Segment::from_pmmr(id, &txhashset.kernel_pmmr_at(&self.header), None)?;
+		self.log("kernel", id, now);
+		Ok(segment)
+	}
+
+	/// A segment of the bitmap accumulator MMR.
+	pub fn bitmap_segment(&self, id: SegmentIdentifier) -> Result<Segment<BitmapChunk>, Error> {
+		let now = Instant::now();
+		let segment = Segment::from_pmmr(id, &self.bitmap_snapshot.readonly_pmmr(), None)?;
+		self.log("bitmap", id, now);
+		Ok(segment)
+	}
+
+	/// A segment of the output MMR, only holding the outputs still unspent.
+	pub fn output_segment(
+		&self,
+		id: SegmentIdentifier,
+	) -> Result<Segment<OutputIdentifier>, Error> {
+		let now = Instant::now();
+		let txhashset = self.txhashset.read();
+		let segment = Segment::from_pmmr(
+			id,
+			&txhashset.output_pmmr_at(&self.header),
+			Some(&self.unspent_snapshot),
+		)?;
+		self.log("output", id, now);
+		Ok(segment)
+	}
+
+	/// A segment of the rangeproof MMR, only holding the rangeproofs of the
+	/// outputs still unspent.
+	pub fn rangeproof_segment(&self, id: SegmentIdentifier) -> Result<Segment<RangeProof>, Error> {
+		let now = Instant::now();
+		let txhashset = self.txhashset.read();
+		let segment = Segment::from_pmmr(
+			id,
+			&txhashset.rangeproof_pmmr_at(&self.header),
+			Some(&self.unspent_snapshot),
+		)?;
+		self.log("rangeproof", id, now);
+		Ok(segment)
+	}
+
+	fn log(&self, name: &str, id: SegmentIdentifier, start: Instant) {
+		debug!(
+			"segmenter: {} segment {:?} at {} took {}ms",
+			name,
+			id,
+			self.header.hash(),
+			start.elapsed().as_millis(),
+		);
+	}
+}
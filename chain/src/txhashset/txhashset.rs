@@ -16,11 +16,11 @@
 //! kernel) along the overall header MMR conveniently and transactionally.
 
 use crate::core::core::committed::Committed;
-use crate::core::core::hash::{Hash, Hashed};
+use crate::core::core::hash::{Hash, HashWriter, Hashed};
 use crate::core::core::merkle_proof::MerkleProof;
 use crate::core::core::pmmr::{self, Backend, ReadonlyPMMR, RewindablePMMR, PMMR};
 use crate::core::core::{Block, BlockHeader, Input, Output, OutputIdentifier, TxKernel};
-use crate::core::ser::{PMMRIndexHashable, PMMRable, ProtocolVersion};
+use crate::core::ser::{PMMRIndexHashable, PMMRable, ProtocolVersion, Writer};
 use crate::error::{Error, ErrorKind};
 use crate::store::{Batch, ChainStore};
 use crate::txhashset::bitmap_accumulator::BitmapAccumulator;
@@ -33,15 +33,16 @@ use kepler_store;
 use kepler_store::pmmr::{clean_files_by_prefix, PMMRBackend};
 use std::collections::HashSet;
 use std::fs::{self, File};
+use std::io::Read;
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use std::time::Instant;
 
-const TXHASHSET_SUBDIR: &str = "txhashset";
+pub(crate) const TXHASHSET_SUBDIR: &str = "txhashset";
 
-const OUTPUT_SUBDIR: &str = "output";
-const RANGE_PROOF_SUBDIR: &str = "rangeproof";
-const KERNEL_SUBDIR: &str = "kernel";
+pub(crate) const OUTPUT_SUBDIR: &str = "output";
+pub(crate) const RANGE_PROOF_SUBDIR: &str = "rangeproof";
+pub(crate) const KERNEL_SUBDIR: &str = "kernel";
 
 const TXHASHSET_ZIP: &str = "txhashset_snapshot";
 
@@ -342,6 +343,31 @@ impl TxHashSet {
 		}
 	}
 
+	/// Readonly view of the output MMR as it was at the provided header.
+	/// Only the size is rewound, leaves spent since are still part of the view.
+	pub fn output_pmmr_at(
+		&self,
+		header: &BlockHeader,
+	) -> ReadonlyPMMR<'_, Output, PMMRBackend<Output>> {
+		ReadonlyPMMR::at(&self.output_pmmr_h.backend, header.output_mmr_size)
+	}
+
+	/// Readonly view of the rangeproof MMR as it was at the provided header.
+	pub fn rangeproof_pmmr_at(
+		&self,
+		header: &BlockHeader,
+	) -> ReadonlyPMMR<'_, RangeProof, PMMRBackend<RangeProof>> {
+		ReadonlyPMMR::at(&self.rproof_pmmr_h.backend, header.output_mmr_size)
+	}
+
+	/// Readonly view of the kernel MMR as it was at the provided header.
+	pub fn kernel_pmmr_at(
+		&self,
+		header: &BlockHeader,
+	) -> ReadonlyPMMR<'_, TxKernel, PMMRBackend<TxKernel>> {
+		ReadonlyPMMR::at(&self.kernel_pmmr_h.backend, header.kernel_mmr_size)
+	}
+
 	/// Return Commit's MMR position
 	pub fn get_output_pos(&self, commit: &Commitment) -> Result<u64, Error> {
 		Ok(self.commit_index.get_output_pos(&commit)?)
@@ -355,6 +381,21 @@ impl TxHashSet {
 			.map_err(|_| ErrorKind::MerkleProof.into())
 	}
 
+	/// build a new merkle proof for the kernel at the given position, against
+	/// the kernel MMR as it was at the provided header.
+	pub fn kernel_merkle_proof(
+		&mut self,
+		header: &BlockHeader,
+		pos: u64,
+	) -> Result<MerkleProof, Error> {
+		if pos > header.kernel_mmr_size {
+			return Err(ErrorKind::MerkleProof.into());
+		}
+		PMMR::at(&mut self.kernel_pmmr_h.backend, header.kernel_mmr_size)
+			.merkle_proof(pos)
+			.map_err(|_| ErrorKind::MerkleProof.into())
+	}
+
 	/// Compact the MMR data files and flush the rm logs
 	pub fn compact(
 		&mut self,
@@ -1219,6 +1260,16 @@ impl<'a> Extension<'a> {
 		Ok(())
 	}
 
+	/// The bitmap accumulator, as of the current extension head.
+	pub fn bitmap_accumulator(&self) -> &BitmapAccumulator {
+		&self.bitmap_accumulator
+	}
+
+	/// Positions of the outputs unspent as of the current extension head.
+	pub fn unspent_output_bitmap(&self) -> Bitmap {
+		self.output_pmmr.leaf_pos_iter().map(|x| x as u32).collect()
+	}
+
 	/// Current root hashes and sums (if applicable) for the Output, range proof
 	/// and kernel MMRs.
 	pub fn roots(&self) -> Result<TxHashSetRoots, Error> {
@@ -1492,6 +1543,7 @@ pub fn zip_read(root_dir: String, header: &BlockHeader) -> Result<File, Error> {
 
 	let txhashset_path = Path::new(&root_dir).join(TXHASHSET_SUBDIR);
 	let zip_path = Path::new(&root_dir).join(txhashset_zip);
+	let zip_hash_path = zip_path.with_extension("zip.hash");
 
 	// if file exist, just re-use it
 	let zip_file = File::open(zip_path.clone());
@@ -1532,6 +1584,11 @@ pub fn zip_read(root_dir: String, header: &BlockHeader) -> Result<File, Error> {
 		// Copy file to another dir
 		file::copy_dir_to(&txhashset_path, &temp_txhashset_path)?;
 
+		// The hash of a previous archive doesn't apply to the new one
+		if zip_hash_path.exists() {
+			fs::remove_file(&zip_hash_path)?;
+		}
+
 		let zip_file = File::create(zip_path.clone())?;
 
 		// Explicit list of files to add to our zip archive.
@@ -1563,6 +1620,42 @@ pub fn zip_read(root_dir: String, header: &BlockHeader) -> Result<File, Error> {
 	Ok(zip_file)
 }
 
+/// Hash of the txhashset archive of the provided header, computed on the
+/// first call and kept next to the archive for the next ones. The archive
+/// must have been created by `zip_read` already.
+pub fn zip_hash(root_dir: String, header: &BlockHeader) -> Result<Hash, Error> {
+	let txhashset_zip = format!("{}_{}.zip", TXHASHSET_ZIP, header.hash().to_string());
+	let zip_path = Path::new(&root_dir).join(txhashset_zip);
+	let zip_hash_path = zip_path.with_extension("zip.hash");
+
+	if let Ok(hex) = fs::read_to_string(&zip_hash_path) {
+		if let Ok(hash) = Hash::from_hex(hex.trim()) {
+			return Ok(hash);
+		}
+	}
+	let hash = archive_hash(&mut File::open(&zip_path)?)?;
+	fs::write(&zip_hash_path, hash.to_hex())?;
+	Ok(hash)
+}
+
+/// Blake2b hash of the whole content of a txhashset archive, advertised
+/// along with the archive so a download resumed from several peers can be
+/// checked before being unzipped.
+pub fn archive_hash(archive: &mut dyn Read) -> Result<Hash, Error> {
+	let mut hasher = HashWriter::default();
+	let mut buf = vec![0u8; 64_000];
+	loop {
+		let n = archive.read(&mut buf)?;
+		if n == 0 {
+			break;
+		}
+		hasher
+			.write_fixed_bytes(&buf[..n])
+			.map_err(|e| ErrorKind::SerErr(e))?;
+	}
+	Ok(hasher.into_hash())
+}
+
 // Explicit list of files to extract from our zip archive.
 // We include *only* these files when building the txhashset zip.
 // We extract *only* these files when receiving a txhashset zip.
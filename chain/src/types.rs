@@ -15,10 +15,13 @@
 //! Base types that the block chain pipeline requires.
 
 use chrono::prelude::{DateTime, Utc};
+use std::mem;
 use std::sync::Arc;
 
 use crate::core::core::hash::{Hash, Hashed, ZERO_HASH};
-use crate::core::core::{Block, BlockHeader, HeaderVersion};
+use crate::core::core::merkle_proof::MerkleProof;
+use crate::core::core::pmmr::{self, SegmentIdentifier};
+use crate::core::core::{Block, BlockHeader, HeaderVersion, OutputIdentifier, TxKernel};
 use crate::core::pow::Difficulty;
 use crate::core::ser::{self, PMMRIndexHashable, Readable, Reader, Writeable, Writer};
 use crate::error::{Error, ErrorKind};
@@ -63,6 +66,11 @@ pub enum SyncStatus {
 		downloaded_size: u64,
 		total_size: u64,
 	},
+	/// Downloading and validating the txhashset segments
+	TxHashsetPibd {
+		recv_segments: u64,
+		total_segments: u64,
+	},
 	/// Setting up before validation
 	TxHashsetSetup,
 	/// Validating the kernels
@@ -91,6 +99,7 @@ pub enum SyncStatus {
 pub struct SyncState {
 	current: RwLock<SyncStatus>,
 	sync_error: Arc<RwLock<Option<Error>>>,
+	adapter: RwLock<Option<Arc<dyn SyncStatusAdapter + Send + Sync>>>,
 }
 
 impl SyncState {
@@ -99,9 +108,15 @@ impl SyncState {
 		SyncState {
 			current: RwLock::new(SyncStatus::Initial),
 			sync_error: Arc::new(RwLock::new(None)),
+			adapter: RwLock::new(None),
 		}
 	}
 
+	/// Sets the adapter notified of the sync status transitions
+	pub fn init_adapter(&self, adapter: Arc<dyn SyncStatusAdapter + Send + Sync>) {
+		*self.adapter.write() = Some(adapter);
+	}
+
 	/// Whether the current state matches any active syncing operation.
 	/// Note: This includes our "initial" state.
 	pub fn is_syncing(&self) -> bool {
@@ -119,21 +134,34 @@ impl SyncState {
 			return;
 		}
 
-		let mut status = self.current.write();
+		let old_status = {
+			let mut status = self.current.write();
+
+			debug!("sync_state: sync_status: {:?} -> {:?}", *status, new_status,);
 
-		debug!("sync_state: sync_status: {:?} -> {:?}", *status, new_status,);
+			let old_status = *status;
+			*status = new_status;
+			old_status
+		};
 
-		*status = new_status;
+		// progress within a phase isn't a transition
+		if mem::discriminant(&old_status) == mem::discriminant(&new_status) {
+			return;
+		}
+		if let Some(adapter) = self.adapter.read().as_ref() {
+			adapter.sync_status_changed(&old_status, &new_status);
+		}
 	}
 
-	/// Update txhashset downloading progress
+	/// Update txhashset downloading progress, of the archive or the segments
 	pub fn update_txhashset_download(&self, new_status: SyncStatus) -> bool {
-		if let SyncStatus::TxHashsetDownload { .. } = new_status {
-			let mut status = self.current.write();
-			*status = new_status;
-			true
-		} else {
-			false
+		match new_status {
+			SyncStatus::TxHashsetDownload { .. } | SyncStatus::TxHashsetPibd { .. } => {
+				let mut status = self.current.write();
+				*status = new_status;
+				true
+			}
+			_ => false,
 		}
 	}
 
@@ -258,6 +286,56 @@ impl OutputRoots {
 	}
 }
 
+/// The trees making up the txhashset, as served in segments during a
+/// segmented state sync.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash, PartialOrd, Ord)]
+pub enum SegmentType {
+	/// Chunks of the bitmap of unspent outputs
+	Bitmap,
+	/// Outputs
+	Output,
+	/// Rangeproofs
+	RangeProof,
+	/// Kernels
+	Kernel,
+}
+
+impl SegmentType {
+	/// Height of the segments we request for this tree, sized so that a
+	/// segment stays within a couple hundred KB.
+	pub fn segment_height(&self) -> u8 {
+		match self {
+			SegmentType::Bitmap => 9,
+			SegmentType::Output => 11,
+			SegmentType::RangeProof => 7,
+			SegmentType::Kernel => 9,
+		}
+	}
+}
+
+/// Identifies a segment of one of the txhashset trees.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
+pub struct SegmentTypeIdentifier {
+	/// The tree the segment belongs to
+	pub segment_type: SegmentType,
+	/// The segment within the tree
+	pub identifier: SegmentIdentifier,
+}
+
+impl SegmentTypeIdentifier {
+	/// Identifier of the segment of the given tree at the given index, with
+	/// the height we use for that tree.
+	pub fn new(segment_type: SegmentType, idx: u64) -> SegmentTypeIdentifier {
+		SegmentTypeIdentifier {
+			segment_type,
+			identifier: SegmentIdentifier {
+				height: segment_type.segment_height(),
+				idx,
+			},
+		}
+	}
+}
+
 /// Minimal struct representing a known MMR position and associated block height.
 #[derive(Debug)]
 pub struct CommitPos {
@@ -283,6 +361,121 @@ impl Writeable for CommitPos {
 	}
 }
 
+/// Proof that an output is in the output MMR of a block, along with what a
+/// node only holding headers needs to check it against the header.
+/// This only proves inclusion: spent outputs stay in the MMR, and the bitmap
+/// root is carried to rebuild the header output root, not to prove the output
+/// is unspent.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct OutputMerkleProof {
+	/// Hash of the header the proof is for
+	pub block_hash: Hash,
+	/// The output proven
+	pub output: OutputIdentifier,
+	/// Position of the output in the output MMR
+	pub mmr_index: u64,
+	/// Root of the output MMR, merged with the bitmap root into the output
+	/// root of the header
+	pub output_pmmr_root: Hash,
+	/// Root of the bitmap accumulator
+	pub bitmap_root: Hash,
+	/// Path from the output to the root of the output MMR
+	pub proof: MerkleProof,
+}
+
+impl OutputMerkleProof {
+	/// Checks the proof against the output root of the provided header.
+	pub fn verify(&self, header: &BlockHeader) -> Result<(), Error> {
+		let roots = OutputRoots {
+			pmmr_root: self.output_pmmr_root,
+			bitmap_root: self.bitmap_root,
+		};
+		if header.hash() != self.block_hash || roots.root(header) != header.output_root {
+			return Err(ErrorKind::InvalidRoot.into());
+		}
+		// the position comes from a peer, it has to be a leaf within the MMR
+		if self.proof.mmr_size != header.output_mmr_size
+			|| self.mmr_index == 0
+			|| self.mmr_index > header.output_mmr_size
+			|| !pmmr::is_leaf(self.mmr_index)
+		{
+			return Err(ErrorKind::MerkleProof.into());
+		}
+		self.proof
+			.verify(self.output_pmmr_root, &self.output, self.mmr_index)
+			.map_err(|_| ErrorKind::MerkleProof.into())
+	}
+}
+
+impl Readable for OutputMerkleProof {
+	fn read(reader: &mut dyn Reader) -> Result<OutputMerkleProof, ser::Error> {
+		Ok(OutputMerkleProof {
+			block_hash: Hash::read(reader)?,
+			output: OutputIdentifier::read(reader)?,
+			mmr_index: reader.read_u64()?,
+			output_pmmr_root: Hash::read(reader)?,
+			bitmap_root: Hash::read(reader)?,
+			proof: MerkleProof::read(reader)?,
+		})
+	}
+}
+
+impl Writeable for OutputMerkleProof {
+	fn write<W: Writer>(&self, writer: &mut W) -> Result<(), ser::Error> {
+		self.block_hash.write(writer)?;
+		self.output.write(writer)?;
+		writer.write_u64(self.mmr_index)?;
+		self.output_pmmr_root.write(writer)?;
+		self.bitmap_root.write(writer)?;
+		self.proof.write(writer)
+	}
+}
+
+/// Proof that a kernel is in the kernel MMR of a block.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct KernelMerkleProof {
+	/// Hash of the header the proof is for
+	pub block_hash: Hash,
+	/// The kernel proven
+	pub kernel: TxKernel,
+	/// Position of the kernel in the kernel MMR
+	pub mmr_index: u64,
+	/// Path from the kernel to the kernel root of the header
+	pub proof: MerkleProof,
+}
+
+impl KernelMerkleProof {
+	/// Checks the proof against the kernel root of the provided header.
+	pub fn verify(&self, header: &BlockHeader) -> Result<(), Error> {
+		if header.hash() != self.block_hash {
+			return Err(ErrorKind::InvalidRoot.into());
+		}
+		self.proof
+			.verify_kernel(header, &self.kernel, self.mmr_index)
+			.map_err(|_| ErrorKind::MerkleProof.into())
+	}
+}
+
+impl Readable for KernelMerkleProof {
+	fn read(reader: &mut dyn Reader) -> Result<KernelMerkleProof, ser::Error> {
+		Ok(KernelMerkleProof {
+			block_hash: Hash::read(reader)?,
+			kernel: TxKernel::read(reader)?,
+			mmr_index: reader.read_u64()?,
+			proof: MerkleProof::read(reader)?,
+		})
+	}
+}
+
+impl Writeable for KernelMerkleProof {
+	fn write<W: Writer>(&self, writer: &mut W) -> Result<(), ser::Error> {
+		self.block_hash.write(writer)?;
+		self.kernel.write(writer)?;
+		writer.write_u64(self.mmr_index)?;
+		self.proof.write(writer)
+	}
+}
+
 /// The tip of a fork. A handle to the fork ancestry from its leaf in the
 /// blockchain tree. References the max height and the latest and previous
 /// blocks
@@ -361,6 +554,19 @@ pub trait ChainAdapter {
 	/// The blockchain pipeline has accepted this block as valid and added
 	/// it to our chain.
 	fn block_accepted(&self, block: &Block, status: BlockStatus, opts: Options);
+
+	/// The chain head has been moved to another fork. The blocks of the
+	/// `disconnected` headers have been rewound (most recent first) and those
+	/// of the `connected` headers applied (oldest first).
+	fn reorg(&self, _disconnected: &[BlockHeader], _connected: &[BlockHeader], _opts: Options) {}
+}
+
+/// Bridge between the sync state and the rest of the system, notified of
+/// the sync status transitions.
+pub trait SyncStatusAdapter {
+	/// The sync status has moved from the phase of `old` to the phase of
+	/// `new`, progress within a phase isn't notified
+	fn sync_status_changed(&self, old: &SyncStatus, new: &SyncStatus);
 }
 
 /// Inform the caller of the current status of a txhashset write operation,
@@ -38,11 +38,15 @@ use self::chain_test_helper::{clean_output_dir, init_chain, mine_chain};
 /// Adapter to retrieve last status
 pub struct StatusAdapter {
 	pub last_status: RwLock<Option<BlockStatus>>,
+	pub last_reorg: RwLock<Option<(Vec<BlockHeader>, Vec<BlockHeader>)>>,
 }
 
 impl StatusAdapter {
 	pub fn new(last_status: RwLock<Option<BlockStatus>>) -> Self {
-		StatusAdapter { last_status }
+		StatusAdapter {
+			last_status,
+			last_reorg: RwLock::new(None),
+		}
 	}
 }
 
@@ -50,6 +54,10 @@ impl ChainAdapter for StatusAdapter {
 	fn block_accepted(&self, _b: &Block, status: BlockStatus, _opts: Options) {
 		*self.last_status.write() = Some(status);
 	}
+
+	fn reorg(&self, disconnected: &[BlockHeader], connected: &[BlockHeader], _opts: Options) {
+		*self.last_reorg.write() = Some((disconnected.to_vec(), connected.to_vec()));
+	}
 }
 
 /// Creates a `Chain` instance with `StatusAdapter` attached to it.
@@ -386,6 +394,17 @@ fn mine_reorg() {
 			Some(BlockStatus::Reorg(REORG_DEPTH))
 		);
 
+		// Check the rewound and applied blocks are reported
+		let (disconnected, connected) = adapter.last_reorg.read().clone().unwrap();
+		assert_eq!(disconnected.len() as u64, REORG_DEPTH);
+		assert_eq!(disconnected[0].hash(), prev.hash());
+		assert_eq!(
+			disconnected.last().unwrap().height,
+			NUM_BLOCKS_MAIN - REORG_DEPTH + 1
+		);
+		assert_eq!(connected.len(), 1);
+		assert_eq!(connected[0].hash(), reorg_head.hash());
+
 		// Chain should be switched to the reorganized chain
 		let head = chain.head_header().unwrap();
 		assert_eq!(head.height, NUM_BLOCKS_MAIN - REORG_DEPTH + 1);
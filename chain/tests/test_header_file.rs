@@ -0,0 +1,70 @@
+// Copyright 2020 The Kepler Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use kepler_core as core;
+
+mod chain_test_helper;
+
+use self::chain_test_helper::{clean_output_dir, init_chain, mine_chain};
+use crate::core::core::hash::Hashed;
+
+#[test]
+fn test_header_file_roundtrip() {
+	let chain_dir = ".header_file_export_test";
+	let import_dir = ".header_file_import_test";
+	clean_output_dir(chain_dir);
+	clean_output_dir(import_dir);
+	let chain = mine_chain(chain_dir, 20);
+
+	let mut file = vec![];
+	assert_eq!(chain.export_headers(&mut file).unwrap(), 19);
+
+	let genesis_hash = chain.get_header_by_height(0).unwrap().hash();
+	let genesis = chain.get_block(&genesis_hash).unwrap();
+	let imported = init_chain(import_dir, genesis);
+	assert_eq!(imported.import_headers(&mut &file[..]).unwrap(), 19);
+	assert_eq!(
+		imported.header_head().unwrap(),
+		chain.header_head().unwrap()
+	);
+	// only the header chain was imported
+	assert_eq!(imported.head().unwrap().height, 0);
+
+	clean_output_dir(chain_dir);
+	clean_output_dir(import_dir);
+}
+
+#[test]
+fn test_header_file_bad_pow() {
+	let chain_dir = ".header_file_pow_test";
+	let import_dir = ".header_file_pow_import_test";
+	clean_output_dir(chain_dir);
+	clean_output_dir(import_dir);
+	let chain = mine_chain(chain_dir, 5);
+
+	let mut file = vec![];
+	chain.export_headers(&mut file).unwrap();
+	// header count, then the version, height and timestamp of the first
+	// header, the proof of work doesn't match the new timestamp
+	file[8 + 2 + 8 + 7] ^= 1;
+
+	let genesis_hash = chain.get_header_by_height(0).unwrap().hash();
+	let genesis = chain.get_block(&genesis_hash).unwrap();
+	let imported = init_chain(import_dir, genesis);
+	assert!(imported.import_headers(&mut &file[..]).is_err());
+	assert_eq!(imported.header_head().unwrap().height, 0);
+
+	clean_output_dir(chain_dir);
+	clean_output_dir(import_dir);
+}
@@ -0,0 +1,129 @@
+// Copyright 2020 The Kepler Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use kepler_chain as chain;
+use kepler_core as core;
+
+mod chain_test_helper;
+
+use self::chain_test_helper::{clean_output_dir, mine_chain};
+use crate::chain::{KernelMerkleProof, OutputMerkleProof};
+use crate::core::core::hash::Hashed;
+use crate::core::ser;
+
+#[test]
+fn test_output_merkle_proof() {
+	let chain_dir = ".merkle_proofs_output_test";
+	clean_output_dir(chain_dir);
+	let chain = mine_chain(chain_dir, 10);
+	let head = chain.head_header().unwrap();
+
+	// an output from a few blocks back
+	let header = chain.get_header_by_height(4).unwrap();
+	let block = chain.get_block(&header.hash()).unwrap();
+	let commit = block.outputs()[0].commitment();
+
+	let proof = chain.output_merkle_proof(&commit).unwrap();
+	assert_eq!(proof.block_hash, head.hash());
+	assert_eq!(proof.output.commit, commit);
+	proof.verify(&head).unwrap();
+
+	// roundtrip through the wire format
+	let mut vec = vec![];
+	ser::serialize_default(&mut vec, &proof).unwrap();
+	let proof: OutputMerkleProof = ser::deserialize_default(&mut &vec[..]).unwrap();
+	proof.verify(&head).unwrap();
+
+	// the proof doesn't hold against another header
+	assert!(proof.verify(&header).is_err());
+
+	// nor for another position
+	let mut bad_proof = proof.clone();
+	bad_proof.mmr_index += 1;
+	assert!(bad_proof.verify(&head).is_err());
+
+	// a peer sending position 0 gets an error rather than a panic
+	let mut bad_proof = proof.clone();
+	bad_proof.mmr_index = 0;
+	assert!(bad_proof.verify(&head).is_err());
+
+	// nor with roots not matching the header
+	let mut bad_proof = proof.clone();
+	bad_proof.bitmap_root = bad_proof.output_pmmr_root;
+	assert!(bad_proof.verify(&head).is_err());
+
+	clean_output_dir(chain_dir);
+}
+
+#[test]
+fn test_kernel_merkle_proof() {
+	let chain_dir = ".merkle_proofs_kernel_test";
+	clean_output_dir(chain_dir);
+	let chain = mine_chain(chain_dir, 10);
+	let head = chain.head_header().unwrap();
+
+	let header = chain.get_header_by_height(6).unwrap();
+	let block = chain.get_block(&header.hash()).unwrap();
+	let excess = block.kernels()[0].excess;
+
+	let proof = chain.kernel_merkle_proof(&excess).unwrap();
+	assert_eq!(proof.block_hash, head.hash());
+	assert_eq!(proof.kernel.excess, excess);
+	proof.verify(&head).unwrap();
+
+	let mut vec = vec![];
+	ser::serialize_default(&mut vec, &proof).unwrap();
+	let proof: KernelMerkleProof = ser::deserialize_default(&mut &vec[..]).unwrap();
+	proof.verify(&head).unwrap();
+
+	assert!(proof.verify(&header).is_err());
+
+	// another kernel can't use the proof
+	let other = chain.get_block(&head.hash()).unwrap().kernels()[0].clone();
+	let mut bad_proof = proof.clone();
+	bad_proof.kernel = other;
+	assert!(bad_proof.verify(&head).is_err());
+
+	clean_output_dir(chain_dir);
+}
+
+#[test]
+fn test_kernel_inclusion_merkle_proof() {
+	let chain_dir = ".merkle_proofs_kernel_inclusion_test";
+	clean_output_dir(chain_dir);
+	let chain = mine_chain(chain_dir, 10);
+	let head = chain.head_header().unwrap();
+
+	let header = chain.get_header_by_height(6).unwrap();
+	let block = chain.get_block(&header.hash()).unwrap();
+	let excess = block.kernels()[0].excess;
+
+	// proven against the header including the kernel, not our head
+	let proof = chain
+		.get_kernel_merkle_proof(&excess, None, None)
+		.unwrap()
+		.unwrap();
+	assert_eq!(proof.block_hash, header.hash());
+	assert_eq!(proof.proof.mmr_size, header.kernel_mmr_size);
+	proof.verify(&header).unwrap();
+	assert!(proof.verify(&head).is_err());
+
+	// the kernel isn't found below its height
+	let proof = chain
+		.get_kernel_merkle_proof(&excess, None, Some(5))
+		.unwrap();
+	assert!(proof.is_none());
+
+	clean_output_dir(chain_dir);
+}
@@ -0,0 +1,74 @@
+// Copyright 2020 The Kepler Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use kepler_chain as chain;
+use kepler_util as util;
+
+use self::chain::{SyncState, SyncStatus, SyncStatusAdapter};
+use self::util::Mutex;
+use std::sync::Arc;
+
+struct TransitionsAdapter {
+	transitions: Mutex<Vec<(SyncStatus, SyncStatus)>>,
+}
+
+impl SyncStatusAdapter for TransitionsAdapter {
+	fn sync_status_changed(&self, old: &SyncStatus, new: &SyncStatus) {
+		self.transitions.lock().push((*old, *new));
+	}
+}
+
+#[test]
+fn test_sync_status_transitions() {
+	let adapter = Arc::new(TransitionsAdapter {
+		transitions: Mutex::new(vec![]),
+	});
+	let sync_state = SyncState::new();
+	sync_state.init_adapter(adapter.clone());
+
+	for height in 0..10 {
+		sync_state.update(SyncStatus::HeaderSync {
+			current_height: height,
+			highest_height: 10,
+		});
+	}
+	sync_state.update(SyncStatus::BodySync {
+		current_height: 0,
+		highest_height: 10,
+	});
+	sync_state.update(SyncStatus::BodySync {
+		current_height: 5,
+		highest_height: 10,
+	});
+	sync_state.update(SyncStatus::NoSync);
+
+	// only the phase changes are notified, not the progress within them
+	let transitions = adapter.transitions.lock();
+	assert_eq!(transitions.len(), 3);
+	assert_eq!(transitions[0].0, SyncStatus::Initial);
+	assert_eq!(
+		transitions[1],
+		(
+			SyncStatus::HeaderSync {
+				current_height: 9,
+				highest_height: 10,
+			},
+			SyncStatus::BodySync {
+				current_height: 0,
+				highest_height: 10,
+			}
+		)
+	);
+	assert_eq!(transitions[2].1, SyncStatus::NoSync);
+}
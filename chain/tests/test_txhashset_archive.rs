@@ -12,9 +12,14 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use kepler_chain as chain;
+use kepler_core as core;
+
 mod chain_test_helper;
 
 use self::chain_test_helper::{clean_output_dir, mine_chain};
+use crate::chain::txhashset;
+use crate::core::core::hash::Hashed;
 
 #[test]
 fn test() {
@@ -25,3 +30,21 @@ fn test() {
 	assert_eq!(10, header.height);
 	clean_output_dir(chain_dir);
 }
+
+#[test]
+fn test_archive_hash() {
+	let chain_dir = ".txhashset_archive_hash_test";
+	clean_output_dir(chain_dir);
+	let chain = mine_chain(chain_dir, 35);
+	let header = chain.txhashset_archive_header().unwrap();
+	let (_, _, mut zip) = chain.txhashset_read(header.hash()).unwrap();
+
+	let archive_hash = chain.txhashset_archive_hash(&header.hash()).unwrap();
+	assert_eq!(archive_hash, txhashset::archive_hash(&mut zip).unwrap());
+	// the second call reads the hash kept next to the archive
+	assert_eq!(
+		archive_hash,
+		chain.txhashset_archive_hash(&header.hash()).unwrap()
+	);
+	clean_output_dir(chain_dir);
+}
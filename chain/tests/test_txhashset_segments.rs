@@ -0,0 +1,200 @@
+// Copyright 2020 The Kepler Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use kepler_chain as chain;
+use kepler_core as core;
+use kepler_keychain as keychain;
+
+mod chain_test_helper;
+
+use self::chain_test_helper::{clean_output_dir, init_chain, mine_chain};
+use crate::chain::txhashset::{Desegmenter, Segmenter, TxHashSet};
+use crate::chain::{Chain, Options, SegmentType, SegmentTypeIdentifier};
+use crate::core::core::hash::{Hash, Hashed};
+use crate::core::core::{Block, KernelFeatures, Transaction};
+use crate::core::libtx::{self, build, ProofBuilder};
+use crate::core::{consensus, global, pow};
+use crate::keychain::{ExtKeychain, ExtKeychainPath, Keychain};
+use chrono::Duration;
+use std::path::PathBuf;
+
+fn mine_block(chain: &Chain, keychain: &ExtKeychain, txs: Vec<Transaction>) {
+	let prev = chain.head_header().unwrap();
+	let height = prev.height + 1;
+	let builder = ProofBuilder::new(keychain);
+	let key_id = ExtKeychainPath::new(1, height as u32, 0, 0, 0).to_identifier();
+	let fees = txs.iter().map(|tx| tx.fee()).sum();
+	let reward = libtx::reward::output(keychain, &builder, &key_id, fees, height, false).unwrap();
+	let next_header_info = consensus::next_difficulty(1, chain.difficulty_iter().unwrap());
+	let mut block = Block::new(&prev, txs, next_header_info.difficulty, reward).unwrap();
+	block.header.timestamp = prev.timestamp + Duration::seconds(60);
+	block.header.pow.secondary_scaling = next_header_info.secondary_scaling;
+	chain.set_txhashset_roots(&mut block).unwrap();
+	pow::pow_size(
+		&mut block.header,
+		next_header_info.difficulty,
+		global::proofsize(),
+		global::min_edge_bits(),
+	)
+	.unwrap();
+	chain.process_block(block, Options::MINE).unwrap();
+}
+
+fn desegment(segmenter: &Segmenter, desegmenter: &mut Desegmenter) {
+	while !desegmenter.is_complete() {
+		let missing = desegmenter.missing_segments();
+		assert!(!missing.is_empty());
+		for segment in missing {
+			let id = segment.identifier;
+			match segment.segment_type {
+				SegmentType::Bitmap => desegmenter.add_bitmap_segment(
+					segmenter.bitmap_segment(id).unwrap(),
+					segmenter.output_root(),
+					segmenter.bitmap_mmr_size(),
+				),
+				SegmentType::Output => desegmenter.add_output_segment(
+					segmenter.output_segment(id).unwrap(),
+					segmenter.bitmap_root(),
+				),
+				SegmentType::RangeProof => {
+					desegmenter.add_rangeproof_segment(segmenter.rangeproof_segment(id).unwrap())
+				}
+				SegmentType::Kernel => {
+					desegmenter.add_kernel_segment(segmenter.kernel_segment(id).unwrap())
+				}
+			}
+			.unwrap();
+		}
+	}
+}
+
+#[test]
+fn test_segments_roundtrip() {
+	let chain_dir = ".txhashset_segments_test";
+	clean_output_dir(chain_dir);
+	let chain = mine_chain(chain_dir, 35);
+	let segmenter = chain.segmenter().unwrap();
+	assert_eq!(
+		segmenter.header().hash(),
+		chain.txhashset_archive_header().unwrap().hash()
+	);
+
+	let dir = PathBuf::from(chain_dir).join("segments");
+	let mut desegmenter = Desegmenter::new(segmenter.header().clone(), dir).unwrap();
+	desegment(&segmenter, &mut desegmenter);
+	let (received, total) = desegmenter.progress();
+	assert_eq!(received, total);
+
+	clean_output_dir(chain_dir);
+}
+
+#[test]
+fn test_segments_wrong_roots() {
+	let chain_dir = ".txhashset_segments_roots_test";
+	clean_output_dir(chain_dir);
+	let chain = mine_chain(chain_dir, 35);
+	let segmenter = chain.segmenter().unwrap();
+
+	let dir = PathBuf::from(chain_dir).join("segments");
+	let mut desegmenter = Desegmenter::new(segmenter.header().clone(), dir).unwrap();
+	let id = desegmenter.missing_segments()[0].identifier;
+
+	// roots that don't merge into the output root of the header
+	assert!(desegmenter
+		.add_bitmap_segment(
+			segmenter.bitmap_segment(id).unwrap(),
+			Hash::default(),
+			segmenter.bitmap_mmr_size(),
+		)
+		.is_err());
+	// outputs can't be accepted before the bitmap is complete
+	let id = SegmentTypeIdentifier::new(SegmentType::Output, 0).identifier;
+	assert!(desegmenter
+		.add_output_segment(
+			segmenter.output_segment(id).unwrap(),
+			segmenter.bitmap_root()
+		)
+		.is_err());
+	let (received, _) = desegmenter.progress();
+	assert_eq!(received, 0);
+
+	clean_output_dir(chain_dir);
+}
+
+#[test]
+fn test_segments_write_txhashset() {
+	let chain_dir = ".txhashset_segments_write_test";
+	clean_output_dir(chain_dir);
+	global::set_mining_mode(global::ChainTypes::AutomatedTesting);
+	let keychain = ExtKeychain::from_random_seed(false).unwrap();
+	let builder = ProofBuilder::new(&keychain);
+	let chain = init_chain(chain_dir, pow::mine_genesis_block().unwrap());
+	for _ in 0..6 {
+		mine_block(&chain, &keychain, vec![]);
+	}
+
+	// spends the first 3 coinbase outputs, at positions 1, 2 and 4, so both
+	// a pruned subtree and a lone pruned leaf have to be rebuilt
+	let inputs = (1..=3)
+		.map(|height| {
+			let key_id = ExtKeychainPath::new(1, height, 0, 0, 0).to_identifier();
+			build::coinbase_input(consensus::reward(height as u64, 0), key_id)
+		})
+		.collect::<Vec<_>>();
+	let amount = (1..=3).map(|h| consensus::reward(h, 0)).sum::<u64>() - 2;
+	let key_id = ExtKeychainPath::new(2, 1, 0, 0, 0).to_identifier();
+	let tx = build::transaction(
+		KernelFeatures::Plain { fee: 2 },
+		inputs
+			.into_iter()
+			.chain(vec![build::output(amount, key_id)])
+			.collect(),
+		&keychain,
+		&builder,
+	)
+	.unwrap();
+	mine_block(&chain, &keychain, vec![tx]);
+	for _ in 0..28 {
+		mine_block(&chain, &keychain, vec![]);
+	}
+
+	let segmenter = chain.segmenter().unwrap();
+	let header = segmenter.header().clone();
+	assert_eq!(header.height, 10);
+	let dir = PathBuf::from(chain_dir).join("segments");
+	let mut desegmenter = Desegmenter::new(header.clone(), dir).unwrap();
+	desegment(&segmenter, &mut desegmenter);
+
+	let root_dir = PathBuf::from(chain_dir).join("rebuilt");
+	desegmenter.write_txhashset(&root_dir).unwrap();
+	let txhashset = TxHashSet::open(
+		root_dir.to_str().unwrap().to_owned(),
+		chain.store(),
+		Some(&header),
+	)
+	.unwrap();
+	txhashset.roots().validate(&header).unwrap();
+
+	// the unspent outputs are all there, the spent ones are gone
+	let (_, unspent) = txhashset.outputs_by_pmmr_index(1, 100, None);
+	let (_, expected) =
+		chain
+			.txhashset()
+			.read()
+			.outputs_by_pmmr_index(1, 100, Some(header.output_mmr_size));
+	assert_eq!(unspent.len(), 8);
+	assert_eq!(unspent, expected);
+
+	clean_output_dir(chain_dir);
+}
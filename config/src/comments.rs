@@ -89,6 +89,18 @@ fn comments() -> HashMap<String, String> {
 #UserTesting - For regular user testing (cuckoo 16)
 #Floonet - For the long term floonet test network
 #Mainnet - For mainnet
+#Custom - For private networks, with the parameters of custom_chain
+"
+		.to_string(),
+	);
+
+	retval.insert(
+		"custom_chain".to_string(),
+		"
+#path to the chain file of a Custom chain, a TOML file setting any of
+#block_time_sec, coinbase_maturity, cut_through_horizon, state_sync_threshold,
+#archive_interval, edge_bits, proof_size, initial_difficulty, genesis_reward, initial_reward,
+#halving_interval, hard_forks, genesis_file and p2p_magic
 "
 		.to_string(),
 	);
@@ -120,6 +132,26 @@ fn comments() -> HashMap<String, String> {
 		.to_string(),
 	);
 
+	retval.insert(
+		"pibd_sync".to_string(),
+		"
+#build the txhashset from segments downloaded from many peers in parallel
+#during fast sync, each segment checked against the header as it arrives,
+#instead of downloading the archive from a single peer (experimental)
+"
+		.to_string(),
+	);
+
+	retval.insert(
+		"header_only".to_string(),
+		"
+#only sync the header chain, with no block bodies, txhashset or transaction
+#pool, fetching merkle proofs of outputs and kernels from full peers on demand
+#through the foreign api
+"
+		.to_string(),
+	);
+
 	retval.insert(
 		"run_tui".to_string(),
 		"
@@ -163,6 +195,24 @@ fn comments() -> HashMap<String, String> {
 #The url where a POST request will be sent when a new block is received by a peer.
 #block_received_url = \"http://127.0.0.1:8080/block\"
 
+#The url where a POST request will be sent when our chain head moves to another fork,
+#with the lists of disconnected and connected blocks.
+#reorg_url = \"http://127.0.0.1:8080/reorg\"
+
+#The urls where a POST request will be sent when a transaction is accepted in our
+#txpool or stempool, or evicted from the txpool.
+#tx_accepted_url = \"http://127.0.0.1:8080/txaccepted\"
+#tx_evicted_url = \"http://127.0.0.1:8080/txevicted\"
+
+#The urls where a POST request will be sent when a peer gets connected, disconnected
+#or banned.
+#peer_connected_url = \"http://127.0.0.1:8080/peerconnected\"
+#peer_disconnected_url = \"http://127.0.0.1:8080/peerdisconnected\"
+#peer_banned_url = \"http://127.0.0.1:8080/peerbanned\"
+
+#The url where a POST request will be sent when the sync status changes.
+#sync_status_url = \"http://127.0.0.1:8080/syncstatus\"
+
 #The number of worker threads that will be assigned to making the http requests.
 "
 		.to_string(),
@@ -176,6 +226,87 @@ fn comments() -> HashMap<String, String> {
 		.to_string(),
 	);
 
+	retval.insert(
+		"max_retries".to_string(),
+		"
+#Events are stored on disk until the receiver acknowledges them with a 2xx status,
+#and failed deliveries are retried with an exponential backoff, including across
+#node restarts. Each request carries the event name and its sequence number in the
+#X-Kepler-Event and X-Kepler-Sequence headers, the sequence increasing by one for
+#each event of the same name so that receivers can detect gaps.
+
+#If set, each request also carries an X-Kepler-Signature header of the form
+#sha256=<hex>, the HMAC-SHA256 of \"<event>.<sequence>.<body>\" keyed with the first
+#line of this file.
+#secret_path = \".webhook_secret\"
+
+#The number of retries before an event is dropped, 0 to retry until delivered.
+"
+		.to_string(),
+	);
+
+	retval.insert(
+		"retry_base_delay".to_string(),
+		"
+#The delay in seconds before the first retry, doubling with each retry.
+"
+		.to_string(),
+	);
+
+	retval.insert(
+		"retry_max_delay".to_string(),
+		"
+#The maximum delay in seconds between two retries.
+"
+		.to_string(),
+	);
+
+	retval.insert(
+		"max_outbox_events".to_string(),
+		"
+#The number of undelivered events kept on disk, the oldest ones being dropped first
+#once reached.
+"
+		.to_string(),
+	);
+
+	retval.insert(
+		"[server.event_sink_config]".to_string(),
+		"
+#########################################
+### EVENT SINK CONFIGURATION          ###
+#########################################
+
+#Local alternative to the webhooks: each chain, pool and peer event is written as
+#a line of JSON of the form {\"event\": <name>, \"timestamp\": <secs>, \"data\": <payload>},
+#the payloads being the same as the webhooks ones.
+
+#The append-only file the events are written to.
+#event_file_path = \"events.json\"
+
+#The Unix domain socket the events are written to. Connected clients receive the
+#events written after they connected, slow clients are disconnected.
+#event_socket_path = \"/tmp/kepler_events.sock\"
+"
+		.to_string(),
+	);
+
+	retval.insert(
+		"event_max_size".to_string(),
+		"
+#maximum event file size in bytes before it is rotated
+"
+		.to_string(),
+	);
+
+	retval.insert(
+		"event_max_files".to_string(),
+		"
+#maximum count of the rotated event files to keep
+"
+		.to_string(),
+	);
+
 	retval.insert(
 		"[server.dandelion_config]".to_string(),
 		"
@@ -296,7 +427,8 @@ fn comments() -> HashMap<String, String> {
 #amount of incoming connections temporarily allowed to exceed peer_max_inbound_count
 #peer_listener_buffer_count = 8
 
-# 15 = Bit flags for FULL_NODE
+# 127 = Bit flags for FULL_NODE, TXHASHSET_RESUME, PIBD_HIST and MERKLE_PROOFS
+# (a header only node advertises 5, for HEADER_HIST and PEER_LIST)
 #This structure needs to be changed internally, to make it more configurable
 
 # A preferred dandelion_peer, mainly used for testing dandelion
@@ -370,6 +502,15 @@ fn comments() -> HashMap<String, String> {
 		"stratum_server_addr".to_string(),
 		"
 #what port and address for the stratum server to listen on
+#comment it out to disable the plain (non-TLS) listener
+
+#what port and address for the stratum server to listen on for TLS
+#connections, both listeners can run at the same time
+#stratum_server_tls_addr = \"127.0.0.1:7418\"
+#path of TLS certificate file for the stratum TLS listener
+#tls_certificate_file = \"\"
+#private key for the TLS certificate
+#tls_certificate_key = \"\"
 "
 		.to_string(),
 	);
@@ -391,6 +532,32 @@ fn comments() -> HashMap<String, String> {
 		.to_string(),
 	);
 
+	retval.insert(
+		"enable_vardiff".to_string(),
+		"
+#whether to adjust the share difficulty of each worker individually, so
+#that every worker submits shares at roughly the target rate below
+"
+		.to_string(),
+	);
+
+	retval.insert(
+		"vardiff_target_shares_per_minute".to_string(),
+		"
+#number of shares per minute each worker should submit when vardiff is enabled
+"
+		.to_string(),
+	);
+
+	retval.insert(
+		"vardiff_retarget_time".to_string(),
+		"
+#length, in seconds, of the window over which a worker share rate is measured
+#before its share difficulty is adjusted
+"
+		.to_string(),
+	);
+
 	retval.insert(
 		"wallet_listener_url".to_string(),
 		"
@@ -407,6 +574,50 @@ fn comments() -> HashMap<String, String> {
 		.to_string(),
 	);
 
+	retval.insert(
+		"stratum_credentials_file".to_string(),
+		"
+#file listing the logins and hashed passwords workers have to log in with,
+#one per line, as printed by `kepler server stratum_credentials --login <login>`
+#if not set, any login is accepted
+#stratum_credentials_file = \"stratum_credentials\"
+"
+		.to_string(),
+	);
+
+	retval.insert(
+		"coinbase_key_file".to_string(),
+		"
+#file holding a base58 extended private key (i.e. a dedicated wallet account
+#key) the node derives coinbase outputs from, without contacting the wallet
+#receiver. Derived key ids are recorded and listed by the `get_coinbase_keys`
+#owner api so the wallet can recover the outputs. An extended public key or a
+#view key can't be used, building an output needs its blinding factor
+"
+		.to_string(),
+	);
+
+	retval.insert(
+		"enable_cpu_miner".to_string(),
+		"
+#whether to run the multithreaded CPU miner of the node, paying to the same
+#coinbase destination as the stratum server. Only for usernet and custom
+#chains, so that they can mine blocks without external miner software
+"
+		.to_string(),
+	);
+
+	retval.insert(
+		"cpu_miner_threads".to_string(),
+		"
+#edge bits of the graphs searched by the CPU miner, the chain minimum if not set
+#cpu_miner_edge_bits = 16
+
+#number of threads of the CPU miner
+"
+		.to_string(),
+	);
+
 	retval.insert(
 		"[logging]".to_string(),
 		"
@@ -112,6 +112,29 @@ fn check_api_secret_files(
 	}
 }
 
+/// Reads the parameters of a custom chain from a chain file. A relative
+/// genesis file is looked up next to the chain file.
+pub fn load_chain_params(file_path: &str) -> Result<global::ChainParams, ConfigError> {
+	let path = PathBuf::from(file_path);
+	if !path.exists() {
+		return Err(ConfigError::FileNotFoundError(file_path.to_owned()));
+	}
+	let contents = fs::read_to_string(&path)?;
+	let mut params: global::ChainParams = toml::from_str(&contents)
+		.map_err(|e| ConfigError::ParseError(file_path.to_owned(), format!("{}", e)))?;
+	params
+		.validate()
+		.map_err(|e| ConfigError::ParseError(file_path.to_owned(), e))?;
+
+	if let Some(genesis_file) = params.genesis_file.clone() {
+		let genesis_path = PathBuf::from(genesis_file);
+		if let (true, Some(dir)) = (genesis_path.is_relative(), path.parent()) {
+			params.genesis_file = Some(dir.join(genesis_path).to_string_lossy().into_owned());
+		}
+	}
+	Ok(params)
+}
+
 /// Handles setup and detection of paths for node
 pub fn initial_setup_server(chain_type: &global::ChainTypes) -> Result<GlobalConfig, ConfigError> {
 	check_api_secret_files(chain_type, API_SECRET_FILE_NAME)?;
@@ -197,6 +220,21 @@ impl GlobalConfig {
 					.unwrap()
 					.wallet_listener_url = "http://127.0.0.1:27415".to_owned();
 			}
+			global::ChainTypes::Custom => {
+				defaults.api_http_addr = "127.0.0.1:37413".to_owned();
+				defaults.p2p_config.port = 37414;
+				defaults.p2p_config.seeding_type = p2p::Seeding::None;
+				defaults
+					.stratum_mining_config
+					.as_mut()
+					.unwrap()
+					.stratum_server_addr = Some("127.0.0.1:37416".to_owned());
+				defaults
+					.stratum_mining_config
+					.as_mut()
+					.unwrap()
+					.wallet_listener_url = "http://127.0.0.1:37415".to_owned();
+			}
 			global::ChainTypes::AutomatedTesting => {
 				panic!("Can't run automated testing directly");
 			}
@@ -42,6 +42,9 @@ pub const BLOCK_TIME_SEC: u64 = 60;
 /// The block initial subsidy amount, 1000 keplers per block
 pub const INITIAL_REWARD: u64 = 1000 * KEPLER_BASE;
 
+/// The genesis block subsidy amount
+pub const GENESIS_REWARD: u64 = 42_000_000 * KEPLER_BASE;
+
 /// Nominal height for standard time intervals, hour is 60 blocks
 pub const HOUR_HEIGHT: u64 = 3600 / BLOCK_TIME_SEC;
 /// A day is 1440 blocks
@@ -60,13 +63,16 @@ pub const HALVING_INTERVAL: u64 = 2 * YEAR_HEIGHT;
 /// Actual block reward for a given total fee amount
 pub fn reward(height: u64, fee: u64) -> u64 {
 	if height == 0 {
-		return 42_000_000 * KEPLER_BASE;
+		return global::genesis_reward();
 	}
-	let halvings = height / HALVING_INTERVAL;
+	let halvings = match global::halving_interval() {
+		0 => 0,
+		interval => height / interval,
+	};
 	if halvings >= 64 {
 		return NANO_KEPLER + fee;
 	}
-	(max(INITIAL_REWARD >> halvings, NANO_KEPLER)).saturating_add(fee)
+	(max(global::initial_reward() >> halvings, NANO_KEPLER)).saturating_add(fee)
 }
 
 /// Target ratio of secondary proof of work to primary proof of work,
@@ -189,12 +195,20 @@ pub fn header_version(height: u64) -> HeaderVersion {
 				HeaderVersion(hf_interval)
 			}
 		}
+		global::ChainTypes::Custom => {
+			let forks = global::CUSTOM_CHAIN.read().hard_forks.clone();
+			HeaderVersion(1 + forks.iter().filter(|&&h| h <= height).count() as u16)
+		}
 	}
 }
 
 /// Check whether the block version is valid at a given height, implements
 /// 6 months interval scheduled hard forks for the first 2 years.
+/// Custom chains only know of the hard forks in their chain file.
 pub fn valid_header_version(height: u64, version: HeaderVersion) -> bool {
+	if *global::CHAIN_TYPE.read() == global::ChainTypes::Custom {
+		return version == header_version(height);
+	}
 	return (height + HARD_FORK_ADJUST_HEIGHT) < 4 * HARD_FORK_INTERVAL
 		&& version == header_version(height);
 }
@@ -357,13 +371,15 @@ where
 		.sum();
 
 	// adjust time delta toward goal subject to dampening and clamping
+	let block_time_sec = global::block_time_sec();
+	let block_time_window = DIFFICULTY_ADJUST_WINDOW * block_time_sec;
 	let adj_ts = clamp(
-		damp(ts_delta, BLOCK_TIME_WINDOW, DIFFICULTY_DAMP_FACTOR),
-		BLOCK_TIME_WINDOW,
+		damp(ts_delta, block_time_window, DIFFICULTY_DAMP_FACTOR),
+		block_time_window,
 		CLAMP_FACTOR,
 	);
 	// minimum difficulty avoids getting stuck due to dampening
-	let difficulty = max(MIN_DIFFICULTY, diff_sum * BLOCK_TIME_SEC / adj_ts);
+	let difficulty = max(MIN_DIFFICULTY, diff_sum * block_time_sec / adj_ts);
 
 	HeaderInfo::from_diff_scaling(Difficulty::from_num(difficulty), sec_pow_scaling)
 }
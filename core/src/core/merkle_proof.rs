@@ -16,15 +16,22 @@
 
 use crate::core::hash::Hash;
 use crate::core::pmmr;
+use crate::core::{BlockHeader, TxKernel};
 use crate::ser;
 use crate::ser::{PMMRIndexHashable, Readable, Reader, Writeable, Writer};
 use util;
 
+/// Maximum number of hashes in the path of a proof we're willing to read,
+/// well above what an MMR of 2^32 leaves needs.
+pub const MAX_PATH_LEN: u64 = 64;
+
 /// Merkle proof errors.
 #[derive(Clone, Debug, PartialEq)]
 pub enum MerkleProofError {
 	/// Merkle proof root hash does not match when attempting to verify.
 	RootMismatch,
+	/// Merkle proof was built against an MMR of another size.
+	SizeMismatch,
 }
 
 /// A Merkle proof that proves a particular element exists in the MMR.
@@ -50,6 +57,9 @@ impl Readable for MerkleProof {
 	fn read(reader: &mut dyn Reader) -> Result<MerkleProof, ser::Error> {
 		let mmr_size = reader.read_u64()?;
 		let path_len = reader.read_u64()?;
+		if path_len > MAX_PATH_LEN {
+			return Err(ser::Error::TooLargeReadErr);
+		}
 		let mut path = Vec::with_capacity(path_len as usize);
 		for _ in 0..path_len {
 			let hash = Hash::read(reader)?;
@@ -84,7 +94,8 @@ impl MerkleProof {
 
 	/// Convert hex string representation back to a Merkle proof instance
 	pub fn from_hex(hex: &str) -> Result<MerkleProof, String> {
-		let bytes = util::from_hex(hex.to_string()).unwrap();
+		let bytes = util::from_hex(hex.to_string())
+			.map_err(|_| "invalid hex for a Merkle Proof".to_string())?;
 		let res = ser::deserialize_default(&mut &bytes[..])
 			.map_err(|_| "failed to deserialize a Merkle Proof".to_string())?;
 		Ok(res)
@@ -105,6 +116,25 @@ impl MerkleProof {
 		proof.verify_consume(root, element, node_pos, &peaks_pos)
 	}
 
+	/// Verifies the Merkle proof of a kernel at the provided position in the
+	/// kernel MMR against the kernel root of the header it's claimed to be
+	/// included under. The position has to be a leaf within the MMR.
+	pub fn verify_kernel(
+		&self,
+		header: &BlockHeader,
+		kernel: &TxKernel,
+		node_pos: u64,
+	) -> Result<(), MerkleProofError> {
+		if self.mmr_size != header.kernel_mmr_size
+			|| node_pos == 0
+			|| node_pos > self.mmr_size
+			|| !pmmr::is_leaf(node_pos)
+		{
+			return Err(MerkleProofError::SizeMismatch);
+		}
+		self.verify(header.kernel_root, kernel, node_pos)
+	}
+
 	/// Consumes the Merkle proof while verifying it.
 	/// The proof can no longer be used by the caller after dong this.
 	/// Caller must clone() the proof first.
@@ -96,6 +96,15 @@ where
 		}
 	}
 
+	/// Get the data from the underlying MMR file, ignoring the leafset.
+	pub fn get_data_from_file(&self, pos: u64) -> Option<T::E> {
+		if pos > self.last_pos {
+			None
+		} else {
+			self.backend.get_data_from_file(pos)
+		}
+	}
+
 	/// Iterator over current (unpruned, unremoved) leaf positions.
 	pub fn leaf_pos_iter(&self) -> impl Iterator<Item = u64> + '_ {
 		self.backend.leaf_pos_iter()
@@ -0,0 +1,491 @@
+// Copyright 2020 The Kepler Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Segments of a PMMR. A segment covers a fixed size, aligned range of leaves
+//! and carries everything needed to rebuild the part of the MMR above them:
+//! the unpruned leaf data, the hashes of the pruned subtrees and a proof
+//! hashing the segment up to the root of the whole MMR. This lets a syncing
+//! node validate each segment independently, as soon as it's received.
+
+use std::cmp::min;
+
+use croaring::Bitmap;
+
+use crate::core::hash::Hash;
+use crate::core::pmmr::{self, Backend, ReadonlyPMMR};
+use crate::ser::{self, PMMRIndexHashable, PMMRable, Readable, Reader, Writeable, Writer};
+
+/// Segment errors.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum SegmentError {
+	/// The segment starts past the last leaf of the MMR.
+	NonExistent,
+	/// A hash needed to build or validate the segment is missing.
+	MissingHash(u64),
+	/// Leaves or hashes out of order, out of range or redundant.
+	Malformed(u64),
+	/// The proof doesn't have the number of hashes expected.
+	InvalidProof,
+	/// The segment doesn't hash to the expected root.
+	RootMismatch,
+}
+
+/// Identifies a segment by its height (a segment of height h holds up to 2^h
+/// leaves) and its index among the segments of that height.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct SegmentIdentifier {
+	/// Height of the segment, log2 of its leaf capacity
+	pub height: u8,
+	/// Index of the segment
+	pub idx: u64,
+}
+
+impl SegmentIdentifier {
+	/// Maximum height of a segment we're willing to read, the height of the
+	/// largest segments requested (output segments).
+	pub const MAX_HEIGHT: u8 = 11;
+
+	/// Maximum number of leaves in a segment of this height.
+	pub fn segment_capacity(&self) -> u64 {
+		1 << self.height
+	}
+
+	/// Number of segments of the given height needed to cover an MMR of
+	/// the given size.
+	pub fn count_segments_required(mmr_size: u64, height: u8) -> u64 {
+		let capacity = 1u64 << height;
+		let n_leaves = pmmr::n_leaves(mmr_size);
+		(n_leaves + capacity - 1) / capacity
+	}
+
+	/// First and last positions covered by this segment in an MMR of the
+	/// given size. The last segment may be partial and cover the few peaks
+	/// on the right of the MMR. None if the segment starts past the end.
+	pub fn pmmr_range(&self, mmr_size: u64) -> Option<(u64, u64)> {
+		let capacity = 1u64.checked_shl(self.height as u32)?;
+		let first_leaf = self.idx.checked_mul(capacity)?;
+		if first_leaf >= pmmr::n_leaves(mmr_size) {
+			return None;
+		}
+		let first = pmmr::insertion_to_pmmr_index(first_leaf + 1);
+		let last = first.saturating_add(capacity.saturating_mul(2) - 2);
+		Some((first, min(last, mmr_size)))
+	}
+
+	/// Whether this segment is full in an MMR of the given size, in which case
+	/// it's a single subtree and the last position is its root.
+	fn is_full(&self, first: u64, last: u64) -> bool {
+		1u64.checked_shl(self.height as u32 + 1)
+			.map_or(false, |x| last - first + 2 == x)
+	}
+}
+
+impl Writeable for SegmentIdentifier {
+	fn write<W: Writer>(&self, writer: &mut W) -> Result<(), ser::Error> {
+		writer.write_u8(self.height)?;
+		writer.write_u64(self.idx)
+	}
+}
+
+impl Readable for SegmentIdentifier {
+	fn read(reader: &mut dyn Reader) -> Result<SegmentIdentifier, ser::Error> {
+		let height = reader.read_u8()?;
+		if height > SegmentIdentifier::MAX_HEIGHT {
+			return Err(ser::Error::CorruptedData);
+		}
+		let idx = reader.read_u64()?;
+		Ok(SegmentIdentifier { height, idx })
+	}
+}
+
+/// Number of entries we allocate room for when reading a segment, more being
+/// allocated as they're read, so that a peer claiming a large number of
+/// entries can't have us allocate for them upfront.
+const READ_CAPACITY: u64 = 256;
+
+/// Hashes linking the root (or the bagged peaks) of a segment to the root of
+/// the whole MMR: siblings up to the peak holding the segment, the bagged
+/// peaks on its right and the peaks on its left, nearest first.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct SegmentProof {
+	hashes: Vec<Hash>,
+}
+
+impl SegmentProof {
+	fn generate<U, B>(
+		pmmr: &ReadonlyPMMR<'_, U, B>,
+		mmr_size: u64,
+		first: u64,
+		last: u64,
+		full: bool,
+	) -> Result<SegmentProof, SegmentError>
+	where
+		U: PMMRable,
+		B: Backend<U>,
+	{
+		let get_hash = |pos: u64| {
+			pmmr.get_from_file(pos)
+				.ok_or(SegmentError::MissingHash(pos))
+		};
+		let peaks = pmmr::peaks(mmr_size);
+		let mut hashes = vec![];
+
+		let peak_pos = if full {
+			let branch = pmmr::family_branch(last, mmr_size);
+			for (_, sibling) in &branch {
+				hashes.push(get_hash(*sibling)?);
+			}
+			let peak_pos = branch.last().map(|x| x.0).unwrap_or(last);
+
+			let mut rhs = None;
+			for peak in peaks.iter().rev().filter(|&&x| x > peak_pos) {
+				let hash = get_hash(*peak)?;
+				rhs = match rhs {
+					None => Some(hash),
+					Some(rhash) => Some((hash, rhash).hash_with_index(mmr_size)),
+				};
+			}
+			hashes.extend(rhs);
+			peak_pos
+		} else {
+			first
+		};
+
+		for peak in peaks.iter().rev().filter(|&&x| x < peak_pos) {
+			hashes.push(get_hash(*peak)?);
+		}
+		Ok(SegmentProof { hashes })
+	}
+
+	/// Hashes the root of a segment with the proof to get the root of the
+	/// whole MMR.
+	fn reconstruct_root(
+		&self,
+		mmr_size: u64,
+		first: u64,
+		last: u64,
+		full: bool,
+		segment_root: Hash,
+	) -> Result<Hash, SegmentError> {
+		let peaks = pmmr::peaks(mmr_size);
+		let mut proof = self.hashes.iter();
+		let mut next = || proof.next().cloned().ok_or(SegmentError::InvalidProof);
+		let mut root = segment_root;
+
+		let peak_pos = if full {
+			let branch = pmmr::family_branch(last, mmr_size);
+			for (parent, sibling) in &branch {
+				let hash = next()?;
+				root = if pmmr::is_left_sibling(*sibling) {
+					(hash, root).hash_with_index(parent - 1)
+				} else {
+					(root, hash).hash_with_index(parent - 1)
+				};
+			}
+			let peak_pos = branch.last().map(|x| x.0).unwrap_or(last);
+			if peaks.iter().any(|&x| x > peak_pos) {
+				root = (root, next()?).hash_with_index(mmr_size);
+			}
+			peak_pos
+		} else {
+			first
+		};
+
+		for _ in peaks.iter().filter(|&&x| x < peak_pos) {
+			root = (next()?, root).hash_with_index(mmr_size);
+		}
+		if next().is_ok() {
+			return Err(SegmentError::InvalidProof);
+		}
+		Ok(root)
+	}
+}
+
+impl Writeable for SegmentProof {
+	fn write<W: Writer>(&self, writer: &mut W) -> Result<(), ser::Error> {
+		writer.write_u64(self.hashes.len() as u64)?;
+		self.hashes.write(writer)
+	}
+}
+
+impl Readable for SegmentProof {
+	fn read(reader: &mut dyn Reader) -> Result<SegmentProof, ser::Error> {
+		// at most one sibling per level and one hash per peak
+		let len = reader.read_u64()?;
+		if len > 128 {
+			return Err(ser::Error::TooLargeReadErr);
+		}
+		let hashes = ser::read_multi(reader, len)?;
+		Ok(SegmentProof { hashes })
+	}
+}
+
+/// A segment of a PMMR, with the unpruned leaves it covers, the hashes of
+/// its pruned subtrees and the proof against the MMR root.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Segment<T> {
+	identifier: SegmentIdentifier,
+	hash_pos: Vec<u64>,
+	hashes: Vec<Hash>,
+	leaf_pos: Vec<u64>,
+	leaf_data: Vec<T>,
+	proof: SegmentProof,
+}
+
+impl<T> Segment<T> {
+	/// Builds a segment from the MMR, as of its current size. Leaves are
+	/// included when they're still in the data file and, if provided, their
+	/// position is in the leaf filter. Every other leaf is sent as a hash, as
+	/// are the roots of subtrees that have been compacted away.
+	pub fn from_pmmr<U, B>(
+		identifier: SegmentIdentifier,
+		pmmr: &ReadonlyPMMR<'_, U, B>,
+		leaf_filter: Option<&Bitmap>,
+	) -> Result<Segment<T>, SegmentError>
+	where
+		U: PMMRable<E = T>,
+		B: Backend<U>,
+	{
+		let mmr_size = pmmr.unpruned_size();
+		let (first, last) = identifier
+			.pmmr_range(mmr_size)
+			.ok_or(SegmentError::NonExistent)?;
+		let full = identifier.is_full(first, last);
+
+		let mut segment = Segment {
+			identifier,
+			hash_pos: vec![],
+			hashes: vec![],
+			leaf_pos: vec![],
+			leaf_data: vec![],
+			proof: SegmentProof::generate(pmmr, mmr_size, first, last, full)?,
+		};
+
+		// whether we can rebuild the hash at each position of the segment
+		let mut known = vec![false; (last - first + 1) as usize];
+		for pos in first..=last {
+			let height = pmmr::bintree_postorder_height(pos);
+			if height == 0 {
+				if leaf_filter.map_or(true, |f| f.contains(pos as u32)) {
+					if let Some(data) = pmmr.get_data_from_file(pos) {
+						segment.leaf_pos.push(pos);
+						segment.leaf_data.push(data);
+						known[(pos - first) as usize] = true;
+						continue;
+					}
+				}
+			} else {
+				let left = pos - (1 << height);
+				match (
+					known[(left - first) as usize],
+					known[(pos - 1 - first) as usize],
+				) {
+					(true, true) => {
+						known[(pos - first) as usize] = true;
+						continue;
+					}
+					(true, false) => return Err(SegmentError::MissingHash(pos - 1)),
+					(false, true) => return Err(SegmentError::MissingHash(left)),
+					(false, false) => {}
+				}
+			}
+			// only the roots of compacted subtrees are still in the hash file
+			if let Some(hash) = pmmr.get_from_file(pos) {
+				segment.hash_pos.push(pos);
+				segment.hashes.push(hash);
+				known[(pos - first) as usize] = true;
+			}
+		}
+
+		for pos in segment_roots(mmr_size, first, last, full) {
+			if !known[(pos - first) as usize] {
+				return Err(SegmentError::MissingHash(pos));
+			}
+		}
+		Ok(segment)
+	}
+
+	/// Identifier of the segment.
+	pub fn identifier(&self) -> SegmentIdentifier {
+		self.identifier
+	}
+
+	/// Positions and data of the leaves in this segment.
+	pub fn leaf_iter(&self) -> impl Iterator<Item = (u64, &T)> + '_ {
+		self.leaf_pos.iter().cloned().zip(self.leaf_data.iter())
+	}
+
+	/// Positions and hashes of the pruned subtrees in this segment.
+	pub fn hash_iter(&self) -> impl Iterator<Item = (u64, Hash)> + '_ {
+		self.hash_pos
+			.iter()
+			.cloned()
+			.zip(self.hashes.iter().cloned())
+	}
+
+	/// Whether every leaf of the segment is included, with no pruned subtree.
+	pub fn is_unpruned(&self) -> bool {
+		self.hash_pos.is_empty()
+	}
+}
+
+impl<T> Segment<T>
+where
+	T: PMMRIndexHashable,
+{
+	/// Rebuilds the segment and hashes it with its proof, giving the root of
+	/// the MMR of the given size this segment claims to belong to.
+	pub fn root(&self, mmr_size: u64) -> Result<Hash, SegmentError> {
+		let (first, last) = self
+			.identifier
+			.pmmr_range(mmr_size)
+			.ok_or(SegmentError::NonExistent)?;
+		let full = self.identifier.is_full(first, last);
+
+		let mut hashes: Vec<Option<Hash>> = vec![None; (last - first + 1) as usize];
+		let mut prev = 0;
+		for (pos, data) in self.leaf_iter() {
+			if pos <= prev || pos < first || pos > last || !pmmr::is_leaf(pos) {
+				return Err(SegmentError::Malformed(pos));
+			}
+			hashes[(pos - first) as usize] = Some(data.hash_with_index(pos - 1));
+			prev = pos;
+		}
+		prev = 0;
+		for (pos, hash) in self.hash_iter() {
+			if pos <= prev || pos < first || pos > last || hashes[(pos - first) as usize].is_some()
+			{
+				return Err(SegmentError::Malformed(pos));
+			}
+			hashes[(pos - first) as usize] = Some(hash);
+			prev = pos;
+		}
+
+		for pos in first..=last {
+			let height = pmmr::bintree_postorder_height(pos);
+			if height == 0 {
+				continue;
+			}
+			let left = pos - (1 << height);
+			let idx = (pos - first) as usize;
+			// a subtree hash can't be sent alongside what's below it, it
+			// would leave the data underneath unchecked
+			match (
+				hashes[(left - first) as usize],
+				hashes[idx - 1],
+				hashes[idx],
+			) {
+				(Some(lhash), Some(rhash), None) => {
+					hashes[idx] = Some((lhash, rhash).hash_with_index(pos - 1));
+				}
+				(None, None, _) => {}
+				_ => return Err(SegmentError::Malformed(pos)),
+			}
+		}
+
+		let mut segment_root = None;
+		for pos in segment_roots(mmr_size, first, last, full).into_iter().rev() {
+			let hash = hashes[(pos - first) as usize].ok_or(SegmentError::MissingHash(pos))?;
+			segment_root = match segment_root {
+				None => Some(hash),
+				Some(rhash) => Some((hash, rhash).hash_with_index(mmr_size)),
+			};
+		}
+		let segment_root = segment_root.ok_or(SegmentError::NonExistent)?;
+
+		self.proof
+			.reconstruct_root(mmr_size, first, last, full, segment_root)
+	}
+
+	/// Validates the segment against the root of an MMR of the given size.
+	pub fn validate(&self, mmr_size: u64, root: Hash) -> Result<(), SegmentError> {
+		if self.root(mmr_size)? == root {
+			Ok(())
+		} else {
+			Err(SegmentError::RootMismatch)
+		}
+	}
+}
+
+/// The subtree roots a segment is made of: the subtree root for a full
+/// segment, the peaks it holds otherwise.
+fn segment_roots(mmr_size: u64, first: u64, last: u64, full: bool) -> Vec<u64> {
+	if full {
+		vec![last]
+	} else {
+		pmmr::peaks(mmr_size)
+			.into_iter()
+			.filter(|&x| x >= first)
+			.collect()
+	}
+}
+
+impl<T: Writeable> Writeable for Segment<T> {
+	fn write<W: Writer>(&self, writer: &mut W) -> Result<(), ser::Error> {
+		self.identifier.write(writer)?;
+		writer.write_u64(self.hashes.len() as u64)?;
+		for (pos, hash) in self.hash_iter() {
+			writer.write_u64(pos)?;
+			hash.write(writer)?;
+		}
+		writer.write_u64(self.leaf_data.len() as u64)?;
+		for (pos, data) in self.leaf_iter() {
+			writer.write_u64(pos)?;
+			data.write(writer)?;
+		}
+		self.proof.write(writer)
+	}
+}
+
+impl<T: Readable> Readable for Segment<T> {
+	fn read(reader: &mut dyn Reader) -> Result<Segment<T>, ser::Error> {
+		let identifier = SegmentIdentifier::read(reader)?;
+		// a segment can't hold more nodes than its full subtree
+		let max_len = (2u64 << identifier.height) - 1;
+
+		let len = reader.read_u64()?;
+		if len > max_len {
+			return Err(ser::Error::TooLargeReadErr);
+		}
+		let capacity = min(len, READ_CAPACITY) as usize;
+		let mut hash_pos = Vec::with_capacity(capacity);
+		let mut hashes = Vec::with_capacity(capacity);
+		for _ in 0..len {
+			hash_pos.push(reader.read_u64()?);
+			hashes.push(Hash::read(reader)?);
+		}
+
+		let len = reader.read_u64()?;
+		if len > max_len {
+			return Err(ser::Error::TooLargeReadErr);
+		}
+		let capacity = min(len, READ_CAPACITY) as usize;
+		let mut leaf_pos = Vec::with_capacity(capacity);
+		let mut leaf_data = Vec::with_capacity(capacity);
+		for _ in 0..len {
+			leaf_pos.push(reader.read_u64()?);
+			leaf_data.push(T::read(reader)?);
+		}
+
+		let proof = SegmentProof::read(reader)?;
+		Ok(Segment {
+			identifier,
+			hash_pos,
+			hashes,
+			leaf_pos,
+			leaf_data,
+			proof,
+		})
+	}
+}
@@ -19,8 +19,8 @@
 use crate::consensus::{
 	graph_weight, valid_header_version, HeaderInfo, BASE_EDGE_BITS, BLOCK_TIME_SEC,
 	COINBASE_MATURITY, CUT_THROUGH_HORIZON, DAY_HEIGHT, DEFAULT_MIN_EDGE_BITS,
-	DIFFICULTY_ADJUST_WINDOW, INITIAL_DIFFICULTY, MAX_BLOCK_WEIGHT, PROOFSIZE,
-	SECOND_POW_EDGE_BITS, STATE_SYNC_THRESHOLD,
+	DIFFICULTY_ADJUST_WINDOW, GENESIS_REWARD, HALVING_INTERVAL, INITIAL_DIFFICULTY, INITIAL_REWARD,
+	MAX_BLOCK_WEIGHT, PROOFSIZE, SECOND_POW_EDGE_BITS, STATE_SYNC_THRESHOLD,
 };
 use crate::core::block::HeaderVersion;
 use crate::pow::{
@@ -43,6 +43,18 @@ use util::RwLock;
 /// This defines the p2p layer protocol version for this node.
 pub const PROTOCOL_VERSION: u32 = 2;
 
+/// Floonet p2p magic number
+pub const FLOONET_P2P_MAGIC: [u8; 2] = [83, 59];
+
+/// Mainnet p2p magic number
+pub const MAINNET_P2P_MAGIC: [u8; 2] = [97, 61];
+
+/// Automated testing and user testing p2p magic number
+pub const TESTING_P2P_MAGIC: [u8; 2] = [73, 43];
+
+/// Default custom chain p2p magic number
+pub const CUSTOM_P2P_MAGIC: [u8; 2] = [67, 85];
+
 /// Automated testing edge_bits
 pub const AUTOMATED_TESTING_MIN_EDGE_BITS: u8 = 10;
 
@@ -114,6 +126,8 @@ pub enum ChainTypes {
 	Floonet,
 	/// Main production network
 	Mainnet,
+	/// Private network, parameters loaded from a chain file
+	Custom,
 }
 
 impl ChainTypes {
@@ -124,6 +138,7 @@ impl ChainTypes {
 			ChainTypes::UserTesting => "user".to_owned(),
 			ChainTypes::Floonet => "floo".to_owned(),
 			ChainTypes::Mainnet => "main".to_owned(),
+			ChainTypes::Custom => "custom".to_owned(),
 		}
 	}
 }
@@ -134,6 +149,112 @@ impl Default for ChainTypes {
 	}
 }
 
+/// Parameters of a custom chain, for private networks. Read from a chain
+/// file, any missing parameter takes its default value.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct ChainParams {
+	/// Block interval, in seconds, the difficulty adjustment targets
+	pub block_time_sec: u64,
+	/// Number of blocks before a coinbase matures and can be spent
+	pub coinbase_maturity: u64,
+	/// Horizon, in blocks, at which cut-through and local pruning happen
+	pub cut_through_horizon: u32,
+	/// Number of blocks in the past at which a txhashset is requested,
+	/// has to be lower than the cut-through horizon
+	pub state_sync_threshold: u32,
+	/// Number of blocks a txhashset archive is reused for, the archive
+	/// height being rounded down to a multiple of it
+	pub archive_interval: u64,
+	/// Cuckatoo edge bits, used for mining and validating
+	pub edge_bits: u8,
+	/// Cuckatoo proof size (cycle length)
+	pub proof_size: usize,
+	/// Difficulty of the genesis block
+	pub initial_difficulty: u64,
+	/// Reward of the genesis block
+	pub genesis_reward: u64,
+	/// Reward of the blocks following genesis, before the first halving
+	pub initial_reward: u64,
+	/// Number of blocks between reward halvings, 0 to never halve
+	pub halving_interval: u64,
+	/// Heights at which the header version is bumped, in increasing order
+	pub hard_forks: Vec<u64>,
+	/// File holding the serialized genesis block, mined on startup with the
+	/// chain parameters if not provided
+	pub genesis_file: Option<String>,
+	/// Magic number of the p2p messages, keeping peers of other chains out
+	pub p2p_magic: [u8; 2],
+}
+
+impl Default for ChainParams {
+	fn default() -> ChainParams {
+		ChainParams {
+			block_time_sec: BLOCK_TIME_SEC,
+			coinbase_maturity: USER_TESTING_COINBASE_MATURITY,
+			cut_through_horizon: USER_TESTING_CUT_THROUGH_HORIZON,
+			state_sync_threshold: TESTING_STATE_SYNC_THRESHOLD,
+			archive_interval: TESTING_TXHASHSET_ARCHIVE_INTERVAL,
+			edge_bits: USER_TESTING_MIN_EDGE_BITS,
+			proof_size: USER_TESTING_PROOF_SIZE,
+			initial_difficulty: TESTING_INITIAL_DIFFICULTY,
+			genesis_reward: GENESIS_REWARD,
+			initial_reward: INITIAL_REWARD,
+			halving_interval: HALVING_INTERVAL,
+			hard_forks: vec![],
+			genesis_file: None,
+			p2p_magic: CUSTOM_P2P_MAGIC,
+		}
+	}
+}
+
+impl ChainParams {
+	/// Checks the parameters are consistent with each other.
+	pub fn validate(&self) -> Result<(), String> {
+		if self.block_time_sec == 0 {
+			return Err("block_time_sec must be positive".to_owned());
+		}
+		if self.edge_bits < AUTOMATED_TESTING_MIN_EDGE_BITS || self.edge_bits > 31 {
+			return Err(format!(
+				"edge_bits must be between {} and 31",
+				AUTOMATED_TESTING_MIN_EDGE_BITS
+			));
+		}
+		if self.proof_size == 0 || self.proof_size % 2 != 0 {
+			return Err("proof_size must be a positive even number".to_owned());
+		}
+		if self.initial_difficulty == 0 {
+			return Err("initial_difficulty must be positive".to_owned());
+		}
+		if self.state_sync_threshold >= self.cut_through_horizon {
+			return Err("state_sync_threshold must be lower than cut_through_horizon".to_owned());
+		}
+		// the txhashset archive is built up to an interval below the sync
+		// threshold, and has to stay within the horizon
+		if self.archive_interval == 0 {
+			return Err("archive_interval must be positive".to_owned());
+		}
+		if self.archive_interval + self.state_sync_threshold as u64
+			>= self.cut_through_horizon as u64
+		{
+			return Err(
+				"archive_interval plus state_sync_threshold must be lower than cut_through_horizon"
+					.to_owned(),
+			);
+		}
+		if self.hard_forks.windows(2).any(|w| w[0] >= w[1]) {
+			return Err("hard_forks must be in increasing order".to_owned());
+		}
+		if self.hard_forks.first() == Some(&0) {
+			return Err("hard_forks can't include the genesis height".to_owned());
+		}
+		if self.p2p_magic == FLOONET_P2P_MAGIC || self.p2p_magic == MAINNET_P2P_MAGIC {
+			return Err("p2p_magic can't be the one of a public network".to_owned());
+		}
+		Ok(())
+	}
+}
+
 /// PoW test mining and verifier context
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub enum PoWContextTypes {
@@ -153,6 +274,10 @@ lazy_static! {
 	/// PoW context type to instantiate
 	pub static ref POW_CONTEXT_TYPE: RwLock<PoWContextTypes> =
 			RwLock::new(PoWContextTypes::Cuckoo);
+
+	/// Parameters of the custom chain, when running one
+	pub static ref CUSTOM_CHAIN: RwLock<ChainParams> =
+			RwLock::new(ChainParams::default());
 }
 
 /// Set the mining mode
@@ -161,6 +286,17 @@ pub fn set_mining_mode(mode: ChainTypes) {
 	*param_ref = mode;
 }
 
+/// Set the parameters of the custom chain
+pub fn set_custom_chain(params: ChainParams) {
+	let mut param_ref = CUSTOM_CHAIN.write();
+	*param_ref = params;
+}
+
+/// Parameters of the custom chain
+pub fn custom_chain() -> ChainParams {
+	CUSTOM_CHAIN.read().clone()
+}
+
 /// Return either a cuckoo context or a cuckatoo context
 /// Single change point
 pub fn create_pow_context<T>(
@@ -207,6 +343,7 @@ pub fn min_edge_bits() -> u8 {
 	match *param_ref {
 		ChainTypes::AutomatedTesting => AUTOMATED_TESTING_MIN_EDGE_BITS,
 		ChainTypes::UserTesting => USER_TESTING_MIN_EDGE_BITS,
+		ChainTypes::Custom => CUSTOM_CHAIN.read().edge_bits,
 		_ => DEFAULT_MIN_EDGE_BITS,
 	}
 }
@@ -219,6 +356,7 @@ pub fn base_edge_bits() -> u8 {
 	match *param_ref {
 		ChainTypes::AutomatedTesting => AUTOMATED_TESTING_MIN_EDGE_BITS,
 		ChainTypes::UserTesting => USER_TESTING_MIN_EDGE_BITS,
+		ChainTypes::Custom => CUSTOM_CHAIN.read().edge_bits,
 		_ => BASE_EDGE_BITS,
 	}
 }
@@ -229,6 +367,7 @@ pub fn proofsize() -> usize {
 	match *param_ref {
 		ChainTypes::AutomatedTesting => AUTOMATED_TESTING_PROOF_SIZE,
 		ChainTypes::UserTesting => USER_TESTING_PROOF_SIZE,
+		ChainTypes::Custom => CUSTOM_CHAIN.read().proof_size,
 		_ => PROOFSIZE,
 	}
 }
@@ -239,6 +378,7 @@ pub fn coinbase_maturity() -> u64 {
 	match *param_ref {
 		ChainTypes::AutomatedTesting => AUTOMATED_TESTING_COINBASE_MATURITY,
 		ChainTypes::UserTesting => USER_TESTING_COINBASE_MATURITY,
+		ChainTypes::Custom => CUSTOM_CHAIN.read().coinbase_maturity,
 		_ => COINBASE_MATURITY,
 	}
 }
@@ -251,6 +391,7 @@ pub fn initial_block_difficulty() -> u64 {
 		ChainTypes::UserTesting => TESTING_INITIAL_DIFFICULTY,
 		ChainTypes::Floonet => INITIAL_DIFFICULTY,
 		ChainTypes::Mainnet => INITIAL_DIFFICULTY,
+		ChainTypes::Custom => CUSTOM_CHAIN.read().initial_difficulty,
 	}
 }
 
@@ -262,6 +403,7 @@ pub fn initial_graph_weight() -> u32 {
 		ChainTypes::UserTesting => TESTING_INITIAL_GRAPH_WEIGHT,
 		ChainTypes::Floonet => graph_weight(0, SECOND_POW_EDGE_BITS) as u32,
 		ChainTypes::Mainnet => graph_weight(0, SECOND_POW_EDGE_BITS) as u32,
+		ChainTypes::Custom => TESTING_INITIAL_GRAPH_WEIGHT,
 	}
 }
 
@@ -273,6 +415,7 @@ pub fn max_block_weight() -> usize {
 		ChainTypes::UserTesting => TESTING_MAX_BLOCK_WEIGHT,
 		ChainTypes::Floonet => MAX_BLOCK_WEIGHT,
 		ChainTypes::Mainnet => MAX_BLOCK_WEIGHT,
+		ChainTypes::Custom => MAX_BLOCK_WEIGHT,
 	}
 }
 
@@ -282,6 +425,7 @@ pub fn cut_through_horizon() -> u32 {
 	match *param_ref {
 		ChainTypes::AutomatedTesting => AUTOMATED_TESTING_CUT_THROUGH_HORIZON,
 		ChainTypes::UserTesting => USER_TESTING_CUT_THROUGH_HORIZON,
+		ChainTypes::Custom => CUSTOM_CHAIN.read().cut_through_horizon,
 		_ => CUT_THROUGH_HORIZON,
 	}
 }
@@ -292,16 +436,65 @@ pub fn state_sync_threshold() -> u32 {
 	match *param_ref {
 		ChainTypes::AutomatedTesting => TESTING_STATE_SYNC_THRESHOLD,
 		ChainTypes::UserTesting => TESTING_STATE_SYNC_THRESHOLD,
+		ChainTypes::Custom => CUSTOM_CHAIN.read().state_sync_threshold,
 		_ => STATE_SYNC_THRESHOLD,
 	}
 }
 
+/// Block interval, in seconds, the difficulty adjustment targets
+pub fn block_time_sec() -> u64 {
+	let param_ref = CHAIN_TYPE.read();
+	match *param_ref {
+		ChainTypes::Custom => CUSTOM_CHAIN.read().block_time_sec,
+		_ => BLOCK_TIME_SEC,
+	}
+}
+
+/// Reward of the genesis block
+pub fn genesis_reward() -> u64 {
+	let param_ref = CHAIN_TYPE.read();
+	match *param_ref {
+		ChainTypes::Custom => CUSTOM_CHAIN.read().genesis_reward,
+		_ => GENESIS_REWARD,
+	}
+}
+
+/// Block reward before the first halving
+pub fn initial_reward() -> u64 {
+	let param_ref = CHAIN_TYPE.read();
+	match *param_ref {
+		ChainTypes::Custom => CUSTOM_CHAIN.read().initial_reward,
+		_ => INITIAL_REWARD,
+	}
+}
+
+/// Number of blocks between reward halvings, 0 when the reward never halves
+pub fn halving_interval() -> u64 {
+	let param_ref = CHAIN_TYPE.read();
+	match *param_ref {
+		ChainTypes::Custom => CUSTOM_CHAIN.read().halving_interval,
+		_ => HALVING_INTERVAL,
+	}
+}
+
+/// Magic number prefixing our p2p messages
+pub fn p2p_magic() -> [u8; 2] {
+	let param_ref = CHAIN_TYPE.read();
+	match *param_ref {
+		ChainTypes::Floonet => FLOONET_P2P_MAGIC,
+		ChainTypes::Mainnet => MAINNET_P2P_MAGIC,
+		ChainTypes::Custom => CUSTOM_CHAIN.read().p2p_magic,
+		_ => TESTING_P2P_MAGIC,
+	}
+}
+
 /// Number of blocks to reuse a txhashset zip for.
 pub fn txhashset_archive_interval() -> u64 {
 	let param_ref = CHAIN_TYPE.read();
 	match *param_ref {
 		ChainTypes::AutomatedTesting => TESTING_TXHASHSET_ARCHIVE_INTERVAL,
 		ChainTypes::UserTesting => TESTING_TXHASHSET_ARCHIVE_INTERVAL,
+		ChainTypes::Custom => CUSTOM_CHAIN.read().archive_interval,
 		_ => TXHASHSET_ARCHIVE_INTERVAL,
 	}
 }
@@ -342,7 +535,7 @@ where
 		let last_ts_delta = if n > 1 {
 			last_n[0].timestamp - last_n[1].timestamp
 		} else {
-			BLOCK_TIME_SEC
+			block_time_sec()
 		};
 		let last_diff = last_n[0].difficulty;
 
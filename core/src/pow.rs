@@ -75,6 +75,11 @@ pub fn verify_size(bh: &BlockHeader) -> Result<(), Error> {
 pub fn mine_genesis_block() -> Result<Block, Error> {
 	let mut gen = genesis::genesis_dev();
 
+	// a custom chain starts at the difficulty set in its chain file
+	if *global::CHAIN_TYPE.read() == global::ChainTypes::Custom {
+		gen.header.pow.total_difficulty = Difficulty::from_num(global::initial_block_difficulty());
+	}
+
 	// total_difficulty on the genesis header *is* the difficulty of that block
 	let genesis_difficulty = gen.header.pow.total_difficulty;
 
@@ -121,6 +126,43 @@ pub fn pow_size(
 	}
 }
 
+/// Same as `pow_size` but with the lean miner, trimming the Cuckatoo graph
+/// before looking for cycles. Much faster from Cuckatoo 19 on.
+pub fn lean_pow_size(
+	bh: &mut BlockHeader,
+	diff: Difficulty,
+	proof_size: usize,
+	sz: u8,
+) -> Result<(), Error> {
+	loop {
+		if let Ok(proofs) = lean_find_cycles(bh, proof_size, sz) {
+			bh.pow.proof = proofs[0].clone();
+			if bh.pow.to_difficulty(bh.height) >= diff {
+				return Ok(());
+			}
+		}
+		bh.pow.nonce = bh.pow.nonce.wrapping_add(1);
+	}
+}
+
+/// Looks for the Cuckatoo cycles of the graph seeded by the header pre-PoW
+/// (its current nonce included) with the lean miner. The proofs returned are
+/// of size `sz`, which should match the edge bits of the header proof.
+pub fn lean_find_cycles(bh: &BlockHeader, proof_size: usize, sz: u8) -> Result<Vec<Proof>, Error> {
+	let pre_pow = bh.pre_pow();
+	let mut miner = lean::Lean::new(sz);
+	miner.set_header(pre_pow.clone());
+	miner.trim();
+
+	let mut ctx = CuckatooContext::<u32>::new_impl(sz, proof_size, MAX_SOLS)?;
+	ctx.set_header_nonce(pre_pow, None, true)?;
+	let mut proofs = miner.find_cycles(ctx)?;
+	for proof in proofs.iter_mut() {
+		proof.edge_bits = sz;
+	}
+	Ok(proofs)
+}
+
 #[cfg(test)]
 mod test {
 	use super::*;
@@ -149,4 +191,33 @@ mod test {
 		assert!(b.header.pow.to_difficulty(0) >= Difficulty::min());
 		assert!(verify_size(&b.header).is_ok());
 	}
+
+	#[test]
+	fn genesis_lean_pow() {
+		global::set_mining_mode(ChainTypes::UserTesting);
+
+		let mut b = genesis::genesis_dev();
+		b.header.pow.proof.edge_bits = global::min_edge_bits();
+		lean_pow_size(
+			&mut b.header,
+			Difficulty::min(),
+			global::proofsize(),
+			global::min_edge_bits(),
+		)
+		.unwrap();
+		assert!(b.header.pow.to_difficulty(0) >= Difficulty::min());
+		assert!(verify_size(&b.header).is_ok());
+	}
+
+	#[test]
+	fn lean_pow_larger_graph() {
+		global::set_mining_mode(ChainTypes::UserTesting);
+		let sz = global::min_edge_bits() + 1;
+
+		let mut b = genesis::genesis_dev();
+		b.header.pow.proof.edge_bits = sz;
+		lean_pow_size(&mut b.header, Difficulty::min(), global::proofsize(), sz).unwrap();
+		assert_eq!(b.header.pow.proof.edge_bits, sz);
+		assert!(verify_size(&b.header).is_ok());
+	}
 }
@@ -47,12 +47,23 @@ impl Lean {
 		self.params.reset_header_nonce(header, Some(nonce)).unwrap();
 	}
 
+	/// Sets the header, its nonce included (as in a header pre-PoW), to seed
+	/// the graph
+	pub fn set_header(&mut self, header: Vec<u8>) {
+		self.params.reset_header_nonce(header, None).unwrap();
+	}
+
 	/// Trim edges in the Cuckatoo graph. This applies multiple trimming rounds
-	/// and works well for Cuckatoo size above 18.
+	/// and works well for Cuckatoo size above 18. On smaller graphs trimming
+	/// stops as soon as a round doesn't kill any edge.
 	pub fn trim(&mut self) {
 		// trimming successively
 		while self.edges.cardinality() > (7 * (self.params.num_edges >> 8) / 8) as u64 {
+			let before = self.edges.cardinality();
 			self.count_and_kill();
+			if self.edges.cardinality() == before {
+				break;
+			}
 		}
 	}
 
@@ -0,0 +1,115 @@
+// Copyright 2020 The Kepler Developers
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use kepler_core::consensus::{header_version, reward, valid_header_version, KEPLER_BASE};
+use kepler_core::core::HeaderVersion;
+use kepler_core::global::{self, ChainParams};
+
+// All tests of this file share the same parameters, as they're global.
+fn setup_custom_chain() {
+	let params = ChainParams {
+		block_time_sec: 10,
+		coinbase_maturity: 5,
+		cut_through_horizon: 100,
+		state_sync_threshold: 50,
+		archive_interval: 20,
+		edge_bits: 12,
+		proof_size: 8,
+		initial_difficulty: 10,
+		genesis_reward: 100 * KEPLER_BASE,
+		initial_reward: 8 * KEPLER_BASE,
+		halving_interval: 1_000,
+		hard_forks: vec![10, 20],
+		genesis_file: None,
+		p2p_magic: [1, 2],
+	};
+	params.validate().unwrap();
+	global::set_custom_chain(params);
+	global::set_mining_mode(global::ChainTypes::Custom);
+}
+
+#[test]
+fn custom_chain_params() {
+	setup_custom_chain();
+	assert_eq!(global::block_time_sec(), 10);
+	assert_eq!(global::coinbase_maturity(), 5);
+	assert_eq!(global::cut_through_horizon(), 100);
+	assert_eq!(global::state_sync_threshold(), 50);
+	assert_eq!(global::txhashset_archive_interval(), 20);
+	assert_eq!(global::min_edge_bits(), 12);
+	assert_eq!(global::base_edge_bits(), 12);
+	assert_eq!(global::proofsize(), 8);
+	assert_eq!(global::initial_block_difficulty(), 10);
+	assert_eq!(global::p2p_magic(), [1, 2]);
+	assert!(!global::is_production_mode());
+}
+
+#[test]
+fn custom_chain_reward() {
+	setup_custom_chain();
+	assert_eq!(reward(0, 0), 100 * KEPLER_BASE);
+	assert_eq!(reward(1, 0), 8 * KEPLER_BASE);
+	assert_eq!(reward(1, 3), 8 * KEPLER_BASE + 3);
+	assert_eq!(reward(999, 0), 8 * KEPLER_BASE);
+	assert_eq!(reward(1_000, 0), 4 * KEPLER_BASE);
+	assert_eq!(reward(1_999, 0), 4 * KEPLER_BASE);
+	assert_eq!(reward(3_000, 0), KEPLER_BASE);
+	assert_eq!(reward(4_000, 0), KEPLER_BASE / 2);
+}
+
+#[test]
+fn custom_chain_hard_forks() {
+	setup_custom_chain();
+	assert_eq!(header_version(0), HeaderVersion(1));
+	assert_eq!(header_version(9), HeaderVersion(1));
+	assert_eq!(header_version(10), HeaderVersion(2));
+	assert_eq!(header_version(19), HeaderVersion(2));
+	assert_eq!(header_version(20), HeaderVersion(3));
+	assert_eq!(header_version(1_000_000), HeaderVersion(3));
+
+	assert!(valid_header_version(9, HeaderVersion(1)));
+	assert!(!valid_header_version(9, HeaderVersion(2)));
+	assert!(valid_header_version(10, HeaderVersion(2)));
+	assert!(valid_header_version(1_000_000, HeaderVersion(3)));
+}
+
+#[test]
+fn invalid_chain_params() {
+	let valid = ChainParams::default();
+	assert!(valid.validate().is_ok());
+
+	let mut params = valid.clone();
+	params.proof_size = 7;
+	assert!(params.validate().is_err());
+
+	let mut params = valid.clone();
+	params.hard_forks = vec![20, 10];
+	assert!(params.validate().is_err());
+
+	let mut params = valid.clone();
+	params.state_sync_threshold = params.cut_through_horizon;
+	assert!(params.validate().is_err());
+
+	// the archive would be built below the horizon
+	let mut params = valid.clone();
+	params.archive_interval = (params.cut_through_horizon - params.state_sync_threshold) as u64;
+	assert!(params.validate().is_err());
+
+	let mut params = valid.clone();
+	params.archive_interval = 0;
+	assert!(params.validate().is_err());
+
+	let mut params = valid.clone();
+	params.p2p_magic = global::MAINNET_P2P_MAGIC;
+	assert!(params.validate().is_err());
+}
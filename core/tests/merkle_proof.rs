@@ -14,10 +14,11 @@
 
 mod common;
 
-use self::core::core::merkle_proof::MerkleProof;
+use self::core::core::merkle_proof::{MerkleProof, MerkleProofError};
 use self::core::core::pmmr::{VecBackend, PMMR};
+use self::core::core::{BlockHeader, TxKernel};
 use self::core::ser::{self, PMMRIndexHashable};
-use crate::common::TestElem;
+use crate::common::{tx1i1o, tx1i2o, tx2i1o, TestElem};
 use kepler_core as core;
 
 #[test]
@@ -43,6 +44,15 @@ fn merkle_proof_ser_deser() {
 	assert_eq!(proof, proof_2);
 }
 
+#[test]
+fn merkle_proof_deser_too_long() {
+	let mut vec = Vec::new();
+	vec.extend_from_slice(&1u64.to_be_bytes());
+	vec.extend_from_slice(&u64::max_value().to_be_bytes());
+	let res: Result<MerkleProof, _> = ser::deserialize_default(&mut &vec[..]);
+	assert_eq!(res, Err(ser::Error::TooLargeReadErr));
+}
+
 #[test]
 fn pmmr_merkle_proof_prune_and_rewind() {
 	let mut ba = VecBackend::new();
@@ -187,3 +197,58 @@ fn pmmr_merkle_proof() {
 	assert_eq!(proof.path, vec![pos_9, pos_6]);
 	assert!(proof.verify(pmmr.root().unwrap(), &elems[6], 11).is_ok());
 }
+
+#[test]
+fn kernel_merkle_proof() {
+	let kernels: Vec<TxKernel> = vec![tx1i1o(), tx1i2o(), tx2i1o()]
+		.iter()
+		.map(|tx| tx.kernels()[0].clone())
+		.collect();
+
+	let mut ba = VecBackend::new();
+	let mut pmmr = PMMR::new(&mut ba);
+	let mut positions = vec![];
+	for kernel in &kernels {
+		positions.push(pmmr.push(kernel).unwrap());
+	}
+
+	let header = BlockHeader {
+		kernel_root: pmmr.root().unwrap(),
+		kernel_mmr_size: pmmr.unpruned_size(),
+		..Default::default()
+	};
+
+	let proof = pmmr.merkle_proof(positions[1]).unwrap();
+	assert!(proof
+		.verify_kernel(&header, &kernels[1], positions[1])
+		.is_ok());
+
+	// another kernel or position doesn't verify
+	assert_eq!(
+		proof.verify_kernel(&header, &kernels[0], positions[1]),
+		Err(MerkleProofError::RootMismatch)
+	);
+	assert!(proof
+		.verify_kernel(&header, &kernels[1], positions[0])
+		.is_err());
+
+	// nor a position that isn't a leaf, without panicking on position 0
+	assert_eq!(
+		proof.verify_kernel(&header, &kernels[1], 0),
+		Err(MerkleProofError::SizeMismatch)
+	);
+	assert_eq!(
+		proof.verify_kernel(&header, &kernels[1], 3),
+		Err(MerkleProofError::SizeMismatch)
+	);
+
+	// nor does a proof built for another kernel mmr size
+	let short_header = BlockHeader {
+		kernel_mmr_size: positions[1],
+		..header.clone()
+	};
+	assert_eq!(
+		proof.verify_kernel(&short_header, &kernels[1], positions[1]),
+		Err(MerkleProofError::SizeMismatch)
+	);
+}
@@ -0,0 +1,163 @@
+// Copyright 2020 The Kepler Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+mod common;
+
+use self::core::core::pmmr::{
+	self, ReadonlyPMMR, Segment, SegmentError, SegmentIdentifier, VecBackend, PMMR,
+};
+use self::core::ser;
+use crate::common::TestElem;
+use croaring::Bitmap;
+use kepler_core as core;
+
+fn build_pmmr(n_leaves: u32) -> VecBackend<TestElem> {
+	let mut ba = VecBackend::new();
+	let mut pmmr = PMMR::new(&mut ba);
+	for x in 0..n_leaves {
+		pmmr.push(&TestElem([0, 0, 0, x])).unwrap();
+	}
+	ba
+}
+
+#[test]
+fn segment_pmmr_range() {
+	// 7 leaves, mmr of size 11 with peaks at 7, 10 and 11
+	let id = SegmentIdentifier { height: 1, idx: 1 };
+	assert_eq!(id.pmmr_range(11), Some((4, 6)));
+	let id = SegmentIdentifier { height: 2, idx: 1 };
+	assert_eq!(id.pmmr_range(11), Some((8, 11)));
+	let id = SegmentIdentifier { height: 2, idx: 2 };
+	assert_eq!(id.pmmr_range(11), None);
+	assert_eq!(SegmentIdentifier::count_segments_required(11, 2), 2);
+	assert_eq!(SegmentIdentifier::count_segments_required(11, 0), 7);
+}
+
+#[test]
+fn segments_validate_against_root() {
+	for n_leaves in 1..40 {
+		let ba = build_pmmr(n_leaves);
+		let mmr_size = ba.size();
+		let pmmr = ReadonlyPMMR::at(&ba, mmr_size);
+		let root = pmmr.root();
+
+		for height in 0..4 {
+			let count = SegmentIdentifier::count_segments_required(mmr_size, height);
+			for idx in 0..count {
+				let id = SegmentIdentifier { height, idx };
+				let segment = Segment::from_pmmr(id, &pmmr, None).unwrap();
+				assert!(segment.is_unpruned());
+				assert_eq!(segment.validate(mmr_size, root), Ok(()));
+			}
+			let id = SegmentIdentifier { height, idx: count };
+			assert_eq!(
+				Segment::from_pmmr(id, &pmmr, None).err(),
+				Some(SegmentError::NonExistent)
+			);
+		}
+	}
+}
+
+#[test]
+fn filtered_segment_validate() {
+	let ba = build_pmmr(23);
+	let mmr_size = ba.size();
+	let pmmr = ReadonlyPMMR::at(&ba, mmr_size);
+	let root = pmmr.root();
+
+	// keep every third leaf only, the others are sent as hashes
+	let mut filter = Bitmap::create();
+	for idx in (0..23).step_by(3) {
+		filter.add(pmmr::insertion_to_pmmr_index(idx + 1) as u32);
+	}
+	for idx in 0..3 {
+		let id = SegmentIdentifier { height: 3, idx };
+		let segment = Segment::from_pmmr(id, &pmmr, Some(&filter)).unwrap();
+		assert!(!segment.is_unpruned());
+		assert!(segment
+			.leaf_iter()
+			.all(|(pos, _)| filter.contains(pos as u32)));
+		assert_eq!(segment.validate(mmr_size, root), Ok(()));
+	}
+}
+
+#[test]
+fn invalid_segment() {
+	let ba = build_pmmr(23);
+	let mmr_size = ba.size();
+	let pmmr = ReadonlyPMMR::at(&ba, mmr_size);
+	let root = pmmr.root();
+	let id = SegmentIdentifier { height: 2, idx: 3 };
+	let segment = Segment::from_pmmr(id, &pmmr, None).unwrap();
+
+	// checked against another mmr
+	let other = build_pmmr(24);
+	let other_root = ReadonlyPMMR::at(&other, other.size()).root();
+	assert!(segment.validate(other.size(), other_root).is_err());
+
+	// a segment whose leaves have been tampered with
+	let mut vec = Vec::new();
+	ser::serialize_default(&mut vec, &segment).unwrap();
+	// identifier, hash count, leaf count, first leaf pos, then its data
+	vec[9 + 8 + 8 + 8 + 15] ^= 1;
+	let tampered: Segment<TestElem> = ser::deserialize_default(&mut &vec[..]).unwrap();
+	assert_eq!(
+		tampered.validate(mmr_size, root),
+		Err(SegmentError::RootMismatch)
+	);
+}
+
+#[test]
+fn segment_ser_deser() {
+	let ba = build_pmmr(13);
+	let pmmr = ReadonlyPMMR::at(&ba, ba.size());
+	let id = SegmentIdentifier { height: 2, idx: 3 };
+	let segment = Segment::from_pmmr(id, &pmmr, None).unwrap();
+
+	let mut vec = Vec::new();
+	ser::serialize_default(&mut vec, &segment).expect("serialization failed");
+	let segment_2: Segment<TestElem> = ser::deserialize_default(&mut &vec[..]).unwrap();
+	assert_eq!(segment, segment_2);
+}
+
+#[test]
+fn segment_deser_oversized() {
+	// too high to be a segment we'd request
+	let mut vec = Vec::new();
+	let id = SegmentIdentifier {
+		height: SegmentIdentifier::MAX_HEIGHT + 1,
+		idx: 0,
+	};
+	ser::serialize_default(&mut vec, &id).unwrap();
+	let res: Result<SegmentIdentifier, _> = ser::deserialize_default(&mut &vec[..]);
+	assert_eq!(res, Err(ser::Error::CorruptedData));
+
+	// more hashes than a segment of its height can hold
+	let mut vec = Vec::new();
+	let id = SegmentIdentifier {
+		height: SegmentIdentifier::MAX_HEIGHT,
+		idx: 0,
+	};
+	ser::serialize_default(&mut vec, &id).unwrap();
+	vec.extend_from_slice(&u64::max_value().to_be_bytes());
+	let res: Result<Segment<TestElem>, _> = ser::deserialize_default(&mut &vec[..]);
+	assert_eq!(res, Err(ser::Error::TooLargeReadErr));
+
+	// as many as it can hold, but not actually sent
+	let mut vec = Vec::new();
+	ser::serialize_default(&mut vec, &id).unwrap();
+	vec.extend_from_slice(&((2u64 << id.height) - 1).to_be_bytes());
+	let res: Result<Segment<TestElem>, _> = ser::deserialize_default(&mut &vec[..]);
+	assert!(res.is_err());
+}
@@ -35,6 +35,16 @@ pub struct ExtKeychain {
 }
 
 impl ExtKeychain {
+	/// Builds a keychain rooted at the provided extended private key, which
+	/// can itself be derived from another keychain (i.e. a wallet account).
+	pub fn from_ext_key(master: ExtendedPrivKey, is_floo: bool) -> ExtKeychain {
+		ExtKeychain {
+			secp: secp::Secp256k1::with_caps(secp::ContextFlag::Commit),
+			master: master,
+			hasher: BIP32KeplerHasher::new(is_floo),
+		}
+	}
+
 	pub fn pub_root_key(&mut self) -> ExtendedPubKey {
 		ExtendedPubKey::from_private(&self.secp, &self.master, &mut self.hasher)
 	}
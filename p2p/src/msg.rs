@@ -14,9 +14,12 @@
 
 //! Message types that transit over the network and related serialization code.
 
+use crate::chain::txhashset::BitmapChunk;
+use crate::chain::SegmentType;
 use crate::conn::Tracker;
 use crate::core::core::hash::Hash;
-use crate::core::core::BlockHeader;
+use crate::core::core::pmmr::{Segment, SegmentIdentifier};
+use crate::core::core::{BlockHeader, OutputIdentifier};
 use crate::core::pow::Difficulty;
 use crate::core::ser::{
 	self, ProtocolVersion, Readable, Reader, StreamingReader, Writeable, Writer,
@@ -33,11 +36,6 @@ use std::sync::Arc;
 /// Kepler's user agent with current version
 pub const USER_AGENT: &str = concat!("MW/Kepler ", env!("CARGO_PKG_VERSION"));
 
-/// Magic numbers expected in the header of every message
-const OTHER_MAGIC: [u8; 2] = [73, 43];
-const FLOONET_MAGIC: [u8; 2] = [83, 59];
-const MAINNET_MAGIC: [u8; 2] = [97, 61];
-
 // Types of messages.
 // Note: Values here are *important* so we should only add new values at the
 // end.
@@ -67,6 +65,20 @@ enum_from_primitive! {
 		TransactionKernel = 20,
 		KernelDataRequest = 21,
 		KernelDataResponse = 22,
+		TxHashSetPartRequest = 23,
+		TxHashSetPart = 24,
+		GetOutputBitmapSegment = 25,
+		OutputBitmapSegment = 26,
+		GetOutputSegment = 27,
+		OutputSegment = 28,
+		GetRangeProofSegment = 29,
+		RangeProofSegment = 30,
+		GetKernelSegment = 31,
+		KernelSegment = 32,
+		GetOutputMerkleProof = 33,
+		OutputMerkleProof = 34,
+		GetKernelMerkleProof = 35,
+		KernelMerkleProof = 36,
 	}
 }
 
@@ -80,6 +92,15 @@ fn default_max_msg_size() -> u64 {
 	max_block_size()
 }
 
+/// Max size of a segment of the height we use for the given tree, each of
+/// its positions holding either a leaf of the given size or a hash.
+fn max_segment_size(segment_type: SegmentType, elmt_size: u64) -> u64 {
+	let leaves = 1u64 << segment_type.segment_height();
+	let nodes = 2 * leaves - 1;
+	// identifier, hashes and leaves with their positions, then the proof
+	9 + 8 + nodes * (8 + 32) + 8 + leaves * (8 + elmt_size) + 8 + 64 * 32
+}
+
 // Max msg size for each msg type.
 fn max_msg_size(msg_type: Type) -> u64 {
 	match msg_type {
@@ -106,15 +127,27 @@ fn max_msg_size(msg_type: Type) -> u64 {
 		Type::TransactionKernel => 32,
 		Type::KernelDataRequest => 0,
 		Type::KernelDataResponse => 8,
-	}
-}
-
+		Type::TxHashSetPartRequest => 80,
+		Type::TxHashSetPart => 112,
+		Type::GetOutputBitmapSegment => 41,
+		Type::OutputBitmapSegment => 72 + max_segment_size(SegmentType::Bitmap, 128),
+		Type::GetOutputSegment => 41,
+		Type::OutputSegment => 64 + max_segment_size(SegmentType::Output, 34),
+		Type::GetRangeProofSegment => 41,
+		Type::RangeProofSegment => 32 + max_segment_size(SegmentType::RangeProof, 8 + 675),
+		Type::GetKernelSegment => 41,
+		Type::KernelSegment => 32 + max_segment_size(SegmentType::Kernel, 120),
+		Type::GetOutputMerkleProof => 33,
+		// header hash, output, position, roots and a proof of up to 64 hashes
+		Type::OutputMerkleProof => 154 + 64 * 32,
+		Type::GetKernelMerkleProof => 33,
+		Type::KernelMerkleProof => 176 + 64 * 32,
+	}
+}
+
+/// Magic number expected in the header of every message
 fn magic() -> [u8; 2] {
-	match *global::CHAIN_TYPE.read() {
-		global::ChainTypes::Floonet => FLOONET_MAGIC,
-		global::ChainTypes::Mainnet => MAINNET_MAGIC,
-		_ => OTHER_MAGIC,
-	}
+	global::p2p_magic()
 }
 
 pub struct Msg {
@@ -709,6 +742,195 @@ impl Readable for TxHashSetArchive {
 	}
 }
 
+/// Request for the txhashset archive of a block from an offset, to resume a
+/// download interrupted after receiving `offset` bytes of the archive with
+/// the provided hash. Only sent to peers with the `TXHASHSET_RESUME`
+/// capability.
+pub struct TxHashSetPartRequest {
+	/// Hash of the block for which the txhashset should be provided
+	pub hash: Hash,
+	/// Height of the corresponding block
+	pub height: u64,
+	/// Hash of the archive already partially downloaded, the zero hash if
+	/// none
+	pub archive_hash: Hash,
+	/// Number of bytes of the archive already downloaded
+	pub offset: u64,
+}
+
+impl Writeable for TxHashSetPartRequest {
+	fn write<W: Writer>(&self, writer: &mut W) -> Result<(), ser::Error> {
+		self.hash.write(writer)?;
+		writer.write_u64(self.height)?;
+		self.archive_hash.write(writer)?;
+		writer.write_u64(self.offset)?;
+		Ok(())
+	}
+}
+
+impl Readable for TxHashSetPartRequest {
+	fn read(reader: &mut dyn Reader) -> Result<TxHashSetPartRequest, ser::Error> {
+		Ok(TxHashSetPartRequest {
+			hash: Hash::read(reader)?,
+			height: reader.read_u64()?,
+			archive_hash: Hash::read(reader)?,
+			offset: reader.read_u64()?,
+		})
+	}
+}
+
+/// Response to a txhashset part request, must include the archive from
+/// `offset` to its end after the message body. The offset is zero when the
+/// requested one can't be served, the archive being a different one.
+pub struct TxHashSetPart {
+	/// Hash of the block for which the txhashset are provided
+	pub hash: Hash,
+	/// Height of the corresponding block
+	pub height: u64,
+	/// Hash of the whole archive, to check it once downloaded
+	pub archive_hash: Hash,
+	/// Size in bytes of the whole archive
+	pub bytes: u64,
+	/// Position in the archive the attachment starts at
+	pub offset: u64,
+}
+
+impl Writeable for TxHashSetPart {
+	fn write<W: Writer>(&self, writer: &mut W) -> Result<(), ser::Error> {
+		self.hash.write(writer)?;
+		writer.write_u64(self.height)?;
+		self.archive_hash.write(writer)?;
+		ser_multiwrite!(writer, [write_u64, self.bytes], [write_u64, self.offset]);
+		Ok(())
+	}
+}
+
+impl Readable for TxHashSetPart {
+	fn read(reader: &mut dyn Reader) -> Result<TxHashSetPart, ser::Error> {
+		let hash = Hash::read(reader)?;
+		let height = reader.read_u64()?;
+		let archive_hash = Hash::read(reader)?;
+		let (bytes, offset) = ser_multiread!(reader, read_u64, read_u64);
+
+		Ok(TxHashSetPart {
+			hash,
+			height,
+			archive_hash,
+			bytes,
+			offset,
+		})
+	}
+}
+
+/// Request for a segment of one of the txhashset trees, at the provided
+/// block, the archive header of the peer. Only sent to peers with the
+/// `PIBD_HIST` capability.
+pub struct SegmentRequest {
+	/// Hash of the block the txhashset is at
+	pub block_hash: Hash,
+	/// Segment requested
+	pub identifier: SegmentIdentifier,
+}
+
+impl Writeable for SegmentRequest {
+	fn write<W: Writer>(&self, writer: &mut W) -> Result<(), ser::Error> {
+		self.block_hash.write(writer)?;
+		self.identifier.write(writer)
+	}
+}
+
+impl Readable for SegmentRequest {
+	fn read(reader: &mut dyn Reader) -> Result<SegmentRequest, ser::Error> {
+		Ok(SegmentRequest {
+			block_hash: Hash::read(reader)?,
+			identifier: SegmentIdentifier::read(reader)?,
+		})
+	}
+}
+
+/// Response to a rangeproof or kernel segment request.
+pub struct SegmentResponse<T> {
+	/// Hash of the block the txhashset is at
+	pub block_hash: Hash,
+	/// The segment requested
+	pub segment: Segment<T>,
+}
+
+impl<T: Writeable> Writeable for SegmentResponse<T> {
+	fn write<W: Writer>(&self, writer: &mut W) -> Result<(), ser::Error> {
+		self.block_hash.write(writer)?;
+		self.segment.write(writer)
+	}
+}
+
+impl<T: Readable> Readable for SegmentResponse<T> {
+	fn read(reader: &mut dyn Reader) -> Result<SegmentResponse<T>, ser::Error> {
+		Ok(SegmentResponse {
+			block_hash: Hash::read(reader)?,
+			segment: Segment::read(reader)?,
+		})
+	}
+}
+
+/// Response to an output segment request, with the root of the bitmap
+/// accumulator the output root is merged with in the header.
+pub struct OutputSegmentResponse {
+	/// The segment requested
+	pub response: SegmentResponse<OutputIdentifier>,
+	/// Root of the bitmap accumulator
+	pub output_bitmap_root: Hash,
+}
+
+impl Writeable for OutputSegmentResponse {
+	fn write<W: Writer>(&self, writer: &mut W) -> Result<(), ser::Error> {
+		self.response.write(writer)?;
+		self.output_bitmap_root.write(writer)
+	}
+}
+
+impl Readable for OutputSegmentResponse {
+	fn read(reader: &mut dyn Reader) -> Result<OutputSegmentResponse, ser::Error> {
+		Ok(OutputSegmentResponse {
+			response: SegmentResponse::read(reader)?,
+			output_bitmap_root: Hash::read(reader)?,
+		})
+	}
+}
+
+/// Response to an output bitmap segment request, with the root of the output
+/// MMR the bitmap root is merged with in the header and the size of the
+/// bitmap MMR.
+pub struct OutputBitmapSegmentResponse {
+	/// Hash of the block the txhashset is at
+	pub block_hash: Hash,
+	/// The segment requested
+	pub segment: Segment<BitmapChunk>,
+	/// Root of the output MMR
+	pub output_root: Hash,
+	/// Size of the bitmap accumulator MMR
+	pub bitmap_mmr_size: u64,
+}
+
+impl Writeable for OutputBitmapSegmentResponse {
+	fn write<W: Writer>(&self, writer: &mut W) -> Result<(), ser::Error> {
+		self.block_hash.write(writer)?;
+		self.segment.write(writer)?;
+		self.output_root.write(writer)?;
+		writer.write_u64(self.bitmap_mmr_size)
+	}
+}
+
+impl Readable for OutputBitmapSegmentResponse {
+	fn read(reader: &mut dyn Reader) -> Result<OutputBitmapSegmentResponse, ser::Error> {
+		Ok(OutputBitmapSegmentResponse {
+			block_hash: Hash::read(reader)?,
+			segment: Segment::read(reader)?,
+			output_root: Hash::read(reader)?,
+			bitmap_mmr_size: reader.read_u64()?,
+		})
+	}
+}
+
 pub struct KernelDataRequest {}
 
 impl Writeable for KernelDataRequest {
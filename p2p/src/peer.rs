@@ -23,21 +23,24 @@ use std::sync::Arc;
 
 use lru_cache::LruCache;
 
-use crate::chain;
+use crate::chain::{self, txhashset::BitmapChunk};
 use crate::conn;
 use crate::core::core::hash::{Hash, Hashed};
+use crate::core::core::pmmr::{Segment, SegmentIdentifier};
 use crate::core::pow::Difficulty;
 use crate::core::ser::Writeable;
 use crate::core::{core, global};
 use crate::handshake::Handshake;
 use crate::msg::{
-	self, BanReason, GetPeerAddrs, KernelDataRequest, Locator, Msg, Ping, TxHashSetRequest, Type,
+	self, BanReason, GetPeerAddrs, KernelDataRequest, Locator, Msg, Ping, SegmentRequest,
+	TxHashSetPartRequest, TxHashSetRequest, Type,
 };
 use crate::protocol::Protocol;
 use crate::types::{
 	Capabilities, ChainAdapter, Error, NetAdapter, P2PConfig, PeerAddr, PeerInfo, ReasonForBan,
 	TxHashSetRead,
 };
+use crate::util::secp::pedersen::{Commitment, RangeProof};
 use chrono::prelude::{DateTime, Utc};
 
 const MAX_TRACK_SIZE: usize = 30;
@@ -402,6 +405,111 @@ impl Peer {
 		)
 	}
 
+	/// Asks for the txhashset archive from an offset, to resume a download
+	/// of the archive with the provided hash
+	pub fn send_txhashset_part_request(
+		&self,
+		height: u64,
+		hash: Hash,
+		archive_hash: Hash,
+		offset: u64,
+	) -> Result<(), Error> {
+		debug!(
+			"Asking {} for txhashset archive at {} {} from {}.",
+			self.info.addr, height, hash, offset
+		);
+		self.state_sync_requested.store(true, Ordering::Relaxed);
+		self.send(
+			&TxHashSetPartRequest {
+				hash,
+				height,
+				archive_hash,
+				offset,
+			},
+			msg::Type::TxHashSetPartRequest,
+		)
+	}
+
+	/// Asks for a segment of the bitmap accumulator at the provided block,
+	/// the archive header of the peer
+	pub fn send_bitmap_segment_request(
+		&self,
+		h: Hash,
+		identifier: SegmentIdentifier,
+	) -> Result<(), Error> {
+		self.send_segment_request(h, identifier, msg::Type::GetOutputBitmapSegment)
+	}
+
+	/// Asks for a segment of the output MMR at the provided block
+	pub fn send_output_segment_request(
+		&self,
+		h: Hash,
+		identifier: SegmentIdentifier,
+	) -> Result<(), Error> {
+		self.send_segment_request(h, identifier, msg::Type::GetOutputSegment)
+	}
+
+	/// Asks for a segment of the rangeproof MMR at the provided block
+	pub fn send_rangeproof_segment_request(
+		&self,
+		h: Hash,
+		identifier: SegmentIdentifier,
+	) -> Result<(), Error> {
+		self.send_segment_request(h, identifier, msg::Type::GetRangeProofSegment)
+	}
+
+	/// Asks for a segment of the kernel MMR at the provided block
+	pub fn send_kernel_segment_request(
+		&self,
+		h: Hash,
+		identifier: SegmentIdentifier,
+	) -> Result<(), Error> {
+		self.send_segment_request(h, identifier, msg::Type::GetKernelSegment)
+	}
+
+	fn send_segment_request(
+		&self,
+		h: Hash,
+		identifier: SegmentIdentifier,
+		msg_type: msg::Type,
+	) -> Result<(), Error> {
+		trace!(
+			"Asking {} for {:?} {:?} at {}.",
+			self.info.addr,
+			msg_type,
+			identifier,
+			h
+		);
+		self.send(
+			&SegmentRequest {
+				block_hash: h,
+				identifier,
+			},
+			msg_type,
+		)
+	}
+
+	/// Asks the peer for a proof of the unspent output with the provided
+	/// commitment.
+	pub fn send_output_merkle_proof_request(&self, commit: Commitment) -> Result<(), Error> {
+		trace!(
+			"Asking {} for an output merkle proof of {:?}.",
+			self.info.addr,
+			commit
+		);
+		self.send(commit, msg::Type::GetOutputMerkleProof)
+	}
+
+	/// Asks the peer for a proof of the kernel with the provided excess.
+	pub fn send_kernel_merkle_proof_request(&self, excess: Commitment) -> Result<(), Error> {
+		trace!(
+			"Asking {} for a kernel merkle proof of {:?}.",
+			self.info.addr,
+			excess
+		);
+		self.send(excess, msg::Type::GetKernelMerkleProof)
+	}
+
 	pub fn send_kernel_data_request(&self) -> Result<(), Error> {
 		debug!("Asking {} for kernel data.", self.info.addr);
 		self.send(&KernelDataRequest {}, msg::Type::KernelDataRequest)
@@ -591,6 +699,116 @@ impl ChainAdapter for TrackingAdapter {
 			.txhashset_download_update(start_time, downloaded_size, total_size)
 	}
 
+	fn get_kernel_segment(
+		&self,
+		hash: Hash,
+		id: SegmentIdentifier,
+	) -> Result<Segment<core::TxKernel>, chain::Error> {
+		self.adapter.get_kernel_segment(hash, id)
+	}
+
+	fn get_bitmap_segment(
+		&self,
+		hash: Hash,
+		id: SegmentIdentifier,
+	) -> Result<(Segment<BitmapChunk>, Hash, u64), chain::Error> {
+		self.adapter.get_bitmap_segment(hash, id)
+	}
+
+	fn get_output_segment(
+		&self,
+		hash: Hash,
+		id: SegmentIdentifier,
+	) -> Result<(Segment<core::OutputIdentifier>, Hash), chain::Error> {
+		self.adapter.get_output_segment(hash, id)
+	}
+
+	fn get_rangeproof_segment(
+		&self,
+		hash: Hash,
+		id: SegmentIdentifier,
+	) -> Result<Segment<RangeProof>, chain::Error> {
+		self.adapter.get_rangeproof_segment(hash, id)
+	}
+
+	fn receive_bitmap_segment(
+		&self,
+		block_hash: Hash,
+		output_root: Hash,
+		bitmap_mmr_size: u64,
+		segment: Segment<BitmapChunk>,
+		peer_info: &PeerInfo,
+	) -> Result<bool, chain::Error> {
+		self.adapter.receive_bitmap_segment(
+			block_hash,
+			output_root,
+			bitmap_mmr_size,
+			segment,
+			peer_info,
+		)
+	}
+
+	fn receive_output_segment(
+		&self,
+		block_hash: Hash,
+		bitmap_root: Hash,
+		segment: Segment<core::OutputIdentifier>,
+		peer_info: &PeerInfo,
+	) -> Result<bool, chain::Error> {
+		self.adapter
+			.receive_output_segment(block_hash, bitmap_root, segment, peer_info)
+	}
+
+	fn receive_rangeproof_segment(
+		&self,
+		block_hash: Hash,
+		segment: Segment<RangeProof>,
+		peer_info: &PeerInfo,
+	) -> Result<bool, chain::Error> {
+		self.adapter
+			.receive_rangeproof_segment(block_hash, segment, peer_info)
+	}
+
+	fn receive_kernel_segment(
+		&self,
+		block_hash: Hash,
+		segment: Segment<core::TxKernel>,
+		peer_info: &PeerInfo,
+	) -> Result<bool, chain::Error> {
+		self.adapter
+			.receive_kernel_segment(block_hash, segment, peer_info)
+	}
+
+	fn get_output_merkle_proof(
+		&self,
+		commit: Commitment,
+	) -> Result<chain::OutputMerkleProof, chain::Error> {
+		self.adapter.get_output_merkle_proof(commit)
+	}
+
+	fn get_kernel_merkle_proof(
+		&self,
+		excess: Commitment,
+	) -> Result<chain::KernelMerkleProof, chain::Error> {
+		self.adapter.get_kernel_merkle_proof(excess)
+	}
+
+	fn output_merkle_proof_received(
+		&self,
+		proof: chain::OutputMerkleProof,
+		peer_info: &PeerInfo,
+	) -> Result<bool, chain::Error> {
+		self.adapter.output_merkle_proof_received(proof, peer_info)
+	}
+
+	fn kernel_merkle_proof_received(
+		&self,
+		proof: chain::KernelMerkleProof,
+		peer_info: &PeerInfo,
+	) -> Result<bool, chain::Error> {
+		self.adapter.kernel_merkle_proof_received(proof, peer_info)
+	}
+
 	fn get_tmp_dir(&self) -> PathBuf {
 		self.adapter.get_tmp_dir()
 	}
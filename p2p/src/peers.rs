@@ -22,17 +22,19 @@ use std::sync::Arc;
 use rand::seq::SliceRandom;
 use rand::thread_rng;
 
-use crate::chain;
+use crate::chain::{self, txhashset::BitmapChunk};
 use crate::core::core;
 use crate::core::core::hash::{Hash, Hashed};
+use crate::core::core::pmmr::{Segment, SegmentIdentifier};
 use crate::core::global;
 use crate::core::pow::Difficulty;
 use crate::peer::Peer;
-use crate::store::{PeerData, PeerStore, State};
+use crate::store::{BanEvent, PeerData, PeerStore, State};
 use crate::types::{
 	Capabilities, ChainAdapter, Error, NetAdapter, P2PConfig, PeerAddr, PeerInfo, ReasonForBan,
 	TxHashSetRead, MAX_PEER_ADDRS,
 };
+use crate::util::secp::pedersen::{Commitment, RangeProof};
 use chrono::prelude::*;
 use chrono::Duration;
 
@@ -42,16 +44,24 @@ pub struct Peers {
 	pub adapter: Arc<dyn ChainAdapter>,
 	store: PeerStore,
 	peers: RwLock<HashMap<PeerAddr, Arc<Peer>>>,
+	/// Peers we keep trying to connect to, the configured `peers_preferred`
+	/// along with the ones marked preferred at runtime
+	preferred: RwLock<Vec<PeerAddr>>,
 	config: P2PConfig,
 }
 
 impl Peers {
 	pub fn new(store: PeerStore, adapter: Arc<dyn ChainAdapter>, config: P2PConfig) -> Peers {
+		let preferred = config
+			.peers_preferred
+			.as_ref()
+			.map_or(vec![], |p| p.peers.clone());
 		Peers {
 			adapter,
 			store,
 			config,
 			peers: RwLock::new(HashMap::new()),
+			preferred: RwLock::new(preferred),
 		}
 	}
 
@@ -73,8 +83,11 @@ impl Peers {
 		};
 		debug!("Saving newly connected peer {}.", peer_data.addr);
 		self.save_peer(&peer_data)?;
+		let peer_info = peer.info.clone();
 		peers.insert(peer_data.addr, peer);
+		drop(peers);
 
+		self.adapter.peer_connected(&peer_info);
 		Ok(())
 	}
 
@@ -91,7 +104,23 @@ impl Peers {
 			last_connected: Utc::now().timestamp(),
 		};
 		debug!("Banning peer {}.", addr);
-		self.save_peer(&peer_data)
+		self.store.save_banned_peer(&peer_data)?;
+		self.adapter.peer_banned(addr, ban_reason);
+		Ok(())
+	}
+
+	/// Marks a peer as preferred until the node is restarted, as if it was
+	/// listed in `peers_preferred`
+	pub fn add_preferred(&self, addr: PeerAddr) {
+		let mut preferred = self.preferred.write();
+		if !preferred.contains(&addr) {
+			preferred.push(addr);
+		}
+	}
+
+	/// Preferred peers, configured or marked at runtime
+	pub fn preferred_peers(&self) -> Vec<PeerAddr> {
+		self.preferred.read().clone()
 	}
 
 	/// Check if this peer address is already known (are we already connected to it)?
@@ -247,9 +276,69 @@ impl Peers {
 		}
 		false
 	}
+
+	/// Bans the peer that sent us a txhashset segment that didn't check out
+	/// against the header we're syncing to.
+	fn check_segment(
+		&self,
+		res: Result<bool, chain::Error>,
+		peer_info: &PeerInfo,
+	) -> Result<bool, chain::Error> {
+		match res {
+			Err(e) => match e.kind() {
+				chain::ErrorKind::InvalidSegment(_)
+				| chain::ErrorKind::InvalidRoot
+				| chain::ErrorKind::InvalidTxHashSet(_) => {
+					debug!(
+						"Received a bad txhashset segment from {}, the peer will be banned: {}",
+						peer_info.addr, e
+					);
+					self.ban_peer(peer_info.addr, ReasonForBan::BadTxHashSet)
+						.map_err(|e| {
+							let err: chain::Error =
+								chain::ErrorKind::Other(format!("ban peer error :{:?}", e)).into();
+							err
+						})?;
+					Ok(false)
+				}
+				_ => Err(e),
+			},
+			Ok(accepted) => Ok(accepted),
+		}
+	}
+
+	/// Bans the peer that sent us a merkle proof that didn't check out
+	/// against our header chain.
+	fn check_merkle_proof(
+		&self,
+		res: Result<bool, chain::Error>,
+		peer_info: &PeerInfo,
+	) -> Result<bool, chain::Error> {
+		match res {
+			Err(e) => match e.kind() {
+				chain::ErrorKind::MerkleProof | chain::ErrorKind::InvalidRoot => {
+					debug!(
+						"Received a bad merkle proof from {}, the peer will be banned: {}",
+						peer_info.addr, e
+					);
+					self.ban_peer(peer_info.addr, ReasonForBan::BadMerkleProof)
+						.map_err(|e| {
+							let err: chain::Error =
+								chain::ErrorKind::Other(format!("ban peer error :{:?}", e)).into();
+							err
+						})?;
+					Ok(false)
+				}
+				_ => Err(e),
+			},
+			Ok(accepted) => Ok(accepted),
+		}
+	}
+
 	/// Ban a peer, disconnecting it if we're currently connected
 	pub fn ban_peer(&self, peer_addr: PeerAddr, ban_reason: ReasonForBan) -> Result<(), Error> {
-		self.update_state(peer_addr, State::Banned)?;
+		self.store.ban_peer(peer_addr, ban_reason)?;
+		self.adapter.peer_banned(peer_addr, ban_reason);
 
 		match self.get_connected_peer(peer_addr) {
 			Some(peer) => {
@@ -258,11 +347,14 @@ impl Peers {
 				peer.send_ban_reason(ban_reason)?;
 				peer.set_banned();
 				peer.stop();
-				let mut peers = self.peers.try_write_for(LOCK_TIMEOUT).ok_or_else(|| {
-					error!("ban_peer: failed to get peers lock");
-					Error::PeerException
-				})?;
-				peers.remove(&peer.info.addr);
+				{
+					let mut peers = self.peers.try_write_for(LOCK_TIMEOUT).ok_or_else(|| {
+						error!("ban_peer: failed to get peers lock");
+						Error::PeerException
+					})?;
+					peers.remove(&peer.info.addr);
+				}
+				self.adapter.peer_disconnected(&peer.info);
 				Ok(())
 			}
 			None => Err(Error::PeerNotFound),
@@ -281,6 +373,30 @@ impl Peers {
 		}
 	}
 
+	/// Disconnect a connected peer without banning it, we may connect to it
+	/// again later
+	pub fn disconnect_peer(&self, peer_addr: PeerAddr) -> Result<(), Error> {
+		let peer = self
+			.get_connected_peer(peer_addr)
+			.ok_or(Error::PeerNotFound)?;
+		debug!("Disconnecting peer {}", peer_addr);
+		peer.stop();
+		{
+			let mut peers = self.peers.try_write_for(LOCK_TIMEOUT).ok_or_else(|| {
+				error!("disconnect_peer: failed to get peers lock");
+				Error::PeerException
+			})?;
+			peers.remove(&peer.info.addr);
+		}
+		self.adapter.peer_disconnected(&peer.info);
+		Ok(())
+	}
+
+	/// All the recorded bans of a peer, oldest first
+	pub fn ban_history(&self, peer_addr: PeerAddr) -> Result<Vec<BanEvent>, Error> {
+		self.store.ban_history(peer_addr).map_err(From::from)
+	}
+
 	fn broadcast<F>(&self, obj_name: &str, inner: F) -> u32
 	where
 		F: Fn(&Peer) -> Result<bool, Error>,
@@ -297,15 +413,18 @@ impl Peers {
 						obj_name, &p.info.addr, e
 					);
 
-					let mut peers = match self.peers.try_write_for(LOCK_TIMEOUT) {
-						Some(peers) => peers,
-						None => {
-							error!("broadcast: failed to get peers lock");
-							break;
-						}
-					};
-					p.stop();
-					peers.remove(&p.info.addr);
+					{
+						let mut peers = match self.peers.try_write_for(LOCK_TIMEOUT) {
+							Some(peers) => peers,
+							None => {
+								error!("broadcast: failed to get peers lock");
+								break;
+							}
+						};
+						p.stop();
+						peers.remove(&p.info.addr);
+					}
+					self.adapter.peer_disconnected(&p.info);
 				}
 			}
 		}
@@ -357,15 +476,18 @@ impl Peers {
 		for p in self.connected_peers().iter() {
 			if let Err(e) = p.send_ping(total_difficulty, height) {
 				debug!("Error pinging peer {:?}: {:?}", &p.info.addr, e);
-				let mut peers = match self.peers.try_write_for(LOCK_TIMEOUT) {
-					Some(peers) => peers,
-					None => {
-						error!("check_all: failed to get peers lock");
-						break;
-					}
-				};
-				p.stop();
-				peers.remove(&p.info.addr);
+				{
+					let mut peers = match self.peers.try_write_for(LOCK_TIMEOUT) {
+						Some(peers) => peers,
+						None => {
+							error!("check_all: failed to get peers lock");
+							break;
+						}
+					};
+					p.stop();
+					peers.remove(&p.info.addr);
+				}
+				self.adapter.peer_disconnected(&p.info);
 			}
 		}
 	}
@@ -465,9 +587,12 @@ impl Peers {
 		let excess_outgoing_count =
 			(self.peer_outbound_count() as usize).saturating_sub(max_outbound_count);
 		if excess_outgoing_count > 0 {
+			// Preferred peers are kept even above the limit
+			let preferred = self.preferred_peers();
 			let mut addrs = self
 				.outgoing_connected_peers()
 				.iter()
+				.filter(|x| !preferred.contains(&x.info.addr))
 				.take(excess_outgoing_count)
 				.map(|x| x.info.addr)
 				.collect::<Vec<_>>();
@@ -488,6 +613,7 @@ impl Peers {
 		}
 
 		// now clean up peer map based on the list to remove
+		let mut removed = vec![];
 		{
 			let mut peers = match self.peers.try_write_for(LOCK_TIMEOUT) {
 				Some(peers) => peers,
@@ -497,10 +623,15 @@ impl Peers {
 				}
 			};
 			for addr in rm {
-				let _ = peers.get(&addr).map(|peer| peer.stop());
-				peers.remove(&addr);
+				if let Some(peer) = peers.remove(&addr) {
+					peer.stop();
+					removed.push(peer);
+				}
 			}
 		}
+		for peer in removed {
+			self.adapter.peer_disconnected(&peer.info);
+		}
 	}
 
 	pub fn stop(&self) {
@@ -725,6 +856,125 @@ impl ChainAdapter for Peers {
 			.txhashset_download_update(start_time, downloaded_size, total_size)
 	}
 
+	fn get_kernel_segment(
+		&self,
+		hash: Hash,
+		id: SegmentIdentifier,
+	) -> Result<Segment<core::TxKernel>, chain::Error> {
+		self.adapter.get_kernel_segment(hash, id)
+	}
+
+	fn get_bitmap_segment(
+		&self,
+		hash: Hash,
+		id: SegmentIdentifier,
+	) -> Result<(Segment<BitmapChunk>, Hash, u64), chain::Error> {
+		self.adapter.get_bitmap_segment(hash, id)
+	}
+
+	fn get_output_segment(
+		&self,
+		hash: Hash,
+		id: SegmentIdentifier,
+	) -> Result<(Segment<core::OutputIdentifier>, Hash), chain::Error> {
+		self.adapter.get_output_segment(hash, id)
+	}
+
+	fn get_rangeproof_segment(
+		&self,
+		hash: Hash,
+		id: SegmentIdentifier,
+	) -> Result<Segment<RangeProof>, chain::Error> {
+		self.adapter.get_rangeproof_segment(hash, id)
+	}
+
+	fn receive_bitmap_segment(
+		&self,
+		block_hash: Hash,
+		output_root: Hash,
+		bitmap_mmr_size: u64,
+		segment: Segment<BitmapChunk>,
+		peer_info: &PeerInfo,
+	) -> Result<bool, chain::Error> {
+		let res = self.adapter.receive_bitmap_segment(
+			block_hash,
+			output_root,
+			bitmap_mmr_size,
+			segment,
+			peer_info,
+		);
+		self.check_segment(res, peer_info)
+	}
+
+	fn receive_output_segment(
+		&self,
+		block_hash: Hash,
+		bitmap_root: Hash,
+		segment: Segment<core::OutputIdentifier>,
+		peer_info: &PeerInfo,
+	) -> Result<bool, chain::Error> {
+		let res = self
+			.adapter
+			.receive_output_segment(block_hash, bitmap_root, segment, peer_info);
+		self.check_segment(res, peer_info)
+	}
+
+	fn receive_rangeproof_segment(
+		&self,
+		block_hash: Hash,
+		segment: Segment<RangeProof>,
+		peer_info: &PeerInfo,
+	) -> Result<bool, chain::Error> {
+		let res = self
+			.adapter
+			.receive_rangeproof_segment(block_hash, segment, peer_info);
+		self.check_segment(res, peer_info)
+	}
+
+	fn receive_kernel_segment(
+		&self,
+		block_hash: Hash,
+		segment: Segment<core::TxKernel>,
+		peer_info: &PeerInfo,
+	) -> Result<bool, chain::Error> {
+		let res = self
+			.adapter
+			.receive_kernel_segment(block_hash, segment, peer_info);
+		self.check_segment(res, peer_info)
+	}
+
+	fn get_output_merkle_proof(
+		&self,
+		commit: Commitment,
+	) -> Result<chain::OutputMerkleProof, chain::Error> {
+		self.adapter.get_output_merkle_proof(commit)
+	}
+
+	fn get_kernel_merkle_proof(
+		&self,
+		excess: Commitment,
+	) -> Result<chain::KernelMerkleProof, chain::Error> {
+		self.adapter.get_kernel_merkle_proof(excess)
+	}
+
+	fn output_merkle_proof_received(
+		&self,
+		proof: chain::OutputMerkleProof,
+		peer_info: &PeerInfo,
+	) -> Result<bool, chain::Error> {
+		let res = self.adapter.output_merkle_proof_received(proof, peer_info);
+		self.check_merkle_proof(res, peer_info)
+	}
+
+	fn kernel_merkle_proof_received(
+		&self,
+		proof: chain::KernelMerkleProof,
+		peer_info: &PeerInfo,
+	) -> Result<bool, chain::Error> {
+		let res = self.adapter.kernel_merkle_proof_received(proof, peer_info);
+		self.check_merkle_proof(res, peer_info)
+	}
+
 	fn get_tmp_dir(&self) -> PathBuf {
 		self.adapter.get_tmp_dir()
 	}
@@ -14,18 +14,19 @@
 
 use crate::chain;
 use crate::conn::{Message, MessageHandler, Tracker};
-use crate::core::core::{self, hash::Hash, hash::Hashed, CompactBlock};
+use crate::core::core::{self, hash::Hash, hash::Hashed, hash::ZERO_HASH, CompactBlock};
 
 use crate::msg::{
-	BanReason, GetPeerAddrs, Headers, KernelDataResponse, Locator, Msg, PeerAddrs, Ping, Pong,
-	TxHashSetArchive, TxHashSetRequest, Type,
+	BanReason, GetPeerAddrs, Headers, KernelDataResponse, Locator, Msg,
+	OutputBitmapSegmentResponse, OutputSegmentResponse, PeerAddrs, Ping, Pong, SegmentRequest,
+	SegmentResponse, TxHashSetArchive, TxHashSetPart, TxHashSetPartRequest, TxHashSetRequest, Type,
 };
-use crate::types::{Error, NetAdapter, PeerInfo};
+use crate::types::{Error, NetAdapter, PeerInfo, TxHashSetDownload};
+use crate::util::secp::pedersen::{Commitment, RangeProof};
 use chrono::prelude::Utc;
-use rand::{thread_rng, Rng};
 use std::cmp;
-use std::fs::{self, File, OpenOptions};
-use std::io::{BufWriter, Seek, SeekFrom};
+use std::fs::File;
+use std::io::{BufWriter, Seek, SeekFrom, Write};
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 use std::time::Instant;
@@ -49,6 +50,139 @@ impl Protocol {
 			state_sync_requested,
 		}
 	}
+
+	/// Saves the txhashset archive attached to the message, from the part
+	/// offset to its end, in the download file of the archive. A download
+	/// stopped halfway is kept to be resumed. Once complete, the archive is
+	/// checked against its hash, when advertised, and handed over to the
+	/// chain.
+	fn receive_txhashset(
+		&self,
+		msg: &mut Message,
+		part: &TxHashSetPart,
+		stopped: Arc<AtomicBool>,
+		tracker: Arc<Tracker>,
+	) -> Result<(), Error> {
+		let (hash, total_size, offset) = (part.hash, part.bytes, part.offset);
+		let archive_hash = if part.archive_hash == ZERO_HASH {
+			None
+		} else {
+			Some(part.archive_hash)
+		};
+
+		if !self.adapter.txhashset_receive_ready() {
+			error!(
+				"handle_payload: txhashset archive received but SyncStatus not on TxHashsetDownload",
+			);
+			return Err(Error::BadMessage);
+		}
+		if !self.state_sync_requested.load(Ordering::Relaxed) {
+			error!("handle_payload: txhashset archive received but from the wrong peer",);
+			return Err(Error::BadMessage);
+		}
+		// Update the sync state requested status
+		self.state_sync_requested.store(false, Ordering::Relaxed);
+
+		let download_start_time = Utc::now();
+		self.adapter
+			.txhashset_download_update(download_start_time, offset, total_size);
+
+		let download = TxHashSetDownload::new(&self.adapter.get_tmp_dir(), hash);
+		// a part we can't append to what we have doesn't touch it, another
+		// peer may still resume it, and the connection to this one is dropped
+		let part_file = match download.open(archive_hash, offset) {
+			Ok(file) => file,
+			Err(e) => {
+				error!(
+					"handle_payload: unusable txhashset part from {}, offset {}: {:?}",
+					self.peer_info.addr, offset, e
+				);
+				return Err(Error::BadMessage);
+			}
+		};
+		let mut now = Instant::now();
+		let mut save_txhashset_to_file = || -> Result<(), Error> {
+			let mut tmp_zip = BufWriter::new(part_file);
+			let total_size = total_size as usize;
+			let mut downloaded_size = offset as usize;
+			let mut request_size = cmp::min(48_000, total_size.saturating_sub(downloaded_size));
+			while request_size > 0 {
+				let size = msg.copy_attachment(request_size, &mut tmp_zip)?;
+				downloaded_size += size;
+				request_size = cmp::min(48_000, total_size - downloaded_size);
+				self.adapter.txhashset_download_update(
+					download_start_time,
+					downloaded_size as u64,
+					total_size as u64,
+				);
+				if now.elapsed().as_secs() > 10 {
+					now = Instant::now();
+					debug!(
+						"handle_payload: txhashset archive: {}/{}",
+						downloaded_size, total_size
+					);
+				}
+				// Increase received bytes quietly (without affecting the counters).
+				// Otherwise we risk banning a peer as "abusive".
+				tracker.inc_quiet_received(size as u64);
+
+				// check the close channel
+				if stopped.load(Ordering::Relaxed) {
+					debug!("stopping txhashset download early");
+					tmp_zip.flush()?;
+					return Err(Error::ConnectionClose);
+				}
+			}
+			debug!(
+				"handle_payload: txhashset archive: {}/{} ... DONE",
+				downloaded_size, total_size
+			);
+			tmp_zip
+				.into_inner()
+				.map_err(|_| Error::Internal)?
+				.sync_all()?;
+			Ok(())
+		};
+
+		if let Err(e) = save_txhashset_to_file() {
+			error!(
+				"handle_payload: txhashset archive save to file fail. err={:?}",
+				e
+			);
+			// what we got so far is kept to resume from
+			return Err(e);
+		}
+
+		trace!(
+			"handle_payload: txhashset archive save to file {:?} success",
+			download.path,
+		);
+
+		if let Some(archive_hash) = archive_hash {
+			let actual = chain::txhashset::archive_hash(&mut File::open(&download.path)?)?;
+			if actual != archive_hash {
+				error!(
+					"handle_payload: txhashset archive hash {} doesn't match the advertised {}",
+					actual, archive_hash
+				);
+				download.remove();
+				return Err(Error::BadMessage);
+			}
+		}
+
+		let tmp_zip = File::open(&download.path)?;
+		let res = self
+			.adapter
+			.txhashset_write(hash, tmp_zip, &self.peer_info)?;
+
+		debug!(
+			"handle_payload: txhashset archive for {}, DONE. Data Ok: {}",
+			hash, !res
+		);
+
+		download.remove();
+		Ok(())
+	}
 }
 
 impl MessageHandler for Protocol {
@@ -346,108 +480,276 @@ impl MessageHandler for Protocol {
 				}
 			}
 
+			Type::TxHashSetPartRequest => {
+				let sm_req: TxHashSetPartRequest = msg.body()?;
+				debug!(
+					"handle_payload: txhashset part req for {} at {} from {}",
+					sm_req.hash, sm_req.height, sm_req.offset
+				);
+
+				let txhashset_header = self.adapter.txhashset_archive_header()?;
+				let txhashset_header_hash = txhashset_header.hash();
+				let txhashset = self.adapter.txhashset_read(txhashset_header_hash);
+
+				if let Some(mut txhashset) = txhashset {
+					let file_sz = txhashset.reader.metadata()?.len();
+					// resume only if the peer has a part of this same archive
+					let offset = if sm_req.archive_hash == txhashset.archive_hash
+						&& sm_req.offset <= file_sz
+					{
+						sm_req.offset
+					} else {
+						0
+					};
+					txhashset.reader.seek(SeekFrom::Start(offset))?;
+					let mut resp = Msg::new(
+						Type::TxHashSetPart,
+						&TxHashSetPart {
+							height: txhashset_header.height as u64,
+							hash: txhashset_header_hash,
+							archive_hash: txhashset.archive_hash,
+							bytes: file_sz,
+							offset,
+						},
+						self.peer_info.version,
+					)?;
+					resp.add_attachment(txhashset.reader);
+					Ok(Some(resp))
+				} else {
+					Ok(None)
+				}
+			}
+
 			Type::TxHashSetArchive => {
 				let sm_arch: TxHashSetArchive = msg.body()?;
 				debug!(
 					"handle_payload: txhashset archive for {} at {}. size={}",
 					sm_arch.hash, sm_arch.height, sm_arch.bytes,
 				);
-				if !self.adapter.txhashset_receive_ready() {
-					error!(
-						"handle_payload: txhashset archive received but SyncStatus not on TxHashsetDownload",
-					);
-					return Err(Error::BadMessage);
+				// older peers don't advertise the archive hash
+				let sm_part = TxHashSetPart {
+					hash: sm_arch.hash,
+					height: sm_arch.height,
+					archive_hash: ZERO_HASH,
+					bytes: sm_arch.bytes,
+					offset: 0,
+				};
+				self.receive_txhashset(&mut msg, &sm_part, stopped, tracker)?;
+				Ok(None)
+			}
+
+			Type::TxHashSetPart => {
+				let sm_part: TxHashSetPart = msg.body()?;
+				debug!(
+					"handle_payload: txhashset part for {} at {}. size={}, offset={}, archive={}",
+					sm_part.hash,
+					sm_part.height,
+					sm_part.bytes,
+					sm_part.offset,
+					sm_part.archive_hash,
+				);
+				self.receive_txhashset(&mut msg, &sm_part, stopped, tracker)?;
+				Ok(None)
+			}
+
+			Type::GetOutputBitmapSegment => {
+				let req: SegmentRequest = msg.body()?;
+				let SegmentRequest {
+					block_hash,
+					identifier,
+				} = req;
+				match self.adapter.get_bitmap_segment(block_hash, identifier) {
+					Ok((segment, output_root, bitmap_mmr_size)) => Ok(Some(Msg::new(
+						Type::OutputBitmapSegment,
+						OutputBitmapSegmentResponse {
+							block_hash,
+							segment,
+							output_root,
+							bitmap_mmr_size,
+						},
+						self.peer_info.version,
+					)?)),
+					Err(e) => {
+						debug!(
+							"handle_payload: GetOutputBitmapSegment {:?} at {} failed: {}",
+							identifier, block_hash, e
+						);
+						Ok(None)
+					}
 				}
-				if !self.state_sync_requested.load(Ordering::Relaxed) {
-					error!("handle_payload: txhashset archive received but from the wrong peer",);
-					return Err(Error::BadMessage);
+			}
+
+			Type::GetOutputSegment => {
+				let req: SegmentRequest = msg.body()?;
+				let SegmentRequest {
+					block_hash,
+					identifier,
+				} = req;
+				match self.adapter.get_output_segment(block_hash, identifier) {
+					Ok((segment, output_bitmap_root)) => Ok(Some(Msg::new(
+						Type::OutputSegment,
+						OutputSegmentResponse {
+							response: SegmentResponse {
+								block_hash,
+								segment,
+							},
+							output_bitmap_root,
+						},
+						self.peer_info.version,
+					)?)),
+					Err(e) => {
+						debug!(
+							"handle_payload: GetOutputSegment {:?} at {} failed: {}",
+							identifier, block_hash, e
+						);
+						Ok(None)
+					}
 				}
-				// Update the sync state requested status
-				self.state_sync_requested.store(false, Ordering::Relaxed);
-
-				let download_start_time = Utc::now();
-				self.adapter
-					.txhashset_download_update(download_start_time, 0, sm_arch.bytes);
-
-				let nonce: u32 = thread_rng().gen_range(0, 1_000_000);
-				let tmp = self.adapter.get_tmpfile_pathname(format!(
-					"txhashset-{}-{}.zip",
-					download_start_time.timestamp(),
-					nonce
-				));
-				let mut now = Instant::now();
-				let mut save_txhashset_to_file = |file| -> Result<(), Error> {
-					let mut tmp_zip =
-						BufWriter::new(OpenOptions::new().write(true).create_new(true).open(file)?);
-					let total_size = sm_arch.bytes as usize;
-					let mut downloaded_size: usize = 0;
-					let mut request_size = cmp::min(48_000, total_size);
-					while request_size > 0 {
-						let size = msg.copy_attachment(request_size, &mut tmp_zip)?;
-						downloaded_size += size;
-						request_size = cmp::min(48_000, total_size - downloaded_size);
-						self.adapter.txhashset_download_update(
-							download_start_time,
-							downloaded_size as u64,
-							total_size as u64,
+			}
+
+			Type::GetRangeProofSegment => {
+				let req: SegmentRequest = msg.body()?;
+				let SegmentRequest {
+					block_hash,
+					identifier,
+				} = req;
+				match self.adapter.get_rangeproof_segment(block_hash, identifier) {
+					Ok(segment) => Ok(Some(Msg::new(
+						Type::RangeProofSegment,
+						SegmentResponse {
+							block_hash,
+							segment,
+						},
+						self.peer_info.version,
+					)?)),
+					Err(e) => {
+						debug!(
+							"handle_payload: GetRangeProofSegment {:?} at {} failed: {}",
+							identifier, block_hash, e
 						);
-						if now.elapsed().as_secs() > 10 {
-							now = Instant::now();
-							debug!(
-								"handle_payload: txhashset archive: {}/{}",
-								downloaded_size, total_size
-							);
-						}
-						// Increase received bytes quietly (without affecting the counters).
-						// Otherwise we risk banning a peer as "abusive".
-						tracker.inc_quiet_received(size as u64);
-
-						// check the close channel
-						if stopped.load(Ordering::Relaxed) {
-							debug!("stopping txhashset download early");
-							return Err(Error::ConnectionClose);
-						}
+						Ok(None)
 					}
-					debug!(
-						"handle_payload: txhashset archive: {}/{} ... DONE",
-						downloaded_size, total_size
-					);
-					tmp_zip
-						.into_inner()
-						.map_err(|_| Error::Internal)?
-						.sync_all()?;
-					Ok(())
-				};
+				}
+			}
 
-				if let Err(e) = save_txhashset_to_file(tmp.clone()) {
-					error!(
-						"handle_payload: txhashset archive save to file fail. err={:?}",
-						e
-					);
-					return Err(e);
+			Type::GetKernelSegment => {
+				let req: SegmentRequest = msg.body()?;
+				let SegmentRequest {
+					block_hash,
+					identifier,
+				} = req;
+				match self.adapter.get_kernel_segment(block_hash, identifier) {
+					Ok(segment) => Ok(Some(Msg::new(
+						Type::KernelSegment,
+						SegmentResponse {
+							block_hash,
+							segment,
+						},
+						self.peer_info.version,
+					)?)),
+					Err(e) => {
+						debug!(
+							"handle_payload: GetKernelSegment {:?} at {} failed: {}",
+							identifier, block_hash, e
+						);
+						Ok(None)
+					}
 				}
+			}
 
-				trace!(
-					"handle_payload: txhashset archive save to file {:?} success",
-					tmp,
-				);
+			Type::OutputBitmapSegment => {
+				let res: OutputBitmapSegmentResponse = msg.body()?;
+				let OutputBitmapSegmentResponse {
+					block_hash,
+					segment,
+					output_root,
+					bitmap_mmr_size,
+				} = res;
+				adapter.receive_bitmap_segment(
+					block_hash,
+					output_root,
+					bitmap_mmr_size,
+					segment,
+					&self.peer_info,
+				)?;
+				Ok(None)
+			}
+
+			Type::OutputSegment => {
+				let res: OutputSegmentResponse = msg.body()?;
+				let OutputSegmentResponse {
+					response,
+					output_bitmap_root,
+				} = res;
+				adapter.receive_output_segment(
+					response.block_hash,
+					output_bitmap_root,
+					response.segment,
+					&self.peer_info,
+				)?;
+				Ok(None)
+			}
 
-				let tmp_zip = File::open(tmp.clone())?;
-				let res = self
-					.adapter
-					.txhashset_write(sm_arch.hash, tmp_zip, &self.peer_info)?;
+			Type::RangeProofSegment => {
+				let res: SegmentResponse<RangeProof> = msg.body()?;
+				adapter.receive_rangeproof_segment(res.block_hash, res.segment, &self.peer_info)?;
+				Ok(None)
+			}
 
-				debug!(
-					"handle_payload: txhashset archive for {} at {}, DONE. Data Ok: {}",
-					sm_arch.hash, sm_arch.height, !res
-				);
+			Type::KernelSegment => {
+				let res: SegmentResponse<core::TxKernel> = msg.body()?;
+				adapter.receive_kernel_segment(res.block_hash, res.segment, &self.peer_info)?;
+				Ok(None)
+			}
 
-				if let Err(e) = fs::remove_file(tmp.clone()) {
-					warn!("fail to remove tmp file: {:?}. err: {}", tmp, e);
+			Type::GetOutputMerkleProof => {
+				let commit: Commitment = msg.body()?;
+				match adapter.get_output_merkle_proof(commit) {
+					Ok(proof) => Ok(Some(Msg::new(
+						Type::OutputMerkleProof,
+						proof,
+						self.peer_info.version,
+					)?)),
+					Err(e) => {
+						debug!(
+							"handle_payload: GetOutputMerkleProof {:?} failed: {}",
+							commit, e
+						);
+						Ok(None)
+					}
+				}
+			}
+
+			Type::OutputMerkleProof => {
+				let proof: chain::OutputMerkleProof = msg.body()?;
+				adapter.output_merkle_proof_received(proof, &self.peer_info)?;
+				Ok(None)
+			}
+
+			Type::GetKernelMerkleProof => {
+				let excess: Commitment = msg.body()?;
+				match adapter.get_kernel_merkle_proof(excess) {
+					Ok(proof) => Ok(Some(Msg::new(
+						Type::KernelMerkleProof,
+						proof,
+						self.peer_info.version,
+					)?)),
+					Err(e) => {
+						debug!(
+							"handle_payload: GetKernelMerkleProof {:?} failed: {}",
+							excess, e
+						);
+						Ok(None)
+					}
 				}
+			}
 
+			Type::KernelMerkleProof => {
+				let proof: chain::KernelMerkleProof = msg.body()?;
+				adapter.kernel_merkle_proof_received(proof, &self.peer_info)?;
 				Ok(None)
 			}
+
 			Type::Error | Type::Hand | Type::Shake => {
 				debug!("Received an unexpected msg: {:?}", msg.header.msg_type);
 				Ok(None)
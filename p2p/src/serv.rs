@@ -20,9 +20,10 @@ use std::sync::Arc;
 use std::thread;
 use std::time::Duration;
 
-use crate::chain;
+use crate::chain::{self, txhashset::BitmapChunk};
 use crate::core::core;
 use crate::core::core::hash::Hash;
+use crate::core::core::pmmr::{Segment, SegmentIdentifier};
 use crate::core::global;
 use crate::core::pow::Difficulty;
 use crate::handshake::Handshake;
@@ -33,6 +34,7 @@ use crate::types::{
 	Capabilities, ChainAdapter, Error, NetAdapter, P2PConfig, PeerAddr, PeerInfo, ReasonForBan,
 	TxHashSetRead,
 };
+use crate::util::secp::pedersen::{Commitment, RangeProof};
 use crate::util::StopState;
 use chrono::prelude::{DateTime, Utc};
 
@@ -147,7 +149,7 @@ impl Server {
 
 		if Peer::is_denied(&self.config, addr) {
 			debug!("connect_peer: peer {} denied, not connecting.", addr);
-			return Err(Error::ConnectionClose);
+			return Err(Error::PeerDenied);
 		}
 
 		if global::is_production_mode() {
@@ -374,6 +376,107 @@ impl ChainAdapter for DummyAdapter {
 		false
 	}
 
+	fn get_kernel_segment(
+		&self,
+		_hash: Hash,
+		_id: SegmentIdentifier,
+	) -> Result<Segment<core::TxKernel>, chain::Error> {
+		unimplemented!()
+	}
+
+	fn get_bitmap_segment(
+		&self,
+		_hash: Hash,
+		_id: SegmentIdentifier,
+	) -> Result<(Segment<BitmapChunk>, Hash, u64), chain::Error> {
+		unimplemented!()
+	}
+
+	fn get_output_segment(
+		&self,
+		_hash: Hash,
+		_id: SegmentIdentifier,
+	) -> Result<(Segment<core::OutputIdentifier>, Hash), chain::Error> {
+		unimplemented!()
+	}
+
+	fn get_rangeproof_segment(
+		&self,
+		_hash: Hash,
+		_id: SegmentIdentifier,
+	) -> Result<Segment<RangeProof>, chain::Error> {
+		unimplemented!()
+	}
+
+	fn receive_bitmap_segment(
+		&self,
+		_block_hash: Hash,
+		_output_root: Hash,
+		_bitmap_mmr_size: u64,
+		_segment: Segment<BitmapChunk>,
+		_peer_info: &PeerInfo,
+	) -> Result<bool, chain::Error> {
+		Ok(false)
+	}
+
+	fn receive_output_segment(
+		&self,
+		_block_hash: Hash,
+		_bitmap_root: Hash,
+		_segment: Segment<core::OutputIdentifier>,
+		_peer_info: &PeerInfo,
+	) -> Result<bool, chain::Error> {
+		Ok(false)
+	}
+
+	fn receive_rangeproof_segment(
+		&self,
+		_block_hash: Hash,
+		_segment: Segment<RangeProof>,
+		_peer_info: &PeerInfo,
+	) -> Result<bool, chain::Error> {
+		Ok(false)
+	}
+
+	fn receive_kernel_segment(
+		&self,
+		_block_hash: Hash,
+		_segment: Segment<core::TxKernel>,
+		_peer_info: &PeerInfo,
+	) -> Result<bool, chain::Error> {
+		Ok(false)
+	}
+
+	fn get_output_merkle_proof(
+		&self,
+		_commit: Commitment,
+	) -> Result<chain::OutputMerkleProof, chain::Error> {
+		Err(chain::ErrorKind::OutputNotFound.into())
+	}
+
+	fn get_kernel_merkle_proof(
+		&self,
+		_excess: Commitment,
+	) -> Result<chain::KernelMerkleProof, chain::Error> {
+		Err(chain::ErrorKind::Other("kernel not found".to_owned()).into())
+	}
+
+	fn output_merkle_proof_received(
+		&self,
+		_proof: chain::OutputMerkleProof,
+		_peer_info: &PeerInfo,
+	) -> Result<bool, chain::Error> {
+		Ok(false)
+	}
+
+	fn kernel_merkle_proof_received(
+		&self,
+		_proof: chain::KernelMerkleProof,
+		_peer_info: &PeerInfo,
+	) -> Result<bool, chain::Error> {
+		Ok(false)
+	}
+
 	fn get_tmp_dir(&self) -> PathBuf {
 		unimplemented!()
 	}
@@ -21,12 +21,17 @@ use rand::thread_rng;
 
 use crate::core::ser::{self, Readable, Reader, Writeable, Writer};
 use crate::types::{Capabilities, PeerAddr, ReasonForBan};
-use kepler_store::{self, option_to_not_found, to_key, Error};
+use kepler_store::{self, option_to_not_found, to_key, to_key_u64, Batch, Error};
 
 const DB_NAME: &str = "peer";
 const STORE_SUBPATH: &str = "peers";
 
 const PEER_PREFIX: u8 = b'P';
+const BAN_PREFIX: u8 = b'B';
+
+/// Maximum number of bans kept in the history of a peer, the oldest being
+/// dropped first
+const MAX_BANS_PER_PEER: usize = 100;
 
 // Types of messages
 enum_from_primitive! {
@@ -108,6 +113,30 @@ impl Readable for PeerData {
 	}
 }
 
+/// A past ban of a peer.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct BanEvent {
+	/// The time the peer was banned
+	pub time: i64,
+	/// The reason for the ban
+	pub reason: ReasonForBan,
+}
+
+impl Writeable for BanEvent {
+	fn write<W: Writer>(&self, writer: &mut W) -> Result<(), ser::Error> {
+		writer.write_i64(self.time)?;
+		writer.write_i32(self.reason as i32)
+	}
+}
+
+impl Readable for BanEvent {
+	fn read(reader: &mut dyn Reader) -> Result<BanEvent, ser::Error> {
+		let time = reader.read_i64()?;
+		let reason = ReasonForBan::from_i32(reader.read_i32()?).ok_or(ser::Error::CorruptedData)?;
+		Ok(BanEvent { time, reason })
+	}
+}
+
 /// Storage facility for peer data.
 pub struct PeerStore {
 	db: kepler_store::Store,
@@ -143,6 +172,7 @@ impl PeerStore {
 	pub fn delete_peer(&self, peer_addr: PeerAddr) -> Result<(), Error> {
 		let batch = self.db.batch()?;
 		batch.delete(&peer_key(peer_addr)[..])?;
+		delete_bans(&batch, peer_addr, 0)?;
 		batch.commit()
 	}
 
@@ -191,7 +221,52 @@ impl PeerStore {
 		batch.commit()
 	}
 
-	/// Deletes peers from the storage that satisfy some condition `predicate`
+	/// Marks a peer as banned for the provided reason, recording the ban in
+	/// its history.
+	pub fn ban_peer(&self, peer_addr: PeerAddr, reason: ReasonForBan) -> Result<(), Error> {
+		let batch = self.db.batch()?;
+
+		let mut peer =
+			option_to_not_found(batch.get_ser::<PeerData>(&peer_key(peer_addr)[..]), || {
+				format!("Peer at address: {}", peer_addr)
+			})?;
+		let event = BanEvent {
+			time: Utc::now().timestamp(),
+			reason,
+		};
+		peer.flags = State::Banned;
+		peer.last_banned = event.time;
+		peer.ban_reason = reason;
+
+		batch.put_ser(&peer_key(peer_addr)[..], &peer)?;
+		add_ban(&batch, peer_addr, &event)?;
+		batch.commit()
+	}
+
+	/// Saves a peer along with its ban in its history, for peers banned
+	/// before we get to know them.
+	pub fn save_banned_peer(&self, p: &PeerData) -> Result<(), Error> {
+		let event = BanEvent {
+			time: p.last_banned,
+			reason: p.ban_reason,
+		};
+		let batch = self.db.batch()?;
+		batch.put_ser(&peer_key(p.addr)[..], p)?;
+		add_ban(&batch, p.addr, &event)?;
+		batch.commit()
+	}
+
+	/// All the recorded bans of a peer, oldest first.
+	pub fn ban_history(&self, peer_addr: PeerAddr) -> Result<Vec<BanEvent>, Error> {
+		Ok(self
+			.db
+			.iter::<BanEvent>(&ban_prefix(peer_addr))?
+			.map(|(_, v)| v)
+			.collect::<Vec<_>>())
+	}
+
+	/// Deletes peers from the storage that satisfy some condition `predicate`,
+	/// along with their ban history
 	pub fn delete_peers<F>(&self, predicate: F) -> Result<(), Error>
 	where
 		F: Fn(&PeerData) -> bool,
@@ -210,6 +285,7 @@ impl PeerStore {
 
 			for peer in to_remove {
 				batch.delete(&peer_key(peer.addr)[..])?;
+				delete_bans(&batch, peer.addr, 0)?;
 			}
 
 			batch.commit()?;
@@ -223,3 +299,53 @@ impl PeerStore {
 fn peer_key(peer_addr: PeerAddr) -> Vec<u8> {
 	to_key(PEER_PREFIX, &mut peer_addr.as_key().into_bytes())
 }
+
+// Address followed by a separator, so an address isn't a prefix of another.
+fn ban_addr(peer_addr: PeerAddr) -> Vec<u8> {
+	let mut addr = peer_addr.as_key().into_bytes();
+	addr.push(0);
+	addr
+}
+
+fn ban_prefix(peer_addr: PeerAddr) -> Vec<u8> {
+	to_key(BAN_PREFIX, &mut ban_addr(peer_addr))
+}
+
+// Big-endian time so the bans of a peer iterate in chronological order,
+// followed by a sequence number for the bans within the same second.
+fn ban_key(peer_addr: PeerAddr, time: i64, seq: u64) -> Vec<u8> {
+	let mut id = ban_addr(peer_addr);
+	id.extend_from_slice(&(time as u64).to_be_bytes());
+	to_key_u64(BAN_PREFIX, &mut id, seq)
+}
+
+// First ban key of the peer at that time not taken yet.
+fn next_ban_key(batch: &Batch<'_>, peer_addr: PeerAddr, time: i64) -> Result<Vec<u8>, Error> {
+	let mut seq = 0;
+	loop {
+		let key = ban_key(peer_addr, time, seq);
+		if batch.get_ser::<BanEvent>(&key)?.is_none() {
+			return Ok(key);
+		}
+		seq += 1;
+	}
+}
+
+// Records a ban in the history of the peer, dropping the oldest ones past
+// the maximum kept.
+fn add_ban(batch: &Batch<'_>, peer_addr: PeerAddr, event: &BanEvent) -> Result<(), Error> {
+	delete_bans(batch, peer_addr, MAX_BANS_PER_PEER - 1)?;
+	batch.put_ser(&next_ban_key(batch, peer_addr, event.time)?[..], event)
+}
+
+// Deletes the oldest bans of the peer, keeping at most `keep` of them.
+fn delete_bans(batch: &Batch<'_>, peer_addr: PeerAddr, keep: usize) -> Result<(), Error> {
+	let keys = batch
+		.iter::<BanEvent>(&ban_prefix(peer_addr))?
+		.map(|(k, _)| k)
+		.collect::<Vec<_>>();
+	for key in keys.iter().take(keys.len().saturating_sub(keep)) {
+		batch.delete(key)?;
+	}
+	Ok(())
+}
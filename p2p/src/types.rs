@@ -14,10 +14,10 @@
 
 use std::convert::From;
 use std::fmt;
-use std::fs::File;
+use std::fs::{self, File, OpenOptions};
 use std::io::{self, Read};
 use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV4, SocketAddrV6, ToSocketAddrs};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
 use std::sync::Arc;
 
@@ -28,12 +28,15 @@ use serde::{Deserialize, Deserializer};
 use kepler_store;
 
 use crate::chain;
+use crate::chain::txhashset::BitmapChunk;
 use crate::core::core;
 use crate::core::core::hash::Hash;
+use crate::core::core::pmmr::{Segment, SegmentIdentifier};
 use crate::core::global;
 use crate::core::pow::Difficulty;
 use crate::core::ser::{self, ProtocolVersion, Readable, Reader, Writeable, Writer};
 use crate::msg::PeerAddrs;
+use crate::util::secp::pedersen::{Commitment, RangeProof};
 use crate::util::RwLock;
 
 /// Maximum number of block headers a peer should ever send
@@ -87,6 +90,8 @@ pub enum Error {
 	PeerNotFound,
 	PeerNotBanned,
 	PeerException,
+	/// The peer is listed in `peers_deny`, or missing from `peers_allow`
+	PeerDenied,
 	Internal,
 }
 
@@ -296,7 +301,10 @@ impl Default for P2PConfig {
 		P2PConfig {
 			host: ipaddr,
 			port: 7414,
-			capabilities: Capabilities::FULL_NODE,
+			capabilities: Capabilities::FULL_NODE
+				| Capabilities::TXHASHSET_RESUME
+				| Capabilities::PIBD_HIST
+				| Capabilities::MERKLE_PROOFS,
 			seeding_type: Seeding::default(),
 			seeds: None,
 			peers_allow: None,
@@ -391,6 +399,15 @@ bitflags! {
 		const PEER_LIST = 0b0000_0100;
 		/// Can broadcast and request txs by kernel hash.
 		const TX_KERNEL_HASH = 0b0000_1000;
+		/// Can serve the TxHashSet archive from an offset, to resume an
+		/// interrupted download.
+		const TXHASHSET_RESUME = 0b0001_0000;
+		/// Can serve the TxHashSet of the archive header in segments, each
+		/// with a proof against the header roots.
+		const PIBD_HIST = 0b0010_0000;
+		/// Can serve merkle proofs of outputs and kernels against the roots
+		/// of its head, to nodes only holding headers.
+		const MERKLE_PROOFS = 0b0100_0000;
 
 		/// All nodes right now are "full nodes".
 		/// Some nodes internally may maintain longer block histories (archival_mode)
@@ -424,6 +441,7 @@ enum_from_primitive! {
 		ManualBan = 5,
 		FraudHeight = 6,
 		BadHandshake = 7,
+		BadMerkleProof = 8,
 	}
 }
 
@@ -537,6 +555,88 @@ pub struct TxHashSetRead {
 	pub kernel_index: u64,
 	/// Binary stream for the txhashset zipped data
 	pub reader: File,
+	/// Hash of the whole archive
+	pub archive_hash: Hash,
+}
+
+/// A txhashset archive being downloaded, kept in the tmp dir until it's
+/// complete so an interrupted download can be resumed, possibly from
+/// another peer serving the same archive.
+pub struct TxHashSetDownload {
+	/// Downloaded part of the archive
+	pub path: PathBuf,
+	/// Hash of the whole archive, as advertised by the peer it came from
+	archive_hash_path: PathBuf,
+}
+
+impl TxHashSetDownload {
+	/// Download of the archive of the provided block
+	pub fn new(tmp_dir: &Path, hash: Hash) -> TxHashSetDownload {
+		TxHashSetDownload {
+			path: tmp_dir.join(format!("txhashset-{}.zip.part", hash)),
+			archive_hash_path: tmp_dir.join(format!("txhashset-{}.zip.part.hash", hash)),
+		}
+	}
+
+	/// Number of bytes downloaded and hash of the archive, if a previous
+	/// download can be resumed
+	pub fn resumable(&self) -> Option<(u64, Hash)> {
+		let size = fs::metadata(&self.path).ok()?.len();
+		let hex = fs::read_to_string(&self.archive_hash_path).ok()?;
+		let archive_hash = Hash::from_hex(hex.trim()).ok()?;
+		if size > 0 {
+			Some((size, archive_hash))
+		} else {
+			None
+		}
+	}
+
+	/// Starts the download over, for the archive with the provided hash
+	fn start(&self, archive_hash: Option<Hash>) -> Result<File, Error> {
+		if let Some(parent) = self.path.parent() {
+			fs::create_dir_all(parent)?;
+			// only one download is kept, downloads of older archives are dropped
+			for entry in fs::read_dir(parent)? {
+				let path = entry?.path();
+				let name = path.file_name().and_then(|n| n.to_str()).unwrap_or("");
+				if name.starts_with("txhashset-")
+					&& name.contains(".zip.part")
+					&& path != self.path
+					&& path != self.archive_hash_path
+				{
+					let _ = fs::remove_file(&path);
+				}
+			}
+		}
+		match archive_hash {
+			Some(h) => fs::write(&self.archive_hash_path, h.to_hex())?,
+			// an archive we can't check, not worth resuming
+			None => {
+				let _ = fs::remove_file(&self.archive_hash_path);
+			}
+		}
+		Ok(File::create(&self.path)?)
+	}
+
+	/// Opens the partial archive to append the rest of the archive from the
+	/// provided offset, which must be the current size of the partial file
+	pub fn open(&self, archive_hash: Option<Hash>, offset: u64) -> Result<File, Error> {
+		if offset == 0 {
+			return self.start(archive_hash);
+		}
+		match (self.resumable(), archive_hash) {
+			(Some((size, h)), Some(archive_hash)) if size == offset && h == archive_hash => {
+				Ok(OpenOptions::new().append(true).open(&self.path)?)
+			}
+			_ => Err(Error::BadMessage),
+		}
+	}
+
+	/// Drops the download, once complete or corrupted
+	pub fn remove(&self) {
+		let _ = fs::remove_file(&self.path);
+		let _ = fs::remove_file(&self.archive_hash_path);
+	}
 }
 
 /// Bridge between the networking layer and the rest of the system. Handles the
@@ -638,12 +738,116 @@ pub trait ChainAdapter: Sync + Send {
 		peer_peer_info: &PeerInfo,
 	) -> Result<bool, chain::Error>;
 
+	/// A segment of the kernel MMR at our archive header, the provided hash.
+	fn get_kernel_segment(
+		&self,
+		hash: Hash,
+		id: SegmentIdentifier,
+	) -> Result<Segment<core::TxKernel>, chain::Error>;
+
+	/// A segment of the bitmap accumulator at our archive header, along with
+	/// the output root and the size of the bitmap MMR needed to check it.
+	fn get_bitmap_segment(
+		&self,
+		hash: Hash,
+		id: SegmentIdentifier,
+	) -> Result<(Segment<BitmapChunk>, Hash, u64), chain::Error>;
+
+	/// A segment of the output MMR at our archive header, along with the
+	/// bitmap root needed to check it.
+	fn get_output_segment(
+		&self,
+		hash: Hash,
+		id: SegmentIdentifier,
+	) -> Result<(Segment<core::OutputIdentifier>, Hash), chain::Error>;
+
+	/// A segment of the rangeproof MMR at our archive header.
+	fn get_rangeproof_segment(
+		&self,
+		hash: Hash,
+		id: SegmentIdentifier,
+	) -> Result<Segment<RangeProof>, chain::Error>;
+
+	/// A segment of the bitmap accumulator has been received. Returns true if
+	/// it was validated and kept, false if it wasn't expected anymore.
+	fn receive_bitmap_segment(
+		&self,
+		block_hash: Hash,
+		output_root: Hash,
+		bitmap_mmr_size: u64,
+		segment: Segment<BitmapChunk>,
+		peer_info: &PeerInfo,
+	) -> Result<bool, chain::Error>;
+
+	/// A segment of the output MMR has been received.
+	fn receive_output_segment(
+		&self,
+		block_hash: Hash,
+		bitmap_root: Hash,
+		segment: Segment<core::OutputIdentifier>,
+		peer_info: &PeerInfo,
+	) -> Result<bool, chain::Error>;
+
+	/// A segment of the rangeproof MMR has been received.
+	fn receive_rangeproof_segment(
+		&self,
+		block_hash: Hash,
+		segment: Segment<RangeProof>,
+		peer_info: &PeerInfo,
+	) -> Result<bool, chain::Error>;
+
+	/// A segment of the kernel MMR has been received.
+	fn receive_kernel_segment(
+		&self,
+		block_hash: Hash,
+		segment: Segment<core::TxKernel>,
+		peer_info: &PeerInfo,
+	) -> Result<bool, chain::Error>;
+
+	/// Proof of the unspent output with the provided commitment against the
+	/// output root of our head.
+	fn get_output_merkle_proof(
+		&self,
+		commit: Commitment,
+	) -> Result<chain::OutputMerkleProof, chain::Error>;
+
+	/// Proof of the kernel with the provided excess against the kernel root
+	/// of our head.
+	fn get_kernel_merkle_proof(
+		&self,
+		excess: Commitment,
+	) -> Result<chain::KernelMerkleProof, chain::Error>;
+
+	/// A proof of an output we asked for has been received. Returns true if
+	/// it checked out against our header chain, false if it wasn't expected.
+	fn output_merkle_proof_received(
+		&self,
+		proof: chain::OutputMerkleProof,
+		peer_info: &PeerInfo,
+	) -> Result<bool, chain::Error>;
+
+	/// A proof of a kernel we asked for has been received.
+	fn kernel_merkle_proof_received(
+		&self,
+		proof: chain::KernelMerkleProof,
+		peer_info: &PeerInfo,
+	) -> Result<bool, chain::Error>;
+
 	/// Get the Kepler specific tmp dir
 	fn get_tmp_dir(&self) -> PathBuf;
 
 	/// Get a tmp file path in above specific tmp dir (create tmp dir if not exist)
 	/// Delete file if tmp file already exists
 	fn get_tmpfile_pathname(&self, tmpfile_name: String) -> PathBuf;
+
+	/// A peer has completed its handshake and is now connected
+	fn peer_connected(&self, _peer_info: &PeerInfo) {}
+
+	/// A connected peer has been disconnected
+	fn peer_disconnected(&self, _peer_info: &PeerInfo) {}
+
+	/// A peer has been banned
+	fn peer_banned(&self, _addr: PeerAddr, _ban_reason: ReasonForBan) {}
 }
 
 /// Additional methods required by the protocol that don't need to be
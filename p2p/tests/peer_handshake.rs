@@ -24,6 +24,7 @@ use std::{thread, time};
 
 use crate::core::core::hash::Hash;
 use crate::core::pow::Difficulty;
+use crate::p2p::msg::PeerAddrs;
 use crate::p2p::types::PeerAddr;
 use crate::p2p::Peer;
 
@@ -91,3 +92,44 @@ fn peer_handshake() {
 	assert_eq!(server_peer.info.total_difficulty(), Difficulty::min());
 	assert!(server.peers.peer_count() > 0);
 }
+
+// Connecting to a denied peer fails without opening a connection, while
+// peers marked preferred at runtime are listed after the configured ones.
+#[test]
+fn peer_denied_and_preferred() {
+	util::init_test_logger();
+
+	let denied = PeerAddr("127.0.0.1:5001".parse().unwrap());
+	let configured = PeerAddr("127.0.0.1:5002".parse().unwrap());
+	let p2p_config = p2p::P2PConfig {
+		host: "127.0.0.1".parse().unwrap(),
+		port: open_port(),
+		peers_deny: Some(PeerAddrs {
+			peers: vec![denied],
+		}),
+		peers_preferred: Some(PeerAddrs {
+			peers: vec![configured],
+		}),
+		..p2p::P2PConfig::default()
+	};
+	let server = p2p::Server::new(
+		".kepler_denied",
+		p2p::Capabilities::UNKNOWN,
+		p2p_config,
+		Arc::new(p2p::DummyAdapter {}),
+		Hash::from_vec(&vec![]),
+		Arc::new(StopState::new()),
+	)
+	.unwrap();
+
+	match server.connect(denied) {
+		Err(p2p::Error::PeerDenied) => {}
+		other => panic!("expected PeerDenied, got {:?}", other.map(|_| ())),
+	}
+	assert_eq!(server.peers.peer_count(), 0);
+
+	let added = PeerAddr("127.0.0.1:5003".parse().unwrap());
+	server.peers.add_preferred(added);
+	server.peers.add_preferred(configured);
+	assert_eq!(server.peers.preferred_peers(), vec![configured, added]);
+}
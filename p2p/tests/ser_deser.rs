@@ -62,10 +62,13 @@ fn test_capabilities() {
 	assert_eq!(
 		p2p::types::Capabilities::from_bits_truncate(0b11111111 as u32),
 		p2p::types::Capabilities::FULL_NODE
+			| p2p::types::Capabilities::TXHASHSET_RESUME
+			| p2p::types::Capabilities::PIBD_HIST
+			| p2p::types::Capabilities::MERKLE_PROOFS
 	);
 	assert_eq!(
 		p2p::types::Capabilities::from_bits_truncate(0b00101111 as u32),
-		p2p::types::Capabilities::FULL_NODE
+		p2p::types::Capabilities::FULL_NODE | p2p::types::Capabilities::PIBD_HIST
 	);
 
 	assert!(
@@ -0,0 +1,70 @@
+// Copyright 2020 The Kepler Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use kepler_core as core;
+use kepler_p2p as p2p;
+
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+
+use crate::core::core::hash::{Hash, ZERO_HASH};
+use crate::p2p::TxHashSetDownload;
+
+// An interrupted download is resumed only for the same archive, from the
+// exact size already downloaded.
+#[test]
+fn test_txhashset_download_resume() {
+	let tmp_dir = Path::new("target/tmp/txhashset_download");
+	let _ = fs::remove_dir_all(tmp_dir);
+
+	let block_hash = Hash::from_vec(&[1, 2, 3]);
+	let archive_hash = Hash::from_vec(&[4, 5, 6]);
+	let download = TxHashSetDownload::new(tmp_dir, block_hash);
+	assert_eq!(download.resumable(), None);
+
+	// older peers don't advertise the archive hash, nothing to resume
+	download.open(None, 0).unwrap().write_all(b"abc").unwrap();
+	assert_eq!(download.resumable(), None);
+
+	download
+		.open(Some(archive_hash), 0)
+		.unwrap()
+		.write_all(b"abcdef")
+		.unwrap();
+	assert_eq!(download.resumable(), Some((6, archive_hash)));
+
+	// another archive or another offset can't be appended
+	assert!(download.open(Some(ZERO_HASH), 6).is_err());
+	assert!(download.open(Some(archive_hash), 4).is_err());
+	assert!(download.open(None, 6).is_err());
+	// and leave what was downloaded to be resumed
+	assert_eq!(download.resumable(), Some((6, archive_hash)));
+
+	download
+		.open(Some(archive_hash), 6)
+		.unwrap()
+		.write_all(b"ghi")
+		.unwrap();
+	assert_eq!(download.resumable(), Some((9, archive_hash)));
+	assert_eq!(fs::read(&download.path).unwrap(), b"abcdefghi".to_vec());
+
+	// starting the download of another archive drops this one
+	let other = TxHashSetDownload::new(tmp_dir, archive_hash);
+	other.open(Some(block_hash), 0).unwrap();
+	assert_eq!(download.resumable(), None);
+
+	other.remove();
+	assert!(!other.path.exists());
+}
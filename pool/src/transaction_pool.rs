@@ -193,10 +193,22 @@ impl TransactionPool {
 
 		// Get last transaction and remove it
 		if let Some(evictable_transaction) = bucket_transactions.last() {
+			let evicted = self
+				.txpool
+				.entries
+				.iter()
+				.filter(|x| x.tx == *evictable_transaction)
+				.cloned()
+				.collect::<Vec<_>>();
+
 			// Remove transaction
 			self.txpool
 				.entries
 				.retain(|x| x.tx != *evictable_transaction);
+
+			for entry in evicted {
+				self.adapter.tx_evicted(&entry);
+			}
 		};
 	}
 
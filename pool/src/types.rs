@@ -277,6 +277,9 @@ pub trait PoolAdapter: Send + Sync {
 
 	/// The stem transaction pool has accepted this transactions as valid.
 	fn stem_tx_accepted(&self, entry: &PoolEntry) -> Result<(), PoolError>;
+
+	/// The transaction pool has evicted this transaction to make room.
+	fn tx_evicted(&self, _entry: &PoolEntry) {}
 }
 
 /// Dummy adapter used as a placeholder for real implementations
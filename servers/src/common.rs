@@ -15,6 +15,8 @@
 //! Modules common to all Kepler server types
 
 pub mod adapters;
+pub mod event_sink;
 pub mod hooks;
+pub mod outbox;
 pub mod stats;
 pub mod types;
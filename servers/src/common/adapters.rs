@@ -23,18 +23,24 @@ use std::sync::{Arc, Weak};
 use std::thread;
 use std::time::Instant;
 
-use crate::chain::{self, BlockStatus, ChainAdapter, Options, SyncState, SyncStatus};
+use crate::chain::txhashset::{BitmapChunk, Desegmenter, Segmenter};
+use crate::chain::{
+	self, BlockStatus, ChainAdapter, Options, SegmentType, SyncState, SyncStatus, SyncStatusAdapter,
+};
 use crate::common::hooks::{ChainEvents, NetEvents};
 use crate::common::types::{ChainValidationMode, DandelionEpoch, ServerConfig};
 use crate::core::core::hash::{Hash, Hashed};
+use crate::core::core::pmmr::{Segment, SegmentIdentifier};
 use crate::core::core::transaction::Transaction;
 use crate::core::core::verifier_cache::VerifierCache;
-use crate::core::core::{BlockHeader, BlockSums, CompactBlock};
+use crate::core::core::{BlockHeader, BlockSums, CompactBlock, OutputIdentifier, TxKernel};
 use crate::core::pow::Difficulty;
 use crate::core::{core, global};
+use crate::kepler::merkle_proofs::MerkleProofRequests;
 use crate::p2p;
-use crate::p2p::types::PeerInfo;
+use crate::p2p::types::{PeerAddr, PeerInfo, ReasonForBan};
 use crate::pool;
+use crate::util::secp::pedersen::{Commitment, RangeProof};
 use crate::util::OneTime;
 use chrono::prelude::*;
 use chrono::Duration;
@@ -51,6 +57,7 @@ pub struct NetToChainAdapter {
 	peers: OneTime<Weak<p2p::Peers>>,
 	config: ServerConfig,
 	hooks: Vec<Box<dyn NetEvents + Send + Sync>>,
+	merkle_proofs: Arc<MerkleProofRequests>,
 }
 
 impl p2p::ChainAdapter for NetToChainAdapter {
@@ -72,7 +79,7 @@ impl p2p::ChainAdapter for NetToChainAdapter {
 		peer_info: &PeerInfo,
 	) -> Result<bool, chain::Error> {
 		// nothing much we can do with a new transaction while syncing
-		if self.sync_state.is_syncing() {
+		if self.sync_state.is_syncing() || self.header_only() {
 			return Ok(true);
 		}
 
@@ -90,7 +97,7 @@ impl p2p::ChainAdapter for NetToChainAdapter {
 		stem: bool,
 	) -> Result<bool, chain::Error> {
 		// nothing much we can do with a new transaction while syncing
-		if self.sync_state.is_syncing() {
+		if self.sync_state.is_syncing() || self.header_only() {
 			return Ok(true);
 		}
 
@@ -120,6 +127,9 @@ impl p2p::ChainAdapter for NetToChainAdapter {
 		peer_info: &PeerInfo,
 		opts: chain::Options,
 	) -> Result<bool, chain::Error> {
+		if self.header_only() {
+			return self.header_received(b.header, peer_info);
+		}
 		if self.chain().block_exists(b.hash())? {
 			return Ok(true);
 		}
@@ -140,6 +150,9 @@ impl p2p::ChainAdapter for NetToChainAdapter {
 		cb: core::CompactBlock,
 		peer_info: &PeerInfo,
 	) -> Result<bool, chain::Error> {
+		if self.header_only() {
+			return self.header_received(cb.header, peer_info);
+		}
 		// No need to process this compact block if we have previously accepted the _full block_.
 		if self.chain().block_exists(cb.hash())? {
 			return Ok(true);
@@ -275,7 +288,9 @@ impl p2p::ChainAdapter for NetToChainAdapter {
 
 		// we have successfully processed a block header
 		// so we can go request the block itself
-		self.request_compact_block(&bh, peer_info);
+		if !self.header_only() {
+			self.request_compact_block(&bh, peer_info);
+		}
 
 		// done receiving the header
 		Ok(true)
@@ -368,11 +383,16 @@ impl p2p::ChainAdapter for NetToChainAdapter {
 	/// the required indexes for a consumer to rewind to a consistent state
 	/// at the provided block hash.
 	fn txhashset_read(&self, h: Hash) -> Option<p2p::TxHashSetRead> {
-		match self.chain().txhashset_read(h.clone()) {
-			Ok((out_index, kernel_index, read)) => Some(p2p::TxHashSetRead {
+		let res = self.chain().txhashset_read(h.clone()).and_then(|read| {
+			let archive_hash = self.chain().txhashset_archive_hash(&h)?;
+			Ok((read, archive_hash))
+		});
+		match res {
+			Ok(((out_index, kernel_index, read), archive_hash)) => Some(p2p::TxHashSetRead {
 				output_index: out_index,
 				kernel_index: kernel_index,
 				reader: read,
+				archive_hash,
 			}),
 			Err(e) => {
 				warn!("Couldn't produce txhashset data for block {}: {:?}", h, e);
@@ -458,6 +478,151 @@ impl p2p::ChainAdapter for NetToChainAdapter {
 		}
 	}
 
+	fn get_kernel_segment(
+		&self,
+		hash: Hash,
+		id: SegmentIdentifier,
+	) -> Result<Segment<TxKernel>, chain::Error> {
+		self.segmenter_at(hash, SegmentType::Kernel, id)?
+			.kernel_segment(id)
+	}
+
+	fn get_bitmap_segment(
+		&self,
+		hash: Hash,
+		id: SegmentIdentifier,
+	) -> Result<(Segment<BitmapChunk>, Hash, u64), chain::Error> {
+		let segmenter = self.segmenter_at(hash, SegmentType::Bitmap, id)?;
+		let segment = segmenter.bitmap_segment(id)?;
+		Ok((
+			segment,
+			segmenter.output_root(),
+			segmenter.bitmap_mmr_size(),
+		))
+	}
+
+	fn get_output_segment(
+		&self,
+		hash: Hash,
+		id: SegmentIdentifier,
+	) -> Result<(Segment<OutputIdentifier>, Hash), chain::Error> {
+		let segmenter = self.segmenter_at(hash, SegmentType::Output, id)?;
+		let segment = segmenter.output_segment(id)?;
+		Ok((segment, segmenter.bitmap_root()))
+	}
+
+	fn get_rangeproof_segment(
+		&self,
+		hash: Hash,
+		id: SegmentIdentifier,
+	) -> Result<Segment<RangeProof>, chain::Error> {
+		self.segmenter_at(hash, SegmentType::RangeProof, id)?
+			.rangeproof_segment(id)
+	}
+
+	fn receive_bitmap_segment(
+		&self,
+		block_hash: Hash,
+		output_root: Hash,
+		bitmap_mmr_size: u64,
+		segment: Segment<BitmapChunk>,
+		peer_info: &PeerInfo,
+	) -> Result<bool, chain::Error> {
+		debug!(
+			"Received bitmap segment {:?} for {} from {}",
+			segment.identifier(),
+			block_hash,
+			peer_info.addr
+		);
+		self.receive_segment(block_hash, |desegmenter| {
+			desegmenter.add_bitmap_segment(segment, output_root, bitmap_mmr_size)
+		})
+	}
+
+	fn receive_output_segment(
+		&self,
+		block_hash: Hash,
+		bitmap_root: Hash,
+		segment: Segment<OutputIdentifier>,
+		peer_info: &PeerInfo,
+	) -> Result<bool, chain::Error> {
+		debug!(
+			"Received output segment {:?} for {} from {}",
+			segment.identifier(),
+			block_hash,
+			peer_info.addr
+		);
+		self.receive_segment(block_hash, |desegmenter| {
+			desegmenter.add_output_segment(segment, bitmap_root)
+		})
+	}
+
+	fn receive_rangeproof_segment(
+		&self,
+		block_hash: Hash,
+		segment: Segment<RangeProof>,
+		peer_info: &PeerInfo,
+	) -> Result<bool, chain::Error> {
+		debug!(
+			"Received rangeproof segment {:?} for {} from {}",
+			segment.identifier(),
+			block_hash,
+			peer_info.addr
+		);
+		self.receive_segment(block_hash, |desegmenter| {
+			desegmenter.add_rangeproof_segment(segment)
+		})
+	}
+
+	fn receive_kernel_segment(
+		&self,
+		block_hash: Hash,
+		segment: Segment<TxKernel>,
+		peer_info: &PeerInfo,
+	) -> Result<bool, chain::Error> {
+		debug!(
+			"Received kernel segment {:?} for {} from {}",
+			segment.identifier(),
+			block_hash,
+			peer_info.addr
+		);
+		self.receive_segment(block_hash, |desegmenter| {
+			desegmenter.add_kernel_segment(segment)
+		})
+	}
+
+	fn get_output_merkle_proof(
+		&self,
+		commit: Commitment,
+	) -> Result<chain::OutputMerkleProof, chain::Error> {
+		self.check_merkle_proofs()?;
+		self.chain().output_merkle_proof(&commit)
+	}
+
+	fn get_kernel_merkle_proof(
+		&self,
+		excess: Commitment,
+	) -> Result<chain::KernelMerkleProof, chain::Error> {
+		self.check_merkle_proofs()?;
+		self.chain().kernel_merkle_proof(&excess)
+	}
+
+	fn output_merkle_proof_received(
+		&self,
+		proof: chain::OutputMerkleProof,
+		_peer_info: &PeerInfo,
+	) -> Result<bool, chain::Error> {
+		self.merkle_proofs.output_proof_received(proof)
+	}
+
+	fn kernel_merkle_proof_received(
+		&self,
+		proof: chain::KernelMerkleProof,
+		_peer_info: &PeerInfo,
+	) -> Result<bool, chain::Error> {
+		self.merkle_proofs.kernel_proof_received(proof)
+	}
+
 	fn get_tmp_dir(&self) -> PathBuf {
 		self.chain().get_tmp_dir()
 	}
@@ -465,6 +630,24 @@ impl p2p::ChainAdapter for NetToChainAdapter {
 	fn get_tmpfile_pathname(&self, tmpfile_name: String) -> PathBuf {
 		self.chain().get_tmpfile_pathname(tmpfile_name)
 	}
+
+	fn peer_connected(&self, peer_info: &PeerInfo) {
+		for hook in &self.hooks {
+			hook.on_peer_connected(peer_info);
+		}
+	}
+
+	fn peer_disconnected(&self, peer_info: &PeerInfo) {
+		for hook in &self.hooks {
+			hook.on_peer_disconnected(peer_info);
+		}
+	}
+
+	fn peer_banned(&self, addr: PeerAddr, ban_reason: ReasonForBan) {
+		for hook in &self.hooks {
+			hook.on_peer_banned(&addr, &ban_reason);
+		}
+	}
 }
 
 impl NetToChainAdapter {
@@ -476,6 +659,7 @@ impl NetToChainAdapter {
 		verifier_cache: Arc<RwLock<dyn VerifierCache>>,
 		config: ServerConfig,
 		hooks: Vec<Box<dyn NetEvents + Send + Sync>>,
+		merkle_proofs: Arc<MerkleProofRequests>,
 	) -> NetToChainAdapter {
 		NetToChainAdapter {
 			sync_state,
@@ -485,6 +669,7 @@ impl NetToChainAdapter {
 			peers: OneTime::new(),
 			config,
 			hooks,
+			merkle_proofs,
 		}
 	}
 
@@ -507,6 +692,71 @@ impl NetToChainAdapter {
 			.expect("Failed to upgrade weak ref to our chain.")
 	}
 
+	/// Whether we only follow the header chain, leaving blocks and
+	/// transactions aside.
+	fn header_only(&self) -> bool {
+		self.config.header_only.unwrap_or(false)
+	}
+
+	/// Merkle proofs are only served at a head we're done syncing to.
+	fn check_merkle_proofs(&self) -> Result<(), chain::Error> {
+		if self.header_only() || self.sync_state.is_syncing() {
+			return Err(chain::ErrorKind::Other("no merkle proofs to serve".to_owned()).into());
+		}
+		Ok(())
+	}
+
+	/// Our segmenter, if the segment requested is at our archive header and
+	/// of the height we use for its tree.
+	fn segmenter_at(
+		&self,
+		hash: Hash,
+		segment_type: SegmentType,
+		id: SegmentIdentifier,
+	) -> Result<Segmenter, chain::Error> {
+		if id.height != segment_type.segment_height() {
+			return Err(chain::ErrorKind::Other(format!(
+				"unexpected {:?} segment height {}",
+				segment_type, id.height
+			))
+			.into());
+		}
+		let segmenter = self.chain().segmenter()?;
+		if segmenter.header().hash() != hash {
+			return Err(chain::ErrorKind::SegmentHeaderMismatch.into());
+		}
+		Ok(segmenter)
+	}
+
+	/// Hands a segment over to the desegmenter of the segmented sync in
+	/// progress, if for the header we're syncing to, and updates the sync
+	/// progress. Segments we're not expecting are dropped.
+	fn receive_segment<F>(&self, block_hash: Hash, add_segment: F) -> Result<bool, chain::Error>
+	where
+		F: FnOnce(&mut Desegmenter) -> Result<(), chain::Error>,
+	{
+		if let SyncStatus::TxHashsetPibd { .. } = self.sync_state.status() {
+		} else {
+			return Ok(false);
+		}
+
+		let desegmenter = self.chain().current_desegmenter();
+		let mut desegmenter = desegmenter.write();
+		let desegmenter = match desegmenter.as_mut() {
+			Some(d) if d.header().hash() == block_hash => d,
+			_ => return Ok(false),
+		};
+		add_segment(desegmenter)?;
+
+		let (recv_segments, total_segments) = desegmenter.progress();
+		self.sync_state
+			.update_txhashset_download(SyncStatus::TxHashsetPibd {
+				recv_segments,
+				total_segments,
+			});
+		Ok(true)
+	}
+
 	// Find the first locator hash that refers to a known header on our main chain.
 	fn find_common_header(&self, locator: &[Hash]) -> Option<BlockHeader> {
 		let header_pmmr = self.chain().header_pmmr();
@@ -705,7 +955,7 @@ impl NetToChainAdapter {
 pub struct ChainToPoolAndNetAdapter {
 	tx_pool: Arc<RwLock<pool::TransactionPool>>,
 	peers: OneTime<Weak<p2p::Peers>>,
-	hooks: Vec<Box<dyn ChainEvents + Send + Sync>>,
+	hooks: Vec<Arc<dyn ChainEvents + Send + Sync>>,
 }
 
 impl ChainAdapter for ChainToPoolAndNetAdapter {
@@ -750,13 +1000,29 @@ impl ChainAdapter for ChainToPoolAndNetAdapter {
 			let _ = self.tx_pool.write().reconcile_reorg_cache(&b.header);
 		}
 	}
+
+	fn reorg(&self, disconnected: &[BlockHeader], connected: &[BlockHeader], opts: Options) {
+		if !opts.contains(chain::Options::SYNC) {
+			for hook in &self.hooks {
+				hook.on_reorg(disconnected, connected);
+			}
+		}
+	}
+}
+
+impl SyncStatusAdapter for ChainToPoolAndNetAdapter {
+	fn sync_status_changed(&self, old: &SyncStatus, new: &SyncStatus) {
+		for hook in &self.hooks {
+			hook.on_sync_status_changed(old, new);
+		}
+	}
 }
 
 impl ChainToPoolAndNetAdapter {
 	/// Construct a ChainToPoolAndNetAdapter instance.
 	pub fn new(
 		tx_pool: Arc<RwLock<pool::TransactionPool>>,
-		hooks: Vec<Box<dyn ChainEvents + Send + Sync>>,
+		hooks: Vec<Arc<dyn ChainEvents + Send + Sync>>,
 	) -> ChainToPoolAndNetAdapter {
 		ChainToPoolAndNetAdapter {
 			tx_pool,
@@ -784,6 +1050,7 @@ impl ChainToPoolAndNetAdapter {
 pub struct PoolToNetAdapter {
 	peers: OneTime<Weak<p2p::Peers>>,
 	dandelion_epoch: Arc<RwLock<DandelionEpoch>>,
+	hooks: Vec<Arc<dyn ChainEvents + Send + Sync>>,
 }
 
 /// Adapter between the Dandelion monitor and the current Dandelion "epoch".
@@ -814,10 +1081,55 @@ impl DandelionAdapter for PoolToNetAdapter {
 
 impl pool::PoolAdapter for PoolToNetAdapter {
 	fn tx_accepted(&self, entry: &pool::PoolEntry) {
+		for hook in &self.hooks {
+			hook.on_tx_accepted(entry, false);
+		}
 		self.peers().broadcast_transaction(&entry.tx);
 	}
 
+	fn tx_evicted(&self, entry: &pool::PoolEntry) {
+		for hook in &self.hooks {
+			hook.on_tx_evicted(entry);
+		}
+	}
+
 	fn stem_tx_accepted(&self, entry: &pool::PoolEntry) -> Result<(), pool::PoolError> {
+		let res = self.stem_tx(entry);
+		if res.is_ok() {
+			for hook in &self.hooks {
+				hook.on_tx_accepted(entry, true);
+			}
+		}
+		res
+	}
+}
+
+impl PoolToNetAdapter {
+	/// Create a new pool to net adapter
+	pub fn new(
+		config: pool::DandelionConfig,
+		hooks: Vec<Arc<dyn ChainEvents + Send + Sync>>,
+	) -> PoolToNetAdapter {
+		PoolToNetAdapter {
+			peers: OneTime::new(),
+			dandelion_epoch: Arc::new(RwLock::new(DandelionEpoch::new(config))),
+			hooks,
+		}
+	}
+
+	/// Setup the p2p server on the adapter
+	pub fn init(&self, peers: Arc<p2p::Peers>) {
+		self.peers.init(Arc::downgrade(&peers));
+	}
+
+	fn peers(&self) -> Arc<p2p::Peers> {
+		self.peers
+			.borrow()
+			.upgrade()
+			.expect("Failed to upgrade weak ref to our peers.")
+	}
+
+	fn stem_tx(&self, entry: &pool::PoolEntry) -> Result<(), pool::PoolError> {
 		// Take write lock on the current epoch.
 		// We need to be able to update the current relay peer if not currently connected.
 		let mut epoch = self.dandelion_epoch.write();
@@ -849,28 +1161,6 @@ impl pool::PoolAdapter for PoolToNetAdapter {
 	}
 }
 
-impl PoolToNetAdapter {
-	/// Create a new pool to net adapter
-	pub fn new(config: pool::DandelionConfig) -> PoolToNetAdapter {
-		PoolToNetAdapter {
-			peers: OneTime::new(),
-			dandelion_epoch: Arc::new(RwLock::new(DandelionEpoch::new(config))),
-		}
-	}
-
-	/// Setup the p2p server on the adapter
-	pub fn init(&self, peers: Arc<p2p::Peers>) {
-		self.peers.init(Arc::downgrade(&peers));
-	}
-
-	fn peers(&self) -> Arc<p2p::Peers> {
-		self.peers
-			.borrow()
-			.upgrade()
-			.expect("Failed to upgrade weak ref to our peers.")
-	}
-}
-
 /// Implements the view of the  required by the TransactionPool to
 /// operate. Mostly needed to break any direct lifecycle or implementation
 /// dependency between the pool and the chain.
@@ -0,0 +1,341 @@
+// Copyright 2020 The Kepler Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Local sink of the node events, writing each of them as a line of JSON to
+//! an append-only file and/or to the clients of a Unix domain socket. The
+//! writes happen on a dedicated thread so the chain is never held up by a
+//! slow disk or a slow client.
+
+use chrono::Utc;
+use serde_json::{json, Value};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{self, Receiver, SyncSender, TrySendError};
+use std::sync::Arc;
+use std::thread;
+
+use crate::common::types::{Error, EventSinkConfig};
+use crate::util::Mutex;
+
+#[cfg(unix)]
+use std::os::unix::fs::FileTypeExt;
+#[cfg(unix)]
+use std::os::unix::net::{UnixListener, UnixStream};
+#[cfg(unix)]
+use std::time::Duration;
+
+/// Number of events waiting to be written above which new events are dropped
+const QUEUE_SIZE: usize = 1024;
+
+/// Maximum time a socket client can block the writer before being dropped
+#[cfg(unix)]
+const CLIENT_WRITE_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Handle to the event sink, cheap to clone so the same sink can be shared
+/// by the network and chain hooks.
+#[derive(Clone)]
+pub struct EventSink {
+	sender: Arc<Mutex<SyncSender<String>>>,
+	/// Number of events dropped because the writer couldn't keep up
+	dropped: Arc<AtomicU64>,
+}
+
+impl EventSink {
+	/// Starts the event sink if a file or a socket is configured.
+	pub fn from_config(config: &EventSinkConfig) -> Result<Option<EventSink>, Error> {
+		if config.event_file_path.is_none() && config.event_socket_path.is_none() {
+			return Ok(None);
+		}
+
+		let file = match config.event_file_path {
+			Some(ref path) => Some(RotatingFile::open(
+				PathBuf::from(path),
+				config.event_max_size,
+				config.event_max_files,
+			)?),
+			None => None,
+		};
+		let clients = match config.event_socket_path {
+			Some(ref path) => Some(listen(path)?),
+			None => None,
+		};
+
+		let (sender, receiver) = mpsc::sync_channel(QUEUE_SIZE);
+		thread::Builder::new()
+			.name("event_sink".to_string())
+			.spawn(move || write_events(receiver, file, clients))
+			.map_err(|e| Error::General(format!("Unable to start the event sink: {}", e)))?;
+
+		Ok(Some(EventSink::new(sender)))
+	}
+
+	fn new(sender: SyncSender<String>) -> EventSink {
+		EventSink {
+			sender: Arc::new(Mutex::new(sender)),
+			dropped: Arc::new(AtomicU64::new(0)),
+		}
+	}
+
+	/// Queues an event for writing, as a single line of the form
+	/// `{"event": <name>, "timestamp": <secs>, "data": <payload>}`. The event
+	/// is dropped if the writer is too far behind.
+	pub fn send(&self, event: &str, payload: Value) {
+		let line = json!({
+			"event": event,
+			"timestamp": Utc::now().timestamp(),
+			"data": payload
+		})
+		.to_string();
+		match self.sender.lock().try_send(line) {
+			Ok(()) => {}
+			Err(TrySendError::Full(_)) => {
+				let dropped = self.dropped.fetch_add(1, Ordering::Relaxed) + 1;
+				error!(
+					"Event sink queue full, dropping {} event ({} dropped so far)",
+					event, dropped
+				);
+			}
+			Err(TrySendError::Disconnected(_)) => {
+				error!("Event sink stopped, dropping {} event", event);
+			}
+		}
+	}
+}
+
+fn write_events(
+	receiver: Receiver<String>,
+	mut file: Option<RotatingFile>,
+	clients: Option<Clients>,
+) {
+	for mut line in receiver {
+		line.push('\n');
+		if let Some(ref mut file) = file {
+			if let Err(e) = file.write_line(line.as_bytes()) {
+				error!("Failed to write event to {}: {}", file.path.display(), e);
+			}
+		}
+		if let Some(ref clients) = clients {
+			write_clients(clients, line.as_bytes());
+		}
+	}
+}
+
+/// Append-only file, rotated to `<path>.1`, `<path>.2`... once it reaches
+/// its maximum size.
+struct RotatingFile {
+	path: PathBuf,
+	file: File,
+	size: u64,
+	max_size: u64,
+	max_files: u32,
+}
+
+impl RotatingFile {
+	fn open(path: PathBuf, max_size: u64, max_files: u32) -> Result<RotatingFile, Error> {
+		let file = RotatingFile::open_file(&path).map_err(|e| {
+			Error::Configuration(format!(
+				"Unable to open event file {}: {}",
+				path.display(),
+				e
+			))
+		})?;
+		let size = file.metadata().map(|m| m.len()).unwrap_or(0);
+		Ok(RotatingFile {
+			path,
+			file,
+			size,
+			max_size,
+			max_files,
+		})
+	}
+
+	fn open_file(path: &Path) -> io::Result<File> {
+		if let Some(parent) = path.parent() {
+			fs::create_dir_all(parent)?;
+		}
+		OpenOptions::new().create(true).append(true).open(path)
+	}
+
+	fn write_line(&mut self, line: &[u8]) -> io::Result<()> {
+		if self.size > 0 && self.size + line.len() as u64 > self.max_size {
+			self.rotate()?;
+		}
+		self.file.write_all(line)?;
+		self.size += line.len() as u64;
+		Ok(())
+	}
+
+	fn rotated_path(&self, index: u32) -> PathBuf {
+		let mut path = self.path.clone().into_os_string();
+		path.push(format!(".{}", index));
+		PathBuf::from(path)
+	}
+
+	fn rotate(&mut self) -> io::Result<()> {
+		self.file.flush()?;
+		if self.max_files == 0 {
+			fs::remove_file(&self.path)?;
+		} else {
+			for index in (1..self.max_files).rev() {
+				let from = self.rotated_path(index);
+				if from.exists() {
+					fs::rename(from, self.rotated_path(index + 1))?;
+				}
+			}
+			fs::rename(&self.path, self.rotated_path(1))?;
+		}
+		self.file = RotatingFile::open_file(&self.path)?;
+		self.size = 0;
+		Ok(())
+	}
+}
+
+#[cfg(unix)]
+type Clients = Arc<Mutex<Vec<UnixStream>>>;
+
+/// Binds the socket and accepts its clients on a dedicated thread. Clients
+/// only receive the events written after they connected.
+#[cfg(unix)]
+fn listen(path: &str) -> Result<Clients, Error> {
+	// Left over by a previous run of the node, anything else at that path
+	// is most likely a configuration mistake and is left alone
+	if let Ok(metadata) = fs::symlink_metadata(path) {
+		if !metadata.file_type().is_socket() {
+			return Err(Error::Configuration(format!(
+				"Event socket path {} exists and isn't a socket",
+				path
+			)));
+		}
+		fs::remove_file(path)?;
+	}
+	let listener = UnixListener::bind(path).map_err(|e| {
+		Error::Configuration(format!("Unable to bind event socket {}: {}", path, e))
+	})?;
+	let clients: Clients = Arc::new(Mutex::new(vec![]));
+	let accepted = clients.clone();
+	thread::Builder::new()
+		.name("event_socket".to_string())
+		.spawn(move || {
+			for stream in listener.incoming() {
+				match stream {
+					Ok(stream) => {
+						if let Err(e) = stream.set_write_timeout(Some(CLIENT_WRITE_TIMEOUT)) {
+							warn!("Unable to set event socket client timeout: {}", e);
+							continue;
+						}
+						debug!("Event socket client connected");
+						accepted.lock().push(stream);
+					}
+					Err(e) => warn!("Event socket connection failed: {}", e),
+				}
+			}
+		})
+		.map_err(|e| Error::General(format!("Unable to start the event socket: {}", e)))?;
+	Ok(clients)
+}
+
+/// Writes the line to every client, dropping the ones that went away or
+/// can't keep up.
+#[cfg(unix)]
+fn write_clients(clients: &Clients, line: &[u8]) {
+	clients
+		.lock()
+		.retain(|mut client| match client.write_all(line) {
+			Ok(()) => true,
+			Err(e) => {
+				debug!("Event socket client dropped: {}", e);
+				false
+			}
+		});
+}
+
+#[cfg(not(unix))]
+type Clients = ();
+
+#[cfg(not(unix))]
+fn listen(_path: &str) -> Result<Clients, Error> {
+	Err(Error::Configuration(
+		"Event sockets are only supported on Unix platforms".to_owned(),
+	))
+}
+
+#[cfg(not(unix))]
+fn write_clients(_clients: &Clients, _line: &[u8]) {}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_rotating_file() {
+		let dir = Path::new("test_output/event_sink");
+		let _ = fs::remove_dir_all(dir);
+		let path = dir.join("events.json");
+
+		let mut file = RotatingFile::open(path.clone(), 10, 2).unwrap();
+		for line in &["aaaaaa\n", "bbbbbb\n", "cccccc\n", "dddddd\n"] {
+			file.write_line(line.as_bytes()).unwrap();
+		}
+		file.file.flush().unwrap();
+
+		assert_eq!(fs::read_to_string(&path).unwrap(), "dddddd\n");
+		assert_eq!(
+			fs::read_to_string(file.rotated_path(1)).unwrap(),
+			"cccccc\n"
+		);
+		assert_eq!(
+			fs::read_to_string(file.rotated_path(2)).unwrap(),
+			"bbbbbb\n"
+		);
+		assert!(!file.rotated_path(3).exists());
+
+		let _ = fs::remove_dir_all(dir);
+	}
+
+	#[test]
+	fn test_queue_full() {
+		let (sender, receiver) = mpsc::sync_channel(2);
+		let sink = EventSink::new(sender);
+		for i in 0..5 {
+			sink.send("block_accepted", json!(i));
+		}
+		assert_eq!(sink.dropped.load(Ordering::Relaxed), 3);
+		let lines: Vec<String> = receiver.try_iter().collect();
+		assert_eq!(lines.len(), 2);
+		assert!(lines[1].contains("\"data\":1"));
+	}
+
+	#[cfg(unix)]
+	#[test]
+	fn test_socket_path() {
+		let dir = Path::new("test_output/event_sink_socket");
+		let _ = fs::remove_dir_all(dir);
+		fs::create_dir_all(dir).unwrap();
+
+		// a regular file isn't replaced by the socket
+		let file = dir.join("events.json");
+		fs::write(&file, "keep me").unwrap();
+		assert!(listen(file.to_str().unwrap()).is_err());
+		assert_eq!(fs::read_to_string(&file).unwrap(), "keep me");
+
+		// a socket left over by a previous run is
+		let socket = dir.join("events.sock");
+		drop(UnixListener::bind(&socket).unwrap());
+		assert!(listen(socket.to_str().unwrap()).is_ok());
+
+		let _ = fs::remove_dir_all(dir);
+	}
+}
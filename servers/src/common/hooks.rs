@@ -19,43 +19,100 @@ extern crate hyper;
 extern crate hyper_rustls;
 extern crate tokio;
 
-use crate::chain::BlockStatus;
-use crate::common::types::{ServerConfig, WebHooksConfig};
+use crate::chain::{BlockStatus, SyncStatus};
+use crate::common::event_sink::EventSink;
+use crate::common::outbox::{self, OutboxEntry, OutboxStore};
+use crate::common::types::{Error, ServerConfig, WebHooksConfig};
 use crate::core::core;
 use crate::core::core::hash::Hashed;
-use crate::p2p::types::PeerAddr;
-use futures::TryFutureExt;
+use crate::p2p::types::{PeerAddr, PeerInfo, PeerInfoDisplay, ReasonForBan};
+use crate::pool::PoolEntry;
+use crate::util::file;
+use crate::util::Mutex;
+use chrono::Utc;
 use hyper::client::HttpConnector;
 use hyper::header::HeaderValue;
 use hyper::Client;
 use hyper::{Body, Method, Request};
 use hyper_rustls::HttpsConnector;
+use ring::hmac;
 use serde::Serialize;
-use serde_json::{json, to_string};
+use serde_json::{json, to_string, Value};
+use std::collections::{HashMap, HashSet};
+use std::sync::mpsc;
+use std::sync::Arc;
+use std::thread;
 use std::time::Duration;
-use tokio::runtime::{Builder, Runtime};
+use tokio::runtime::{Builder, Handle, Runtime};
+use tokio::time;
+
+/// Header holding the name of the event
+const EVENT_HEADER: &str = "x-kepler-event";
+/// Header holding the sequence number of the event
+const SEQUENCE_HEADER: &str = "x-kepler-sequence";
+/// Header holding the HMAC-SHA256 signature of the event
+const SIGNATURE_HEADER: &str = "x-kepler-signature";
+/// Interval between two scans of the outbox for events due for a retry
+const RETRY_INTERVAL: Duration = Duration::from_secs(1);
+/// Maximum number of deliveries in progress at once, the others waiting in
+/// the outbox
+const MAX_IN_FLIGHT: usize = 64;
+/// Number of events waiting to be stored in the outbox above which new
+/// events are dropped
+const QUEUE_SIZE: usize = 1024;
 
 /// Returns the list of event hooks that will be initialized for network events
-pub fn init_net_hooks(config: &ServerConfig) -> Vec<Box<dyn NetEvents + Send + Sync>> {
+pub fn init_net_hooks(
+	config: &ServerConfig,
+	event_sink: &Option<EventSink>,
+) -> Result<Vec<Box<dyn NetEvents + Send + Sync>>, Error> {
 	let mut list: Vec<Box<dyn NetEvents + Send + Sync>> = Vec::new();
 	list.push(Box::new(EventLogger));
-	if config.webhook_config.block_received_url.is_some()
-		|| config.webhook_config.tx_received_url.is_some()
-		|| config.webhook_config.header_received_url.is_some()
+	if let Some(sink) = event_sink {
+		list.push(Box::new(sink.clone()));
+	}
+	let webhook_config = &config.webhook_config;
+	if webhook_config.block_received_url.is_some()
+		|| webhook_config.tx_received_url.is_some()
+		|| webhook_config.header_received_url.is_some()
+		|| webhook_config.peer_connected_url.is_some()
+		|| webhook_config.peer_disconnected_url.is_some()
+		|| webhook_config.peer_banned_url.is_some()
 	{
-		list.push(Box::new(WebHook::from_config(&config.webhook_config)));
+		list.push(Box::new(WebHook::from_config(
+			webhook_config,
+			&config.db_root,
+			"net",
+		)?));
 	}
-	list
+	Ok(list)
 }
 
-/// Returns the list of event hooks that will be initialized for chain events
-pub fn init_chain_hooks(config: &ServerConfig) -> Vec<Box<dyn ChainEvents + Send + Sync>> {
-	let mut list: Vec<Box<dyn ChainEvents + Send + Sync>> = Vec::new();
-	list.push(Box::new(EventLogger));
-	if config.webhook_config.block_accepted_url.is_some() {
-		list.push(Box::new(WebHook::from_config(&config.webhook_config)));
+/// Returns the list of event hooks that will be initialized for chain events,
+/// shared by the chain, the transaction pool and the sync state
+pub fn init_chain_hooks(
+	config: &ServerConfig,
+	event_sink: &Option<EventSink>,
+) -> Result<Vec<Arc<dyn ChainEvents + Send + Sync>>, Error> {
+	let mut list: Vec<Arc<dyn ChainEvents + Send + Sync>> = Vec::new();
+	list.push(Arc::new(EventLogger));
+	if let Some(sink) = event_sink {
+		list.push(Arc::new(sink.clone()));
 	}
-	list
+	let webhook_config = &config.webhook_config;
+	if webhook_config.block_accepted_url.is_some()
+		|| webhook_config.reorg_url.is_some()
+		|| webhook_config.tx_accepted_url.is_some()
+		|| webhook_config.tx_evicted_url.is_some()
+		|| webhook_config.sync_status_url.is_some()
+	{
+		list.push(Arc::new(WebHook::from_config(
+			webhook_config,
+			&config.db_root,
+			"chain",
+		)?));
+	}
+	Ok(list)
 }
 
 #[allow(unused_variables)]
@@ -69,6 +126,15 @@ pub trait NetEvents {
 
 	/// Triggers when a new block header arrives
 	fn on_header_received(&self, header: &core::BlockHeader, addr: &PeerAddr) {}
+
+	/// Triggers when a peer completes its handshake and gets connected
+	fn on_peer_connected(&self, peer: &PeerInfo) {}
+
+	/// Triggers when a connected peer gets disconnected
+	fn on_peer_disconnected(&self, peer: &PeerInfo) {}
+
+	/// Triggers when a peer is banned
+	fn on_peer_banned(&self, addr: &PeerAddr, reason: &ReasonForBan) {}
 }
 
 #[allow(unused_variables)]
@@ -76,6 +142,19 @@ pub trait NetEvents {
 pub trait ChainEvents {
 	/// Triggers when a new block is accepted by the chain (might be a Reorg or a Fork)
 	fn on_block_accepted(&self, block: &core::Block, status: &BlockStatus) {}
+
+	/// Triggers when the chain head moves to another fork, with the headers of
+	/// the rewound blocks (most recent first) and of the applied ones (oldest first)
+	fn on_reorg(&self, disconnected: &[core::BlockHeader], connected: &[core::BlockHeader]) {}
+
+	/// Triggers when a transaction is accepted in the txpool or the stempool
+	fn on_tx_accepted(&self, entry: &PoolEntry, stem: bool) {}
+
+	/// Triggers when a transaction is evicted from the txpool to make room
+	fn on_tx_evicted(&self, entry: &PoolEntry) {}
+
+	/// Triggers when the sync status moves to another phase
+	fn on_sync_status_changed(&self, old: &SyncStatus, new: &SyncStatus) {}
 }
 
 /// Basic Logger
@@ -112,6 +191,21 @@ impl NetEvents for EventLogger {
 			addr
 		);
 	}
+
+	fn on_peer_connected(&self, peer: &PeerInfo) {
+		debug!(
+			"Peer {} connected ({:?}, {})",
+			peer.addr, peer.direction, peer.user_agent
+		);
+	}
+
+	fn on_peer_disconnected(&self, peer: &PeerInfo) {
+		debug!("Peer {} disconnected", peer.addr);
+	}
+
+	fn on_peer_banned(&self, addr: &PeerAddr, reason: &ReasonForBan) {
+		debug!("Peer {} banned: {:?}", addr, reason);
+	}
 }
 
 impl ChainEvents for EventLogger {
@@ -144,6 +238,31 @@ impl ChainEvents for EventLogger {
 			}
 		}
 	}
+
+	fn on_reorg(&self, disconnected: &[core::BlockHeader], connected: &[core::BlockHeader]) {
+		debug!(
+			"reorg: {} block(s) disconnected, {} block(s) connected",
+			disconnected.len(),
+			connected.len(),
+		);
+	}
+
+	fn on_tx_accepted(&self, entry: &PoolEntry, stem: bool) {
+		debug!(
+			"tx {} accepted in the {} ({:?})",
+			entry.tx.hash(),
+			if stem { "stempool" } else { "txpool" },
+			entry.src,
+		);
+	}
+
+	fn on_tx_evicted(&self, entry: &PoolEntry) {
+		debug!("tx {} evicted from the txpool", entry.tx.hash());
+	}
+
+	fn on_sync_status_changed(&self, old: &SyncStatus, new: &SyncStatus) {
+		debug!("sync status: {:?} -> {:?}", old, new);
+	}
 }
 
 fn parse_url(value: &Option<String>) -> Option<hyper::Uri> {
@@ -166,6 +285,223 @@ fn parse_url(value: &Option<String>) -> Option<hyper::Uri> {
 	}
 }
 
+/// Delivers the events stored in the outbox, retrying the failed deliveries
+/// with an exponential backoff until they get acknowledged.
+struct Delivery {
+	/// The hyper client to be used for all requests
+	client: Client<HttpsConnector<HttpConnector>>,
+	/// Events waiting to be delivered
+	outbox: OutboxStore,
+	/// Ids of the entries with a request in progress
+	in_flight: Mutex<HashSet<u64>>,
+	/// Key used to sign the payloads, if a secret is configured
+	key: Option<hmac::Key>,
+	/// timeout in seconds for the http request
+	timeout: u64,
+	/// delay in seconds before the first retry
+	retry_base_delay: u64,
+	/// maximum delay in seconds between two retries
+	retry_max_delay: u64,
+	/// number of retries before an event is dropped, unlimited if not set
+	max_retries: Option<u32>,
+	/// number of undelivered events kept, the oldest being dropped first
+	max_outbox_events: u64,
+}
+
+impl Delivery {
+	/// Marks the entry as in flight, returns false if it already was or if
+	/// too many deliveries are in progress.
+	fn acquire(&self, id: u64) -> bool {
+		let mut in_flight = self.in_flight.lock();
+		in_flight.len() < MAX_IN_FLIGHT && in_flight.insert(id)
+	}
+
+	fn release(&self, id: u64) {
+		self.in_flight.lock().remove(&id);
+	}
+
+	fn request(&self, entry: &OutboxEntry) -> Result<Request<Body>, String> {
+		let url: hyper::Uri = entry.url.parse().map_err(|e| format!("{}", e))?;
+		let mut req = Request::new(Body::from(entry.payload.clone()));
+		*req.method_mut() = Method::POST;
+		*req.uri_mut() = url;
+		let headers = req.headers_mut();
+		headers.insert(
+			hyper::header::CONTENT_TYPE,
+			HeaderValue::from_static("application/json"),
+		);
+		headers.insert(
+			EVENT_HEADER,
+			HeaderValue::from_str(&entry.event).map_err(|e| format!("{}", e))?,
+		);
+		headers.insert(SEQUENCE_HEADER, HeaderValue::from(entry.sequence));
+		if let Some(ref key) = self.key {
+			let signature = format!(
+				"sha256={}",
+				outbox::signature(key, &entry.event, entry.sequence, &entry.payload)
+			);
+			headers.insert(
+				SIGNATURE_HEADER,
+				HeaderValue::from_str(&signature).map_err(|e| format!("{}", e))?,
+			);
+		}
+		Ok(req)
+	}
+
+	/// POSTs an entry, removing it from the outbox once acknowledged with a
+	/// 2xx status and scheduling a retry otherwise.
+	async fn deliver(self: Arc<Self>, entry: OutboxEntry) {
+		let req = match self.request(&entry) {
+			Ok(req) => req,
+			Err(e) => {
+				error!("Dropping {} event {}: {}", entry.event, entry.sequence, e);
+				if let Err(e) = self.outbox.delete(entry.id) {
+					error!("Failed to delete webhook event {}: {:?}", entry.id, e);
+				}
+				self.release(entry.id);
+				return;
+			}
+		};
+		let timeout = Duration::from_secs(self.timeout);
+		let res = match time::timeout(timeout, self.client.request(req)).await {
+			Ok(Ok(res)) if res.status().is_success() => Ok(()),
+			Ok(Ok(res)) => Err(format!("status {}", res.status())),
+			Ok(Err(e)) => Err(format!("{}", e)),
+			Err(_) => Err("timeout".to_owned()),
+		};
+		match res {
+			Ok(()) => {
+				if let Err(e) = self.outbox.delete(entry.id) {
+					error!("Failed to delete webhook event {}: {:?}", entry.id, e);
+				}
+			}
+			Err(e) => self.failed(entry.clone(), e),
+		}
+		self.release(entry.id);
+	}
+
+	fn failed(&self, mut entry: OutboxEntry, reason: String) {
+		entry.attempts += 1;
+		if self.max_retries.map_or(false, |max| entry.attempts > max) {
+			error!(
+				"Dropping {} event {} after {} attempts to POST to {}: {}",
+				entry.event, entry.sequence, entry.attempts, entry.url, reason
+			);
+			if let Err(e) = self.outbox.delete(entry.id) {
+				error!("Failed to delete webhook event {}: {:?}", entry.id, e);
+			}
+			return;
+		}
+		let delay =
+			outbox::retry_delay(entry.attempts, self.retry_base_delay, self.retry_max_delay);
+		warn!(
+			"Error sending POST request to {} ({}), retrying {} event {} in {} secs",
+			entry.url, reason, entry.event, entry.sequence, delay
+		);
+		entry.next_attempt = Utc::now().timestamp() + delay as i64;
+		if let Err(e) = self.outbox.save(&entry) {
+			error!("Failed to save webhook event {}: {:?}", entry.id, e);
+		}
+	}
+
+	/// Stores an event in the outbox, dropping the oldest ones if it's full,
+	/// and spawns its delivery unless too many are already in progress.
+	fn store(self: &Arc<Self>, runtime: &Handle, event: QueuedEvent) {
+		let entry =
+			match self
+				.outbox
+				.enqueue(&event.name, event.sequence, &event.url, event.payload)
+			{
+				Ok(entry) => entry,
+				Err(e) => {
+					error!(
+						"Failed to store {} event in the webhook outbox: {:?}",
+						event.name, e
+					);
+					return;
+				}
+			};
+		match self.outbox.truncate(self.max_outbox_events) {
+			Ok(0) => {}
+			Ok(dropped) => warn!("Webhook outbox full, dropped {} oldest events", dropped),
+			Err(e) => error!("Failed to truncate the webhook outbox: {:?}", e),
+		}
+		if self.acquire(entry.id) {
+			runtime.spawn(self.clone().deliver(entry));
+		}
+	}
+
+	/// Spawns the delivery of the entries due for a (re)try, including the
+	/// ones left over from a previous run of the node, as long as there's
+	/// room for more deliveries in progress.
+	fn retry_due(self: &Arc<Self>) {
+		if self.in_flight.lock().len() >= MAX_IN_FLIGHT {
+			return;
+		}
+		// the entries in flight are still due, skipped below
+		let entries = match self.outbox.due(Utc::now().timestamp(), MAX_IN_FLIGHT * 2) {
+			Ok(entries) => entries,
+			Err(e) => {
+				error!("Failed to read the webhook outbox: {:?}", e);
+				return;
+			}
+		};
+		for entry in entries {
+			if self.acquire(entry.id) {
+				tokio::spawn(self.clone().deliver(entry));
+			}
+		}
+	}
+}
+
+/// An event waiting to be stored in the outbox
+struct QueuedEvent {
+	name: String,
+	sequence: u64,
+	url: String,
+	payload: String,
+}
+
+/// Queue of the events to be stored in the outbox. Sequence numbers are
+/// assigned when an event is queued, so an event dropped because the queue
+/// is full leaves a gap receivers can detect.
+struct EventQueue {
+	/// Last sequence number assigned to each event name
+	sequences: Mutex<HashMap<String, u64>>,
+	queue: mpsc::SyncSender<QueuedEvent>,
+}
+
+impl EventQueue {
+	fn new(sequences: HashMap<String, u64>, queue: mpsc::SyncSender<QueuedEvent>) -> EventQueue {
+		EventQueue {
+			sequences: Mutex::new(sequences),
+			queue,
+		}
+	}
+
+	/// Assigns the event the next sequence number of its name and queues
+	/// it, returns false if it had to be dropped.
+	fn push(&self, name: &str, url: String, payload: String) -> bool {
+		let mut sequences = self.sequences.lock();
+		let sequence = sequences.entry(name.to_owned()).or_insert(0);
+		*sequence += 1;
+		let queued = QueuedEvent {
+			name: name.to_owned(),
+			sequence: *sequence,
+			url,
+			payload,
+		};
+		if let Err(e) = self.queue.try_send(queued) {
+			error!(
+				"Dropping {} event {}, webhook queue unavailable: {}",
+				name, sequence, e
+			);
+			return false;
+		}
+		true
+	}
+}
+
 /// A struct that holds the hyper/tokio runtime.
 struct WebHook {
 	/// url to POST transaction data when a new transaction arrives from a peer
@@ -176,27 +512,38 @@ struct WebHook {
 	block_received_url: Option<hyper::Uri>,
 	/// url to POST block data when a new block is accepted by our node (might be a reorg or a fork)
 	block_accepted_url: Option<hyper::Uri>,
-	/// The hyper client to be used for all requests
-	client: Client<HttpsConnector<HttpConnector>>,
-	/// The tokio event loop
+	/// url to POST the disconnected and connected blocks when our chain head moves to another fork
+	reorg_url: Option<hyper::Uri>,
+	/// url to POST transaction data when a transaction is accepted in our pool
+	tx_accepted_url: Option<hyper::Uri>,
+	/// url to POST transaction data when a transaction is evicted from our pool
+	tx_evicted_url: Option<hyper::Uri>,
+	/// url to POST peer data when a peer gets connected
+	peer_connected_url: Option<hyper::Uri>,
+	/// url to POST peer data when a peer gets disconnected
+	peer_disconnected_url: Option<hyper::Uri>,
+	/// url to POST peer data when a peer is banned
+	peer_banned_url: Option<hyper::Uri>,
+	/// url to POST the previous and new sync status when it changes
+	sync_status_url: Option<hyper::Uri>,
+	/// Events sent to the thread storing them in the outbox, so the callers
+	/// (some holding the chain or pool locks) don't wait on the disk
+	queue: EventQueue,
+	/// The tokio event loop, only kept to run the deliveries and the retry
+	/// loop until the hook is dropped
+	#[allow(dead_code)]
 	runtime: Runtime,
 }
 
 impl WebHook {
-	/// Instantiates a Webhook struct
-	fn new(
-		tx_received_url: Option<hyper::Uri>,
-		header_received_url: Option<hyper::Uri>,
-		block_received_url: Option<hyper::Uri>,
-		block_accepted_url: Option<hyper::Uri>,
-		nthreads: u16,
-		timeout: u16,
-	) -> WebHook {
-		let keep_alive = Duration::from_secs(timeout as u64);
+	/// Instantiates a Webhook struct from a configuration file, with its
+	/// outbox stored under `db_root` with the provided name
+	fn from_config(config: &WebHooksConfig, db_root: &str, name: &str) -> Result<WebHook, Error> {
+		let keep_alive = Duration::from_secs(config.timeout as u64);
 
 		info!(
 			"Spawning {} threads for webhooks (timeout set to {} secs)",
-			nthreads, timeout
+			config.nthreads, config.timeout
 		);
 
 		let https = HttpsConnector::new();
@@ -204,49 +551,99 @@ impl WebHook {
 			.keep_alive_timeout(keep_alive)
 			.build::<_, hyper::Body>(https);
 
-		WebHook {
-			tx_received_url,
-			block_received_url,
-			header_received_url,
-			block_accepted_url,
+		let key = match &config.secret_path {
+			Some(path) => match file::get_first_line(Some(path.clone())) {
+				Some(secret) => Some(hmac::Key::new(hmac::HMAC_SHA256, secret.trim().as_bytes())),
+				None => {
+					return Err(Error::Configuration(format!(
+						"Unable to read webhook secret file {}",
+						path
+					)));
+				}
+			},
+			None => None,
+		};
+		let outbox = OutboxStore::new(db_root, name).map_err(|e| {
+			Error::General(format!(
+				"Unable to open the {} webhook outbox: {:?}",
+				name, e
+			))
+		})?;
+		let sequences = outbox.last_sequences().map_err(|e| {
+			Error::General(format!(
+				"Unable to read the {} webhook outbox: {:?}",
+				name, e
+			))
+		})?;
+		let delivery = Arc::new(Delivery {
 			client,
-			runtime: Builder::new()
-				.threaded_scheduler()
-				.enable_all()
-				.core_threads(nthreads as usize)
-				.build()
-				.unwrap(),
-		}
-	}
-
-	/// Instantiates a Webhook struct from a configuration file
-	fn from_config(config: &WebHooksConfig) -> WebHook {
-		WebHook::new(
-			parse_url(&config.tx_received_url),
-			parse_url(&config.header_received_url),
-			parse_url(&config.block_received_url),
-			parse_url(&config.block_accepted_url),
-			config.nthreads,
-			config.timeout,
-		)
-	}
+			outbox,
+			in_flight: Mutex::new(HashSet::new()),
+			key,
+			timeout: config.timeout as u64,
+			retry_base_delay: config.retry_base_delay,
+			retry_max_delay: config.retry_max_delay,
+			max_retries: match config.max_retries {
+				0 => None,
+				max => Some(max),
+			},
+			max_outbox_events: config.max_outbox_events,
+		});
 
-	fn post(&self, url: hyper::Uri, data: String) {
-		let mut req = Request::new(Body::from(data));
-		*req.method_mut() = Method::POST;
-		*req.uri_mut() = url.clone();
-		req.headers_mut().insert(
-			hyper::header::CONTENT_TYPE,
-			HeaderValue::from_static("application/json"),
-		);
+		let runtime = Builder::new()
+			.threaded_scheduler()
+			.enable_all()
+			.core_threads(config.nthreads as usize)
+			.build()?;
 
-		let future = self.client.request(req).map_err(move |_res| {
-			warn!("Error sending POST request to {}", url);
+		// Also picks up the events not delivered before the last shutdown
+		let retry = delivery.clone();
+		runtime.spawn(async move {
+			loop {
+				retry.retry_due();
+				time::delay_for(RETRY_INTERVAL).await;
+			}
 		});
 
-		self.runtime.spawn(future);
+		let (queue, queued) = mpsc::sync_channel(QUEUE_SIZE);
+		let handle = runtime.handle().clone();
+		let _ = thread::Builder::new()
+			.name(format!("webhook_outbox_{}", name))
+			.spawn(move || {
+				for event in queued {
+					delivery.store(&handle, event);
+				}
+			});
+
+		Ok(WebHook {
+			tx_received_url: parse_url(&config.tx_received_url),
+			header_received_url: parse_url(&config.header_received_url),
+			block_received_url: parse_url(&config.block_received_url),
+			block_accepted_url: parse_url(&config.block_accepted_url),
+			reorg_url: parse_url(&config.reorg_url),
+			tx_accepted_url: parse_url(&config.tx_accepted_url),
+			tx_evicted_url: parse_url(&config.tx_evicted_url),
+			peer_connected_url: parse_url(&config.peer_connected_url),
+			peer_disconnected_url: parse_url(&config.peer_disconnected_url),
+			peer_banned_url: parse_url(&config.peer_banned_url),
+			sync_status_url: parse_url(&config.sync_status_url),
+			queue: EventQueue::new(sequences, queue),
+			runtime,
+		})
+	}
+
+	/// Queues the event to be stored in the outbox before attempting its
+	/// delivery, so it can be retried if the receiver is unavailable.
+	fn post(&self, event: &str, url: hyper::Uri, data: String) {
+		self.queue.push(event, url.to_string(), data);
 	}
-	fn make_request<T: Serialize>(&self, payload: &T, uri: &Option<hyper::Uri>) -> bool {
+
+	fn make_request<T: Serialize>(
+		&self,
+		event: &str,
+		payload: &T,
+		uri: &Option<hyper::Uri>,
+	) -> bool {
 		if let Some(url) = uri {
 			let payload = match to_string(payload) {
 				Ok(serialized) => serialized,
@@ -254,38 +651,109 @@ impl WebHook {
 					return false; // print error message
 				}
 			};
-			self.post(url.clone(), payload);
+			self.post(event, url.clone(), payload);
 		}
 		true
 	}
 }
 
-impl ChainEvents for WebHook {
-	fn on_block_accepted(&self, block: &core::Block, status: &BlockStatus) {
-		let status_str = match status {
-			BlockStatus::Reorg(_) => "reorg",
-			BlockStatus::Fork => "fork",
-			BlockStatus::Next => "head",
-		};
+/// JSON payloads of the events, shared by the webhooks and the event sink
+fn block_accepted_payload(block: &core::Block, status: &BlockStatus) -> Value {
+	let status_str = match status {
+		BlockStatus::Reorg(_) => "reorg",
+		BlockStatus::Fork => "fork",
+		BlockStatus::Next => "head",
+	};
 
-		// Add additional `depth` field to the JSON in case of reorg
-		let payload = if let BlockStatus::Reorg(depth) = status {
-			json!({
-				"hash": block.header.hash().to_hex(),
-				"status": status_str,
-				"data": block,
-
-				"depth": depth
-			})
-		} else {
-			json!({
-				"hash": block.header.hash().to_hex(),
-				"status": status_str,
-				"data": block
-			})
-		};
+	// Add additional `depth` field to the JSON in case of reorg
+	if let BlockStatus::Reorg(depth) = status {
+		json!({
+			"hash": block.header.hash().to_hex(),
+			"status": status_str,
+			"data": block,
+
+			"depth": depth
+		})
+	} else {
+		json!({
+			"hash": block.header.hash().to_hex(),
+			"status": status_str,
+			"data": block
+		})
+	}
+}
+
+fn reorg_payload(disconnected: &[core::BlockHeader], connected: &[core::BlockHeader]) -> Value {
+	let summary = |headers: &[core::BlockHeader]| {
+		headers
+			.iter()
+			.map(|h| json!({"hash": h.hash().to_hex(), "height": h.height}))
+			.collect::<Vec<_>>()
+	};
+	json!({
+		"depth": disconnected.len(),
+		"disconnected": summary(disconnected),
+		"connected": summary(connected)
+	})
+}
+
+fn tx_accepted_payload(entry: &PoolEntry, stem: bool) -> Value {
+	json!({
+		"hash": entry.tx.hash().to_hex(),
+		"stem": stem,
+		"source": entry.src,
+		"data": entry.tx
+	})
+}
+
+fn tx_payload(tx: &core::Transaction) -> Value {
+	json!({
+		"hash": tx.hash().to_hex(),
+		"data": tx
+	})
+}
+
+fn sync_status_payload(old: &SyncStatus, new: &SyncStatus) -> Value {
+	json!({
+		"old": old,
+		"new": new
+	})
+}
+
+fn block_received_payload(block: &core::Block, addr: &PeerAddr) -> Value {
+	json!({
+		"hash": block.header.hash().to_hex(),
+		"peer": addr,
+		"data": block
+	})
+}
+
+fn header_received_payload(header: &core::BlockHeader, addr: &PeerAddr) -> Value {
+	json!({
+		"hash": header.hash().to_hex(),
+		"peer": addr,
+		"data": header
+	})
+}
 
-		if !self.make_request(&payload, &self.block_accepted_url) {
+fn peer_payload(peer: &PeerInfo) -> Value {
+	json!({
+		"peer": peer.addr,
+		"data": PeerInfoDisplay::from(peer.clone())
+	})
+}
+
+fn peer_banned_payload(addr: &PeerAddr, reason: &ReasonForBan) -> Value {
+	json!({
+		"peer": addr,
+		"reason": reason
+	})
+}
+
+impl ChainEvents for WebHook {
+	fn on_block_accepted(&self, block: &core::Block, status: &BlockStatus) {
+		let payload = block_accepted_payload(block, status);
+		if !self.make_request("block_accepted", &payload, &self.block_accepted_url) {
 			error!(
 				"Failed to serialize block {} at height {}",
 				block.hash(),
@@ -293,28 +761,49 @@ impl ChainEvents for WebHook {
 			);
 		}
 	}
+
+	fn on_reorg(&self, disconnected: &[core::BlockHeader], connected: &[core::BlockHeader]) {
+		let payload = reorg_payload(disconnected, connected);
+		if !self.make_request("reorg", &payload, &self.reorg_url) {
+			error!("Failed to serialize reorg");
+		}
+	}
+
+	fn on_tx_accepted(&self, entry: &PoolEntry, stem: bool) {
+		let payload = tx_accepted_payload(entry, stem);
+		if !self.make_request("tx_accepted", &payload, &self.tx_accepted_url) {
+			error!("Failed to serialize transaction {}", entry.tx.hash());
+		}
+	}
+
+	fn on_tx_evicted(&self, entry: &PoolEntry) {
+		let payload = tx_payload(&entry.tx);
+		if !self.make_request("tx_evicted", &payload, &self.tx_evicted_url) {
+			error!("Failed to serialize transaction {}", entry.tx.hash());
+		}
+	}
+
+	fn on_sync_status_changed(&self, old: &SyncStatus, new: &SyncStatus) {
+		let payload = sync_status_payload(old, new);
+		if !self.make_request("sync_status", &payload, &self.sync_status_url) {
+			error!("Failed to serialize sync status {:?}", new);
+		}
+	}
 }
 
 impl NetEvents for WebHook {
 	/// Triggers when a new transaction arrives
 	fn on_transaction_received(&self, tx: &core::Transaction) {
-		let payload = json!({
-			"hash": tx.hash().to_hex(),
-			"data": tx
-		});
-		if !self.make_request(&payload, &self.tx_received_url) {
+		let payload = tx_payload(tx);
+		if !self.make_request("tx_received", &payload, &self.tx_received_url) {
 			error!("Failed to serialize transaction {}", tx.hash());
 		}
 	}
 
 	/// Triggers when a new block arrives
 	fn on_block_received(&self, block: &core::Block, addr: &PeerAddr) {
-		let payload = json!({
-			"hash": block.header.hash().to_hex(),
-			"peer": addr,
-			"data": block
-		});
-		if !self.make_request(&payload, &self.block_received_url) {
+		let payload = block_received_payload(block, addr);
+		if !self.make_request("block_received", &payload, &self.block_received_url) {
 			error!(
 				"Failed to serialize block {} at height {}",
 				block.hash().to_hex(),
@@ -325,12 +814,8 @@ impl NetEvents for WebHook {
 
 	/// Triggers when a new block header arrives
 	fn on_header_received(&self, header: &core::BlockHeader, addr: &PeerAddr) {
-		let payload = json!({
-			"hash": header.hash().to_hex(),
-			"peer": addr,
-			"data": header
-		});
-		if !self.make_request(&payload, &self.header_received_url) {
+		let payload = header_received_payload(header, addr);
+		if !self.make_request("header_received", &payload, &self.header_received_url) {
 			error!(
 				"Failed to serialize header {} at height {}",
 				header.hash(),
@@ -338,4 +823,123 @@ impl NetEvents for WebHook {
 			);
 		}
 	}
+
+	/// Triggers when a peer completes its handshake and gets connected
+	fn on_peer_connected(&self, peer: &PeerInfo) {
+		let payload = peer_payload(peer);
+		if !self.make_request("peer_connected", &payload, &self.peer_connected_url) {
+			error!("Failed to serialize peer {}", peer.addr);
+		}
+	}
+
+	/// Triggers when a connected peer gets disconnected
+	fn on_peer_disconnected(&self, peer: &PeerInfo) {
+		let payload = peer_payload(peer);
+		if !self.make_request("peer_disconnected", &payload, &self.peer_disconnected_url) {
+			error!("Failed to serialize peer {}", peer.addr);
+		}
+	}
+
+	/// Triggers when a peer is banned
+	fn on_peer_banned(&self, addr: &PeerAddr, reason: &ReasonForBan) {
+		let payload = peer_banned_payload(addr, reason);
+		if !self.make_request("peer_banned", &payload, &self.peer_banned_url) {
+			error!("Failed to serialize peer {}", addr);
+		}
+	}
+}
+
+impl ChainEvents for EventSink {
+	fn on_block_accepted(&self, block: &core::Block, status: &BlockStatus) {
+		self.send("block_accepted", block_accepted_payload(block, status));
+	}
+
+	fn on_reorg(&self, disconnected: &[core::BlockHeader], connected: &[core::BlockHeader]) {
+		self.send("reorg", reorg_payload(disconnected, connected));
+	}
+
+	fn on_tx_accepted(&self, entry: &PoolEntry, stem: bool) {
+		self.send("tx_accepted", tx_accepted_payload(entry, stem));
+	}
+
+	fn on_tx_evicted(&self, entry: &PoolEntry) {
+		self.send("tx_evicted", tx_payload(&entry.tx));
+	}
+
+	fn on_sync_status_changed(&self, old: &SyncStatus, new: &SyncStatus) {
+		self.send("sync_status", sync_status_payload(old, new));
+	}
+}
+
+impl NetEvents for EventSink {
+	fn on_transaction_received(&self, tx: &core::Transaction) {
+		self.send("tx_received", tx_payload(tx));
+	}
+
+	fn on_block_received(&self, block: &core::Block, addr: &PeerAddr) {
+		self.send("block_received", block_received_payload(block, addr));
+	}
+
+	fn on_header_received(&self, header: &core::BlockHeader, addr: &PeerAddr) {
+		self.send("header_received", header_received_payload(header, addr));
+	}
+
+	fn on_peer_connected(&self, peer: &PeerInfo) {
+		self.send("peer_connected", peer_payload(peer));
+	}
+
+	fn on_peer_disconnected(&self, peer: &PeerInfo) {
+		self.send("peer_disconnected", peer_payload(peer));
+	}
+
+	fn on_peer_banned(&self, addr: &PeerAddr, reason: &ReasonForBan) {
+		self.send("peer_banned", peer_banned_payload(addr, reason));
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use std::fs;
+
+	#[test]
+	fn test_queue_overflow_gap() {
+		let db_root = ".kepler_webhook_queue";
+		let _ = fs::remove_dir_all(db_root);
+		{
+			let outbox = OutboxStore::new(db_root, "test").unwrap();
+			let (tx, rx) = mpsc::sync_channel(2);
+			let queue = EventQueue::new(outbox.last_sequences().unwrap(), tx);
+			let store = |rx: &mpsc::Receiver<QueuedEvent>| {
+				for event in rx.try_iter() {
+					outbox
+						.enqueue(&event.name, event.sequence, &event.url, event.payload)
+						.unwrap();
+				}
+			};
+
+			assert!(queue.push("block", "http://a".to_owned(), "1".to_owned()));
+			assert!(queue.push("block", "http://a".to_owned(), "2".to_owned()));
+			// the queue is full, the third event is dropped
+			assert!(!queue.push("block", "http://a".to_owned(), "3".to_owned()));
+			store(&rx);
+			assert!(queue.push("block", "http://a".to_owned(), "4".to_owned()));
+			store(&rx);
+
+			let sequences = outbox
+				.due(0, 10)
+				.unwrap()
+				.into_iter()
+				.map(|e| e.sequence)
+				.collect::<Vec<_>>();
+			assert_eq!(sequences, vec![1, 2, 4]);
+
+			// and carried on from after a restart
+			let (tx, rx) = mpsc::sync_channel(2);
+			let queue = EventQueue::new(outbox.last_sequences().unwrap(), tx);
+			assert!(queue.push("block", "http://a".to_owned(), "5".to_owned()));
+			assert_eq!(rx.try_recv().unwrap().sequence, 5);
+		}
+		let _ = fs::remove_dir_all(db_root);
+	}
 }
@@ -0,0 +1,338 @@
+// Copyright 2020 The Kepler Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Persistent outbox of the webhook events. Events are stored before being
+//! sent and only removed once the receiver acknowledged them, so they survive
+//! both receiver and node restarts.
+
+use ring::hmac;
+use std::collections::HashMap;
+
+use crate::core::ser::{self, Readable, Reader, Writeable, Writer};
+use crate::store::{self, to_key, to_key_u64, u64_to_key, Batch, Error};
+use crate::util;
+
+const DB_NAME: &str = "webhooks";
+const STORE_SUBPATH: &str = "outbox";
+
+const ENTRY_PREFIX: u8 = b'O';
+const DUE_PREFIX: u8 = b'D';
+const SEQUENCE_PREFIX: u8 = b'S';
+const LAST_ID_KEY: &[u8] = b"I";
+const COUNT_KEY: &[u8] = b"C";
+
+/// A webhook event waiting to be delivered.
+#[derive(Debug, Clone, PartialEq)]
+pub struct OutboxEntry {
+	/// Position of the entry in the outbox
+	pub id: u64,
+	/// Name of the event
+	pub event: String,
+	/// Sequence number of the event, increasing by one for each event of the
+	/// same name
+	pub sequence: u64,
+	/// Url the event is posted to
+	pub url: String,
+	/// JSON payload of the event
+	pub payload: String,
+	/// Number of failed delivery attempts
+	pub attempts: u32,
+	/// Timestamp before which the delivery shouldn't be attempted again
+	pub next_attempt: i64,
+}
+
+impl Writeable for OutboxEntry {
+	fn write<W: Writer>(&self, writer: &mut W) -> Result<(), ser::Error> {
+		writer.write_u64(self.id)?;
+		writer.write_bytes(&self.event)?;
+		writer.write_u64(self.sequence)?;
+		writer.write_bytes(&self.url)?;
+		writer.write_bytes(&self.payload)?;
+		writer.write_u32(self.attempts)?;
+		writer.write_i64(self.next_attempt)
+	}
+}
+
+impl Readable for OutboxEntry {
+	fn read(reader: &mut dyn Reader) -> Result<OutboxEntry, ser::Error> {
+		let id = reader.read_u64()?;
+		let event = read_string(reader)?;
+		let sequence = reader.read_u64()?;
+		let url = read_string(reader)?;
+		let payload = read_string(reader)?;
+		let attempts = reader.read_u32()?;
+		let next_attempt = reader.read_i64()?;
+		Ok(OutboxEntry {
+			id,
+			event,
+			sequence,
+			url,
+			payload,
+			attempts,
+			next_attempt,
+		})
+	}
+}
+
+fn read_string(reader: &mut dyn Reader) -> Result<String, ser::Error> {
+	let bytes = reader.read_bytes_len_prefix()?;
+	String::from_utf8(bytes).map_err(|_| ser::Error::CorruptedData)
+}
+
+/// Storage of the webhook events not delivered yet.
+pub struct OutboxStore {
+	db: store::Store,
+}
+
+impl OutboxStore {
+	/// Opens the outbox with the provided name under the root path.
+	pub fn new(db_root: &str, name: &str) -> Result<OutboxStore, Error> {
+		let env_name = format!("{}/{}", DB_NAME, name);
+		let db = store::Store::new(db_root, Some(&env_name), Some(STORE_SUBPATH), None)?;
+		Ok(OutboxStore { db: db })
+	}
+
+	/// Last sequence number assigned to each event name, to carry on from
+	/// after a restart.
+	pub fn last_sequences(&self) -> Result<HashMap<String, u64>, Error> {
+		let prefix = to_key(SEQUENCE_PREFIX, &mut vec![]);
+		let mut sequences = HashMap::new();
+		for (k, sequence) in self.db.iter::<u64>(&prefix)? {
+			let event = String::from_utf8_lossy(&k[prefix.len()..]).into_owned();
+			sequences.insert(event, sequence);
+		}
+		Ok(sequences)
+	}
+
+	/// Adds an event to the outbox with the sequence number assigned to it
+	/// when it was raised.
+	pub fn enqueue(
+		&self,
+		event: &str,
+		sequence: u64,
+		url: &str,
+		payload: String,
+	) -> Result<OutboxEntry, Error> {
+		let batch = self.db.batch()?;
+		let seq_key = sequence_key(event);
+		let last_sequence = batch.get_ser::<u64>(&seq_key[..])?.unwrap_or(0);
+		let id = batch.get_ser::<u64>(LAST_ID_KEY)?.unwrap_or(0) + 1;
+		let count = batch.get_ser::<u64>(COUNT_KEY)?.unwrap_or(0) + 1;
+		let entry = OutboxEntry {
+			id,
+			event: event.to_owned(),
+			sequence,
+			url: url.to_owned(),
+			payload,
+			attempts: 0,
+			next_attempt: 0,
+		};
+		batch.put_ser(&seq_key[..], &last_sequence.max(sequence))?;
+		batch.put_ser(LAST_ID_KEY, &id)?;
+		batch.put_ser(COUNT_KEY, &count)?;
+		batch.put_ser(&u64_to_key(ENTRY_PREFIX, id)[..], &entry)?;
+		batch.put_ser(&due_key(entry.next_attempt, id)[..], &id)?;
+		batch.commit()?;
+		Ok(entry)
+	}
+
+	/// Saves an entry after a failed delivery attempt, unless it's been
+	/// dropped from the outbox in the meantime.
+	pub fn save(&self, entry: &OutboxEntry) -> Result<(), Error> {
+		let batch = self.db.batch()?;
+		let key = u64_to_key(ENTRY_PREFIX, entry.id);
+		let old = match batch.get_ser::<OutboxEntry>(&key[..])? {
+			Some(old) => old,
+			None => return Ok(()),
+		};
+		batch.delete(&due_key(old.next_attempt, old.id)[..])?;
+		batch.put_ser(&due_key(entry.next_attempt, entry.id)[..], &entry.id)?;
+		batch.put_ser(&key[..], entry)?;
+		batch.commit()
+	}
+
+	/// Removes a delivered (or abandoned) entry.
+	pub fn delete(&self, id: u64) -> Result<(), Error> {
+		let batch = self.db.batch()?;
+		if let Some(entry) = batch.get_ser::<OutboxEntry>(&u64_to_key(ENTRY_PREFIX, id)[..])? {
+			remove(&batch, &entry)?;
+		}
+		batch.commit()
+	}
+
+	/// Entries due for a delivery attempt at the provided time, the ones
+	/// waiting for the longest first, at most `limit` of them. Only the due
+	/// entries are read, using the index of their next attempt time.
+	pub fn due(&self, now: i64, limit: usize) -> Result<Vec<OutboxEntry>, Error> {
+		let prefix = to_key(DUE_PREFIX, &mut vec![]);
+		let ids = self
+			.db
+			.iter::<u64>(&prefix)?
+			.take_while(|(k, _)| due_time(&k[prefix.len()..]) <= now.max(0) as u64)
+			.take(limit)
+			.map(|(_, id)| id)
+			.collect::<Vec<_>>();
+		let mut entries = Vec::with_capacity(ids.len());
+		for id in ids {
+			if let Some(entry) = self.db.get_ser(&u64_to_key(ENTRY_PREFIX, id)[..])? {
+				entries.push(entry);
+			}
+		}
+		Ok(entries)
+	}
+
+	/// Drops the oldest entries until at most `max` are left in the outbox,
+	/// returning the number of entries dropped.
+	pub fn truncate(&self, max: u64) -> Result<u64, Error> {
+		let count = self.db.get_ser::<u64>(COUNT_KEY)?.unwrap_or(0);
+		if count <= max {
+			return Ok(0);
+		}
+		let prefix = to_key(ENTRY_PREFIX, &mut vec![]);
+		let oldest = self
+			.db
+			.iter::<OutboxEntry>(&prefix)?
+			.take((count - max) as usize)
+			.map(|(_, v)| v)
+			.collect::<Vec<_>>();
+		let batch = self.db.batch()?;
+		for entry in &oldest {
+			remove(&batch, entry)?;
+		}
+		batch.commit()?;
+		Ok(oldest.len() as u64)
+	}
+}
+
+/// Removes an entry along with its due time, keeping the entry count.
+fn remove(batch: &Batch<'_>, entry: &OutboxEntry) -> Result<(), Error> {
+	let count = batch.get_ser::<u64>(COUNT_KEY)?.unwrap_or(0);
+	batch.put_ser(COUNT_KEY, &count.saturating_sub(1))?;
+	batch.delete(&due_key(entry.next_attempt, entry.id)[..])?;
+	batch.delete(&u64_to_key(ENTRY_PREFIX, entry.id)[..])
+}
+
+/// Index key of an entry by its next attempt time, sorting the entries by
+/// time then id.
+fn due_key(next_attempt: i64, id: u64) -> Vec<u8> {
+	let mut time = (next_attempt.max(0) as u64).to_be_bytes().to_vec();
+	to_key_u64(DUE_PREFIX, &mut time, id)
+}
+
+fn due_time(key: &[u8]) -> u64 {
+	let mut time = [0u8; 8];
+	time.copy_from_slice(&key[..8]);
+	u64::from_be_bytes(time)
+}
+
+fn sequence_key(event: &str) -> Vec<u8> {
+	to_key(SEQUENCE_PREFIX, &mut event.as_bytes().to_vec())
+}
+
+/// Delay in seconds before the next delivery attempt, doubling with each
+/// failed attempt up to the provided maximum.
+pub fn retry_delay(attempts: u32, base_delay: u64, max_delay: u64) -> u64 {
+	let exp = attempts.saturating_sub(1).min(32);
+	base_delay.saturating_mul(1u64 << exp).min(max_delay)
+}
+
+/// Hex encoded HMAC-SHA256 of the name, sequence number and payload of an
+/// event, computed over `<event>.<sequence>.<payload>`. Sequences are per
+/// event name, so the name is signed too.
+pub fn signature(key: &hmac::Key, event: &str, sequence: u64, payload: &str) -> String {
+	let message = format!("{}.{}.{}", event, sequence, payload);
+	let tag = hmac::sign(key, message.as_bytes());
+	util::to_hex(tag.as_ref().to_vec())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use std::fs;
+
+	#[test]
+	fn test_outbox() {
+		let db_root = ".kepler_outbox";
+		let _ = fs::remove_dir_all(db_root);
+		{
+			let outbox = OutboxStore::new(db_root, "test").unwrap();
+			let first = outbox
+				.enqueue("block", 1, "http://a", "1".to_owned())
+				.unwrap();
+			let second = outbox
+				.enqueue("block", 2, "http://a", "2".to_owned())
+				.unwrap();
+			let third = outbox.enqueue("tx", 1, "http://b", "3".to_owned()).unwrap();
+			assert_eq!(
+				outbox.due(0, 10).unwrap(),
+				vec![first.clone(), second, third.clone()]
+			);
+			assert_eq!(outbox.due(0, 1).unwrap(), vec![first.clone()]);
+
+			// a retried entry is only due once its next attempt time is reached
+			let mut retried = first.clone();
+			retried.attempts = 1;
+			retried.next_attempt = 100;
+			outbox.save(&retried).unwrap();
+			assert_eq!(outbox.due(99, 10).unwrap().len(), 2);
+			assert_eq!(outbox.due(100, 10).unwrap().last(), Some(&retried));
+
+			// the oldest entries are dropped first, and aren't saved back
+			assert_eq!(outbox.truncate(1).unwrap(), 2);
+			outbox.save(&retried).unwrap();
+			assert_eq!(outbox.due(100, 10).unwrap(), vec![third.clone()]);
+			assert_eq!(outbox.truncate(1).unwrap(), 0);
+
+			outbox.delete(third.id).unwrap();
+			outbox.delete(third.id).unwrap();
+			assert!(outbox.due(100, 10).unwrap().is_empty());
+			let fourth = outbox.enqueue("tx", 2, "http://b", "4".to_owned()).unwrap();
+			assert_eq!(fourth.id, 4);
+			let sequences = outbox.last_sequences().unwrap();
+			assert_eq!(sequences.get("block"), Some(&2));
+			assert_eq!(sequences.get("tx"), Some(&2));
+			assert_eq!(outbox.truncate(1).unwrap(), 0);
+		}
+		let _ = fs::remove_dir_all(db_root);
+	}
+
+	#[test]
+	fn test_retry_delay() {
+		assert_eq!(retry_delay(1, 2, 600), 2);
+		assert_eq!(retry_delay(2, 2, 600), 4);
+		assert_eq!(retry_delay(5, 2, 600), 32);
+		assert_eq!(retry_delay(10, 2, 600), 600);
+		assert_eq!(retry_delay(u32::max_value(), 2, 600), 600);
+	}
+
+	#[test]
+	fn test_signature() {
+		// RFC 4231 test case 2
+		let key = hmac::Key::new(hmac::HMAC_SHA256, b"Jefe");
+		let tag = hmac::sign(&key, b"what do ya want for nothing?");
+		assert_eq!(
+			util::to_hex(tag.as_ref().to_vec()),
+			"5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+		);
+
+		let sig = signature(&key, "tx_accepted", 7, "{}");
+		assert_eq!(
+			sig,
+			util::to_hex(hmac::sign(&key, b"tx_accepted.7.{}").as_ref().to_vec())
+		);
+		assert_ne!(sig, signature(&key, "tx_accepted", 8, "{}"));
+		// a body can't be replayed as another event
+		assert_ne!(sig, signature(&key, "tx_evicted", 7, "{}"));
+	}
+}
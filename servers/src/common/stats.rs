@@ -19,14 +19,18 @@ use crate::util::RwLock;
 use std::sync::Arc;
 use std::time::SystemTime;
 
-use crate::core::consensus::graph_weight;
-use crate::core::core::hash::Hash;
+use crate::core::consensus::{self, graph_weight, HeaderInfo};
+use crate::core::core::hash::{Hash, Hashed};
+use crate::core::core::{Block, KernelFeatures};
+use crate::core::global;
 use crate::core::ser::ProtocolVersion;
 
 use chrono::prelude::*;
 
 use crate::chain::SyncStatus;
 use crate::p2p;
+use crate::pool::PoolEntry;
+use crate::util;
 use kepler_core::pow::Difficulty;
 
 /// Server state info collection struct, to be passed around into internals
@@ -35,12 +39,18 @@ use kepler_core::pow::Difficulty;
 pub struct ServerStateInfo {
 	/// Stratum stats
 	pub stratum_stats: Arc<RwLock<StratumStats>>,
+	/// CPU miner stats
+	pub cpu_miner_stats: Arc<RwLock<CpuMinerStats>>,
+	/// Block download stats, updated by body sync
+	pub body_sync_stats: Arc<RwLock<BodySyncStats>>,
 }
 
 impl Default for ServerStateInfo {
 	fn default() -> ServerStateInfo {
 		ServerStateInfo {
 			stratum_stats: Arc::new(RwLock::new(StratumStats::default())),
+			cpu_miner_stats: Arc::new(RwLock::new(CpuMinerStats::default())),
+			body_sync_stats: Arc::new(RwLock::new(BodySyncStats::default())),
 		}
 	}
 }
@@ -58,14 +68,20 @@ pub struct ServerStats {
 	pub sync_status: SyncStatus,
 	/// Handle to current stratum server stats
 	pub stratum_stats: StratumStats,
+	/// CPU miner stats
+	pub cpu_miner_stats: CpuMinerStats,
 	/// Peer stats
 	pub peer_stats: Vec<PeerStats>,
 	/// Difficulty calculation statistics
 	pub diff_stats: DiffStats,
 	/// Transaction pool statistics
 	pub tx_stats: Option<TxStats>,
+	/// Most recent blocks, latest first
+	pub block_stats: Vec<BlockStats>,
 	/// Disk usage in GB
 	pub disk_usage_gb: String,
+	/// Block downloads of the last body sync round
+	pub body_sync_stats: BodySyncStats,
 }
 
 /// Chain Statistics
@@ -91,6 +107,29 @@ pub struct TxStats {
 	pub stem_pool_size: usize,
 	/// Number of transaction kernels in the stem pool
 	pub stem_pool_kernels: usize,
+	/// Transactions of the transaction pool and the stem pool
+	pub entries: Vec<PoolEntryStats>,
+}
+
+/// Transaction waiting in the transaction pool or the stem pool
+#[derive(Clone, Serialize, Debug)]
+pub struct PoolEntryStats {
+	/// Transaction hash
+	pub hash: Hash,
+	/// Whether the transaction is in the stem pool
+	pub stem: bool,
+	/// Where the transaction originated from
+	pub source: String,
+	/// When the transaction was added to the pool
+	pub received: DateTime<Utc>,
+	/// Transaction weight
+	pub weight: usize,
+	/// Transaction fee
+	pub fee: u64,
+	/// Fee per thousand weight units
+	pub fee_to_weight: u64,
+	/// Excess commitments of the transaction kernels
+	pub kernel_excesses: Vec<String>,
 }
 /// Struct to return relevant information about stratum workers
 #[derive(Clone, Serialize, Debug)]
@@ -134,6 +173,27 @@ pub struct StratumStats {
 	pub worker_stats: Vec<WorkerStats>,
 }
 
+/// Struct to return relevant information about the CPU miner of the node
+#[derive(Clone, Serialize, Debug, Default)]
+pub struct CpuMinerStats {
+	/// whether the CPU miner is enabled
+	pub is_enabled: bool,
+	/// whether the CPU miner is running
+	pub is_running: bool,
+	/// Number of mining threads
+	pub num_threads: usize,
+	/// what block height we're mining at
+	pub block_height: u64,
+	/// current network difficulty we're working on
+	pub network_difficulty: u64,
+	/// cuckoo size used for mining
+	pub edge_bits: u16,
+	/// Graphs searched per second over all threads, on the current block
+	pub graphs_per_sec: f64,
+	/// number of valid blocks found
+	pub num_blocks_found: u64,
+}
+
 /// Stats on the last WINDOW blocks and the difficulty calculation
 #[derive(Clone)]
 pub struct DiffStats {
@@ -168,6 +228,62 @@ pub struct DiffBlock {
 	pub is_secondary: bool,
 }
 
+/// Summary of a block, to be listed by the TUI
+#[derive(Clone, Debug)]
+pub struct BlockStats {
+	/// Block height
+	pub height: u64,
+	/// Block hash
+	pub hash: Hash,
+	/// Block timestamp
+	pub time: DateTime<Utc>,
+	/// Network difficulty the block was mined at
+	pub difficulty: u64,
+	/// Number of inputs
+	pub num_inputs: usize,
+	/// Number of outputs
+	pub num_outputs: usize,
+	/// Number of kernels
+	pub num_kernels: usize,
+	/// Total fees of the block transactions
+	pub fees: u64,
+}
+
+/// Kernel of a block, as displayed in the block details
+#[derive(Clone, Debug)]
+pub struct BlockKernelStats {
+	/// Kernel features
+	pub features: String,
+	/// Kernel excess commitment
+	pub excess: String,
+	/// Kernel fee
+	pub fee: u64,
+	/// Kernel lock height
+	pub lock_height: u64,
+}
+
+/// Output of a block, as displayed in the block details
+#[derive(Clone, Debug)]
+pub struct BlockOutputStats {
+	/// Output features
+	pub features: String,
+	/// Output commitment
+	pub commit: String,
+}
+
+/// Full content of a block, as displayed in the block details
+#[derive(Clone, Debug)]
+pub struct BlockDetails {
+	/// Block summary
+	pub stats: BlockStats,
+	/// Commitments of the spent outputs
+	pub inputs: Vec<String>,
+	/// Block outputs
+	pub outputs: Vec<BlockOutputStats>,
+	/// Block kernels
+	pub kernels: Vec<BlockKernelStats>,
+}
+
 /// Struct to return relevant information about peers
 #[derive(Clone, Debug)]
 pub struct PeerStats {
@@ -191,6 +307,49 @@ pub struct PeerStats {
 	pub sent_bytes_per_sec: u64,
 	/// Number of bytes we've received from the peer.
 	pub received_bytes_per_sec: u64,
+	/// Capabilities advertised by the peer
+	pub capabilities: p2p::Capabilities,
+}
+
+/// Stored and live data about a peer, as displayed in the peer details
+#[derive(Clone, Debug)]
+pub struct PeerDetails {
+	/// Data stored about the peer
+	pub data: p2p::PeerData,
+	/// Live stats, if the peer is connected
+	pub stats: Option<PeerStats>,
+	/// Recorded bans of the peer, oldest first
+	pub ban_history: Vec<p2p::BanEvent>,
+}
+
+/// Block downloads scheduled by body sync
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct BodySyncStats {
+	/// Lowest height of the download window
+	pub window_start: u64,
+	/// Highest height of the download window
+	pub window_end: u64,
+	/// Blocks requested and not received yet
+	pub in_flight: usize,
+	/// Download stats of the peers we're syncing from, fastest first
+	pub peers: Vec<PeerDownloadStats>,
+}
+
+/// Block downloads from a single peer during body sync
+#[derive(Clone, Debug, Serialize)]
+pub struct PeerDownloadStats {
+	/// Address
+	pub addr: String,
+	/// Blocks requested from the peer
+	pub requested: u64,
+	/// Requested blocks received
+	pub received: u64,
+	/// Requests that timed out and were sent to another peer
+	pub reassigned: u64,
+	/// Blocks requested and not received yet
+	pub in_flight: usize,
+	/// Estimated throughput, in blocks per second, if known yet
+	pub blocks_per_sec: Option<f64>,
 }
 
 impl PartialEq for PeerStats {
@@ -211,6 +370,154 @@ impl PartialEq for DiffBlock {
 	}
 }
 
+impl PartialEq for PoolEntryStats {
+	fn eq(&self, other: &PoolEntryStats) -> bool {
+		self.hash == other.hash && self.stem == other.stem
+	}
+}
+
+impl PoolEntryStats {
+	/// Convert from a pool entry
+	pub fn from_entry(entry: &PoolEntry, stem: bool) -> PoolEntryStats {
+		PoolEntryStats {
+			hash: entry.tx.hash(),
+			stem,
+			source: format!("{:?}", entry.src),
+			received: entry.tx_at,
+			weight: entry.tx.tx_weight(),
+			fee: entry.tx.fee(),
+			fee_to_weight: entry.tx.fee_to_weight(),
+			kernel_excesses: entry
+				.tx
+				.kernels()
+				.iter()
+				.map(|k| util::to_hex(k.excess.0.to_vec()))
+				.collect(),
+		}
+	}
+}
+
+impl DiffStats {
+	/// Stats of the difficulty adjustment window ending at the provided tip
+	/// height, from the window headers as returned by
+	/// `global::difficulty_data_to_vector`
+	pub fn from_window(last_blocks: Vec<HeaderInfo>, tip_height: u64) -> DiffStats {
+		let diff_entries = DiffBlock::from_header_infos(&last_blocks, tip_height);
+
+		let block_time_sum = diff_entries.iter().fold(0, |sum, t| sum + t.duration);
+		let block_diff_sum = diff_entries.iter().fold(0, |sum, d| sum + d.difficulty);
+		DiffStats {
+			height: tip_height,
+			last_blocks: diff_entries,
+			average_block_time: block_time_sum / (consensus::DIFFICULTY_ADJUST_WINDOW - 1),
+			average_difficulty: block_diff_sum / (consensus::DIFFICULTY_ADJUST_WINDOW - 1),
+			window_size: consensus::DIFFICULTY_ADJUST_WINDOW,
+		}
+	}
+}
+
+impl DiffBlock {
+	/// Blocks ending at the provided tip height, from their headers oldest
+	/// first. The oldest header is only used for the duration of the next
+	/// block.
+	pub fn from_header_infos(headers: &[HeaderInfo], tip_height: u64) -> Vec<DiffBlock> {
+		let mut height = tip_height as i64 - headers.len() as i64 + 1;
+		headers
+			.windows(2)
+			.map(|pair| {
+				let prev = &pair[0];
+				let next = &pair[1];
+
+				height += 1;
+
+				DiffBlock {
+					block_height: height,
+					block_hash: next.block_hash,
+					difficulty: next.difficulty.to_num(),
+					time: next.timestamp,
+					duration: next.timestamp - prev.timestamp,
+					secondary_scaling: next.secondary_scaling,
+					is_secondary: next.is_secondary,
+				}
+			})
+			.collect()
+	}
+
+	/// Estimated network hashrate (graphs per second) at the time of the
+	/// block, computed as for the stratum server
+	pub fn network_hashrate(&self) -> f64 {
+		let edge_bits = if self.is_secondary {
+			consensus::SECOND_POW_EDGE_BITS
+		} else {
+			global::min_edge_bits()
+		};
+		let height = self.block_height.max(0) as u64;
+		42.0 * (self.difficulty as f64 / graph_weight(height, edge_bits) as f64)
+			/ global::block_time_sec() as f64
+	}
+}
+
+impl PartialEq for BlockStats {
+	fn eq(&self, other: &BlockStats) -> bool {
+		self.hash == other.hash
+	}
+}
+
+impl BlockStats {
+	/// Summary of a block, provided the network difficulty it was mined at
+	pub fn from_block(block: &Block, difficulty: u64) -> BlockStats {
+		BlockStats {
+			height: block.header.height,
+			hash: block.hash(),
+			time: block.header.timestamp,
+			difficulty,
+			num_inputs: block.inputs().len(),
+			num_outputs: block.outputs().len(),
+			num_kernels: block.kernels().len(),
+			fees: block.total_fees(),
+		}
+	}
+}
+
+impl BlockDetails {
+	/// Details of a block, provided the network difficulty it was mined at
+	pub fn from_block(block: &Block, difficulty: u64) -> BlockDetails {
+		BlockDetails {
+			stats: BlockStats::from_block(block, difficulty),
+			inputs: block
+				.inputs()
+				.iter()
+				.map(|i| util::to_hex(i.commitment().0.to_vec()))
+				.collect(),
+			outputs: block
+				.outputs()
+				.iter()
+				.map(|o| BlockOutputStats {
+					features: format!("{:?}", o.features),
+					commit: util::to_hex(o.commitment().0.to_vec()),
+				})
+				.collect(),
+			kernels: block
+				.kernels()
+				.iter()
+				.map(|k| {
+					let (fee, lock_height) = match k.features {
+						KernelFeatures::Plain { fee } => (fee, 0),
+						KernelFeatures::Coinbase => (0, 0),
+						KernelFeatures::HeightLocked { fee, lock_height } => (fee, lock_height),
+					};
+					BlockKernelStats {
+						features: k.features.as_string(),
+						excess: util::to_hex(k.excess.0.to_vec()),
+						fee,
+						lock_height,
+					}
+				})
+				.collect(),
+		}
+	}
+}
+
 impl StratumStats {
 	/// Calculate network hashrate
 	pub fn network_hashrate(&self, height: u64) -> f64 {
@@ -246,6 +553,7 @@ impl PeerStats {
 			last_seen: peer.info.last_seen(),
 			sent_bytes_per_sec: peer.last_min_sent_bytes().unwrap_or(0) / 60,
 			received_bytes_per_sec: peer.last_min_received_bytes().unwrap_or(0) / 60,
+			capabilities: peer.info.capabilities,
 		}
 	}
 }
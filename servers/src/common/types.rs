@@ -160,6 +160,11 @@ pub struct ServerConfig {
 	#[serde(default)]
 	pub chain_type: ChainTypes,
 
+	/// Chain file holding the parameters of a custom chain, only used with
+	/// the Custom chain type
+	#[serde(default)]
+	pub custom_chain: Option<String>,
+
 	/// Automatically run full chain validation during normal block processing?
 	#[serde(default)]
 	pub chain_validation_mode: ChainValidationMode,
@@ -171,6 +176,22 @@ pub struct ServerConfig {
 	/// (To assist testing on solo chains)
 	pub skip_sync_wait: Option<bool>,
 
+	/// Header file to import the header chain from on startup, before
+	/// syncing with our peers, as written by `kepler server export_headers`
+	#[serde(default)]
+	pub headers_file: Option<String>,
+
+	/// Whether to build the txhashset from segments downloaded from several
+	/// peers in parallel, each checked as it arrives, instead of the archive
+	#[serde(default)]
+	pub pibd_sync: Option<bool>,
+
+	/// Whether to only sync and validate the header chain, with no block
+	/// bodies, txhashset or transaction pool. Merkle proofs of outputs and
+	/// kernels are then fetched from full peers on demand.
+	#[serde(default)]
+	pub header_only: Option<bool>,
+
 	/// Whether to run the TUI
 	/// if enabled, this will disable logging to stdout
 	pub run_tui: Option<bool>,
@@ -199,6 +220,10 @@ pub struct ServerConfig {
 	/// Configuration for the webhooks that trigger on certain events
 	#[serde(default)]
 	pub webhook_config: WebHooksConfig,
+
+	/// Configuration for the local sink writing the events as JSON lines
+	#[serde(default)]
+	pub event_sink_config: EventSinkConfig,
 }
 
 impl Default for ServerConfig {
@@ -214,14 +239,19 @@ impl Default for ServerConfig {
 			dandelion_config: pool::DandelionConfig::default(),
 			stratum_mining_config: Some(StratumServerConfig::default()),
 			chain_type: ChainTypes::default(),
+			custom_chain: None,
 			archive_mode: Some(false),
 			chain_validation_mode: ChainValidationMode::default(),
 			pool_config: pool::PoolConfig::default(),
 			skip_sync_wait: Some(false),
+			headers_file: None,
+			pibd_sync: Some(false),
+			header_only: Some(false),
 			run_tui: Some(true),
 			run_test_miner: Some(false),
 			test_miner_wallet_url: None,
 			webhook_config: WebHooksConfig::default(),
+			event_sink_config: EventSinkConfig::default(),
 		}
 	}
 }
@@ -236,6 +266,19 @@ pub struct StratumServerConfig {
 	/// If enabled, the address and port to listen on
 	pub stratum_server_addr: Option<String>,
 
+	/// If set, the address and port to listen on for TLS connections. Can be
+	/// used alongside the plain `stratum_server_addr` listener.
+	#[serde(default)]
+	pub stratum_server_tls_addr: Option<String>,
+
+	/// TLS certificate file for the stratum TLS listener
+	#[serde(default)]
+	pub tls_certificate_file: Option<String>,
+
+	/// TLS certificate private key file for the stratum TLS listener
+	#[serde(default)]
+	pub tls_certificate_key: Option<String>,
+
 	/// How long to wait before stopping the miner, recollecting transactions
 	/// and starting again
 	pub attempt_time_per_block: u32,
@@ -243,12 +286,64 @@ pub struct StratumServerConfig {
 	/// Minimum difficulty for worker shares
 	pub minimum_share_difficulty: u64,
 
+	/// Adjust the share difficulty of each worker individually (vardiff) so
+	/// that it submits shares at `vardiff_target_shares_per_minute`
+	#[serde(default)]
+	pub enable_vardiff: bool,
+
+	/// Share rate each worker is targeted at when vardiff is enabled
+	#[serde(default = "default_vardiff_target_shares_per_minute")]
+	pub vardiff_target_shares_per_minute: f64,
+
+	/// Length in seconds of the window over which a worker share rate is
+	/// measured before its difficulty is retargeted
+	#[serde(default = "default_vardiff_retarget_time")]
+	pub vardiff_retarget_time: u64,
+
 	/// Base address to the HTTP wallet receiver
 	pub wallet_listener_url: String,
 
 	/// Attributes the reward to a random private key instead of contacting the
 	/// wallet receiver. Mostly used for tests.
 	pub burn_reward: bool,
+
+	/// If set, the node builds the coinbase itself from the extended private
+	/// key stored in this file instead of contacting the wallet receiver,
+	/// deriving a fresh key id for each block
+	#[serde(default)]
+	pub coinbase_key_file: Option<String>,
+
+	/// If set, workers have to log in with a login and password listed in
+	/// this file before they can mine
+	#[serde(default)]
+	pub stratum_credentials_file: Option<String>,
+
+	/// Run the multithreaded CPU miner of the node, paying to the same
+	/// coinbase destination as the stratum server. Only for non-production
+	/// chains.
+	#[serde(default)]
+	pub enable_cpu_miner: bool,
+
+	/// Number of threads of the CPU miner
+	#[serde(default = "default_cpu_miner_threads")]
+	pub cpu_miner_threads: usize,
+
+	/// Edge bits of the graphs searched by the CPU miner, the chain minimum
+	/// if not set
+	#[serde(default)]
+	pub cpu_miner_edge_bits: Option<u8>,
+}
+
+fn default_vardiff_target_shares_per_minute() -> f64 {
+	6.0
+}
+
+fn default_cpu_miner_threads() -> usize {
+	1
+}
+
+fn default_vardiff_retarget_time() -> u64 {
+	90
 }
 
 impl Default for StratumServerConfig {
@@ -256,10 +351,21 @@ impl Default for StratumServerConfig {
 		StratumServerConfig {
 			wallet_listener_url: "http://127.0.0.1:7415".to_string(),
 			burn_reward: false,
+			coinbase_key_file: None,
+			stratum_credentials_file: None,
 			attempt_time_per_block: 15,
 			minimum_share_difficulty: 1,
+			enable_vardiff: false,
+			vardiff_target_shares_per_minute: default_vardiff_target_shares_per_minute(),
+			vardiff_retarget_time: default_vardiff_retarget_time(),
 			enable_stratum_server: Some(false),
 			stratum_server_addr: Some("127.0.0.1:7416".to_string()),
+			stratum_server_tls_addr: None,
+			tls_certificate_file: None,
+			tls_certificate_key: None,
+			enable_cpu_miner: false,
+			cpu_miner_threads: default_cpu_miner_threads(),
+			cpu_miner_edge_bits: None,
 		}
 	}
 }
@@ -275,18 +381,66 @@ pub struct WebHooksConfig {
 	pub block_received_url: Option<String>,
 	/// url to POST block data when a new block is accepted by our node (might be a reorg or a fork)
 	pub block_accepted_url: Option<String>,
+	/// url to POST the disconnected and connected blocks when our chain head moves to another fork
+	pub reorg_url: Option<String>,
+	/// url to POST transaction data when a transaction is accepted in our pool
+	pub tx_accepted_url: Option<String>,
+	/// url to POST transaction data when a transaction is evicted from our pool
+	pub tx_evicted_url: Option<String>,
+	/// url to POST peer data when a peer gets connected
+	pub peer_connected_url: Option<String>,
+	/// url to POST peer data when a peer gets disconnected
+	pub peer_disconnected_url: Option<String>,
+	/// url to POST peer data when a peer is banned
+	pub peer_banned_url: Option<String>,
+	/// url to POST the previous and new sync status when it changes
+	pub sync_status_url: Option<String>,
 	/// number of worker threads in the tokio runtime
 	#[serde(default = "default_nthreads")]
 	pub nthreads: u16,
 	/// timeout in seconds for the http request
 	#[serde(default = "default_timeout")]
 	pub timeout: u16,
+	/// file holding the secret used to sign the events with HMAC-SHA256
+	#[serde(default)]
+	pub secret_path: Option<String>,
+	/// number of retries before an undelivered event is dropped, events are
+	/// retried until delivered if 0
+	#[serde(default = "default_max_retries")]
+	pub max_retries: u32,
+	/// delay in seconds before the first retry of an undelivered event,
+	/// doubling with each retry
+	#[serde(default = "default_retry_base_delay")]
+	pub retry_base_delay: u64,
+	/// maximum delay in seconds between two retries of an undelivered event
+	#[serde(default = "default_retry_max_delay")]
+	pub retry_max_delay: u64,
+	/// number of undelivered events kept on disk, the oldest ones being
+	/// dropped first
+	#[serde(default = "default_max_outbox_events")]
+	pub max_outbox_events: u64,
 }
 
 fn default_timeout() -> u16 {
 	10
 }
 
+fn default_max_retries() -> u32 {
+	20
+}
+
+fn default_max_outbox_events() -> u64 {
+	10_000
+}
+
+fn default_retry_base_delay() -> u64 {
+	1
+}
+
+fn default_retry_max_delay() -> u64 {
+	600
+}
+
 fn default_nthreads() -> u16 {
 	4
 }
@@ -298,8 +452,57 @@ impl Default for WebHooksConfig {
 			header_received_url: None,
 			block_received_url: None,
 			block_accepted_url: None,
+			reorg_url: None,
+			tx_accepted_url: None,
+			tx_evicted_url: None,
+			peer_connected_url: None,
+			peer_disconnected_url: None,
+			peer_banned_url: None,
+			sync_status_url: None,
 			nthreads: default_nthreads(),
 			timeout: default_timeout(),
+			secret_path: None,
+			max_retries: default_max_retries(),
+			retry_base_delay: default_retry_base_delay(),
+			retry_max_delay: default_retry_max_delay(),
+			max_outbox_events: default_max_outbox_events(),
+		}
+	}
+}
+
+/// Local event sink configuration. Each event is written as a line of JSON
+/// to a file and/or to the clients of a Unix domain socket.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct EventSinkConfig {
+	/// append-only file the events are written to
+	#[serde(default)]
+	pub event_file_path: Option<String>,
+	/// file size in bytes above which the event file is rotated
+	#[serde(default = "default_event_max_size")]
+	pub event_max_size: u64,
+	/// number of rotated event files to keep
+	#[serde(default = "default_event_max_files")]
+	pub event_max_files: u32,
+	/// Unix domain socket the events are written to, for each connected client
+	#[serde(default)]
+	pub event_socket_path: Option<String>,
+}
+
+fn default_event_max_size() -> u64 {
+	1024 * 1024 * 16
+}
+
+fn default_event_max_files() -> u32 {
+	8
+}
+
+impl Default for EventSinkConfig {
+	fn default() -> EventSinkConfig {
+		EventSinkConfig {
+			event_file_path: None,
+			event_max_size: default_event_max_size(),
+			event_max_files: default_event_max_files(),
+			event_socket_path: None,
 		}
 	}
 }
@@ -15,6 +15,7 @@
 //! Kepler P2P / API server
 
 pub mod dandelion_monitor;
+pub mod merkle_proofs;
 pub mod seed;
 pub mod server;
 pub mod sync;
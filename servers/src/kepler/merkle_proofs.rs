@@ -0,0 +1,257 @@
+// Copyright 2020 The Kepler Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Merkle proofs of outputs and kernels fetched on demand from full peers,
+//! for a node only holding the header chain. Proofs are checked against our
+//! headers as they're received, a peer sending a bad one gets banned.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Weak};
+use std::time::{Duration, Instant};
+
+use crate::api;
+use crate::chain::{self, KernelMerkleProof, OutputMerkleProof};
+use crate::core::core::hash::Hash;
+use crate::core::core::BlockHeader;
+use crate::p2p::{self, Capabilities, Peer};
+use crate::util::secp::pedersen::Commitment;
+use crate::util::{Condvar, Mutex, OneTime};
+
+/// Peers asked for the same proof
+const PROOF_PEERS: usize = 3;
+
+/// Delay after which we give up waiting for a proof
+const PROOF_TIMEOUT_SECS: u64 = 10;
+
+/// Maximum number of proofs of each kind we're waiting for at once
+const MAX_PENDING_PROOFS: usize = 64;
+
+/// A proof asked to our peers, none until one that checks out has been
+/// received, and the number of callers waiting for it.
+struct Request<T> {
+	proof: Option<T>,
+	waiters: usize,
+}
+
+/// Proofs asked to our peers, keyed by commitment. Waiters are woken up as
+/// soon as a proof is received.
+struct Pending<T> {
+	requests: Mutex<HashMap<Commitment, Request<T>>>,
+	received: Condvar,
+}
+
+impl<T> Pending<T> {
+	fn new() -> Pending<T> {
+		Pending {
+			requests: Mutex::new(HashMap::new()),
+			received: Condvar::new(),
+		}
+	}
+
+	fn is_pending(&self, commit: &Commitment) -> bool {
+		self.requests
+			.lock()
+			.get(commit)
+			.map_or(false, |x| x.proof.is_none())
+	}
+
+	/// Keeps the proof received if we're still waiting for it, waking up the
+	/// waiters.
+	fn fulfill(&self, commit: Commitment, proof: T) -> bool {
+		match self.requests.lock().get_mut(&commit) {
+			Some(request) if request.proof.is_none() => {
+				request.proof = Some(proof);
+				self.received.notify_all();
+				true
+			}
+			_ => false,
+		}
+	}
+}
+
+pub struct MerkleProofRequests {
+	chain: Weak<chain::Chain>,
+	peers: OneTime<Weak<p2p::Peers>>,
+	outputs: Pending<OutputMerkleProof>,
+	kernels: Pending<KernelMerkleProof>,
+}
+
+impl MerkleProofRequests {
+	pub fn new(chain: Arc<chain::Chain>) -> MerkleProofRequests {
+		MerkleProofRequests {
+			chain: Arc::downgrade(&chain),
+			peers: OneTime::new(),
+			outputs: Pending::new(),
+			kernels: Pending::new(),
+		}
+	}
+
+	/// Initialize with a reference to our peers. Should only be called once.
+	pub fn init(&self, peers: Arc<p2p::Peers>) {
+		self.peers.init(Arc::downgrade(&peers));
+	}
+
+	fn chain(&self) -> Arc<chain::Chain> {
+		self.chain
+			.upgrade()
+			.expect("Failed to upgrade weak ref to our chain.")
+	}
+
+	fn peers(&self) -> Arc<p2p::Peers> {
+		self.peers
+			.borrow()
+			.upgrade()
+			.expect("Failed to upgrade weak ref to our peers.")
+	}
+
+	/// The header a proof was built against, if it's on our header chain.
+	/// Peers ahead of us or on another fork send proofs we can't check.
+	fn header(&self, hash: &Hash) -> Option<BlockHeader> {
+		let chain = self.chain();
+		let header = chain.get_block_header(hash).ok()?;
+		chain.is_on_current_chain(&header).ok()?;
+		Some(header)
+	}
+
+	/// A proof of an output has been received, kept if we asked for it and
+	/// it checks out against our header chain.
+	pub fn output_proof_received(&self, proof: OutputMerkleProof) -> Result<bool, chain::Error> {
+		let commit = proof.output.commit;
+		if !self.outputs.is_pending(&commit) {
+			return Ok(false);
+		}
+		let header = match self.header(&proof.block_hash) {
+			Some(header) => header,
+			None => return Ok(false),
+		};
+		proof.verify(&header)?;
+		Ok(self.outputs.fulfill(commit, proof))
+	}
+
+	/// A proof of a kernel has been received, kept if we asked for it and
+	/// it checks out against our header chain.
+	pub fn kernel_proof_received(&self, proof: KernelMerkleProof) -> Result<bool, chain::Error> {
+		let excess = proof.kernel.excess;
+		if !self.kernels.is_pending(&excess) {
+			return Ok(false);
+		}
+		let header = match self.header(&proof.block_hash) {
+			Some(header) => header,
+			None => return Ok(false),
+		};
+		proof.verify(&header)?;
+		Ok(self.kernels.fulfill(excess, proof))
+	}
+
+	/// Asks our peers serving proofs for one, unless it's already been asked
+	/// for, then waits until one checking out against our header chain has
+	/// been received.
+	fn fetch<T, F>(
+		&self,
+		pending: &Pending<T>,
+		commit: Commitment,
+		send: F,
+	) -> Result<T, api::Error>
+	where
+		T: Clone,
+		F: Fn(&Peer, Commitment) -> Result<(), p2p::Error>,
+	{
+		let first = {
+			let mut requests = pending.requests.lock();
+			let first = !requests.contains_key(&commit);
+			if first && requests.len() >= MAX_PENDING_PROOFS {
+				return Err(api::ErrorKind::Internal(
+					"too many merkle proofs requested".to_owned(),
+				)
+				.into());
+			}
+			requests
+				.entry(commit)
+				.or_insert(Request {
+					proof: None,
+					waiters: 0,
+				})
+				.waiters += 1;
+			first
+		};
+
+		let sent = if first {
+			self.send_requests(commit, &send)
+		} else {
+			Ok(())
+		};
+
+		let deadline = Instant::now() + Duration::from_secs(PROOF_TIMEOUT_SECS);
+		let mut requests = pending.requests.lock();
+		let res = sent.and_then(|_| loop {
+			if let Some(proof) = requests.get(&commit).and_then(|x| x.proof.clone()) {
+				break Ok(proof);
+			}
+			if Instant::now() >= deadline {
+				break Err(api::ErrorKind::NotFound.into());
+			}
+			pending.received.wait_until(&mut requests, deadline);
+		});
+
+		// the last waiter drops the request
+		if let Some(request) = requests.get_mut(&commit) {
+			request.waiters -= 1;
+			if request.waiters == 0 {
+				requests.remove(&commit);
+			}
+		}
+		res
+	}
+
+	fn send_requests<F>(&self, commit: Commitment, send: &F) -> Result<(), api::Error>
+	where
+		F: Fn(&Peer, Commitment) -> Result<(), p2p::Error>,
+	{
+		let peers: Vec<Arc<Peer>> = self
+			.peers()
+			.most_work_peers()
+			.into_iter()
+			.filter(|p| p.info.capabilities.contains(Capabilities::MERKLE_PROOFS))
+			.take(PROOF_PEERS)
+			.collect();
+		if peers.is_empty() {
+			return Err(
+				api::ErrorKind::Internal("no peer serving merkle proofs".to_owned()).into(),
+			);
+		}
+		for peer in peers {
+			if let Err(e) = send(&peer, commit) {
+				debug!(
+					"merkle_proofs: request to {} failed: {:?}",
+					peer.info.addr, e
+				);
+			}
+		}
+		Ok(())
+	}
+}
+
+impl api::MerkleProofs for MerkleProofRequests {
+	fn output_merkle_proof(&self, commit: Commitment) -> Result<OutputMerkleProof, api::Error> {
+		self.fetch(&self.outputs, commit, |peer, commit| {
+			peer.send_output_merkle_proof_request(commit)
+		})
+	}
+
+	fn kernel_merkle_proof(&self, excess: Commitment) -> Result<KernelMerkleProof, api::Error> {
+		self.fetch(&self.kernels, excess, |peer, excess| {
+			peer.send_kernel_merkle_proof_request(excess)
+		})
+	}
+}
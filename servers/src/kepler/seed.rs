@@ -38,8 +38,8 @@ const MAINNET_DNS_SEEDS: &'static [&'static str] = &[
 	"seed2.kepler.network",
 	"seed3.kepler.network",
 	"seed4.kepler.network",
-        "seed5.kepler.network",
-        "seed6.kepler.network",
+	"seed5.kepler.network",
+	"seed6.kepler.network",
 ];
 const FLOONET_DNS_SEEDS: &'static [&'static str] = &["testseed1.kepler.network"];
 
@@ -104,12 +104,7 @@ pub fn connect_and_monitor(
 					);
 
 					// monitor additional peers if we need to add more
-					monitor_peers(
-						peers.clone(),
-						p2p_server.config.clone(),
-						tx.clone(),
-						preferred_peers.clone(),
-					);
+					monitor_peers(peers.clone(), p2p_server.config.clone(), tx.clone());
 
 					prev = Utc::now();
 					start_attempt = cmp::min(6, start_attempt + 1);
@@ -132,12 +127,7 @@ pub fn connect_and_monitor(
 		})
 }
 
-fn monitor_peers(
-	peers: Arc<p2p::Peers>,
-	config: p2p::P2PConfig,
-	tx: mpsc::Sender<PeerAddr>,
-	preferred_peers_list: Option<Vec<PeerAddr>>,
-) {
+fn monitor_peers(peers: Arc<p2p::Peers>, config: p2p::P2PConfig, tx: mpsc::Sender<PeerAddr>) {
 	// regularly check if we need to acquire more peers  and if so, gets
 	// them from db
 	let total_count = peers.all_peers().len();
@@ -204,16 +194,11 @@ fn monitor_peers(
 		connected_peers.push(p.info.addr)
 	}
 
-	// Attempt to connect to preferred peers if there is some
-	if let Some(preferred_peers) = preferred_peers_list {
-		for p in preferred_peers {
-			if !connected_peers.is_empty() {
-				if !connected_peers.contains(&p) {
-					tx.send(p).unwrap();
-				}
-			} else {
-				tx.send(p).unwrap();
-			}
+	// Attempt to connect to preferred peers if there is some, including the
+	// ones marked preferred at runtime
+	for p in peers.preferred_peers() {
+		if !connected_peers.contains(&p) {
+			tx.send(p).unwrap();
 		}
 	}
 
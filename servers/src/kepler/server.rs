@@ -19,6 +19,8 @@
 use std::fs;
 use std::fs::File;
 use std::io::prelude::*;
+use std::io::{BufReader, BufWriter};
+use std::net::SocketAddr;
 use std::path::Path;
 use std::sync::{mpsc, Arc};
 use std::{
@@ -35,16 +37,24 @@ use crate::chain::{self, SyncState, SyncStatus};
 use crate::common::adapters::{
 	ChainToPoolAndNetAdapter, NetToChainAdapter, PoolToChainAdapter, PoolToNetAdapter,
 };
+use crate::common::event_sink::EventSink;
 use crate::common::hooks::{init_chain_hooks, init_net_hooks};
 use crate::common::stats::{
-	ChainStats, DiffBlock, DiffStats, PeerStats, ServerStateInfo, ServerStats, TxStats,
+	BlockDetails, BlockStats, ChainStats, DiffBlock, DiffStats, PeerDetails, PeerStats,
+	PoolEntryStats, ServerStateInfo, ServerStats, TxStats,
 };
 use crate::common::types::{Error, ServerConfig, StratumServerConfig};
-use crate::core::core::hash::Hashed;
+use crate::core::core::hash::{Hash, Hashed, ZERO_HASH};
 use crate::core::core::verifier_cache::{LruVerifierCache, VerifierCache};
-use crate::core::ser::ProtocolVersion;
-use crate::core::{consensus, genesis, global, pow};
+use crate::core::core::{Block, BlockHeader};
+use crate::core::ser::{self, ProtocolVersion};
+use crate::core::{genesis, global, pow};
+use crate::kepler::merkle_proofs::MerkleProofRequests;
 use crate::kepler::{dandelion_monitor, seed, sync};
+use crate::mining::block_generator::BlockGenerator;
+use crate::mining::cpu_miner::CpuMiner;
+use crate::mining::local_coinbase::LocalCoinbase;
+use crate::mining::stratum_store::StratumStore;
 use crate::mining::stratumserver;
 use crate::mining::test_miner::Miner;
 use crate::p2p;
@@ -54,6 +64,9 @@ use crate::util::file::get_first_line;
 use crate::util::{RwLock, StopState};
 use kepler_util::logger::LogEntry;
 
+/// Number of recent blocks summarized in the server stats
+const RECENT_BLOCKS_COUNT: u64 = 50;
+
 /// Kepler server holding internal structures.
 pub struct Server {
 	/// server config
@@ -71,13 +84,19 @@ pub struct Server {
 	pub sync_state: Arc<SyncState>,
 	/// To be passed around to collect stats and info
 	state_info: ServerStateInfo,
+	/// Summaries of the recent blocks, along with the head they're as of
+	recent_blocks_cache: RwLock<(Hash, Vec<BlockStats>)>,
+	/// Per-login share accounting of the stratum server, if enabled
+	stratum_store: Option<Arc<StratumStore>>,
+	/// Builds the coinbase without a wallet listener, if configured
+	local_coinbase: Option<Arc<LocalCoinbase>>,
 	/// Stop flag
 	pub stop_state: Arc<StopState>,
 	/// Maintain a lock_file so we do not run multiple Kepler nodes from same dir.
 	lock_file: Arc<File>,
 	connect_thread: Option<JoinHandle<()>>,
 	sync_thread: JoinHandle<()>,
-	dandelion_thread: JoinHandle<()>,
+	dandelion_thread: Option<JoinHandle<()>>,
 }
 
 impl Server {
@@ -101,13 +120,17 @@ impl Server {
 			let enable_stratum_server = c.enable_stratum_server;
 			if let Some(s) = enable_stratum_server {
 				if s {
-					{
-						let mut stratum_stats = serv.state_info.stratum_stats.write();
-						stratum_stats.is_enabled = true;
+					// a stratum server not running as configured doesn't start
+					// the node either
+					if let Err(e) = serv.start_stratum_server(c.clone()) {
+						serv.stop();
+						return Err(e);
 					}
-					serv.start_stratum_server(c.clone());
 				}
 			}
+			if c.enable_cpu_miner {
+				serv.start_cpu_miner(c.clone());
+			}
 		}
 
 		if let Some(s) = enable_test_miner {
@@ -145,6 +168,72 @@ impl Server {
 		Ok(Arc::new(lock_file))
 	}
 
+	fn genesis(config: &ServerConfig) -> Result<Block, Error> {
+		let genesis = match config.chain_type {
+			global::ChainTypes::AutomatedTesting => pow::mine_genesis_block()?,
+			global::ChainTypes::UserTesting => pow::mine_genesis_block()?,
+			global::ChainTypes::Floonet => genesis::genesis_floo(),
+			global::ChainTypes::Mainnet => genesis::genesis_main(),
+			global::ChainTypes::Custom => match global::custom_chain().genesis_file {
+				Some(path) => Server::read_genesis(&path)?,
+				None => pow::mine_genesis_block()?,
+			},
+		};
+		Ok(genesis)
+	}
+
+	/// Reads the serialized genesis block of a custom chain.
+	fn read_genesis(path: &str) -> Result<Block, Error> {
+		let mut reader = BufReader::new(File::open(path)?);
+		let genesis: Block = ser::deserialize_default(&mut reader)
+			.map_err(|e| Error::Configuration(format!("Invalid genesis file {}: {}", path, e)))?;
+		if genesis.header.height != 0 {
+			return Err(Error::Configuration(format!(
+				"Invalid genesis file {}: block at height {}",
+				path, genesis.header.height
+			)));
+		}
+		Ok(genesis)
+	}
+
+	/// Imports the headers of a trusted header file, checking each of them
+	/// as if received from a peer.
+	fn import_headers(chain: &chain::Chain, path: &str) -> Result<(), Error> {
+		info!("Importing headers from {}", path);
+		let now = time::Instant::now();
+		let mut reader = BufReader::new(File::open(path)?);
+		let count = chain.import_headers(&mut reader)?;
+		info!(
+			"Imported {} headers from {} in {}s, header head now at {}",
+			count,
+			path,
+			now.elapsed().as_secs(),
+			chain.header_head()?.height
+		);
+		Ok(())
+	}
+
+	/// Writes the header chain of the node to a header file, for another node
+	/// to import with `headers_file`. The node can't be running meanwhile.
+	/// Returns the number of headers written.
+	pub fn export_headers(config: &ServerConfig, path: &str) -> Result<u64, Error> {
+		let _lock_file = Server::one_kepler_at_a_time(config)?;
+		let verifier_cache = Arc::new(RwLock::new(LruVerifierCache::new()));
+		let chain = chain::Chain::init(
+			config.db_root.clone(),
+			Arc::new(chain::types::NoopAdapter {}),
+			Server::genesis(config)?,
+			pow::verify_size,
+			verifier_cache,
+			config.archive_mode.unwrap_or(false),
+		)?;
+
+		let mut writer = BufWriter::new(File::create(path)?);
+		let count = chain.export_headers(&mut writer)?;
+		writer.flush()?;
+		Ok(count)
+	}
+
 	/// Instantiates a new server associated with the provided future reactor.
 	pub fn new(config: ServerConfig) -> Result<Server, Error> {
 		// Obtain our lock_file or fail immediately with an error.
@@ -163,8 +252,14 @@ impl Server {
 		// We cache rangeproof verification and kernel signature verification.
 		let verifier_cache = Arc::new(RwLock::new(LruVerifierCache::new()));
 
+		let event_sink = EventSink::from_config(&config.event_sink_config)?;
+		let chain_hooks = init_chain_hooks(&config, &event_sink)?;
+
 		let pool_adapter = Arc::new(PoolToChainAdapter::new());
-		let pool_net_adapter = Arc::new(PoolToNetAdapter::new(config.dandelion_config.clone()));
+		let pool_net_adapter = Arc::new(PoolToNetAdapter::new(
+			config.dandelion_config.clone(),
+			chain_hooks.clone(),
+		));
 		let tx_pool = Arc::new(RwLock::new(pool::TransactionPool::new(
 			config.pool_config.clone(),
 			pool_adapter.clone(),
@@ -174,17 +269,10 @@ impl Server {
 
 		let sync_state = Arc::new(SyncState::new());
 
-		let chain_adapter = Arc::new(ChainToPoolAndNetAdapter::new(
-			tx_pool.clone(),
-			init_chain_hooks(&config),
-		));
+		let chain_adapter = Arc::new(ChainToPoolAndNetAdapter::new(tx_pool.clone(), chain_hooks));
+		sync_state.init_adapter(chain_adapter.clone());
 
-		let genesis = match config.chain_type {
-			global::ChainTypes::AutomatedTesting => pow::mine_genesis_block().unwrap(),
-			global::ChainTypes::UserTesting => pow::mine_genesis_block().unwrap(),
-			global::ChainTypes::Floonet => genesis::genesis_floo(),
-			global::ChainTypes::Mainnet => genesis::genesis_main(),
-		};
+		let genesis = Server::genesis(&config)?;
 
 		info!("Starting server, genesis block: {}", genesis.hash());
 
@@ -199,18 +287,34 @@ impl Server {
 
 		pool_adapter.set_chain(shared_chain.clone());
 
+		// Bulk load the header chain before syncing the rest with our peers
+		if let Some(ref path) = config.headers_file {
+			Server::import_headers(&shared_chain, path)?;
+		}
+
+		// Only following the header chain, we can't serve much to our peers
+		let header_only = config.header_only.unwrap_or(false);
+		let capabilities = if header_only {
+			info!("Running header only, no block bodies or txhashset");
+			p2p::Capabilities::HEADER_HIST | p2p::Capabilities::PEER_LIST
+		} else {
+			config.p2p_config.capabilities
+		};
+
+		let merkle_proofs = Arc::new(MerkleProofRequests::new(shared_chain.clone()));
 		let net_adapter = Arc::new(NetToChainAdapter::new(
 			sync_state.clone(),
 			shared_chain.clone(),
 			tx_pool.clone(),
 			verifier_cache.clone(),
 			config.clone(),
-			init_net_hooks(&config),
+			init_net_hooks(&config, &event_sink)?,
+			merkle_proofs.clone(),
 		));
 
 		let p2p_server = Arc::new(p2p::Server::new(
 			&config.db_root,
-			config.p2p_config.capabilities,
+			capabilities,
 			config.p2p_config.clone(),
 			net_adapter.clone(),
 			genesis.hash(),
@@ -221,6 +325,7 @@ impl Server {
 		chain_adapter.init(p2p_server.peers.clone());
 		pool_net_adapter.init(p2p_server.peers.clone());
 		net_adapter.init(p2p_server.peers.clone());
+		merkle_proofs.init(p2p_server.peers.clone());
 
 		let mut connect_thread = None;
 
@@ -258,10 +363,15 @@ impl Server {
 		let skip_sync_wait = config.skip_sync_wait.unwrap_or(false);
 		sync_state.update(SyncStatus::AwaitingPeers(!skip_sync_wait));
 
+		let state_info = ServerStateInfo::default();
+
 		let sync_thread = sync::run_sync(
 			sync_state.clone(),
 			p2p_server.peers.clone(),
 			shared_chain.clone(),
+			state_info.body_sync_stats.clone(),
+			config.pibd_sync.unwrap_or(false),
+			header_only,
 			stop_state.clone(),
 		)?;
 
@@ -291,26 +401,75 @@ impl Server {
 			}
 		};
 
+		let stratum_enabled = config
+			.stratum_mining_config
+			.as_ref()
+			.and_then(|c| c.enable_stratum_server)
+			.unwrap_or(false);
+		let stratum_store = if stratum_enabled {
+			Some(Arc::new(StratumStore::new(&config.db_root)?))
+		} else {
+			None
+		};
+		let local_coinbase = match config
+			.stratum_mining_config
+			.as_ref()
+			.and_then(|c| c.coinbase_key_file.clone())
+		{
+			Some(key_file) => Some(Arc::new(LocalCoinbase::new(&key_file, &config.db_root)?)),
+			None => None,
+		};
+
+		let block_generator = if !header_only && BlockGenerator::is_available() {
+			Some(Arc::new(BlockGenerator::new(
+				shared_chain.clone(),
+				tx_pool.clone(),
+				verifier_cache.clone(),
+				local_coinbase.clone(),
+			)))
+		} else {
+			None
+		};
+
 		// TODO fix API shutdown and join this thread
 		api::node_apis(
 			&config.api_http_addr,
 			shared_chain.clone(),
 			tx_pool.clone(),
-			p2p_server.peers.clone(),
+			p2p_server.clone(),
 			sync_state.clone(),
 			api_secret.clone(),
 			foreign_api_secret.clone(),
 			tls_conf.clone(),
+			stratum_store
+				.clone()
+				.map(|s| s as Arc<dyn api::StratumAccounts>),
+			local_coinbase
+				.clone()
+				.map(|c| c as Arc<dyn api::CoinbaseKeys>),
+			if header_only {
+				Some(merkle_proofs as Arc<dyn api::MerkleProofs>)
+			} else {
+				None
+			},
+			// Mines blocks on demand through the owner api, on testing and
+			// custom chains
+			block_generator.map(|g| g as Arc<dyn api::BlockGeneration>),
 		)?;
 
-		info!("Starting dandelion monitor: {}", &config.api_http_addr);
-		let dandelion_thread = dandelion_monitor::monitor_transactions(
-			config.dandelion_config.clone(),
-			tx_pool.clone(),
-			pool_net_adapter.clone(),
-			verifier_cache.clone(),
-			stop_state.clone(),
-		)?;
+		// No transaction ever gets to the pool when running header only
+		let dandelion_thread = if header_only {
+			None
+		} else {
+			info!("Starting dandelion monitor: {}", &config.api_http_addr);
+			Some(dandelion_monitor::monitor_transactions(
+				config.dandelion_config.clone(),
+				tx_pool.clone(),
+				pool_net_adapter.clone(),
+				verifier_cache.clone(),
+				stop_state.clone(),
+			)?)
+		};
 
 		warn!("Kepler server started.");
 		Ok(Server {
@@ -320,9 +479,10 @@ impl Server {
 			tx_pool,
 			verifier_cache,
 			sync_state,
-			state_info: ServerStateInfo {
-				..Default::default()
-			},
+			state_info,
+			recent_blocks_cache: RwLock::new((ZERO_HASH, vec![])),
+			stratum_store,
+			local_coinbase,
 			stop_state,
 			lock_file,
 			connect_thread,
@@ -350,8 +510,14 @@ impl Server {
 		self.p2p.peers.peer_count()
 	}
 
-	/// Start a minimal "stratum" mining service on a separate thread
-	pub fn start_stratum_server(&self, config: StratumServerConfig) {
+	/// Start a minimal "stratum" mining service on a separate thread, unless
+	/// we're running header only. Fails if its credentials file or TLS
+	/// certificate can't be loaded.
+	pub fn start_stratum_server(&self, config: StratumServerConfig) -> Result<(), Error> {
+		if self.header_only() {
+			warn!("The stratum server can't run on a header only node, not starting it");
+			return Ok(());
+		}
 		let edge_bits = global::min_edge_bits();
 		let proof_size = global::proofsize();
 		let sync_state = self.sync_state.clone();
@@ -362,22 +528,66 @@ impl Server {
 			self.tx_pool.clone(),
 			self.verifier_cache.clone(),
 			self.state_info.stratum_stats.clone(),
-		);
+			self.stratum_store.clone(),
+			self.local_coinbase.clone(),
+			self.stop_state.clone(),
+		)?;
+		{
+			let mut stratum_stats = self.state_info.stratum_stats.write();
+			stratum_stats.is_enabled = true;
+		}
 		let _ = thread::Builder::new()
 			.name("stratum_server".to_string())
 			.spawn(move || {
 				stratum_server.run_loop(edge_bits as u32, proof_size, sync_state);
 			});
+		Ok(())
+	}
+
+	/// Start the multithreaded CPU miner on a separate thread, unless we're
+	/// on a production chain or running header only
+	pub fn start_cpu_miner(&self, config: StratumServerConfig) {
+		if global::is_production_mode() {
+			warn!("The CPU miner only runs on usernet and custom chains, not starting it");
+			return;
+		}
+		if self.header_only() {
+			warn!("The CPU miner can't run on a header only node, not starting it");
+			return;
+		}
+		{
+			let mut cpu_miner_stats = self.state_info.cpu_miner_stats.write();
+			cpu_miner_stats.is_enabled = true;
+		}
+		let miner = CpuMiner::new(
+			config,
+			self.chain.clone(),
+			self.tx_pool.clone(),
+			self.verifier_cache.clone(),
+			self.stop_state.clone(),
+			self.sync_state.clone(),
+			self.local_coinbase.clone(),
+		);
+		let stats = self.state_info.cpu_miner_stats.clone();
+		let _ = thread::Builder::new()
+			.name("cpu_miner".to_string())
+			.spawn(move || miner.run_loop(stats));
 	}
 
 	/// Start mining for blocks internally on a separate thread. Relies on
-	/// internal miner, and should only be used for automated testing. Burns
-	/// reward if wallet_listener_url is 'None'
+	/// internal miner, and should only be used for automated testing. Builds
+	/// the coinbase locally if a coinbase key is configured, otherwise burns
+	/// reward if wallet_listener_url is 'None'. Not started when running
+	/// header only.
 	pub fn start_test_miner(
 		&self,
 		wallet_listener_url: Option<String>,
 		stop_state: Arc<StopState>,
 	) {
+		if self.header_only() {
+			warn!("The test miner can't run on a header only node, not starting it");
+			return;
+		}
 		info!("start_test_miner - start",);
 		let sync_state = self.sync_state.clone();
 		let config_wallet_url = match wallet_listener_url.clone() {
@@ -388,10 +598,21 @@ impl Server {
 		let config = StratumServerConfig {
 			attempt_time_per_block: 60,
 			burn_reward: false,
+			coinbase_key_file: None,
+			stratum_credentials_file: None,
 			enable_stratum_server: None,
 			stratum_server_addr: None,
+			stratum_server_tls_addr: None,
+			tls_certificate_file: None,
+			tls_certificate_key: None,
 			wallet_listener_url: config_wallet_url,
 			minimum_share_difficulty: 1,
+			enable_vardiff: false,
+			vardiff_target_shares_per_minute: 0.0,
+			vardiff_retarget_time: 0,
+			enable_cpu_miner: false,
+			cpu_miner_threads: 1,
+			cpu_miner_edge_bits: None,
 		};
 
 		let mut miner = Miner::new(
@@ -401,6 +622,7 @@ impl Server {
 			self.verifier_cache.clone(),
 			stop_state,
 			sync_state,
+			self.local_coinbase.clone(),
 		);
 		miner.set_debug_output_id(format!("Port {}", self.config.p2p_config.port));
 		let _ = thread::Builder::new()
@@ -408,6 +630,12 @@ impl Server {
 			.spawn(move || miner.run_loop(wallet_listener_url));
 	}
 
+	/// Whether the node only follows the header chain, with no block bodies
+	/// to mine on top of
+	fn header_only(&self) -> bool {
+		self.config.header_only.unwrap_or(false)
+	}
+
 	/// The chain head
 	pub fn head(&self) -> Result<chain::Tip, Error> {
 		self.chain.head().map_err(|e| e.into())
@@ -423,57 +651,151 @@ impl Server {
 		ProtocolVersion::local()
 	}
 
+	/// Network difficulty a block was mined at, from the total difficulty of
+	/// its header and of the previous one
+	fn block_difficulty(&self, header: &BlockHeader) -> Result<u64, Error> {
+		if header.height == 0 {
+			return Ok(header.total_difficulty().to_num());
+		}
+		let prev = self.chain.get_previous_header(header)?;
+		Ok((header.total_difficulty() - prev.total_difficulty()).to_num())
+	}
+
+	/// Summaries of the most recent blocks, latest first, only built again
+	/// once the head moves. Blocks whose body isn't available are skipped.
+	fn recent_blocks(&self, count: u64) -> Vec<BlockStats> {
+		let head = match self.chain.head() {
+			Ok(head) => head,
+			Err(_) => return vec![],
+		};
+		{
+			let cache = self.recent_blocks_cache.read();
+			if cache.0 == head.last_block_h {
+				return cache.1.clone();
+			}
+		}
+
+		let mut blocks = vec![];
+		let mut hash = head.last_block_h;
+		for _ in 0..count {
+			let header = match self.chain.get_block_header(&hash) {
+				Ok(header) => header,
+				Err(_) => break,
+			};
+			if let (Ok(block), Ok(difficulty)) =
+				(self.chain.get_block(&hash), self.block_difficulty(&header))
+			{
+				blocks.push(BlockStats::from_block(&block, difficulty));
+			}
+			if header.height == 0 {
+				break;
+			}
+			hash = header.prev_hash;
+		}
+		*self.recent_blocks_cache.write() = (head.last_block_h, blocks.clone());
+		blocks
+	}
+
+	/// Full content of a block, looked up by height or by hash
+	pub fn get_block_details(&self, height_or_hash: &str) -> Result<BlockDetails, Error> {
+		let height_or_hash = height_or_hash.trim();
+		let hash = match height_or_hash.parse::<u64>() {
+			Ok(height) => self.chain.get_header_by_height(height)?.hash(),
+			Err(_) => Hash::from_hex(height_or_hash).map_err(|_| {
+				Error::ArgumentError(format!("Invalid height or hash {}", height_or_hash))
+			})?,
+		};
+		let block = self.chain.get_block(&hash)?;
+		let difficulty = self.block_difficulty(&block.header)?;
+		Ok(BlockDetails::from_block(&block, difficulty))
+	}
+
+	/// Difficulty data of the last `count` blocks of the header chain, oldest
+	/// first. Fewer are returned close to genesis.
+	pub fn get_diff_blocks(&self, count: u64) -> Result<Vec<DiffBlock>, Error> {
+		let tip_height = self.head()?.height;
+		let mut headers: Vec<_> = self
+			.chain
+			.difficulty_iter()?
+			.take(count as usize + 1)
+			.collect();
+		headers.reverse();
+		Ok(DiffBlock::from_header_infos(&headers, tip_height))
+	}
+
+	/// Stored data, live stats and ban history of a known peer
+	pub fn get_peer_details(&self, addr: &str) -> Result<PeerDetails, Error> {
+		let addr = parse_peer_addr(addr)?;
+		let data = self.p2p.peers.get_peer(addr)?;
+		let stats = self
+			.p2p
+			.peers
+			.get_connected_peer(addr)
+			.map(|p| PeerStats::from_peer(&p));
+		let ban_history = self.p2p.peers.ban_history(addr)?;
+		Ok(PeerDetails {
+			data,
+			stats,
+			ban_history,
+		})
+	}
+
+	/// All the peers currently banned
+	pub fn banned_peers(&self) -> Vec<p2p::PeerData> {
+		self.p2p
+			.peers
+			.all_peers()
+			.into_iter()
+			.filter(|p| p.flags == p2p::State::Banned)
+			.collect()
+	}
+
+	/// Manually bans a peer, disconnecting it if we're connected to it
+	pub fn ban_peer(&self, addr: &str) -> Result<(), Error> {
+		let addr = parse_peer_addr(addr)?;
+		match self
+			.p2p
+			.peers
+			.ban_peer(addr, p2p::types::ReasonForBan::ManualBan)
+		{
+			// The ban is still recorded when we aren't connected to the peer
+			Ok(()) | Err(p2p::Error::PeerNotFound) => Ok(()),
+			Err(e) => Err(e.into()),
+		}
+	}
+
+	/// Lifts the ban of a peer
+	pub fn unban_peer(&self, addr: &str) -> Result<(), Error> {
+		let addr = parse_peer_addr(addr)?;
+		self.p2p.peers.unban_peer(addr)?;
+		Ok(())
+	}
+
+	/// Disconnects a peer without banning it
+	pub fn disconnect_peer(&self, addr: &str) -> Result<(), Error> {
+		let addr = parse_peer_addr(addr)?;
+		self.p2p.peers.disconnect_peer(addr)?;
+		Ok(())
+	}
+
 	/// Returns a set of stats about this server. This and the ServerStats
 	/// structure
 	/// can be updated over time to include any information needed by tests or
 	/// other consumers
 	pub fn get_server_stats(&self) -> Result<ServerStats, Error> {
 		let stratum_stats = self.state_info.stratum_stats.read().clone();
+		let cpu_miner_stats = self.state_info.cpu_miner_stats.read().clone();
+		let body_sync_stats = self.state_info.body_sync_stats.read().clone();
 
 		// Fill out stats on our current difficulty calculation
 		// TODO: check the overhead of calculating this again isn't too much
 		// could return it from next_difficulty, but would rather keep consensus
 		// code clean. This may be handy for testing but not really needed
 		// for release
-		let diff_stats = {
-			let last_blocks: Vec<consensus::HeaderInfo> =
-				global::difficulty_data_to_vector(self.chain.difficulty_iter()?)
-					.into_iter()
-					.collect();
-
-			let tip_height = self.head()?.height as i64;
-			let mut height = tip_height as i64 - last_blocks.len() as i64 + 1;
-
-			let diff_entries: Vec<DiffBlock> = last_blocks
-				.windows(2)
-				.map(|pair| {
-					let prev = &pair[0];
-					let next = &pair[1];
-
-					height += 1;
-
-					DiffBlock {
-						block_height: height,
-						block_hash: next.block_hash,
-						difficulty: next.difficulty.to_num(),
-						time: next.timestamp,
-						duration: next.timestamp - prev.timestamp,
-						secondary_scaling: next.secondary_scaling,
-						is_secondary: next.is_secondary,
-					}
-				})
-				.collect();
-
-			let block_time_sum = diff_entries.iter().fold(0, |sum, t| sum + t.duration);
-			let block_diff_sum = diff_entries.iter().fold(0, |sum, d| sum + d.difficulty);
-			DiffStats {
-				height: height as u64,
-				last_blocks: diff_entries,
-				average_block_time: block_time_sum / (consensus::DIFFICULTY_ADJUST_WINDOW - 1),
-				average_difficulty: block_diff_sum / (consensus::DIFFICULTY_ADJUST_WINDOW - 1),
-				window_size: consensus::DIFFICULTY_ADJUST_WINDOW,
-			}
-		};
+		let diff_stats = DiffStats::from_window(
+			global::difficulty_data_to_vector(self.chain.difficulty_iter()?),
+			self.head()?.height,
+		);
 
 		let peer_stats = self
 			.p2p
@@ -492,6 +814,18 @@ impl Server {
 			tx_pool_kernels: pool.txpool.kernel_count(),
 			stem_pool_size: pool.stempool.size(),
 			stem_pool_kernels: pool.stempool.kernel_count(),
+			entries: pool
+				.txpool
+				.entries
+				.iter()
+				.map(|e| PoolEntryStats::from_entry(e, false))
+				.chain(
+					pool.stempool
+						.entries
+						.iter()
+						.map(|e| PoolEntryStats::from_entry(e, true)),
+				)
+				.collect(),
 		});
 
 		let head = self.chain.head_header()?;
@@ -514,6 +848,8 @@ impl Server {
 			_ => None,
 		};
 
+		let block_stats = self.recent_blocks(RECENT_BLOCKS_COUNT);
+
 		let disk_usage_bytes = WalkDir::new(&self.config.db_root)
 			.min_depth(1)
 			.max_depth(3)
@@ -532,9 +868,12 @@ impl Server {
 			sync_status: self.sync_state.status(),
 			disk_usage_gb: disk_usage_gb,
 			stratum_stats: stratum_stats,
+			cpu_miner_stats,
 			peer_stats: peer_stats,
 			diff_stats: diff_stats,
 			tx_stats: tx_stats,
+			block_stats: block_stats,
+			body_sync_stats,
 		})
 	}
 
@@ -558,9 +897,11 @@ impl Server {
 				Ok(_) => info!("sync thread stopped"),
 			}
 
-			match self.dandelion_thread.join() {
-				Err(e) => error!("failed to join to dandelion_monitor thread: {:?}", e),
-				Ok(_) => info!("dandelion_monitor thread stopped"),
+			if let Some(dandelion_thread) = self.dandelion_thread {
+				match dandelion_thread.join() {
+					Err(e) => error!("failed to join to dandelion_monitor thread: {:?}", e),
+					Ok(_) => info!("dandelion_monitor thread stopped"),
+				}
 			}
 		}
 		// this call is blocking and makes sure all peers stop, however
@@ -589,3 +930,52 @@ impl Server {
 		info!("stop_test_miner - stop",);
 	}
 }
+
+/// Parses a peer address as entered by a user
+fn parse_peer_addr(addr: &str) -> Result<PeerAddr, Error> {
+	addr.trim()
+		.parse::<SocketAddr>()
+		.map(PeerAddr)
+		.map_err(|_| Error::ArgumentError(format!("Invalid peer address {}", addr)))
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	#[test]
+	fn header_only_does_not_mine() {
+		global::set_mining_mode(global::ChainTypes::AutomatedTesting);
+		let db_root = ".kepler_header_only_server";
+		let _ = fs::remove_dir_all(db_root);
+		{
+			let mut config = ServerConfig::default();
+			config.db_root = db_root.to_owned();
+			config.api_http_addr = "127.0.0.1:23413".to_owned();
+			config.api_secret_path = None;
+			config.foreign_api_secret_path = None;
+			config.chain_type = global::ChainTypes::AutomatedTesting;
+			config.skip_sync_wait = Some(true);
+			config.header_only = Some(true);
+			config.p2p_config.port = 23414;
+			config.p2p_config.seeding_type = p2p::Seeding::Programmatic;
+			let mut mining_config = StratumServerConfig::default();
+			mining_config.stratum_server_addr = Some("127.0.0.1:23416".to_owned());
+			mining_config.burn_reward = true;
+			mining_config.enable_cpu_miner = true;
+
+			let server = Server::new(config).unwrap();
+			assert!(server.dandelion_thread.is_none());
+			server.start_stratum_server(mining_config.clone()).unwrap();
+			server.start_cpu_miner(mining_config);
+			server.start_test_miner(None, server.stop_state.clone());
+			thread::sleep(time::Duration::from_secs(3));
+
+			assert!(!server.state_info.stratum_stats.read().is_enabled);
+			assert!(!server.state_info.cpu_miner_stats.read().is_enabled);
+			assert_eq!(server.head().unwrap().height, 0);
+			server.stop();
+		}
+		let _ = fs::remove_dir_all(db_root);
+	}
+}
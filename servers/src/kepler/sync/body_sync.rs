@@ -12,24 +12,143 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+//! Full block download, spread over all the peers advertising more work.
+//! Blocks are requested within a window starting right above our body head,
+//! each peer getting as many requests as its measured throughput allows.
+//! Requests left unanswered are reassigned to another peer, sooner for the
+//! block right above the head as it holds back the whole window.
+
 use chrono::prelude::{DateTime, Utc};
 use chrono::Duration;
 use std::cmp;
+use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
 
 use crate::chain::{self, SyncState, SyncStatus};
+use crate::common::stats::{BodySyncStats, PeerDownloadStats};
 use crate::core::core::hash::Hash;
-use crate::p2p;
+use crate::p2p::{self, Peer, PeerAddr};
+use crate::util::RwLock;
+
+/// Number of blocks above the body head we download ahead. Blocks received
+/// out of order wait in the orphan pool, so this stays under its size.
+const DOWNLOAD_WINDOW: u64 = 128;
+
+/// Requests sent to a peer before we know its throughput
+const INITIAL_PEER_REQUESTS: usize = 8;
+
+/// Bounds of the requests sent to a peer at once
+const MIN_PEER_REQUESTS: usize = 2;
+const MAX_PEER_REQUESTS: usize = 32;
+
+/// Seconds of download kept in flight with each peer, given its throughput
+const PEER_REQUESTS_SECS: f64 = 4.0;
+
+/// Delay after which an unanswered request goes to another peer
+const REQUEST_TIMEOUT_SECS: i64 = 10;
+
+/// Shorter delay for the block right above the body head
+const STALL_TIMEOUT_SECS: i64 = 3;
+
+/// Time between two scheduling rounds
+const ROUND_INTERVAL_MS: i64 = 500;
+
+/// Time between two throughput samples of a peer
+const SAMPLE_INTERVAL_SECS: i64 = 2;
+
+/// Weight of the last sample in the throughput estimate
+const SAMPLE_WEIGHT: f64 = 0.3;
+
+/// A block requested and not received yet
+struct BlockRequest {
+	peer: PeerAddr,
+	requested_at: DateTime<Utc>,
+	/// Peers the block was requested from before, which didn't deliver it
+	/// in time
+	failed_peers: Vec<PeerAddr>,
+}
+
+/// Download tracking of a single peer
+struct PeerDownload {
+	requested: u64,
+	received: u64,
+	reassigned: u64,
+	/// Estimated throughput in blocks per second, once sampled
+	rate: Option<f64>,
+	/// Blocks received since the last sample
+	sample_received: u64,
+	/// Whether the peer had requests in flight since the last sample, a
+	/// sample of an idle peer telling nothing about its throughput
+	sample_busy: bool,
+	sampled_at: DateTime<Utc>,
+}
+
+impl PeerDownload {
+	fn new(now: DateTime<Utc>) -> PeerDownload {
+		PeerDownload {
+			requested: 0,
+			received: 0,
+			reassigned: 0,
+			rate: None,
+			sample_received: 0,
+			sample_busy: false,
+			sampled_at: now,
+		}
+	}
+
+	/// Number of requests the peer should have in flight
+	fn capacity(&self) -> usize {
+		match self.rate {
+			None => INITIAL_PEER_REQUESTS,
+			Some(rate) => {
+				let wanted = (rate * PEER_REQUESTS_SECS).ceil() as usize;
+				cmp::max(MIN_PEER_REQUESTS, cmp::min(MAX_PEER_REQUESTS, wanted))
+			}
+		}
+	}
+
+	fn delivered(&mut self) {
+		self.received += 1;
+		self.sample_received += 1;
+	}
+
+	/// A request timed out, the throughput estimate is halved
+	fn timed_out(&mut self) {
+		self.reassigned += 1;
+		self.rate = Some(self.rate.unwrap_or(0.0) / 2.0);
+	}
+
+	/// Folds the blocks received since the last sample into the throughput
+	/// estimate, once the sample interval elapsed
+	fn sample(&mut self, now: DateTime<Utc>, busy: bool) {
+		self.sample_busy |= busy;
+		let elapsed = now - self.sampled_at;
+		if elapsed < Duration::seconds(SAMPLE_INTERVAL_SECS) {
+			return;
+		}
+		if self.sample_busy {
+			let rate = self.sample_received as f64 * 1000.0 / elapsed.num_milliseconds() as f64;
+			self.rate = Some(match self.rate {
+				Some(prev) => prev * (1.0 - SAMPLE_WEIGHT) + rate * SAMPLE_WEIGHT,
+				None => rate,
+			});
+		}
+		self.sample_received = 0;
+		self.sample_busy = busy;
+		self.sampled_at = now;
+	}
+}
 
 pub struct BodySync {
 	chain: Arc<chain::Chain>,
 	peers: Arc<p2p::Peers>,
 	sync_state: Arc<SyncState>,
 
-	blocks_requested: u64,
+	in_flight: HashMap<Hash, BlockRequest>,
+	downloads: HashMap<PeerAddr, PeerDownload>,
+	next_round: DateTime<Utc>,
 
-	receive_timeout: DateTime<Utc>,
-	prev_blocks_received: u64,
+	stats: Arc<RwLock<BodySyncStats>>,
 }
 
 impl BodySync {
@@ -37,14 +156,16 @@ impl BodySync {
 		sync_state: Arc<SyncState>,
 		peers: Arc<p2p::Peers>,
 		chain: Arc<chain::Chain>,
+		stats: Arc<RwLock<BodySyncStats>>,
 	) -> BodySync {
 		BodySync {
 			sync_state,
 			peers,
 			chain,
-			blocks_requested: 0,
-			receive_timeout: Utc::now(),
-			prev_blocks_received: 0,
+			in_flight: HashMap::new(),
+			downloads: HashMap::new(),
+			next_round: Utc::now(),
+			stats,
 		}
 	}
 
@@ -55,17 +176,20 @@ impl BodySync {
 		head: &chain::Tip,
 		highest_height: u64,
 	) -> Result<bool, chain::Error> {
-		// run the body_sync every 5s
-		if self.body_sync_due()? {
-			if self.body_sync()? {
-				return Ok(true);
-			}
+		let now = Utc::now();
+		if now < self.next_round {
+			return Ok(false);
+		}
+		self.next_round = now + Duration::milliseconds(ROUND_INTERVAL_MS);
 
-			self.sync_state.update(SyncStatus::BodySync {
-				current_height: head.height,
-				highest_height: highest_height,
-			});
+		if self.body_sync()? {
+			return Ok(true);
 		}
+
+		self.sync_state.update(SyncStatus::BodySync {
+			current_height: head.height,
+			highest_height: highest_height,
+		});
 		Ok(false)
 	}
 
@@ -99,97 +223,265 @@ impl BodySync {
 
 		hashes.reverse();
 
+		let now = Utc::now();
+		let body_head = self.chain.head()?;
+		let header_head = self.chain.header_head()?;
 		let peers = self.peers.more_work_peers()?;
 
-		// if we have 5 peers to sync from then ask for 50 blocks total (peer_count *
-		// 10) max will be 80 if all 8 peers are advertising more work
-		// also if the chain is already saturated with orphans, throttle
-		let block_count = cmp::min(
-			cmp::min(100, peers.len() * 10),
-			chain::MAX_ORPHAN_SIZE.saturating_sub(self.chain.orphans_len()) + 1,
-		);
+		// The hashes go from the fork point up to the header head, only the
+		// ones within the window are considered
+		let first_height = (header_head.height + 1).saturating_sub(hashes.len() as u64);
+		let window_end = body_head.height + DOWNLOAD_WINDOW;
+		let window: Vec<(u64, Hash)> = hashes
+			.into_iter()
+			.enumerate()
+			.map(|(i, h)| (first_height + i as u64, h))
+			.take_while(|(height, _)| *height <= window_end)
+			.collect();
 
-		let hashes_to_get = hashes
-			.iter()
-			.filter(|x| {
-				// only ask for blocks that we have not yet processed
-				// either successfully stored or in our orphan list
-				!self.chain.get_block(x).is_ok() && !self.chain.is_orphan(x)
-			})
-			.take(block_count)
-			.collect::<Vec<_>>();
+		// only ask for blocks that we have not yet processed
+		// either successfully stored or in our orphan list
+		let missing: Vec<(u64, Hash)> = window
+			.into_iter()
+			.filter(|(_, h)| !self.block_received(h))
+			.collect();
 
-		if hashes_to_get.len() > 0 {
-			let body_head = self.chain.head()?;
-			let header_head = self.chain.header_head()?;
+		self.resolve_requests(&missing);
+		self.sample_peers(&peers, now);
+		let retries = self.reassign_stalled(&peers, &missing, now);
+		self.request_missing(&peers, &missing, retries, now);
+		self.update_stats(&missing);
 
-			debug!(
-				"block_sync: {}/{} requesting blocks {:?} from {} peers",
-				body_head.height,
-				header_head.height,
-				hashes_to_get,
-				peers.len(),
-			);
+		Ok(false)
+	}
+
+	/// Whether the block was either successfully stored or is in our orphan
+	/// list
+	fn block_received(&self, h: &Hash) -> bool {
+		self.chain.block_exists(*h).unwrap_or(false) || self.chain.is_orphan(h)
+	}
 
-			// reinitialize download tracking state
-			self.blocks_requested = 0;
-			self.receive_timeout = Utc::now() + Duration::seconds(6);
-
-			let mut peers_iter = peers.iter().cycle();
-			for hash in hashes_to_get.clone() {
-				if let Some(peer) = peers_iter.next() {
-					if let Err(e) = peer.send_block_request(*hash, chain::Options::SYNC) {
-						debug!("Skipped request to {}: {:?}", peer.info.addr, e);
-						peer.stop();
-					} else {
-						self.blocks_requested += 1;
-					}
+	/// Drops the requests of blocks we don't miss anymore, crediting the
+	/// peers that delivered them
+	fn resolve_requests(&mut self, missing: &[(u64, Hash)]) {
+		let missing: HashSet<&Hash> = missing.iter().map(|(_, h)| h).collect();
+		let done: Vec<Hash> = self
+			.in_flight
+			.keys()
+			.filter(|h| !missing.contains(h))
+			.cloned()
+			.collect();
+		for h in done {
+			let req = match self.in_flight.remove(&h) {
+				Some(req) => req,
+				None => continue,
+			};
+			// the block may also not be needed anymore, after a fork switch
+			if self.block_received(&h) {
+				if let Some(download) = self.downloads.get_mut(&req.peer) {
+					download.delivered();
 				}
 			}
 		}
-		return Ok(false);
 	}
 
-	// Should we run block body sync and ask for more full blocks?
-	fn body_sync_due(&mut self) -> Result<bool, chain::Error> {
-		let blocks_received = self.blocks_received()?;
+	/// Updates the throughput estimates of the peers we're syncing from and
+	/// forgets about the ones gone
+	fn sample_peers(&mut self, peers: &[Arc<Peer>], now: DateTime<Utc>) {
+		let mut busy: HashSet<PeerAddr> = HashSet::new();
+		for req in self.in_flight.values() {
+			busy.insert(req.peer);
+		}
+		let connected: HashSet<PeerAddr> = peers.iter().map(|p| p.info.addr).collect();
+		self.downloads.retain(|addr, _| connected.contains(addr));
+		for addr in connected {
+			self.downloads
+				.entry(addr)
+				.or_insert_with(|| PeerDownload::new(now))
+				.sample(now, busy.contains(&addr));
+		}
+	}
 
-		// some blocks have been requested
-		if self.blocks_requested > 0 {
-			// but none received since timeout, ask again
-			let timeout = Utc::now() > self.receive_timeout;
-			if timeout && blocks_received <= self.prev_blocks_received {
+	/// Takes back the requests timed out or sent to peers gone, returning
+	/// the peers that failed each of them
+	fn reassign_stalled(
+		&mut self,
+		peers: &[Arc<Peer>],
+		missing: &[(u64, Hash)],
+		now: DateTime<Utc>,
+	) -> HashMap<Hash, Vec<PeerAddr>> {
+		let connected: HashSet<PeerAddr> = peers.iter().map(|p| p.info.addr).collect();
+		let mut retries = HashMap::new();
+		for (i, (height, h)) in missing.iter().enumerate() {
+			let timeout = if i == 0 {
+				Duration::seconds(STALL_TIMEOUT_SECS)
+			} else {
+				Duration::seconds(REQUEST_TIMEOUT_SECS)
+			};
+			let stalled = match self.in_flight.get(h) {
+				Some(req) => !connected.contains(&req.peer) || now - req.requested_at > timeout,
+				None => false,
+			};
+			if !stalled {
+				continue;
+			}
+			if let Some(mut req) = self.in_flight.remove(h) {
 				debug!(
-					"body_sync: expecting {} more blocks and none received for a while",
-					self.blocks_requested,
+					"body_sync: block {} at {} not received from {}, reassigning",
+					h, height, req.peer
 				);
-				return Ok(true);
+				if let Some(download) = self.downloads.get_mut(&req.peer) {
+					download.timed_out();
+				}
+				req.failed_peers.push(req.peer);
+				retries.insert(*h, req.failed_peers);
 			}
 		}
+		retries
+	}
 
-		if blocks_received > self.prev_blocks_received {
-			// some received, update for next check
-			self.receive_timeout = Utc::now() + Duration::seconds(1);
-			self.blocks_requested = self
-				.blocks_requested
-				.saturating_sub(blocks_received - self.prev_blocks_received);
-			self.prev_blocks_received = blocks_received;
+	/// Requests the missing blocks not in flight, lowest first, each from
+	/// the peer with the most room left that didn't fail it already
+	fn request_missing(
+		&mut self,
+		peers: &[Arc<Peer>],
+		missing: &[(u64, Hash)],
+		mut retries: HashMap<Hash, Vec<PeerAddr>>,
+		now: DateTime<Utc>,
+	) {
+		let mut load: HashMap<PeerAddr, usize> = HashMap::new();
+		for req in self.in_flight.values() {
+			*load.entry(req.peer).or_insert(0) += 1;
 		}
+		let mut available: Vec<&Arc<Peer>> = peers.iter().collect();
+		let mut requested = 0;
 
-		// off by one to account for broadcast adding a couple orphans
-		if self.blocks_requested < 2 {
-			// no pending block requests, ask more
-			debug!("body_sync: no pending block request, asking more");
-			return Ok(true);
+		for (_, h) in missing {
+			if self.in_flight.contains_key(h) {
+				continue;
+			}
+			let failed_peers = retries.remove(h).unwrap_or_default();
+			let room = |p: &Arc<Peer>| {
+				let capacity = self
+					.downloads
+					.get(&p.info.addr)
+					.map_or(INITIAL_PEER_REQUESTS, |d| d.capacity());
+				capacity.saturating_sub(*load.get(&p.info.addr).unwrap_or(&0))
+			};
+			let best = |others_only: bool| {
+				available
+					.iter()
+					.filter(|p| !others_only || !failed_peers.contains(&p.info.addr))
+					.map(|p| (room(p), *p))
+					.filter(|(room, _)| *room > 0)
+					.max_by_key(|(room, _)| *room)
+					.map(|(_, p)| p.clone())
+			};
+			let peer = match best(true).or_else(|| best(false)) {
+				Some(peer) => peer,
+				// every peer has as many requests as it can handle
+				None => break,
+			};
+
+			if let Err(e) = peer.send_block_request(*h, chain::Options::SYNC) {
+				debug!("Skipped request to {}: {:?}", peer.info.addr, e);
+				peer.stop();
+				available.retain(|p| p.info.addr != peer.info.addr);
+				retries.insert(*h, failed_peers);
+				continue;
+			}
+			*load.entry(peer.info.addr).or_insert(0) += 1;
+			if let Some(download) = self.downloads.get_mut(&peer.info.addr) {
+				download.requested += 1;
+			}
+			self.in_flight.insert(
+				*h,
+				BlockRequest {
+					peer: peer.info.addr,
+					requested_at: now,
+					failed_peers,
+				},
+			);
+			requested += 1;
 		}
 
-		Ok(false)
+		if requested > 0 {
+			debug!(
+				"body_sync: requested {} blocks, {} in flight with {} peers",
+				requested,
+				self.in_flight.len(),
+				load.len(),
+			);
+		}
 	}
 
-	// Total numbers received on this chain, including the head and orphans
-	fn blocks_received(&self) -> Result<u64, chain::Error> {
-		Ok((self.chain.head()?).height
-			+ self.chain.orphans_len() as u64
-			+ self.chain.orphans_evicted_len() as u64)
+	fn update_stats(&self, missing: &[(u64, Hash)]) {
+		let mut load: HashMap<PeerAddr, usize> = HashMap::new();
+		for req in self.in_flight.values() {
+			*load.entry(req.peer).or_insert(0) += 1;
+		}
+		let mut peers: Vec<PeerDownloadStats> = self
+			.downloads
+			.iter()
+			.map(|(addr, d)| PeerDownloadStats {
+				addr: addr.to_string(),
+				requested: d.requested,
+				received: d.received,
+				reassigned: d.reassigned,
+				in_flight: *load.get(addr).unwrap_or(&0),
+				blocks_per_sec: d.rate,
+			})
+			.collect();
+		peers.sort_by(|a, b| {
+			let rate = |p: &PeerDownloadStats| p.blocks_per_sec.unwrap_or(0.0);
+			rate(b)
+				.partial_cmp(&rate(a))
+				.unwrap_or(cmp::Ordering::Equal)
+		});
+
+		let mut stats = self.stats.write();
+		stats.window_start = missing.first().map_or(0, |(height, _)| *height);
+		stats.window_end = missing.last().map_or(0, |(height, _)| *height);
+		stats.in_flight = self.in_flight.len();
+		stats.peers = peers;
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	#[test]
+	fn test_peer_download_capacity() {
+		let start = Utc::now();
+		let mut download = PeerDownload::new(start);
+		assert_eq!(download.capacity(), INITIAL_PEER_REQUESTS);
+
+		// 10 blocks over 2s while busy, 5 blocks/s
+		for _ in 0..10 {
+			download.delivered();
+		}
+		download.sample(start + Duration::seconds(1), true);
+		assert_eq!(download.rate, None);
+		download.sample(start + Duration::seconds(2), false);
+		assert_eq!(download.rate, Some(5.0));
+		assert_eq!(download.capacity(), 20);
+
+		// an idle peer keeps its estimate
+		download.sample(start + Duration::seconds(4), false);
+		assert_eq!(download.rate, Some(5.0));
+
+		// timeouts halve it, down to the min requests
+		download.timed_out();
+		assert_eq!(download.capacity(), 10);
+		for _ in 0..3 {
+			download.timed_out();
+		}
+		assert_eq!(download.capacity(), MIN_PEER_REQUESTS);
+		assert_eq!(download.reassigned, 4);
+
+		// and fast peers are capped
+		download.rate = Some(100.0);
+		assert_eq!(download.capacity(), MAX_PEER_REQUESTS);
 	}
 }
@@ -0,0 +1,199 @@
+// Copyright 2020 The Kepler Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Segmented txhashset download, spread over all the peers able to serve
+//! segments of their archive header. Each segment comes with a merkle proof
+//! and is checked against the header as soon as it's received, so a peer
+//! sending bad data is caught (and banned) without waiting for the whole
+//! state. Requests left unanswered go to another peer.
+
+use chrono::prelude::{DateTime, Utc};
+use chrono::Duration;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use crate::chain::{self, SegmentType, SegmentTypeIdentifier};
+use crate::core::core::hash::Hashed;
+use crate::core::core::BlockHeader;
+use crate::p2p::{self, Capabilities, Peer, PeerAddr};
+
+/// Segments requested from a peer at once
+const MAX_PEER_REQUESTS: usize = 8;
+
+/// Delay after which an unanswered request goes to another peer
+const REQUEST_TIMEOUT_SECS: i64 = 15;
+
+/// A segment requested and not received yet
+struct SegmentRequest {
+	peer: PeerAddr,
+	requested_at: DateTime<Utc>,
+}
+
+pub struct SegmentSync {
+	peers: Arc<p2p::Peers>,
+	chain: Arc<chain::Chain>,
+	header: Option<BlockHeader>,
+	requests: HashMap<SegmentTypeIdentifier, SegmentRequest>,
+	next_peer: usize,
+}
+
+impl SegmentSync {
+	pub fn new(peers: Arc<p2p::Peers>, chain: Arc<chain::Chain>) -> SegmentSync {
+		SegmentSync {
+			peers,
+			chain,
+			header: None,
+			requests: HashMap::new(),
+			next_peer: 0,
+		}
+	}
+
+	/// Peers able to serve segments, advertising at least the work of the
+	/// header we sync to.
+	fn segment_peers(&self) -> Vec<Arc<Peer>> {
+		self.peers
+			.most_work_peers()
+			.into_iter()
+			.filter(|p| p.info.capabilities.contains(Capabilities::PIBD_HIST))
+			.collect()
+	}
+
+	/// Starts the download of the segments of the txhashset at the provided
+	/// header. Returns false when it can't be done that way, leaving the
+	/// archive download as the only option.
+	pub fn start(&mut self, header: &BlockHeader) -> bool {
+		self.reset();
+		if self.segment_peers().is_empty() {
+			debug!("segment_sync: no peer serving segments");
+			return false;
+		}
+		if let Err(e) = self.chain.desegmenter(header) {
+			info!(
+				"segment_sync: can't download segments at {}: {}",
+				header.hash(),
+				e
+			);
+			return false;
+		}
+		info!(
+			"segment_sync: downloading txhashset segments at {} {}",
+			header.height,
+			header.hash()
+		);
+		self.header = Some(header.clone());
+		true
+	}
+
+	/// Drops the requests in flight and the segments received.
+	pub fn reset(&mut self) {
+		self.header = None;
+		self.requests.clear();
+		self.chain.reset_desegmenter();
+	}
+
+	/// Requests the segments we're still missing, round robin over the peers
+	/// serving them. Returns true once all segments have been received.
+	pub fn check_run(&mut self) -> Result<bool, chain::Error> {
+		let header = match self.header {
+			Some(ref header) => header.clone(),
+			None => {
+				return Err(
+					chain::ErrorKind::SyncError("segment sync not started".to_owned()).into(),
+				)
+			}
+		};
+		let missing = {
+			let desegmenter = self.chain.current_desegmenter();
+			let desegmenter = desegmenter.read();
+			match desegmenter.as_ref() {
+				Some(d) if d.header().hash() == header.hash() => {
+					if d.is_complete() {
+						return Ok(true);
+					}
+					d.missing_segments()
+				}
+				_ => {
+					return Err(
+						chain::ErrorKind::SyncError("segment sync dropped".to_owned()).into(),
+					)
+				}
+			}
+		};
+
+		// forget the segments received and the requests timed out
+		let now = Utc::now();
+		let timeout = Duration::seconds(REQUEST_TIMEOUT_SECS);
+		self.requests.retain(|id, req| {
+			if now - req.requested_at > timeout {
+				debug!(
+					"segment_sync: {:?} {} timed out with {}",
+					id.segment_type, id.identifier.idx, req.peer
+				);
+				return false;
+			}
+			missing.contains(id)
+		});
+
+		let peers = self.segment_peers();
+		if peers.is_empty() {
+			return Err(chain::ErrorKind::SyncError("no peer serving segments".to_owned()).into());
+		}
+		let mut in_flight: HashMap<PeerAddr, usize> = HashMap::new();
+		for req in self.requests.values() {
+			*in_flight.entry(req.peer).or_insert(0) += 1;
+		}
+
+		let bhash = header.hash();
+		for id in missing {
+			if self.requests.contains_key(&id) {
+				continue;
+			}
+			let peer = (0..peers.len())
+				.map(|i| &peers[(self.next_peer + i) % peers.len()])
+				.find(|p| in_flight.get(&p.info.addr).cloned().unwrap_or(0) < MAX_PEER_REQUESTS);
+			let peer = match peer {
+				Some(peer) => peer,
+				None => break,
+			};
+			self.next_peer = self.next_peer.wrapping_add(1);
+
+			let res = match id.segment_type {
+				SegmentType::Bitmap => peer.send_bitmap_segment_request(bhash, id.identifier),
+				SegmentType::Output => peer.send_output_segment_request(bhash, id.identifier),
+				SegmentType::RangeProof => {
+					peer.send_rangeproof_segment_request(bhash, id.identifier)
+				}
+				SegmentType::Kernel => peer.send_kernel_segment_request(bhash, id.identifier),
+			};
+			if let Err(e) = res {
+				debug!(
+					"segment_sync: request to {} failed: {:?}",
+					peer.info.addr, e
+				);
+				// leave the peer aside for this round
+				in_flight.insert(peer.info.addr, MAX_PEER_REQUESTS);
+				continue;
+			}
+			*in_flight.entry(peer.info.addr).or_insert(0) += 1;
+			self.requests.insert(
+				id,
+				SegmentRequest {
+					peer: peer.info.addr,
+					requested_at: now,
+				},
+			);
+		}
+		Ok(false)
+	}
+}
@@ -17,8 +17,10 @@ use chrono::Duration;
 use std::sync::Arc;
 
 use crate::chain::{self, SyncState, SyncStatus};
-use crate::core::core::hash::Hashed;
+use crate::core::core::hash::{Hashed, ZERO_HASH};
+use crate::core::core::BlockHeader;
 use crate::core::global;
+use crate::kepler::sync::segment_sync::SegmentSync;
 use crate::p2p::{self, Peer};
 
 /// Fast sync has 3 "states":
@@ -26,7 +28,9 @@ use crate::p2p::{self, Peer};
 /// * once all headers are sync'd, requesting the txhashset state
 /// * once we have the state, get blocks after that
 ///
-/// The StateSync struct implements and monitors the middle step.
+/// The StateSync struct implements and monitors the middle step. When
+/// segmented sync is enabled, the txhashset is built from segments
+/// downloaded from many peers, the archive being the fallback.
 pub struct StateSync {
 	sync_state: Arc<SyncState>,
 	peers: Arc<p2p::Peers>,
@@ -34,6 +38,7 @@ pub struct StateSync {
 
 	prev_state_sync: Option<DateTime<Utc>>,
 	state_sync_peer: Option<Arc<Peer>>,
+	segment_sync: Option<SegmentSync>,
 }
 
 impl StateSync {
@@ -41,13 +46,20 @@ impl StateSync {
 		sync_state: Arc<SyncState>,
 		peers: Arc<p2p::Peers>,
 		chain: Arc<chain::Chain>,
+		pibd_sync: bool,
 	) -> StateSync {
+		let segment_sync = if pibd_sync {
+			Some(SegmentSync::new(peers.clone(), chain.clone()))
+		} else {
+			None
+		};
 		StateSync {
 			sync_state,
 			peers,
 			chain,
 			prev_state_sync: None,
 			state_sync_peer: None,
+			segment_sync,
 		}
 	}
 
@@ -111,6 +123,13 @@ impl StateSync {
 			return false;
 		}
 
+		if let SyncStatus::TxHashsetPibd { .. } = self.sync_state.status() {
+			if !sync_need_restart {
+				self.check_segments(header_head);
+				return true;
+			}
+		}
+
 		// run fast sync if applicable, normally only run one-time, except restart in error
 		if sync_need_restart || header_head.height == highest_height {
 			let (go, download_timeout) = self.state_sync_due();
@@ -125,16 +144,6 @@ impl StateSync {
 			}
 
 			if go {
-				self.state_sync_peer = None;
-				match self.request_state(&header_head) {
-					Ok(peer) => {
-						self.state_sync_peer = Some(peer);
-					}
-					Err(e) => self
-						.sync_state
-						.set_sync_error(chain::ErrorKind::SyncError(format!("{:?}", e)).into()),
-				}
-
 				// to avoid the confusing log,
 				// update the final HeaderSync state mainly for 'current_height'
 				{
@@ -147,29 +156,110 @@ impl StateSync {
 					}
 				}
 
-				self.sync_state.update(SyncStatus::TxHashsetDownload {
-					start_time: Utc::now(),
-					prev_update_time: Utc::now(),
-					update_time: Utc::now(),
-					prev_downloaded_size: 0,
-					downloaded_size: 0,
-					total_size: 0,
-				});
+				if !self.start_segments(header_head) {
+					self.request_archive(header_head);
+				}
 			}
 		}
 		true
 	}
 
-	fn request_state(&self, header_head: &chain::Tip) -> Result<Arc<Peer>, p2p::Error> {
+	/// Starts the segmented download, if enabled and possible.
+	fn start_segments(&mut self, header_head: &chain::Tip) -> bool {
+		let segment_sync = match self.segment_sync.as_mut() {
+			Some(segment_sync) => segment_sync,
+			None => return false,
+		};
+		let txhashset_head = match StateSync::txhashset_head(&self.chain, header_head) {
+			Ok(head) => head,
+			Err(_) => return false,
+		};
+		if !segment_sync.start(&txhashset_head) {
+			return false;
+		}
+		self.sync_state.update(SyncStatus::TxHashsetPibd {
+			recv_segments: 0,
+			total_segments: 0,
+		});
+		true
+	}
+
+	/// Keeps requesting the missing segments. Once they've all been received
+	/// and checked, the txhashset is built from them. If that fails or the
+	/// segmented download can't go on, the archive is requested instead.
+	fn check_segments(&mut self, header_head: &chain::Tip) {
+		let segment_sync = match self.segment_sync.as_mut() {
+			Some(segment_sync) => segment_sync,
+			None => return,
+		};
+		match segment_sync.check_run() {
+			Ok(false) => return,
+			Ok(true) => {
+				info!("state_sync: all txhashset segments received and valid");
+				match self
+					.chain
+					.txhashset_segments_write(self.sync_state.as_ref())
+				{
+					Ok(()) => {
+						info!("state_sync: txhashset built from segments");
+						segment_sync.reset();
+						return;
+					}
+					Err(e) => {
+						self.chain.clean_txhashset_sandbox();
+						error!(
+							"state_sync: failed to build the txhashset from segments, falling back to the archive: {}",
+							e
+						);
+					}
+				}
+			}
+			Err(e) => info!(
+				"state_sync: segment download stopped, falling back to the archive: {}",
+				e
+			),
+		}
+		segment_sync.reset();
+		self.prev_state_sync = Some(Utc::now());
+		self.request_archive(header_head);
+	}
+
+	/// Requests the txhashset archive and waits for it.
+	fn request_archive(&mut self, header_head: &chain::Tip) {
+		self.state_sync_peer = None;
+		match self.request_state(&header_head) {
+			Ok(peer) => {
+				self.state_sync_peer = Some(peer);
+			}
+			Err(e) => self
+				.sync_state
+				.set_sync_error(chain::ErrorKind::SyncError(format!("{:?}", e)).into()),
+		}
+
+		self.sync_state.update(SyncStatus::TxHashsetDownload {
+			start_time: Utc::now(),
+			prev_update_time: Utc::now(),
+			update_time: Utc::now(),
+			prev_downloaded_size: 0,
+			downloaded_size: 0,
+			total_size: 0,
+		});
+	}
+
+	/// Header of the txhashset to download, at the state sync threshold
+	/// below the header head and on an archive interval.
+	fn txhashset_head(
+		chain: &chain::Chain,
+		header_head: &chain::Tip,
+	) -> Result<BlockHeader, p2p::Error> {
 		let threshold = global::state_sync_threshold() as u64;
 		let archive_interval = global::txhashset_archive_interval();
 		let mut txhashset_height = header_head.height.saturating_sub(threshold);
 		txhashset_height = txhashset_height.saturating_sub(txhashset_height % archive_interval);
 
-		if let Some(peer) = self.peers.most_work_peer() {
-			// ask for txhashset at state_sync_threshold
-			let mut txhashset_head = self
-				.chain
+		// ask for txhashset at state_sync_threshold
+		let mut txhashset_head =
+			chain
 				.get_block_header(&header_head.prev_block_h)
 				.map_err(|e| {
 					error!(
@@ -178,34 +268,62 @@ impl StateSync {
 					);
 					p2p::Error::Internal
 				})?;
-			while txhashset_head.height > txhashset_height {
-				txhashset_head = self
-					.chain
-					.get_previous_header(&txhashset_head)
-					.map_err(|e| {
-						error!(
-							"chain error during getting a previous block header {}: {:?}",
-							txhashset_head.hash(),
-							e
-						);
-						p2p::Error::Internal
-					})?;
-			}
-			let bhash = txhashset_head.hash();
-			debug!(
-				"state_sync: before txhashset request, header head: {} / {}, txhashset_head: {} / {}",
-				header_head.height,
-				header_head.last_block_h,
-				txhashset_head.height,
-				bhash
-			);
-			if let Err(e) = peer.send_txhashset_request(txhashset_head.height, bhash) {
-				error!("state_sync: send_txhashset_request err! {:?}", e);
-				return Err(e);
+		while txhashset_head.height > txhashset_height {
+			txhashset_head = chain.get_previous_header(&txhashset_head).map_err(|e| {
+				error!(
+					"chain error during getting a previous block header {}: {:?}",
+					txhashset_head.hash(),
+					e
+				);
+				p2p::Error::Internal
+			})?;
+		}
+		Ok(txhashset_head)
+	}
+
+	fn request_state(&self, header_head: &chain::Tip) -> Result<Arc<Peer>, p2p::Error> {
+		let mut most_work_peers = self.peers.most_work_peers();
+		if most_work_peers.is_empty() {
+			return Err(p2p::Error::PeerException);
+		}
+
+		let txhashset_head = StateSync::txhashset_head(&self.chain, header_head)?;
+		let bhash = txhashset_head.hash();
+		debug!(
+			"state_sync: before txhashset request, header head: {} / {}, txhashset_head: {} / {}",
+			header_head.height, header_head.last_block_h, txhashset_head.height, bhash
+		);
+
+		// Resume a previous download of this archive if there's one, from a
+		// peer able to serve only the rest of it
+		let download = p2p::TxHashSetDownload::new(&self.chain.get_tmp_dir(), bhash);
+		let (offset, archive_hash) = download.resumable().unwrap_or((0, ZERO_HASH));
+		let can_resume = |p: &Arc<Peer>| {
+			p.info
+				.capabilities
+				.contains(p2p::Capabilities::TXHASHSET_RESUME)
+		};
+		let peer = match most_work_peers.iter().position(|p| can_resume(p)) {
+			Some(i) => most_work_peers.swap_remove(i),
+			None => most_work_peers.pop().expect("checked non empty"),
+		};
+
+		let res = if can_resume(&peer) {
+			if offset > 0 {
+				info!(
+					"state_sync: resuming txhashset download from {} after {} bytes",
+					peer.info.addr, offset
+				);
 			}
-			return Ok(peer.clone());
+			peer.send_txhashset_part_request(txhashset_head.height, bhash, archive_hash, offset)
+		} else {
+			peer.send_txhashset_request(txhashset_head.height, bhash)
+		};
+		if let Err(e) = res {
+			error!("state_sync: send_txhashset_request err! {:?}", e);
+			return Err(e);
 		}
-		Err(p2p::Error::PeerException)
+		Ok(peer)
 	}
 
 	// For now this is a one-time thing (it can be slow) at initial startup.
@@ -230,5 +348,8 @@ impl StateSync {
 	fn state_sync_reset(&mut self) {
 		self.prev_state_sync = None;
 		self.state_sync_peer = None;
+		if let Some(segment_sync) = self.segment_sync.as_mut() {
+			segment_sync.reset();
+		}
 	}
 }
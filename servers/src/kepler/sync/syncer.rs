@@ -12,11 +12,13 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use crate::util::RwLock;
 use std::sync::Arc;
 use std::thread;
 use std::time;
 
 use crate::chain::{self, SyncState, SyncStatus};
+use crate::common::stats::BodySyncStats;
 use crate::core::global;
 use crate::core::pow::Difficulty;
 use crate::kepler::sync::body_sync::BodySync;
@@ -29,12 +31,23 @@ pub fn run_sync(
 	sync_state: Arc<SyncState>,
 	peers: Arc<p2p::Peers>,
 	chain: Arc<chain::Chain>,
+	body_sync_stats: Arc<RwLock<BodySyncStats>>,
+	pibd_sync: bool,
+	header_only: bool,
 	stop_state: Arc<StopState>,
 ) -> std::io::Result<std::thread::JoinHandle<()>> {
 	thread::Builder::new()
 		.name("sync".to_string())
 		.spawn(move || {
-			let runner = SyncRunner::new(sync_state, peers, chain, stop_state);
+			let runner = SyncRunner::new(
+				sync_state,
+				peers,
+				chain,
+				body_sync_stats,
+				pibd_sync,
+				header_only,
+				stop_state,
+			);
 			runner.sync_loop();
 		})
 }
@@ -43,6 +56,9 @@ pub struct SyncRunner {
 	sync_state: Arc<SyncState>,
 	peers: Arc<p2p::Peers>,
 	chain: Arc<chain::Chain>,
+	body_sync_stats: Arc<RwLock<BodySyncStats>>,
+	pibd_sync: bool,
+	header_only: bool,
 	stop_state: Arc<StopState>,
 }
 
@@ -51,12 +67,18 @@ impl SyncRunner {
 		sync_state: Arc<SyncState>,
 		peers: Arc<p2p::Peers>,
 		chain: Arc<chain::Chain>,
+		body_sync_stats: Arc<RwLock<BodySyncStats>>,
+		pibd_sync: bool,
+		header_only: bool,
 		stop_state: Arc<StopState>,
 	) -> SyncRunner {
 		SyncRunner {
 			sync_state,
 			peers,
 			chain,
+			body_sync_stats,
+			pibd_sync,
+			header_only,
 			stop_state,
 		}
 	}
@@ -129,11 +151,13 @@ impl SyncRunner {
 			self.sync_state.clone(),
 			self.peers.clone(),
 			self.chain.clone(),
+			self.body_sync_stats.clone(),
 		);
 		let mut state_sync = StateSync::new(
 			self.sync_state.clone(),
 			self.peers.clone(),
 			self.chain.clone(),
+			self.pibd_sync,
 		);
 
 		// Highest height seen on the network, generally useful for a fast test on
@@ -199,9 +223,15 @@ impl SyncRunner {
 			// except for state sync that only runs if body sync return true (means txhashset is needed)
 			unwrap_or_restart_loop!(header_sync.check_run(&header_head, highest_height));
 
+			// nothing more to sync when only following the header chain
+			if self.header_only {
+				continue;
+			}
+
 			let mut check_state_sync = false;
 			match self.sync_state.status() {
 				SyncStatus::TxHashsetDownload { .. }
+				| SyncStatus::TxHashsetPibd { .. }
 				| SyncStatus::TxHashsetSetup
 				| SyncStatus::TxHashsetRangeProofsValidation { .. }
 				| SyncStatus::TxHashsetKernelsValidation { .. }
@@ -236,7 +266,7 @@ impl SyncRunner {
 	/// Whether we're currently syncing the chain or we're fully caught up and
 	/// just receiving blocks through gossip.
 	fn needs_syncing(&self) -> Result<(bool, u64), chain::Error> {
-		let local_diff = self.chain.head()?.total_difficulty;
+		let local_diff = self.local_head()?.total_difficulty;
 		let mut is_syncing = self.sync_state.is_syncing();
 		let peer = self.peers.most_work_peer();
 
@@ -251,7 +281,7 @@ impl SyncRunner {
 		// difficulty than us
 		if is_syncing {
 			if peer_info.total_difficulty() <= local_diff {
-				let ch = self.chain.head()?;
+				let ch = self.local_head()?;
 				info!(
 					"synchronized at {} @ {} [{}]",
 					local_diff.to_num(),
@@ -290,4 +320,14 @@ impl SyncRunner {
 		}
 		Ok((is_syncing, peer_info.height()))
 	}
+
+	/// The head we sync to our peers, only the header chain when running
+	/// header only.
+	fn local_head(&self) -> Result<chain::Tip, chain::Error> {
+		if self.header_only {
+			self.chain.header_head()
+		} else {
+			self.chain.head()
+		}
+	}
 }
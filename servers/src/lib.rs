@@ -39,6 +39,12 @@ pub mod common;
 mod kepler;
 mod mining;
 
-pub use crate::common::stats::{DiffBlock, PeerStats, ServerStats, StratumStats, WorkerStats};
+pub use crate::common::stats::{
+	BlockDetails, BlockKernelStats, BlockOutputStats, BlockStats, BodySyncStats, ChainStats,
+	CpuMinerStats, DiffBlock, DiffStats, PeerDetails, PeerDownloadStats, PeerStats, PoolEntryStats,
+	ServerStats, StratumStats, TxStats, WorkerStats,
+};
 pub use crate::common::types::{ServerConfig, StratumServerConfig};
 pub use crate::kepler::server::Server;
+pub use crate::mining::genesis;
+pub use crate::mining::stratum_auth::credentials_entry as stratum_credentials_entry;
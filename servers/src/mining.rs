@@ -14,6 +14,12 @@
 
 //! Mining + Mining server
 
+pub mod block_generator;
+pub mod cpu_miner;
+pub mod genesis;
+pub mod local_coinbase;
 mod mine_block;
+pub mod stratum_auth;
+pub mod stratum_store;
 pub mod stratumserver;
 pub mod test_miner;
@@ -0,0 +1,231 @@
+// Copyright 2020 The Kepler Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! On demand block generation, for the integration tests of wallets and
+//! other clients needing to mine an exact number of blocks.
+//!
+//! Blocks are built like the miners do, solved at the chain minimum graph
+//! size with the lean solver and processed before returning, so it's only
+//! available on the testing and custom chains. A mock time can be set for
+//! their timestamps to be deterministic.
+
+use chrono::prelude::{DateTime, NaiveDateTime, Utc};
+use chrono::Duration;
+use std::sync::Arc;
+
+use crate::api;
+use crate::chain;
+use crate::common::types::Error;
+use crate::core::core::hash::{Hash, Hashed};
+use crate::core::core::verifier_cache::VerifierCache;
+use crate::core::global::{self, ChainTypes};
+use crate::core::pow;
+use crate::mining::local_coinbase::LocalCoinbase;
+use crate::mining::mine_block;
+use crate::pool;
+use crate::util::{Mutex, RwLock};
+
+/// Maximum number of blocks generated in a single call
+pub const MAX_GENERATED_BLOCKS: u64 = 1000;
+
+pub struct BlockGenerator {
+	chain: Arc<chain::Chain>,
+	tx_pool: Arc<RwLock<pool::TransactionPool>>,
+	verifier_cache: Arc<RwLock<dyn VerifierCache>>,
+	local_coinbase: Option<Arc<LocalCoinbase>>,
+	mock_time: RwLock<Option<i64>>,
+	// generated blocks are built on top of each other, one call at a time
+	generate_lock: Mutex<()>,
+}
+
+impl BlockGenerator {
+	/// Creates a new block generator, mining on top of the chain head with
+	/// transactions of the pool.
+	pub fn new(
+		chain: Arc<chain::Chain>,
+		tx_pool: Arc<RwLock<pool::TransactionPool>>,
+		verifier_cache: Arc<RwLock<dyn VerifierCache>>,
+		local_coinbase: Option<Arc<LocalCoinbase>>,
+	) -> BlockGenerator {
+		BlockGenerator {
+			chain,
+			tx_pool,
+			verifier_cache,
+			local_coinbase,
+			mock_time: RwLock::new(None),
+			generate_lock: Mutex::new(()),
+		}
+	}
+
+	/// Whether blocks can be generated on demand on the current chain
+	pub fn is_available() -> bool {
+		matches!(
+			*global::CHAIN_TYPE.read(),
+			ChainTypes::AutomatedTesting | ChainTypes::UserTesting | ChainTypes::Custom
+		)
+	}
+
+	/// Mines a block on top of the chain head and processes it. The coinbase
+	/// is built by the wallet listener at the provided destination, or as
+	/// configured for the miners if none is provided. Unlike the miners, we
+	/// don't retry if the wallet listener can't be reached. Blocks our peers
+	/// would reject as too far in the future aren't generated.
+	fn generate_block(&self, coinbase_destination: &Option<String>) -> Result<Hash, Error> {
+		let head = self.chain.head_header()?;
+		let local_coinbase = match coinbase_destination {
+			Some(_) => None,
+			None => self.local_coinbase.clone(),
+		};
+		let (mut b, _) = mine_block::build_block(
+			&self.chain,
+			&self.tx_pool,
+			self.verifier_cache.clone(),
+			None,
+			coinbase_destination.clone(),
+			local_coinbase,
+		)?;
+		if let Some(mock_time) = *self.mock_time.read() {
+			let secs = mock_time.max(head.timestamp.timestamp() + 1);
+			b.header.timestamp =
+				DateTime::<Utc>::from_utc(NaiveDateTime::from_timestamp(secs, 0), Utc);
+		}
+		// same bound as for the headers received from peers
+		let max_time = Utc::now() + Duration::seconds(12 * (global::block_time_sec() as i64));
+		if b.header.timestamp > max_time {
+			return Err(Error::ArgumentError(format!(
+				"block time {} is more than 12 blocks in future",
+				b.header.timestamp
+			)));
+		}
+
+		let difficulty = b.header.total_difficulty() - head.total_difficulty();
+		// the chain minimum graph size can be large on custom chains, the lean
+		// solver keeps the memory use down
+		pow::lean_pow_size(
+			&mut b.header,
+			difficulty,
+			global::proofsize(),
+			global::min_edge_bits(),
+		)?;
+		let hash = b.hash();
+		self.chain.process_block(b, chain::Options::MINE)?;
+		Ok(hash)
+	}
+}
+
+impl api::BlockGeneration for BlockGenerator {
+	fn generate_blocks(
+		&self,
+		n: u64,
+		coinbase_destination: Option<String>,
+	) -> Result<Vec<String>, api::Error> {
+		if n > MAX_GENERATED_BLOCKS {
+			return Err(api::ErrorKind::Argument(format!(
+				"can't generate more than {} blocks at once",
+				MAX_GENERATED_BLOCKS
+			))
+			.into());
+		}
+		let _lock = self.generate_lock.lock();
+		let mut hashes = vec![];
+		for _ in 0..n {
+			let hash = self.generate_block(&coinbase_destination).map_err(|e| {
+				api::ErrorKind::Internal(format!("failed to generate block: {:?}", e))
+			})?;
+			hashes.push(hash.to_hex());
+		}
+		Ok(hashes)
+	}
+
+	fn set_mock_time(&self, timestamp: Option<i64>) -> Result<(), api::Error> {
+		*self.mock_time.write() = timestamp;
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+	use crate::api::BlockGeneration;
+	use crate::common::adapters::PoolToChainAdapter;
+	use crate::core::core::verifier_cache::LruVerifierCache;
+	use crate::pool::{PoolConfig, TransactionPool};
+	use std::fs;
+
+	#[test]
+	fn generate_blocks_at_mock_time() {
+		global::set_mining_mode(ChainTypes::AutomatedTesting);
+		let db_root = ".kepler_block_generator";
+		let _ = fs::remove_dir_all(db_root);
+		{
+			let verifier_cache = Arc::new(RwLock::new(LruVerifierCache::new()));
+			let chain = Arc::new(
+				chain::Chain::init(
+					db_root.to_owned(),
+					Arc::new(chain::types::NoopAdapter {}),
+					pow::mine_genesis_block().unwrap(),
+					pow::verify_size,
+					verifier_cache.clone(),
+					false,
+				)
+				.unwrap(),
+			);
+			let chain_adapter = Arc::new(PoolToChainAdapter::new());
+			chain_adapter.set_chain(chain.clone());
+			let tx_pool = Arc::new(RwLock::new(TransactionPool::new(
+				PoolConfig::default(),
+				chain_adapter,
+				verifier_cache.clone(),
+				Arc::new(pool::types::NoopAdapter {}),
+			)));
+			let generator = BlockGenerator::new(chain.clone(), tx_pool, verifier_cache, None);
+
+			let genesis_time = chain.head_header().unwrap().timestamp.timestamp();
+			generator.set_mock_time(Some(genesis_time + 100)).unwrap();
+			let hashes = generator.generate_blocks(3, None).unwrap();
+			assert_eq!(hashes.len(), 3);
+			let head = chain.head().unwrap();
+			assert_eq!(head.height, 3);
+			assert_eq!(head.last_block_h.to_hex(), hashes[2]);
+
+			// the mock time doesn't move, each block is a second after the previous
+			let times: Vec<i64> = (1..=3)
+				.map(|h| chain.get_header_by_height(h).unwrap().timestamp.timestamp())
+				.collect();
+			assert_eq!(
+				times,
+				vec![genesis_time + 100, genesis_time + 101, genesis_time + 102]
+			);
+
+			// too many blocks at once
+			assert!(generator
+				.generate_blocks(MAX_GENERATED_BLOCKS + 1, None)
+				.is_err());
+
+			// an unreachable wallet listener fails the call instead of
+			// retrying, and doesn't block the next calls
+			let unreachable = Some("http://127.0.0.1:1".to_owned());
+			assert!(generator.generate_blocks(1, unreachable).is_err());
+			assert_eq!(generator.generate_blocks(1, None).unwrap().len(), 1);
+			assert_eq!(chain.head().unwrap().height, 4);
+
+			// a mock time our peers would reject isn't used
+			let far_future = Utc::now().timestamp() + 13 * global::block_time_sec() as i64;
+			generator.set_mock_time(Some(far_future)).unwrap();
+			assert!(generator.generate_blocks(1, None).is_err());
+			assert_eq!(chain.head().unwrap().height, 4);
+		}
+		let _ = fs::remove_dir_all(db_root);
+	}
+}
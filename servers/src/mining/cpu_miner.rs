@@ -0,0 +1,369 @@
+// Copyright 2020 The Kepler Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Multithreaded CPU miner, so that usernet and custom chains can mine real
+//! blocks without external miner software.
+//!
+//! Every thread runs the lean miner on its own nonces of the same block,
+//! until one of them finds a proof of the block difficulty, the chain head
+//! changes or the attempt time is over, in which case the block is rebuilt
+//! with the latest transactions. Those chains only accept Cuckatoo proofs
+//! (the Cuckaroo variants are restricted to Floonet), so graphs are always
+//! Cuckatoo ones, of the chain minimum size unless configured otherwise.
+
+use chrono::prelude::Utc;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{mpsc, Arc};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::chain::{self, SyncState};
+use crate::common::stats::CpuMinerStats;
+use crate::common::types::StratumServerConfig;
+use crate::core::core::hash::Hashed;
+use crate::core::core::verifier_cache::VerifierCache;
+use crate::core::core::BlockHeader;
+use crate::core::global;
+use crate::core::pow::{self, Difficulty};
+use crate::mining::local_coinbase::LocalCoinbase;
+use crate::mining::mine_block;
+use crate::pool;
+use crate::util::{RwLock, StopState};
+
+// How often the mining threads are checked on and the hashrate updated
+const CHECK_INTERVAL: Duration = Duration::from_millis(500);
+
+pub struct CpuMiner {
+	config: StratumServerConfig,
+	chain: Arc<chain::Chain>,
+	tx_pool: Arc<RwLock<pool::TransactionPool>>,
+	verifier_cache: Arc<RwLock<dyn VerifierCache>>,
+	stop_state: Arc<StopState>,
+	sync_state: Arc<SyncState>,
+	local_coinbase: Option<Arc<LocalCoinbase>>,
+}
+
+impl CpuMiner {
+	/// Creates a new CPU miner, mining on top of the chain head with
+	/// transactions of the pool.
+	pub fn new(
+		config: StratumServerConfig,
+		chain: Arc<chain::Chain>,
+		tx_pool: Arc<RwLock<pool::TransactionPool>>,
+		verifier_cache: Arc<RwLock<dyn VerifierCache>>,
+		stop_state: Arc<StopState>,
+		sync_state: Arc<SyncState>,
+		local_coinbase: Option<Arc<LocalCoinbase>>,
+	) -> CpuMiner {
+		CpuMiner {
+			config,
+			chain,
+			tx_pool,
+			verifier_cache,
+			stop_state,
+			sync_state,
+			local_coinbase,
+		}
+	}
+
+	/// Starts the mining loop, building a new block on top of the chain head
+	/// anytime required and looking for a proof of work on it with all the
+	/// mining threads. Hashrate and found blocks are reported in the provided
+	/// stats.
+	pub fn run_loop(&self, stats: Arc<RwLock<CpuMinerStats>>) {
+		let num_threads = self.config.cpu_miner_threads.max(1);
+		let min_edge_bits = global::min_edge_bits();
+		let edge_bits = match self.config.cpu_miner_edge_bits {
+			Some(edge_bits) if edge_bits < min_edge_bits => {
+				warn!(
+					"CPU miner edge bits {} below the chain minimum, mining at {}",
+					edge_bits, min_edge_bits
+				);
+				min_edge_bits
+			}
+			Some(edge_bits) => edge_bits,
+			None => min_edge_bits,
+		};
+		let wallet_listener_url = if self.config.burn_reward {
+			None
+		} else {
+			Some(self.config.wallet_listener_url.clone())
+		};
+		info!(
+			"Starting CPU miner with {} threads on Cuckatoo{}",
+			num_threads, edge_bits
+		);
+		{
+			let mut stats = stats.write();
+			stats.is_running = true;
+			stats.num_threads = num_threads;
+			stats.edge_bits = edge_bits as u16;
+		}
+
+		// we keep the key id of the coinbase while nothing was found, only
+		// deriving a new one for each new block
+		let mut key_id = None;
+
+		while !self.stop_state.is_stopped() {
+			if self.sync_state.is_syncing() {
+				thread::sleep(Duration::from_secs(5));
+				continue;
+			}
+
+			let head = match self.chain.head_header() {
+				Ok(head) => head,
+				Err(e) => {
+					error!("CPU miner: unable to read the chain head: {:?}", e);
+					thread::sleep(Duration::from_secs(5));
+					continue;
+				}
+			};
+			let (mut b, block_fees) = mine_block::get_block(
+				&self.chain,
+				&self.tx_pool,
+				self.verifier_cache.clone(),
+				key_id.clone(),
+				wallet_listener_url.clone(),
+				self.local_coinbase.clone(),
+			);
+			b.header.pow.proof.edge_bits = edge_bits;
+			let difficulty = b.header.total_difficulty() - head.total_difficulty();
+			{
+				let mut stats = stats.write();
+				stats.block_height = b.header.height;
+				stats.network_difficulty = difficulty.to_num();
+			}
+
+			match self.mine(&b.header, &head, difficulty, edge_bits, num_threads, &stats) {
+				Some(header) => {
+					b.header = header;
+					info!(
+						"CPU miner: found a proof of work, adding block {} at {}",
+						b.hash(),
+						b.header.height
+					);
+					match self.chain.process_block(b, chain::Options::MINE) {
+						Ok(_) => stats.write().num_blocks_found += 1,
+						Err(e) => error!("CPU miner: error validating mined block: {:?}", e),
+					}
+					key_id = None;
+				}
+				None => key_id = block_fees.key_id(),
+			}
+		}
+
+		{
+			let mut stats = stats.write();
+			stats.is_running = false;
+			stats.graphs_per_sec = 0.0;
+		}
+		info!("CPU miner exit.");
+	}
+
+	/// Looks for a proof of work of at least the provided difficulty on the
+	/// header, with the header nonce incremented by the number of threads
+	/// from a different start in each thread. Stops as soon as one is found,
+	/// the chain head changes or the attempt time is over.
+	fn mine(
+		&self,
+		header: &BlockHeader,
+		head: &BlockHeader,
+		difficulty: Difficulty,
+		edge_bits: u8,
+		num_threads: usize,
+		stats: &RwLock<CpuMinerStats>,
+	) -> Option<BlockHeader> {
+		let start = Instant::now();
+		let deadline = Utc::now().timestamp() + self.config.attempt_time_per_block as i64;
+		let done = Arc::new(AtomicBool::new(false));
+		let graphs = Arc::new(AtomicU64::new(0));
+		let (tx, rx) = mpsc::channel();
+
+		let threads = (0..num_threads)
+			.filter_map(|n| {
+				let mut header = header.clone();
+				header.pow.nonce = header.pow.nonce.wrapping_add(n as u64);
+				let done = done.clone();
+				let graphs = graphs.clone();
+				let tx = tx.clone();
+				thread::Builder::new()
+					.name(format!("cpu_miner_{}", n))
+					.spawn(move || {
+						let proof_size = global::proofsize();
+						while !done.load(Ordering::Relaxed) {
+							if let Ok(proofs) =
+								pow::lean_find_cycles(&header, proof_size, edge_bits)
+							{
+								header.pow.proof = proofs[0].clone();
+								if header.pow.to_difficulty(header.height) >= difficulty {
+									let _ = tx.send(header);
+									return;
+								}
+							}
+							graphs.fetch_add(1, Ordering::Relaxed);
+							header.pow.nonce = header.pow.nonce.wrapping_add(num_threads as u64);
+						}
+					})
+					.ok()
+			})
+			.collect::<Vec<_>>();
+
+		let mut found = None;
+		loop {
+			if let Ok(header) = rx.recv_timeout(CHECK_INTERVAL) {
+				found = Some(header);
+			}
+			let secs = start.elapsed().as_secs_f64();
+			if secs > 0.0 {
+				stats.write().graphs_per_sec = graphs.load(Ordering::Relaxed) as f64 / secs;
+			}
+			let head_changed = match self.chain.head() {
+				Ok(tip) => tip.last_block_h != head.hash(),
+				Err(_) => true,
+			};
+			if found.is_some()
+				|| head_changed
+				|| self.stop_state.is_stopped()
+				|| Utc::now().timestamp() >= deadline
+			{
+				break;
+			}
+		}
+		done.store(true, Ordering::Relaxed);
+		for thread in threads {
+			let _ = thread.join();
+		}
+		if found.is_none() {
+			debug!(
+				"CPU miner: no proof of work found on block {} after {} graphs",
+				header.height,
+				graphs.load(Ordering::Relaxed)
+			);
+		}
+		found
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+	use crate::chain::SyncStatus;
+	use crate::common::adapters::PoolToChainAdapter;
+	use crate::core::core::verifier_cache::LruVerifierCache;
+	use crate::core::global::ChainTypes;
+	use crate::pool::{PoolConfig, TransactionPool};
+	use std::fs;
+
+	#[test]
+	fn cpu_miner_finds_blocks() {
+		global::set_mining_mode(ChainTypes::AutomatedTesting);
+		let db_root = ".kepler_cpu_miner";
+		let _ = fs::remove_dir_all(db_root);
+		{
+			let verifier_cache = Arc::new(RwLock::new(LruVerifierCache::new()));
+			let chain = Arc::new(
+				chain::Chain::init(
+					db_root.to_owned(),
+					Arc::new(chain::types::NoopAdapter {}),
+					pow::mine_genesis_block().unwrap(),
+					pow::verify_size,
+					verifier_cache.clone(),
+					false,
+				)
+				.unwrap(),
+			);
+			let chain_adapter = Arc::new(PoolToChainAdapter::new());
+			chain_adapter.set_chain(chain.clone());
+			let tx_pool = Arc::new(RwLock::new(TransactionPool::new(
+				PoolConfig::default(),
+				chain_adapter,
+				verifier_cache.clone(),
+				Arc::new(pool::types::NoopAdapter {}),
+			)));
+			let sync_state = Arc::new(SyncState::new());
+			sync_state.update(SyncStatus::NoSync);
+			let stop_state = Arc::new(StopState::new());
+			let config = StratumServerConfig {
+				burn_reward: true,
+				enable_cpu_miner: true,
+				cpu_miner_threads: 2,
+				..StratumServerConfig::default()
+			};
+			let miner = Arc::new(CpuMiner::new(
+				config,
+				chain.clone(),
+				tx_pool,
+				verifier_cache,
+				stop_state.clone(),
+				sync_state,
+				None,
+			));
+
+			// the threads stop looking as soon as the chain head isn't the one
+			// the block is built on
+			let genesis = chain.head_header().unwrap();
+			let stale_head = BlockHeader {
+				height: 1,
+				..genesis.clone()
+			};
+			let mut header = genesis.clone();
+			header.height = 1;
+			let stats = Arc::new(RwLock::new(CpuMinerStats::default()));
+			let found = miner.mine(
+				&header,
+				&stale_head,
+				Difficulty::from_num(u64::max_value()),
+				global::min_edge_bits(),
+				2,
+				&stats,
+			);
+			assert!(found.is_none());
+
+			let stats = Arc::new(RwLock::new(CpuMinerStats::default()));
+			let thread = {
+				let miner = miner.clone();
+				let stats = stats.clone();
+				thread::spawn(move || miner.run_loop(stats))
+			};
+			let start = Instant::now();
+			while stats.read().num_blocks_found < 3 {
+				assert!(start.elapsed() < Duration::from_secs(120));
+				thread::sleep(Duration::from_millis(100));
+			}
+			{
+				let stats = stats.read();
+				assert!(stats.is_running);
+				assert_eq!(stats.num_threads, 2);
+				assert_eq!(stats.edge_bits, global::min_edge_bits() as u16);
+				assert!(stats.block_height >= 3);
+			}
+			assert!(chain.head().unwrap().height >= 3);
+			stop_state.stop();
+			thread.join().unwrap();
+
+			// every block of the chain was found by the miner, each with a
+			// valid proof of work
+			let stats = stats.read();
+			assert!(!stats.is_running);
+			assert_eq!(stats.graphs_per_sec, 0.0);
+			let height = chain.head().unwrap().height;
+			assert_eq!(stats.num_blocks_found, height);
+			for h in 1..=height {
+				let header = chain.get_header_by_height(h).unwrap();
+				pow::verify_size(&header).unwrap();
+			}
+		}
+		let _ = fs::remove_dir_all(db_root);
+	}
+}
@@ -0,0 +1,161 @@
+// Copyright 2020 The Kepler Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Genesis blocks of custom chains.
+//!
+//! The genesis block pays its coinbase (the chain genesis reward) to a key
+//! derived from a provided extended private key, commits to an arbitrary
+//! message through its `prev_root` and is then either mined with the lean
+//! miner or given a proof of work found by an external miner on its pre-PoW.
+//! The txhashset roots are computed by applying the block to a throwaway
+//! chain, so they always match what a node will rebuild at startup.
+
+use chrono::{DateTime, Utc};
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::sync::Arc;
+
+use crate::chain;
+use crate::chain::types::{NoopAdapter, TxHashSetRoots};
+use crate::common::types::Error;
+use crate::core::consensus;
+use crate::core::core::hash::Hashed;
+use crate::core::core::verifier_cache::LruVerifierCache;
+use crate::core::core::Block;
+use crate::core::libtx::{self, ProofBuilder};
+use crate::core::pow::{self, Difficulty, Proof};
+use crate::core::{genesis, global, ser};
+use crate::keychain::{BlindingFactor, ExtKeychain, Keychain};
+use crate::mining::local_coinbase::LocalCoinbase;
+use crate::util::{self, RwLock};
+
+/// Parameters of a custom chain genesis block.
+pub struct GenesisParams {
+	/// Timestamp of the genesis header.
+	pub timestamp: DateTime<Utc>,
+	/// Message the genesis header commits to, hashed as its `prev_root`.
+	pub message: String,
+	/// File holding the extended private key the genesis coinbase is paid to.
+	pub key_file: String,
+}
+
+impl GenesisParams {
+	/// Builds the genesis block, complete except for its proof of work.
+	pub fn build(&self) -> Result<Block, Error> {
+		if *global::CHAIN_TYPE.read() != global::ChainTypes::Custom {
+			return Err(Error::ArgumentError(
+				"Genesis blocks can only be built for a custom chain".to_owned(),
+			));
+		}
+		let key = fs::read_to_string(&self.key_file)?;
+		let keychain = LocalCoinbase::parse_key(key.trim())?;
+		let key_id = ExtKeychain::derive_key_id(3, 1, 0, 0, 0);
+		let (output, kernel) = libtx::reward::output(
+			&keychain,
+			&ProofBuilder::new(&keychain),
+			&key_id,
+			0,
+			0,
+			false,
+		)?;
+
+		let mut gen = genesis::genesis_dev().with_reward(output, kernel);
+		gen.header.version = consensus::header_version(0);
+		gen.header.timestamp = self.timestamp;
+		gen.header.prev_root = self.message.as_bytes().to_vec().hash();
+		gen.header.pow.total_difficulty = Difficulty::from_num(global::initial_block_difficulty());
+		gen.header.pow.proof.edge_bits = global::min_edge_bits();
+		// a single coinbase output and kernel
+		gen.header.output_mmr_size = 1;
+		gen.header.kernel_mmr_size = 1;
+
+		let db_root = std::env::temp_dir().join(format!("kepler-genesis-{}", std::process::id()));
+		let db_root = db_root.to_string_lossy().into_owned();
+		let roots = txhashset_roots(&db_root, &gen);
+		let _ = fs::remove_dir_all(&db_root);
+		let roots = roots?;
+		gen.header.output_root = roots.output_root(&gen.header);
+		gen.header.range_proof_root = roots.rproof_root;
+		gen.header.kernel_root = roots.kernel_root;
+		Ok(gen)
+	}
+}
+
+/// Applies the genesis block to a fresh chain in the provided directory and
+/// reads back its txhashset roots.
+fn txhashset_roots(db_root: &str, gen: &Block) -> Result<TxHashSetRoots, Error> {
+	let chain = chain::Chain::init(
+		db_root.to_owned(),
+		Arc::new(NoopAdapter {}),
+		gen.clone(),
+		pow::verify_size,
+		Arc::new(RwLock::new(LruVerifierCache::new())),
+		false,
+	)?;
+	let txhashset = chain.txhashset();
+	let roots = txhashset.read().roots();
+	Ok(roots)
+}
+
+/// Hex of the genesis header pre-PoW, for external miners.
+pub fn pre_pow(gen: &Block) -> String {
+	util::to_hex(gen.header.pre_pow())
+}
+
+/// Mines the genesis block at the chain initial difficulty with the lean miner.
+pub fn mine(gen: &mut Block) -> Result<(), Error> {
+	let diff = gen.header.pow.total_difficulty;
+	pow::lean_pow_size(
+		&mut gen.header,
+		diff,
+		global::proofsize(),
+		global::min_edge_bits(),
+	)?;
+	Ok(())
+}
+
+/// Sets a proof of work found by an external miner on the genesis pre-PoW.
+pub fn set_proof(gen: &mut Block, nonce: u64, nonces: Vec<u64>) {
+	gen.header.pow.nonce = nonce;
+	gen.header.pow.proof = Proof::new(nonces);
+}
+
+/// Validates the proof of work and the body of the genesis block.
+pub fn validate(gen: &Block) -> Result<(), Error> {
+	pow::verify_size(&gen.header)?;
+	if gen.header.pow.to_difficulty(0) < gen.header.pow.total_difficulty {
+		return Err(Error::ArgumentError(
+			"The genesis proof of work is below the chain initial difficulty".to_owned(),
+		));
+	}
+	gen.validate(
+		&BlindingFactor::zero(),
+		Arc::new(RwLock::new(LruVerifierCache::new())),
+	)?;
+	Ok(())
+}
+
+/// Writes the genesis block to the provided file, in the binary format read
+/// through the chain file `genesis_file`, and as JSON next to it.
+pub fn write(gen: &Block, path: &str) -> Result<(), Error> {
+	let mut file = BufWriter::new(File::create(path)?);
+	ser::serialize_default(&mut file, gen)
+		.map_err(|e| Error::General(format!("Genesis serialization failed: {}", e)))?;
+	file.flush()?;
+
+	let json = serde_json::to_string_pretty(gen)
+		.map_err(|e| Error::General(format!("Genesis serialization failed: {}", e)))?;
+	fs::write(format!("{}.json", path), json)?;
+	Ok(())
+}
@@ -0,0 +1,312 @@
+// Copyright 2020 The Kepler Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Coinbase outputs built by the node itself, without a wallet listener.
+//!
+//! Building a coinbase output requires its blinding factor (for the range
+//! proof and the kernel signature), so neither an extended public key nor a
+//! `ViewKey`, which can only find and rewind outputs, is enough: they can't
+//! build an output the wallet is able to spend. The node is instead given a
+//! dedicated extended private key, typically a wallet account key, and
+//! derives a fresh key id from it for every block. Each derived id is
+//! recorded so the owner wallet can recover the outputs later.
+
+use chrono::Utc;
+use std::fs;
+use std::str::FromStr;
+
+use crate::api;
+use crate::common::types::Error;
+use crate::core::core::{Output, TxKernel};
+use crate::core::libtx::{self, ProofBuilder};
+use crate::core::ser::{self, Readable, Reader, Writeable, Writer};
+use crate::core::{consensus, global};
+use crate::keychain::extkey_bip32::{ExtendedPrivKey, ExtendedPubKey};
+use crate::keychain::{ExtKeychain, Identifier, Keychain};
+use crate::mining::mine_block::BlockFees;
+use crate::store::{self, to_key};
+use crate::util;
+use crate::util::secp::pedersen::Commitment;
+
+const DB_NAME: &str = "coinbase";
+const STORE_SUBPATH: &str = "keys";
+
+const KEY_PREFIX: u8 = b'K';
+const NEXT_INDEX_KEY: &[u8] = b"N";
+
+/// A coinbase key id derived by the node, along with the last coinbase output
+/// it was used for.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CoinbaseKey {
+	/// Key id, relative to the configured extended key
+	pub key_id: Identifier,
+	/// Height of the block the output was built for
+	pub height: u64,
+	/// Output commitment
+	pub commit: Commitment,
+	/// Output value
+	pub value: u64,
+	/// Timestamp of the derivation
+	pub timestamp: i64,
+}
+
+impl Writeable for CoinbaseKey {
+	fn write<W: Writer>(&self, writer: &mut W) -> Result<(), ser::Error> {
+		self.key_id.write(writer)?;
+		writer.write_u64(self.height)?;
+		self.commit.write(writer)?;
+		writer.write_u64(self.value)?;
+		writer.write_i64(self.timestamp)
+	}
+}
+
+impl Readable for CoinbaseKey {
+	fn read(reader: &mut dyn Reader) -> Result<CoinbaseKey, ser::Error> {
+		let key_id = Identifier::read(reader)?;
+		let height = reader.read_u64()?;
+		let commit = Commitment::read(reader)?;
+		let value = reader.read_u64()?;
+		let timestamp = reader.read_i64()?;
+		Ok(CoinbaseKey {
+			key_id,
+			height,
+			commit,
+			value,
+			timestamp,
+		})
+	}
+}
+
+impl From<CoinbaseKey> for api::CoinbaseKey {
+	fn from(key: CoinbaseKey) -> api::CoinbaseKey {
+		api::CoinbaseKey {
+			key_id: key.key_id.to_hex(),
+			height: key.height,
+			commit: util::to_hex(key.commit.0.to_vec()),
+			value: key.value,
+			timestamp: key.timestamp,
+		}
+	}
+}
+
+/// Storage of the coinbase key ids derived by the node.
+pub struct CoinbaseStore {
+	db: store::Store,
+}
+
+impl CoinbaseStore {
+	/// Instantiates a new coinbase store under the provided root path.
+	pub fn new(db_root: &str) -> Result<CoinbaseStore, store::Error> {
+		let db = store::Store::new(db_root, Some(DB_NAME), Some(STORE_SUBPATH), None)?;
+		Ok(CoinbaseStore { db: db })
+	}
+
+	/// Reserves the next derivation index, so a key id is never handed out
+	/// twice, even across restarts.
+	pub fn next_index(&self) -> Result<u32, store::Error> {
+		let batch = self.db.batch()?;
+		let index = batch.get_ser::<u32>(NEXT_INDEX_KEY)?.unwrap_or(0);
+		batch.put_ser(NEXT_INDEX_KEY, &(index + 1))?;
+		batch.commit()?;
+		Ok(index)
+	}
+
+	/// Records the coinbase output a key id was used for.
+	pub fn save_key(&self, key: &CoinbaseKey) -> Result<(), store::Error> {
+		let batch = self.db.batch()?;
+		batch.put_ser(&key_key(&key.key_id)[..], key)?;
+		batch.commit()
+	}
+
+	/// All the key ids derived so far.
+	pub fn all_keys(&self) -> Result<Vec<CoinbaseKey>, store::Error> {
+		let key = to_key(KEY_PREFIX, &mut "".to_string().into_bytes());
+		Ok(self
+			.db
+			.iter::<CoinbaseKey>(&key)?
+			.map(|(_, v)| v)
+			.collect::<Vec<_>>())
+	}
+}
+
+fn key_key(key_id: &Identifier) -> Vec<u8> {
+	to_key(KEY_PREFIX, &mut key_id.to_bytes().to_vec())
+}
+
+/// Builds coinbase outputs from a configured extended private key.
+pub struct LocalCoinbase {
+	keychain: ExtKeychain,
+	store: CoinbaseStore,
+}
+
+impl LocalCoinbase {
+	/// Loads the extended key from the provided file and opens the store
+	/// recording the derived key ids.
+	pub fn new(key_file: &str, db_root: &str) -> Result<LocalCoinbase, Error> {
+		let content = fs::read_to_string(key_file).map_err(|e| {
+			Error::Configuration(format!(
+				"Unable to read coinbase key file {}: {}",
+				key_file, e
+			))
+		})?;
+		let keychain = LocalCoinbase::parse_key(content.trim())?;
+		Ok(LocalCoinbase {
+			keychain,
+			store: CoinbaseStore::new(db_root)?,
+		})
+	}
+
+	/// Parses the extended private key of a coinbase key file. Extended
+	/// public keys are rejected with an explicit error, see the module docs.
+	pub fn parse_key(key: &str) -> Result<ExtKeychain, Error> {
+		if ExtendedPubKey::from_str(key).is_ok() {
+			return Err(Error::Configuration(
+				"The coinbase key file holds an extended public key, an extended private key \
+				 is required to build the range proof and kernel of coinbase outputs, public \
+				 or view keys can't produce outputs the wallet can spend"
+					.to_owned(),
+			));
+		}
+		let master = ExtendedPrivKey::from_str(key)
+			.map_err(|e| Error::Configuration(format!("Invalid coinbase extended key: {:?}", e)))?;
+		Ok(ExtKeychain::from_ext_key(master, global::is_floonet()))
+	}
+
+	/// Builds the coinbase output and kernel for the provided block fees.
+	/// The key id of the block fees is reused if set, otherwise a fresh one is
+	/// derived.
+	pub fn build(&self, block_fees: BlockFees) -> Result<(Output, TxKernel, BlockFees), Error> {
+		let key_id = match block_fees.key_id() {
+			Some(key_id) => key_id,
+			None => ExtKeychain::derive_key_id(2, 0, self.store.next_index()?, 0, 0),
+		};
+		let (output, kernel) = libtx::reward::output(
+			&self.keychain,
+			&ProofBuilder::new(&self.keychain),
+			&key_id,
+			block_fees.fees,
+			block_fees.height,
+			false,
+		)?;
+
+		self.store.save_key(&CoinbaseKey {
+			key_id: key_id.clone(),
+			height: block_fees.height,
+			commit: output.commitment(),
+			value: consensus::reward(block_fees.height, block_fees.fees),
+			timestamp: Utc::now().timestamp(),
+		})?;
+
+		let block_fees = BlockFees {
+			key_id: Some(key_id),
+			..block_fees
+		};
+		debug!("local coinbase: {:?}", block_fees);
+		Ok((output, kernel, block_fees))
+	}
+}
+
+impl api::CoinbaseKeys for LocalCoinbase {
+	fn get_coinbase_keys(
+		&self,
+		min_height: Option<u64>,
+	) -> Result<Vec<api::CoinbaseKey>, api::Error> {
+		let min_height = min_height.unwrap_or(0);
+		self.store
+			.all_keys()
+			.map(|keys| {
+				keys.into_iter()
+					.filter(|k| k.height >= min_height)
+					.map(|k| k.into())
+					.collect()
+			})
+			.map_err(|e| {
+				api::ErrorKind::Internal(format!("failed to read coinbase keys: {:?}", e)).into()
+			})
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::keychain::SwitchCommitmentType;
+
+	#[test]
+	fn test_parse_coinbase_key() {
+		let mut keychain = ExtKeychain::from_random_seed(false).unwrap();
+		let xprv = keychain.master.to_string();
+		let parsed = LocalCoinbase::parse_key(&xprv).unwrap();
+		assert_eq!(parsed.master.to_string(), xprv);
+
+		let xpub = keychain.pub_root_key().to_string();
+		assert!(LocalCoinbase::parse_key(&xpub).is_err());
+		assert!(LocalCoinbase::parse_key("not a key").is_err());
+	}
+
+	#[test]
+	fn test_build_coinbase() {
+		global::set_mining_mode(global::ChainTypes::AutomatedTesting);
+		let db_root = ".kepler_local_coinbase";
+		let _ = fs::remove_dir_all(db_root);
+		fs::create_dir_all(db_root).unwrap();
+		{
+			let keychain = ExtKeychain::from_random_seed(false).unwrap();
+			let key_file = format!("{}/coinbase_key", db_root);
+			fs::write(&key_file, keychain.master.to_string()).unwrap();
+			let local_coinbase = LocalCoinbase::new(&key_file, db_root).unwrap();
+
+			let block_fees = BlockFees {
+				fees: 10,
+				height: 5,
+				key_id: None,
+			};
+			let (output, kernel, block_fees) = local_coinbase.build(block_fees).unwrap();
+			assert!(output.is_coinbase());
+			assert!(kernel.is_coinbase());
+			output.verify_proof().unwrap();
+			kernel.verify().unwrap();
+			let key_id = block_fees.key_id().unwrap();
+			assert_eq!(key_id, ExtKeychain::derive_key_id(2, 0, 0, 0, 0));
+
+			// the output is the configured key's, at the derived key id
+			let commit = keychain
+				.commit(
+					consensus::reward(5, 10),
+					&key_id,
+					SwitchCommitmentType::Regular,
+				)
+				.unwrap();
+			assert_eq!(output.commitment(), commit);
+
+			// the next block gets the next index, even once reopened
+			drop(local_coinbase);
+			let local_coinbase = LocalCoinbase::new(&key_file, db_root).unwrap();
+			let block_fees = BlockFees {
+				fees: 0,
+				height: 6,
+				key_id: None,
+			};
+			let (_, _, block_fees) = local_coinbase.build(block_fees).unwrap();
+			assert_eq!(
+				block_fees.key_id().unwrap(),
+				ExtKeychain::derive_key_id(2, 0, 1, 0, 0)
+			);
+
+			let keys = local_coinbase.store.all_keys().unwrap();
+			assert_eq!(keys.len(), 2);
+			assert_eq!(keys.iter().filter(|k| k.commit == commit).count(), 1);
+		}
+		let _ = fs::remove_dir_all(db_root);
+	}
+}
@@ -32,6 +32,7 @@ use crate::core::libtx::secp_ser;
 use crate::core::libtx::ProofBuilder;
 use crate::core::{consensus, core, global};
 use crate::keychain::{ExtKeychain, Identifier, Keychain};
+use crate::mining::local_coinbase::LocalCoinbase;
 use crate::pool;
 
 /// Fees in block to use for coinbase amount calculation
@@ -67,7 +68,8 @@ pub struct CbData {
 }
 
 // Ensure a block suitable for mining is built and returned
-// If a wallet listener URL is not provided the reward will be "burnt"
+// The coinbase is built locally if a local coinbase is provided, otherwise
+// if a wallet listener URL is not provided the reward will be "burnt"
 // Warning: This call does not return until/unless a new block can be built
 pub fn get_block(
 	chain: &Arc<chain::Chain>,
@@ -75,6 +77,7 @@ pub fn get_block(
 	verifier_cache: Arc<RwLock<dyn VerifierCache>>,
 	key_id: Option<Identifier>,
 	wallet_listener_url: Option<String>,
+	local_coinbase: Option<Arc<LocalCoinbase>>,
 ) -> (core::Block, BlockFees) {
 	let wallet_retry_interval = 5;
 	// get the latest chain state and build a block on top of it
@@ -84,6 +87,7 @@ pub fn get_block(
 		verifier_cache.clone(),
 		key_id.clone(),
 		wallet_listener_url.clone(),
+		local_coinbase.clone(),
 	);
 	while let Err(e) = result {
 		let mut new_key_id = key_id.to_owned();
@@ -124,19 +128,21 @@ pub fn get_block(
 			verifier_cache.clone(),
 			new_key_id,
 			wallet_listener_url.clone(),
+			local_coinbase.clone(),
 		);
 	}
 	return result.unwrap();
 }
 
 /// Builds a new block with the chain head as previous and eligible
-/// transactions from the pool.
-fn build_block(
+/// transactions from the pool, in a single attempt.
+pub fn build_block(
 	chain: &Arc<chain::Chain>,
 	tx_pool: &Arc<RwLock<pool::TransactionPool>>,
 	verifier_cache: Arc<RwLock<dyn VerifierCache>>,
 	key_id: Option<Identifier>,
 	wallet_listener_url: Option<String>,
+	local_coinbase: Option<Arc<LocalCoinbase>>,
 ) -> Result<(core::Block, BlockFees), Error> {
 	let head = chain.head_header()?;
 
@@ -176,7 +182,8 @@ fn build_block(
 		height,
 	};
 
-	let (output, kernel, block_fees) = get_coinbase(wallet_listener_url, block_fees)?;
+	let (output, kernel, block_fees) =
+		get_coinbase(wallet_listener_url, local_coinbase, block_fees)?;
 	let mut b = core::Block::from_reward(&head, txs, output, kernel, difficulty.difficulty)?;
 
 	// making sure we're not spending time mining a useless block
@@ -237,12 +244,16 @@ fn burn_reward(block_fees: BlockFees) -> Result<(core::Output, core::TxKernel, B
 	Ok((out, kernel, block_fees))
 }
 
-// Connect to the wallet listener and get coinbase.
-// Warning: If a wallet listener URL is not provided the reward will be "burnt"
+// Build the coinbase locally or connect to the wallet listener and get coinbase.
+// Warning: If neither is provided the reward will be "burnt"
 fn get_coinbase(
 	wallet_listener_url: Option<String>,
+	local_coinbase: Option<Arc<LocalCoinbase>>,
 	block_fees: BlockFees,
 ) -> Result<(core::Output, core::TxKernel, BlockFees), Error> {
+	if let Some(local_coinbase) = local_coinbase {
+		return local_coinbase.build(block_fees);
+	}
 	match wallet_listener_url {
 		None => {
 			// Burn it
@@ -0,0 +1,289 @@
+// Copyright 2020 The Kepler Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Stratum worker credentials. Passwords are stored hashed with
+//! PBKDF2-HMAC-SHA256, one login per line of the credentials file:
+//!
+//! `login:iterations:salt_hex:hash_hex`
+//!
+//! Empty lines and lines starting with `#` are ignored.
+
+use rand::{thread_rng, Rng};
+use ring::pbkdf2;
+use std::collections::HashMap;
+use std::fs;
+use std::net::IpAddr;
+use std::num::NonZeroU32;
+use std::time::{Duration, Instant};
+
+use crate::common::types::Error;
+use crate::util;
+use crate::util::Mutex;
+
+/// Number of PBKDF2 iterations used when hashing a new password.
+const DEFAULT_ITERATIONS: u32 = 100_000;
+const SALT_LEN: usize = 16;
+const HASH_LEN: usize = 32;
+
+/// Maximum delay before an address can try to log in again after failed
+/// attempts, doubling with each failure.
+const MAX_LOGIN_RETRY_DELAY_SECS: u64 = 60;
+/// Maximum number of addresses whose failed logins are tracked.
+const MAX_THROTTLED_ADDRS: usize = 10_000;
+
+#[derive(Debug, Clone)]
+struct Credential {
+	iterations: NonZeroU32,
+	salt: Vec<u8>,
+	hash: Vec<u8>,
+}
+
+impl Credential {
+	fn verify(&self, password: &str) -> bool {
+		pbkdf2::verify(
+			pbkdf2::PBKDF2_HMAC_SHA256,
+			self.iterations,
+			&self.salt,
+			password.as_bytes(),
+			&self.hash,
+		)
+		.is_ok()
+	}
+}
+
+/// Set of logins allowed to connect to the stratum server, with their hashed
+/// passwords.
+#[derive(Debug, Clone, Default)]
+pub struct StratumCredentials {
+	logins: HashMap<String, Credential>,
+}
+
+impl StratumCredentials {
+	/// Loads the credentials from the provided file.
+	pub fn from_file(path: &str) -> Result<StratumCredentials, Error> {
+		let content = fs::read_to_string(path).map_err(|e| {
+			Error::Configuration(format!(
+				"Unable to read stratum credentials file {}: {}",
+				path, e
+			))
+		})?;
+		StratumCredentials::parse(&content)
+	}
+
+	fn parse(content: &str) -> Result<StratumCredentials, Error> {
+		let mut logins = HashMap::new();
+		for (n, line) in content.lines().enumerate() {
+			let line = line.trim();
+			if line.is_empty() || line.starts_with('#') {
+				continue;
+			}
+			let invalid = || {
+				Error::Configuration(format!(
+					"Invalid stratum credentials entry at line {}",
+					n + 1
+				))
+			};
+			let parts: Vec<&str> = line.split(':').collect();
+			if parts.len() != 4 || parts[0].is_empty() {
+				return Err(invalid());
+			}
+			let iterations = parts[1]
+				.parse::<u32>()
+				.ok()
+				.and_then(NonZeroU32::new)
+				.ok_or_else(invalid)?;
+			let salt = util::from_hex(parts[2].to_string()).map_err(|_| invalid())?;
+			let hash = util::from_hex(parts[3].to_string()).map_err(|_| invalid())?;
+			logins.insert(
+				parts[0].to_string(),
+				Credential {
+					iterations,
+					salt,
+					hash,
+				},
+			);
+		}
+		Ok(StratumCredentials { logins })
+	}
+
+	/// Whether the provided password matches the one of this login. Unknown
+	/// logins are checked against a dummy hash, so they take as long to
+	/// reject as a wrong password.
+	pub fn verify(&self, login: &str, password: &str) -> bool {
+		match self.logins.get(login) {
+			Some(c) => c.verify(password),
+			None => {
+				let dummy = Credential {
+					iterations: NonZeroU32::new(DEFAULT_ITERATIONS).unwrap(),
+					salt: vec![0; SALT_LEN],
+					hash: vec![0; HASH_LEN],
+				};
+				dummy.verify(password);
+				false
+			}
+		}
+	}
+}
+
+/// Failed login attempts from an address.
+struct Failures {
+	count: u32,
+	next_login: Instant,
+}
+
+/// Throttles the login attempts of the addresses that failed to log in,
+/// shared by all the connections so reconnecting doesn't reset it.
+pub struct LoginThrottle {
+	failures: Mutex<HashMap<IpAddr, Failures>>,
+	max_addrs: usize,
+}
+
+impl LoginThrottle {
+	/// New throttle tracking up to `MAX_THROTTLED_ADDRS` addresses.
+	pub fn new() -> LoginThrottle {
+		LoginThrottle::with_max_addrs(MAX_THROTTLED_ADDRS)
+	}
+
+	fn with_max_addrs(max_addrs: usize) -> LoginThrottle {
+		LoginThrottle {
+			failures: Mutex::new(HashMap::new()),
+			max_addrs,
+		}
+	}
+
+	/// Whether the address may try to log in, not having failed too recently.
+	pub fn allowed(&self, addr: IpAddr) -> bool {
+		self.failures
+			.lock()
+			.get(&addr)
+			.map_or(true, |f| Instant::now() >= f.next_login)
+	}
+
+	/// Delays the next login attempt from the address, doubling the delay
+	/// with each failed attempt. Once too many addresses are tracked, the
+	/// ones free to log in again are forgotten first, then the ones whose
+	/// delay ends the soonest.
+	pub fn failed(&self, addr: IpAddr) {
+		let now = Instant::now();
+		let mut failures = self.failures.lock();
+		if !failures.contains_key(&addr) && failures.len() >= self.max_addrs {
+			failures.retain(|_, f| f.next_login > now);
+			if failures.len() >= self.max_addrs {
+				let soonest = failures
+					.iter()
+					.min_by_key(|(_, f)| f.next_login)
+					.map(|(addr, _)| *addr);
+				if let Some(soonest) = soonest {
+					failures.remove(&soonest);
+				}
+			}
+		}
+		let f = failures.entry(addr).or_insert(Failures {
+			count: 0,
+			next_login: now,
+		});
+		f.count = f.count.saturating_add(1);
+		let delay = (1u64 << (f.count - 1).min(6)).min(MAX_LOGIN_RETRY_DELAY_SECS);
+		f.next_login = now + Duration::from_secs(delay);
+	}
+
+	/// Forgets the failed attempts of an address that logged in.
+	pub fn succeeded(&self, addr: IpAddr) {
+		self.failures.lock().remove(&addr);
+	}
+}
+
+/// Builds a credentials file entry for the provided login and password, with
+/// a freshly generated salt.
+pub fn credentials_entry(login: &str, password: &str) -> String {
+	let mut salt = [0u8; SALT_LEN];
+	thread_rng().fill(&mut salt);
+	let iterations = NonZeroU32::new(DEFAULT_ITERATIONS).unwrap();
+	let mut hash = [0u8; HASH_LEN];
+	pbkdf2::derive(
+		pbkdf2::PBKDF2_HMAC_SHA256,
+		iterations,
+		&salt,
+		password.as_bytes(),
+		&mut hash,
+	);
+	format!(
+		"{}:{}:{}:{}",
+		login,
+		iterations,
+		util::to_hex(salt.to_vec()),
+		util::to_hex(hash.to_vec())
+	)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_credentials_verify() {
+		let content = format!(
+			"# stratum logins\n\n{}\n{}\n",
+			credentials_entry("alice", "secret"),
+			credentials_entry("bob", "hunter2"),
+		);
+		let credentials = StratumCredentials::parse(&content).unwrap();
+		assert_eq!(credentials.logins.len(), 2);
+		assert!(credentials.verify("alice", "secret"));
+		assert!(credentials.verify("bob", "hunter2"));
+		assert!(!credentials.verify("alice", "hunter2"));
+		assert!(!credentials.verify("carol", "secret"));
+	}
+
+	#[test]
+	fn test_login_throttle() {
+		let throttle = LoginThrottle::with_max_addrs(2);
+		let addr: IpAddr = "10.0.0.1".parse().unwrap();
+		assert!(throttle.allowed(addr));
+
+		throttle.failed(addr);
+		assert!(!throttle.allowed(addr));
+		let next_login = throttle.failures.lock()[&addr].next_login;
+		assert!(next_login <= Instant::now() + Duration::from_secs(1));
+
+		// the delay doubles with each failure, up to the maximum
+		for _ in 0..10 {
+			throttle.failed(addr);
+		}
+		let next_login = throttle.failures.lock()[&addr].next_login;
+		assert!(next_login > Instant::now() + Duration::from_secs(30));
+		assert!(next_login <= Instant::now() + Duration::from_secs(MAX_LOGIN_RETRY_DELAY_SECS));
+
+		// the number of addresses tracked is bounded, the soonest allowed
+		// to log in again being forgotten first
+		let other: IpAddr = "10.0.0.2".parse().unwrap();
+		let third: IpAddr = "10.0.0.3".parse().unwrap();
+		throttle.failed(other);
+		throttle.failed(third);
+		assert_eq!(throttle.failures.lock().len(), 2);
+		assert!(!throttle.allowed(addr));
+		assert!(throttle.allowed(other));
+		assert!(!throttle.allowed(third));
+
+		throttle.succeeded(addr);
+		assert!(throttle.allowed(addr));
+	}
+
+	#[test]
+	fn test_credentials_invalid() {
+		assert!(StratumCredentials::parse("alice:secret").is_err());
+		assert!(StratumCredentials::parse("alice:0:00:00").is_err());
+		assert!(StratumCredentials::parse("alice:10:zz:00").is_err());
+	}
+}
@@ -0,0 +1,262 @@
+// Copyright 2020 The Kepler Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Storage implementation for the stratum server per-login share accounting.
+
+use crate::api;
+use crate::core::ser::{self, Readable, Reader, Writeable, Writer};
+use crate::store::{self, to_key, Error};
+
+const DB_NAME: &str = "stratum";
+const STORE_SUBPATH: &str = "logins";
+
+const LOGIN_PREFIX: u8 = b'L';
+const COUNT_KEY: &[u8] = b"C";
+
+/// Share accounting of a single stratum login, accumulated over all the
+/// workers that logged in with it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LoginStats {
+	/// Login used by the workers
+	pub login: String,
+	/// Number of valid shares submitted
+	pub num_accepted: u64,
+	/// Number of invalid shares submitted
+	pub num_rejected: u64,
+	/// Number of shares submitted too late
+	pub num_stale: u64,
+	/// Number of valid blocks found
+	pub num_blocks_found: u64,
+	/// Timestamp of the last share submitted
+	pub last_share: i64,
+}
+
+impl LoginStats {
+	/// Empty share accounting for a login.
+	pub fn new(login: &str) -> LoginStats {
+		LoginStats {
+			login: login.to_owned(),
+			num_accepted: 0,
+			num_rejected: 0,
+			num_stale: 0,
+			num_blocks_found: 0,
+			last_share: 0,
+		}
+	}
+
+	/// Adds the shares accounted in `other` to this login.
+	pub fn add(&mut self, other: &LoginStats) {
+		self.num_accepted += other.num_accepted;
+		self.num_rejected += other.num_rejected;
+		self.num_stale += other.num_stale;
+		self.num_blocks_found += other.num_blocks_found;
+		self.last_share = self.last_share.max(other.last_share);
+	}
+}
+
+impl Writeable for LoginStats {
+	fn write<W: Writer>(&self, writer: &mut W) -> Result<(), ser::Error> {
+		writer.write_bytes(&self.login)?;
+		writer.write_u64(self.num_accepted)?;
+		writer.write_u64(self.num_rejected)?;
+		writer.write_u64(self.num_stale)?;
+		writer.write_u64(self.num_blocks_found)?;
+		writer.write_i64(self.last_share)
+	}
+}
+
+impl Readable for LoginStats {
+	fn read(reader: &mut dyn Reader) -> Result<LoginStats, ser::Error> {
+		let login = reader.read_bytes_len_prefix()?;
+		let num_accepted = reader.read_u64()?;
+		let num_rejected = reader.read_u64()?;
+		let num_stale = reader.read_u64()?;
+		let num_blocks_found = reader.read_u64()?;
+		let last_share = reader.read_i64()?;
+		let login = String::from_utf8(login).map_err(|_| ser::Error::CorruptedData)?;
+		Ok(LoginStats {
+			login,
+			num_accepted,
+			num_rejected,
+			num_stale,
+			num_blocks_found,
+			last_share,
+		})
+	}
+}
+
+impl From<LoginStats> for api::StratumLoginStats {
+	fn from(stats: LoginStats) -> api::StratumLoginStats {
+		api::StratumLoginStats {
+			login: stats.login,
+			num_accepted: stats.num_accepted,
+			num_rejected: stats.num_rejected,
+			num_stale: stats.num_stale,
+			num_blocks_found: stats.num_blocks_found,
+			last_share: stats.last_share,
+		}
+	}
+}
+
+/// Storage facility for the stratum login share accounting.
+pub struct StratumStore {
+	db: store::Store,
+}
+
+impl StratumStore {
+	/// Instantiates a new stratum store under the provided root path.
+	pub fn new(db_root: &str) -> Result<StratumStore, Error> {
+		let db = store::Store::new(db_root, Some(DB_NAME), Some(STORE_SUBPATH), None)?;
+		let store = StratumStore { db: db };
+		if store.db.get_ser::<u64>(COUNT_KEY)?.is_none() {
+			let count = store.all_logins()?.len() as u64;
+			let batch = store.db.batch()?;
+			batch.put_ser(COUNT_KEY, &count)?;
+			batch.commit()?;
+		}
+		Ok(store)
+	}
+
+	/// Share accounting of a login, if any share has been recorded for it.
+	pub fn get_login(&self, login: &str) -> Result<Option<LoginStats>, Error> {
+		self.db.get_ser(&login_key(login)[..])
+	}
+
+	/// Share accounting of all known logins.
+	pub fn all_logins(&self) -> Result<Vec<LoginStats>, Error> {
+		let key = to_key(LOGIN_PREFIX, &mut "".to_string().into_bytes());
+		Ok(self
+			.db
+			.iter::<LoginStats>(&key)?
+			.map(|(_, v)| v)
+			.collect::<Vec<_>>())
+	}
+
+	/// Adds the shares accounted since the last call to the stored ones, in
+	/// a single batch. Logins not stored yet are skipped once `max_logins`
+	/// are, returns the number of logins skipped.
+	pub fn add_shares(&self, shares: &[LoginStats], max_logins: u64) -> Result<usize, Error> {
+		let batch = self.db.batch()?;
+		let mut count = batch.get_ser::<u64>(COUNT_KEY)?.unwrap_or(0);
+		let mut skipped = 0;
+		for added in shares {
+			let key = login_key(&added.login);
+			let mut stats = match batch.get_ser::<LoginStats>(&key[..])? {
+				Some(stats) => stats,
+				None if count < max_logins => {
+					count += 1;
+					LoginStats::new(&added.login)
+				}
+				None => {
+					skipped += 1;
+					continue;
+				}
+			};
+			stats.add(added);
+			batch.put_ser(&key[..], &stats)?;
+		}
+		batch.put_ser(COUNT_KEY, &count)?;
+		batch.commit()?;
+		Ok(skipped)
+	}
+}
+
+impl api::StratumAccounts for StratumStore {
+	fn get_login_stats(
+		&self,
+		login: Option<String>,
+	) -> Result<Vec<api::StratumLoginStats>, api::Error> {
+		let logins = match login {
+			Some(login) => self.get_login(&login).map(|l| l.into_iter().collect()),
+			None => self.all_logins(),
+		};
+		logins
+			.map(|l| l.into_iter().map(|s| s.into()).collect())
+			.map_err(|e| {
+				api::ErrorKind::Internal(format!("failed to read stratum logins: {:?}", e)).into()
+			})
+	}
+}
+
+fn login_key(login: &str) -> Vec<u8> {
+	to_key(LOGIN_PREFIX, &mut login.as_bytes().to_vec())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use std::fs;
+
+	fn shares(login: &str, accepted: u64, rejected: u64, last_share: i64) -> LoginStats {
+		let mut stats = LoginStats::new(login);
+		stats.num_accepted = accepted;
+		stats.num_rejected = rejected;
+		stats.last_share = last_share;
+		stats
+	}
+
+	#[test]
+	fn test_add_shares() {
+		let db_root = ".kepler_stratum_store";
+		let _ = fs::remove_dir_all(db_root);
+		{
+			let store = StratumStore::new(db_root).unwrap();
+			let added = vec![shares("alice", 3, 1, 10), shares("bob", 1, 0, 12)];
+			assert_eq!(store.add_shares(&added, 10).unwrap(), 0);
+			let added = vec![shares("alice", 2, 0, 20)];
+			assert_eq!(store.add_shares(&added, 10).unwrap(), 0);
+
+			// accumulated over the flushes, keeping the latest share time
+			let alice = store.get_login("alice").unwrap().unwrap();
+			assert_eq!(alice, shares("alice", 5, 1, 20));
+			let bob = store.get_login("bob").unwrap().unwrap();
+			assert_eq!(bob, shares("bob", 1, 0, 12));
+			assert_eq!(store.all_logins().unwrap().len(), 2);
+		}
+		let _ = fs::remove_dir_all(db_root);
+	}
+
+	#[test]
+	fn test_add_shares_max_logins() {
+		let db_root = ".kepler_stratum_store_max";
+		let _ = fs::remove_dir_all(db_root);
+		{
+			let store = StratumStore::new(db_root).unwrap();
+			let added = vec![
+				shares("alice", 1, 0, 10),
+				shares("bob", 1, 0, 10),
+				shares("carol", 1, 0, 10),
+			];
+			// new logins past the cap are skipped
+			assert_eq!(store.add_shares(&added, 2).unwrap(), 1);
+			assert_eq!(store.all_logins().unwrap().len(), 2);
+			assert!(store.get_login("carol").unwrap().is_none());
+
+			// while the ones already stored keep being accounted
+			let added = vec![shares("alice", 1, 0, 20), shares("dave", 1, 0, 20)];
+			assert_eq!(store.add_shares(&added, 2).unwrap(), 1);
+			assert_eq!(store.get_login("alice").unwrap().unwrap().num_accepted, 2);
+			assert!(store.get_login("dave").unwrap().is_none());
+		}
+		{
+			// the count of stored logins survives a restart
+			let store = StratumStore::new(db_root).unwrap();
+			let added = vec![shares("carol", 1, 0, 30)];
+			assert_eq!(store.add_shares(&added, 2).unwrap(), 1);
+			assert_eq!(store.add_shares(&added, 3).unwrap(), 0);
+			assert_eq!(store.all_logins().unwrap().len(), 3);
+		}
+		let _ = fs::remove_dir_all(db_root);
+	}
+}
@@ -17,35 +17,65 @@
 use futures::channel::mpsc;
 use futures::pin_mut;
 use futures::{SinkExt, StreamExt, TryStreamExt};
+use tokio::io::{AsyncRead, AsyncWrite};
 use tokio::net::TcpListener;
 use tokio::runtime::Runtime;
+use tokio::sync::Semaphore;
+use tokio_rustls::TlsAcceptor;
 use tokio_util::codec::{Framed, LinesCodec};
 
-use crate::util::RwLock;
+use crate::util::{Mutex, RwLock, StopState};
 use chrono::prelude::Utc;
 use serde;
 use serde_json;
 use serde_json::Value;
 use std::collections::HashMap;
-use std::net::SocketAddr;
+use std::net::{IpAddr, SocketAddr};
 use std::sync::Arc;
-use std::time::{Duration, SystemTime};
-use std::{cmp, thread};
+use std::time::{Duration, Instant, SystemTime};
+use std::{cmp, mem, thread};
 
+use crate::api::TLSConfig;
 use crate::chain::{self, SyncState};
 use crate::common::stats::{StratumStats, WorkerStats};
-use crate::common::types::StratumServerConfig;
+use crate::common::types::{Error, StratumServerConfig};
 use crate::core::core::hash::Hashed;
 use crate::core::core::verifier_cache::VerifierCache;
 use crate::core::core::Block;
 use crate::core::{pow, ser};
 use crate::keychain;
+use crate::mining::local_coinbase::LocalCoinbase;
 use crate::mining::mine_block;
+use crate::mining::stratum_auth::{LoginThrottle, StratumCredentials};
+use crate::mining::stratum_store::{LoginStats, StratumStore};
 use crate::pool;
 use crate::util;
 
 type Tx = mpsc::UnboundedSender<String>;
 
+/// Maximum factor by which a worker share difficulty can move up or down in a
+/// single vardiff retarget.
+const VARDIFF_MAX_ADJUSTMENT: f64 = 4.0;
+
+/// Share rate deviation from the target (as a fraction of the target) within
+/// which we keep the current worker share difficulty, to avoid resending jobs
+/// for insignificant changes.
+const VARDIFF_VARIANCE: f64 = 0.25;
+
+/// How often the main loop checks whether worker difficulties need a retarget.
+const VARDIFF_CHECK_INTERVAL_SECS: i64 = 1;
+
+/// How often the shares accounted per login are written to the store.
+const ACCOUNTING_FLUSH_INTERVAL_SECS: i64 = 5;
+
+/// Maximum number of logins with a share accounting in the store, shares of
+/// new logins being ignored once reached.
+const MAX_ACCOUNTED_LOGINS: u64 = 10_000;
+
+/// Maximum number of password checks running at once, the other logins
+/// waiting for their turn.
+const MAX_CONCURRENT_LOGINS: usize = 2;
+
 // ----------------------------------------
 // http://www.jsonrpc.org/specification
 // RPC Methods
@@ -125,6 +155,24 @@ impl RpcError {
 			message: "Invalid Request".to_string(),
 		}
 	}
+	pub fn login_first() -> Self {
+		RpcError {
+			code: -32500,
+			message: "login first".to_string(),
+		}
+	}
+	pub fn invalid_login() -> Self {
+		RpcError {
+			code: -32504,
+			message: "Invalid login or password".to_string(),
+		}
+	}
+	pub fn login_throttled() -> Self {
+		RpcError {
+			code: -32505,
+			message: "Too many failed logins - Please wait".to_string(),
+		}
+	}
 }
 
 impl From<RpcError> for Value {
@@ -175,6 +223,31 @@ pub struct WorkerStatus {
 	accepted: u64,
 	rejected: u64,
 	stale: u64,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	login: Option<LoginStatus>,
+}
+
+/// Share accounting of the login a worker is using, across all the workers
+/// and sessions that used that login
+#[derive(Serialize, Deserialize, Debug)]
+pub struct LoginStatus {
+	login: String,
+	accepted: u64,
+	rejected: u64,
+	stale: u64,
+	blocks_found: u64,
+}
+
+impl From<LoginStats> for LoginStatus {
+	fn from(stats: LoginStats) -> LoginStatus {
+		LoginStatus {
+			login: stats.login,
+			accepted: stats.num_accepted,
+			rejected: stats.num_rejected,
+			stale: stats.num_stale,
+			blocks_found: stats.num_blocks_found,
+		}
+	}
 }
 
 struct State {
@@ -202,10 +275,23 @@ impl State {
 
 struct Handler {
 	id: String,
+	config: StratumServerConfig,
 	workers: Arc<WorkersList>,
 	sync_state: Arc<SyncState>,
 	chain: Arc<chain::Chain>,
 	current_state: Arc<RwLock<State>>,
+	// logins allowed to connect, if authentication is enabled
+	credentials: Option<StratumCredentials>,
+	// delays the logins from the addresses that failed to log in
+	login_throttle: LoginThrottle,
+	// bounds the number of password checks running at once
+	login_checks: Semaphore,
+	// per-login share accounting
+	store: Option<Arc<StratumStore>>,
+	// shares accounted since the last write to the store, per login
+	pending_shares: Mutex<HashMap<String, LoginStats>>,
+	// builds the coinbase without the wallet listener, if configured
+	local_coinbase: Option<Arc<LocalCoinbase>>,
 }
 
 impl Handler {
@@ -213,15 +299,26 @@ impl Handler {
 		id: String,
 		stratum_stats: Arc<RwLock<StratumStats>>,
 		sync_state: Arc<SyncState>,
-		minimum_share_difficulty: u64,
+		config: StratumServerConfig,
 		chain: Arc<chain::Chain>,
+		store: Option<Arc<StratumStore>>,
+		local_coinbase: Option<Arc<LocalCoinbase>>,
+		credentials: Option<StratumCredentials>,
 	) -> Self {
+		let minimum_share_difficulty = config.minimum_share_difficulty;
 		Handler {
 			id: id,
+			config: config,
 			workers: Arc::new(WorkersList::new(stratum_stats.clone())),
 			sync_state: sync_state,
 			chain: chain,
 			current_state: Arc::new(RwLock::new(State::new(minimum_share_difficulty))),
+			credentials: credentials,
+			login_throttle: LoginThrottle::new(),
+			login_checks: Semaphore::new(MAX_CONCURRENT_LOGINS),
+			store: store,
+			pending_shares: Mutex::new(HashMap::new()),
+			local_coinbase: local_coinbase,
 		}
 	}
 	pub fn from_stratum(stratum: &StratumServer) -> Self {
@@ -229,18 +326,97 @@ impl Handler {
 			stratum.id.clone(),
 			stratum.stratum_stats.clone(),
 			stratum.sync_state.clone(),
-			stratum.config.minimum_share_difficulty,
+			stratum.config.clone(),
 			stratum.chain.clone(),
+			stratum.store.clone(),
+			stratum.local_coinbase.clone(),
+			stratum.credentials.clone(),
 		)
 	}
 	fn handle_rpc_requests(&self, request: RpcRequest, worker_id: usize) -> String {
+		let response = match request.method.as_str() {
+			"login" | "keepalive" => Ok(()),
+			_ => self.check_authenticated(worker_id),
+		}
+		.and_then(|_| self.dispatch_rpc_request(&request, worker_id));
+
+		// Package the reply as RpcResponse json
+		let resp = match response {
+			Err(rpc_error) => RpcResponse {
+				id: request.id,
+				jsonrpc: String::from("2.0"),
+				method: request.method,
+				result: None,
+				error: Some(rpc_error.into()),
+			},
+			Ok(response) => RpcResponse {
+				id: request.id,
+				jsonrpc: String::from("2.0"),
+				method: request.method,
+				result: Some(response),
+				error: None,
+			},
+		};
+		serde_json::to_string(&resp).unwrap()
+	}
+	// When authentication is enabled, workers have to log in before doing
+	// anything else
+	fn check_authenticated(&self, worker_id: usize) -> Result<(), RpcError> {
+		if self.credentials.is_some() && !self.workers.get_worker(worker_id)?.authenticated {
+			return Err(RpcError::login_first());
+		}
+		Ok(())
+	}
+	// Update the share accounting of the login used by a worker, if any. Only
+	// kept in memory until the next flush to the store.
+	fn account_share(&self, worker_id: usize, f: impl FnOnce(&mut LoginStats)) {
+		if self.store.is_none() {
+			return;
+		}
+		if let Ok(Worker {
+			login: Some(login), ..
+		}) = self.workers.get_worker(worker_id)
+		{
+			let mut pending_shares = self.pending_shares.lock();
+			let stats = pending_shares
+				.entry(login.clone())
+				.or_insert_with(|| LoginStats::new(&login));
+			f(stats);
+			stats.last_share = Utc::now().timestamp();
+		}
+	}
+	// Write the shares accounted since the last flush to the store
+	fn flush_shares(&self) {
+		let store = match self.store {
+			Some(ref store) => store,
+			None => return,
+		};
+		let shares = mem::take(&mut *self.pending_shares.lock());
+		if shares.is_empty() {
+			return;
+		}
+		let shares = shares.into_values().collect::<Vec<_>>();
+		match store.add_shares(&shares, MAX_ACCOUNTED_LOGINS) {
+			Ok(0) => {}
+			Ok(skipped) => warn!(
+				"Stratum: too many logins accounted, ignored the shares of {} new ones",
+				skipped
+			),
+			Err(e) => error!("Stratum: failed to save share accounting: {:?}", e),
+		}
+	}
+	fn dispatch_rpc_request(
+		&self,
+		request: &RpcRequest,
+		worker_id: usize,
+	) -> Result<Value, RpcError> {
 		self.workers.last_seen(worker_id);
 
 		// Call the handler function for requested method
-		let response = match request.method.as_str() {
-			"login" => self.handle_login(request.params, worker_id),
+		match request.method.as_str() {
+			"login" => self.handle_login(request.params.clone(), worker_id),
 			"submit" => {
-				let res = self.handle_submit(request.params, worker_id);
+				let res = self.handle_submit(request.params.clone(), worker_id);
 				// this key_id has been used now, reset
 				if let Ok((_, true)) = res {
 					self.current_state.write().current_key_id = None;
@@ -252,7 +428,7 @@ impl Handler {
 				if self.sync_state.is_syncing() {
 					Err(RpcError::node_is_syncing())
 				} else {
-					self.handle_getjobtemplate()
+					self.handle_getjobtemplate(worker_id)
 				}
 			}
 			"status" => self.handle_status(worker_id),
@@ -260,29 +436,25 @@ impl Handler {
 				// Called undefined method
 				Err(RpcError::method_not_found())
 			}
-		};
-
-		// Package the reply as RpcResponse json
-		let resp = match response {
-			Err(rpc_error) => RpcResponse {
-				id: request.id,
-				jsonrpc: String::from("2.0"),
-				method: request.method,
-				result: None,
-				error: Some(rpc_error.into()),
-			},
-			Ok(response) => RpcResponse {
-				id: request.id,
-				jsonrpc: String::from("2.0"),
-				method: request.method,
-				result: Some(response),
-				error: None,
-			},
-		};
-		serde_json::to_string(&resp).unwrap()
+		}
 	}
 	fn handle_login(&self, params: Option<Value>, worker_id: usize) -> Result<Value, RpcError> {
 		let params: LoginParams = parse_params(params)?;
+		if let Some(ref credentials) = self.credentials {
+			let addr = self.workers.get_worker(worker_id)?.addr;
+			if !self.login_throttle.allowed(addr) {
+				return Err(RpcError::login_throttled());
+			}
+			if !credentials.verify(&params.login, &params.pass) {
+				warn!(
+					"(Server ID: {}) Worker {} ({}) failed to log in as {}",
+					self.id, worker_id, addr, params.login
+				);
+				self.login_throttle.failed(addr);
+				return Err(RpcError::invalid_login());
+			}
+			self.login_throttle.succeeded(addr);
+		}
 		self.workers.login(worker_id, params.login, params.agent)?;
 		return Ok("ok".into());
 	}
@@ -305,18 +477,29 @@ impl Handler {
 				.unwrap()
 				.header
 				.height,
-			difficulty: stats.pow_difficulty,
+			difficulty: self.worker_difficulty(worker_id)?,
 			accepted: stats.num_accepted,
 			rejected: stats.num_rejected,
 			stale: stats.num_stale,
+			login: self.login_status(worker_id),
 		};
 		let response = serde_json::to_value(&status).unwrap();
 		return Ok(response);
 	}
+	// Share accounting of the login used by a worker, if any
+	fn login_status(&self, worker_id: usize) -> Option<LoginStatus> {
+		let login = self.workers.get_worker(worker_id).ok()?.login?;
+		let stats = match self.store {
+			Some(ref store) => store.get_login(&login).ok()?,
+			None => None,
+		};
+		Some(stats.unwrap_or_else(|| LoginStats::new(&login)).into())
+	}
+
 	// Handle GETJOBTEMPLATE message
-	fn handle_getjobtemplate(&self) -> Result<Value, RpcError> {
+	fn handle_getjobtemplate(&self, worker_id: usize) -> Result<Value, RpcError> {
 		// Build a JobTemplate from a BlockHeader and return JSON
-		let job_template = self.build_block_template();
+		let job_template = self.build_block_template(self.worker_difficulty(worker_id)?);
 		let response = serde_json::to_value(&job_template).unwrap();
 		debug!(
 			"(Server ID: {}) sending block {} with id {} to single worker",
@@ -325,8 +508,25 @@ impl Handler {
 		return Ok(response);
 	}
 
+	// Share difficulty currently requested from a worker. This is the global
+	// minimum share difficulty unless vardiff is enabled, in which case it is
+	// the worker own difficulty, bounded by the minimum and the difficulty of
+	// the block being mined.
+	fn worker_difficulty(&self, worker_id: usize) -> Result<u64, RpcError> {
+		let state = self.current_state.read();
+		if !self.config.enable_vardiff {
+			return Ok(state.minimum_share_difficulty);
+		}
+		let worker = self.workers.get_worker(worker_id)?;
+		Ok(clamp_share_difficulty(
+			worker.share_difficulty,
+			state.minimum_share_difficulty,
+			state.current_difficulty,
+		))
+	}
+
 	// Build and return a JobTemplate for mining the current block
-	fn build_block_template(&self) -> JobTemplate {
+	fn build_block_template(&self, difficulty: u64) -> JobTemplate {
 		let bh = self
 			.current_state
 			.read()
@@ -346,7 +546,7 @@ impl Handler {
 		let job_template = JobTemplate {
 			height: bh.height,
 			job_id: (self.current_state.read().current_block_versions.len() - 1) as u64,
-			difficulty: self.current_state.read().minimum_share_difficulty,
+			difficulty,
 			pre_pow,
 		};
 		return job_template;
@@ -376,6 +576,7 @@ impl Handler {
 					self.id, params.height, params.edge_bits, params.nonce, params.job_id,
 				);
 			self.workers.update_stats(worker_id, |ws| ws.num_stale += 1);
+			self.account_share(worker_id, |ls| ls.num_stale += 1);
 			return Err(RpcError::too_late());
 		}
 
@@ -396,20 +597,35 @@ impl Handler {
 				);
 			self.workers
 				.update_stats(worker_id, |worker_stats| worker_stats.num_rejected += 1);
+			self.account_share(worker_id, |ls| ls.num_rejected += 1);
 			return Err(RpcError::cannot_validate());
 		}
 
 		// Get share difficulty
 		share_difficulty = b.header.pow.to_difficulty(b.header.height).to_num();
+		// Difficulty this worker has been asked for. Following a vardiff retarget
+		// we keep accepting shares at the previous difficulty, as the worker may
+		// still be working on a job sent before the change.
+		let worker = self.workers.get_worker(worker_id)?;
+		let required_difficulty = if self.config.enable_vardiff {
+			clamp_share_difficulty(
+				cmp::min(worker.share_difficulty, worker.prev_share_difficulty),
+				state.minimum_share_difficulty,
+				state.current_difficulty,
+			)
+		} else {
+			state.minimum_share_difficulty
+		};
 		// If the difficulty is too low its an error
-		if share_difficulty < state.minimum_share_difficulty {
+		if share_difficulty < required_difficulty {
 			// Return error status
 			error!(
 					"(Server ID: {}) Share at height {}, hash {}, edge_bits {}, nonce {}, job_id {} rejected due to low difficulty: {}/{}",
-					self.id, params.height, b.hash(), params.edge_bits, params.nonce, params.job_id, share_difficulty, state.minimum_share_difficulty,
+					self.id, params.height, b.hash(), params.edge_bits, params.nonce, params.job_id, share_difficulty, required_difficulty,
 				);
 			self.workers
 				.update_stats(worker_id, |worker_stats| worker_stats.num_rejected += 1);
+			self.account_share(worker_id, |ls| ls.num_rejected += 1);
 			return Err(RpcError::too_low_difficulty());
 		}
 
@@ -432,11 +648,13 @@ impl Handler {
 					);
 				self.workers
 					.update_stats(worker_id, |worker_stats| worker_stats.num_rejected += 1);
+				self.account_share(worker_id, |ls| ls.num_rejected += 1);
 				return Err(RpcError::cannot_validate());
 			}
 			share_is_block = true;
 			self.workers
 				.update_stats(worker_id, |worker_stats| worker_stats.num_blocks_found += 1);
+			self.account_share(worker_id, |ls| ls.num_blocks_found += 1);
 			// Log message to make it obvious we found a block
 			let stats = self.workers.get_stats(worker_id)?;
 			warn!(
@@ -464,11 +682,11 @@ impl Handler {
 					);
 				self.workers
 					.update_stats(worker_id, |worker_stats| worker_stats.num_rejected += 1);
+				self.account_share(worker_id, |ls| ls.num_rejected += 1);
 				return Err(RpcError::cannot_validate());
 			}
 		}
 		// Log this as a valid share
-		let worker = self.workers.get_worker(worker_id)?;
 		let submitted_by = match worker.login {
 			None => worker.id.to_string(),
 			Some(login) => login.clone(),
@@ -488,6 +706,8 @@ impl Handler {
 			);
 		self.workers
 			.update_stats(worker_id, |worker_stats| worker_stats.num_accepted += 1);
+		self.account_share(worker_id, |ls| ls.num_accepted += 1);
+		self.workers.add_vardiff_share(worker_id);
 		let submit_response;
 		if share_is_block {
 			submit_response = format!("blockfound - {}", b.hash().to_hex());
@@ -500,11 +720,9 @@ impl Handler {
 		));
 	} // handle submit a solution
 
-	fn broadcast_job(&self) {
-		debug!("broadcast job");
-		// Package new block into RpcRequest
-		let job_template = self.build_block_template();
-		let job_template_json = serde_json::to_string(&job_template).unwrap();
+	// Package a JobTemplate into a "job" RpcRequest
+	fn build_job_request(&self, job_template: &JobTemplate) -> String {
+		let job_template_json = serde_json::to_string(job_template).unwrap();
 		// Issue #1159 - use a serde_json Value type to avoid extra quoting
 		let job_template_value: Value = serde_json::from_str(&job_template_json).unwrap();
 		let job_request = RpcRequest {
@@ -513,7 +731,22 @@ impl Handler {
 			method: String::from("job"),
 			params: Some(job_template_value),
 		};
-		let job_request_json = serde_json::to_string(&job_request).unwrap();
+		serde_json::to_string(&job_request).unwrap()
+	}
+
+	fn broadcast_job(&self) {
+		debug!("broadcast job");
+		if self.config.enable_vardiff {
+			// Every worker mines at its own difficulty, send each one its job
+			for worker_id in self.workers.ids() {
+				self.send_job(worker_id);
+			}
+			return;
+		}
+		// Package new block into RpcRequest
+		let difficulty = self.current_state.read().minimum_share_difficulty;
+		let job_template = self.build_block_template(difficulty);
+		let job_request_json = self.build_job_request(&job_template);
 		debug!(
 			"(Server ID: {}) sending block {} with id {} to stratum clients",
 			self.id, job_template.height, job_template.job_id,
@@ -521,17 +754,63 @@ impl Handler {
 		self.workers.broadcast(job_request_json.clone());
 	}
 
+	// Send the current job to a single worker, at that worker difficulty
+	fn send_job(&self, worker_id: usize) {
+		let difficulty = match self.worker_difficulty(worker_id) {
+			Ok(d) => d,
+			Err(_) => return,
+		};
+		let job_template = self.build_block_template(difficulty);
+		let job_request_json = self.build_job_request(&job_template);
+		debug!(
+			"(Server ID: {}) sending block {} with id {} and difficulty {} to worker {}",
+			self.id, job_template.height, job_template.job_id, difficulty, worker_id,
+		);
+		self.workers.send_to(worker_id, job_request_json);
+	}
+
+	// Adjust the share difficulty of every worker whose vardiff window has
+	// elapsed, and send a fresh job to the ones whose difficulty changed
+	fn retarget_workers(&self) {
+		let (minimum, maximum) = {
+			let state = self.current_state.read();
+			// no block to mine yet
+			if state.current_difficulty == <u64>::max_value() {
+				return;
+			}
+			(state.minimum_share_difficulty, state.current_difficulty)
+		};
+		let retargeted = self.workers.retarget(
+			self.config.vardiff_target_shares_per_minute,
+			self.config.vardiff_retarget_time,
+			minimum,
+			maximum,
+		);
+		for worker_id in retargeted {
+			self.send_job(worker_id);
+		}
+	}
+
 	pub fn run(
 		&self,
 		config: &StratumServerConfig,
 		tx_pool: &Arc<RwLock<pool::TransactionPool>>,
 		verifier_cache: Arc<RwLock<dyn VerifierCache>>,
+		stop_state: &StopState,
 	) {
 		debug!("Run main loop");
 		let mut deadline: i64 = 0;
+		let mut next_retarget: i64 = 0;
+		let mut next_flush: i64 = 0;
 		let mut head = self.chain.head().unwrap();
 		let mut current_hash = head.prev_block_h;
 		loop {
+			// write the shares accounted since the last flush before exiting
+			if stop_state.is_stopped() {
+				self.flush_shares();
+				break;
+			}
+
 			// get the latest chain state
 			head = self.chain.head().unwrap();
 			let latest_hash = head.last_block_h;
@@ -547,8 +826,10 @@ impl Handler {
 					debug!("resend updated block");
 					let mut state = self.current_state.write();
 					let mut wallet_listener_url: Option<String> = None;
+					let mut local_coinbase = None;
 					if !config.burn_reward {
 						wallet_listener_url = Some(config.wallet_listener_url.clone());
+						local_coinbase = self.local_coinbase.clone();
 					}
 					// If this is a new block, clear the current_block version history
 					let clear_blocks = current_hash != latest_hash;
@@ -560,6 +841,7 @@ impl Handler {
 						verifier_cache.clone(),
 						state.current_key_id.clone(),
 						wallet_listener_url,
+						local_coinbase,
 					);
 
 					state.current_difficulty =
@@ -588,6 +870,16 @@ impl Handler {
 				self.broadcast_job();
 			}
 
+			if config.enable_vardiff && Utc::now().timestamp() >= next_retarget {
+				self.retarget_workers();
+				next_retarget = Utc::now().timestamp() + VARDIFF_CHECK_INTERVAL_SECS;
+			}
+
+			if Utc::now().timestamp() >= next_flush {
+				self.flush_shares();
+				next_flush = Utc::now().timestamp() + ACCOUNTING_FLUSH_INTERVAL_SECS;
+			}
+
 			// sleep before restarting loop
 			thread::sleep(Duration::from_millis(5));
 		} // Main Loop
@@ -596,7 +888,11 @@ impl Handler {
 
 // ----------------------------------------
 // Worker Factory Thread Function
-fn accept_connections(listen_addr: SocketAddr, handler: Arc<Handler>) {
+fn accept_connections(
+	listen_addr: SocketAddr,
+	tls_acceptor: Option<TlsAcceptor>,
+	handler: Arc<Handler>,
+) {
 	info!("Start tokio stratum server");
 	let task = async move {
 		let mut listener = TcpListener::bind(&listen_addr).await.expect(&format!(
@@ -608,51 +904,25 @@ fn accept_connections(listen_addr: SocketAddr, handler: Arc<Handler>) {
 			.filter_map(|s| async { s.map_err(|e| error!("accept error = {:?}", e)).ok() })
 			.for_each(move |socket| {
 				let handler = handler.clone();
+				let tls_acceptor = tls_acceptor.clone();
 				async move {
-					// Spawn a task to process the connection
-					let (tx, mut rx) = mpsc::unbounded();
-
-					let worker_id = handler.workers.add_worker(tx);
-					info!("Worker {} connected", worker_id);
-
-					let framed = Framed::new(socket, LinesCodec::new());
-					let (mut writer, mut reader) = framed.split();
-
-					let h = handler.clone();
-					let read = async move {
-						while let Some(line) = reader
-							.try_next()
-							.await
-							.map_err(|e| error!("error reading line: {}", e))?
-						{
-							let request = serde_json::from_str(&line)
-								.map_err(|e| error!("error serializing line: {}", e))?;
-							let resp = h.handle_rpc_requests(request, worker_id);
-							h.workers.send_to(worker_id, resp);
-						}
-
-						Result::<_, ()>::Ok(())
+					let addr = match socket.peer_addr() {
+						Ok(addr) => addr.ip(),
+						Err(e) => return error!("Stratum: no peer address: {}", e),
 					};
-
-					let write = async move {
-						while let Some(line) = rx.next().await {
-							let line = line + "\n";
-							writer
-								.send(line)
-								.await
-								.map_err(|e| error!("error writing line: {}", e))?;
+					match tls_acceptor {
+						None => handle_connection(socket, addr, handler),
+						Some(acceptor) => {
+							// Run the handshake in its own task so a slow client
+							// does not hold up the accept loop
+							tokio::spawn(async move {
+								match acceptor.accept(socket).await {
+									Ok(stream) => handle_connection(stream, addr, handler),
+									Err(e) => error!("Stratum: TLS handshake failed: {}", e),
+								}
+							});
 						}
-
-						Result::<_, ()>::Ok(())
-					};
-
-					let task = async move {
-						pin_mut!(read, write);
-						futures::future::select(read, write).await;
-						handler.workers.remove_worker(worker_id);
-						info!("Worker {} disconnected", worker_id);
-					};
-					tokio::spawn(task);
+					}
 				}
 			});
 		server.await
@@ -662,6 +932,68 @@ fn accept_connections(listen_addr: SocketAddr, handler: Arc<Handler>) {
 	rt.block_on(task);
 }
 
+// Spawn a task to process a single (plain or TLS) worker connection
+fn handle_connection<S>(socket: S, addr: IpAddr, handler: Arc<Handler>)
+where
+	S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+	let (tx, mut rx) = mpsc::unbounded();
+
+	let worker_id = handler
+		.workers
+		.add_worker(tx, addr, handler.config.minimum_share_difficulty);
+	info!("Worker {} connected from {}", worker_id, addr);
+
+	let framed = Framed::new(socket, LinesCodec::new());
+	let (mut writer, mut reader) = framed.split();
+
+	let h = handler.clone();
+	let read = async move {
+		while let Some(line) = reader
+			.try_next()
+			.await
+			.map_err(|e| error!("error reading line: {}", e))?
+		{
+			let request: RpcRequest =
+				serde_json::from_str(&line).map_err(|e| error!("error serializing line: {}", e))?;
+			let resp = if request.method == "login" {
+				// Checking the password is slow on purpose, keep it off the
+				// threads serving the connections and only run a few at once
+				let _permit = h.login_checks.acquire().await;
+				let h = h.clone();
+				tokio::task::spawn_blocking(move || h.handle_rpc_requests(request, worker_id))
+					.await
+					.map_err(|e| error!("error handling login: {}", e))?
+			} else {
+				h.handle_rpc_requests(request, worker_id)
+			};
+			h.workers.send_to(worker_id, resp);
+		}
+
+		Result::<_, ()>::Ok(())
+	};
+
+	let write = async move {
+		while let Some(line) = rx.next().await {
+			let line = line + "\n";
+			writer
+				.send(line)
+				.await
+				.map_err(|e| error!("error writing line: {}", e))?;
+		}
+
+		Result::<_, ()>::Ok(())
+	};
+
+	let task = async move {
+		pin_mut!(read, write);
+		futures::future::select(read, write).await;
+		handler.workers.remove_worker(worker_id);
+		info!("Worker {} disconnected", worker_id);
+	};
+	tokio::spawn(task);
+}
+
 // ----------------------------------------
 // Worker Object - a connected stratum client - a miner, pool, proxy, etc...
 
@@ -672,17 +1004,33 @@ pub struct Worker {
 	login: Option<String>,
 	authenticated: bool,
 	tx: Tx,
+	// address the worker connected from
+	addr: IpAddr,
+	// share difficulty requested from this worker (vardiff)
+	share_difficulty: u64,
+	// share difficulty before the last retarget, still accepted for shares
+	// of jobs sent before the change
+	prev_share_difficulty: u64,
+	// start of the current vardiff window
+	vardiff_window_start: Instant,
+	// number of accepted shares in the current vardiff window
+	vardiff_window_shares: u64,
 }
 
 impl Worker {
 	/// Creates a new Stratum Worker.
-	pub fn new(id: usize, tx: Tx) -> Worker {
+	pub fn new(id: usize, tx: Tx, addr: IpAddr, share_difficulty: u64) -> Worker {
 		Worker {
 			id: id,
 			agent: String::from(""),
 			login: None,
 			authenticated: false,
 			tx: tx,
+			addr: addr,
+			share_difficulty: share_difficulty,
+			prev_share_difficulty: share_difficulty,
+			vardiff_window_start: Instant::now(),
+			vardiff_window_shares: 0,
 		}
 	}
 } // impl Worker
@@ -700,17 +1048,17 @@ impl WorkersList {
 		}
 	}
 
-	pub fn add_worker(&self, tx: Tx) -> usize {
+	pub fn add_worker(&self, tx: Tx, addr: IpAddr, share_difficulty: u64) -> usize {
 		let mut stratum_stats = self.stratum_stats.write();
 		let worker_id = stratum_stats.worker_stats.len();
-		let worker = Worker::new(worker_id, tx);
+		let worker = Worker::new(worker_id, tx, addr, share_difficulty);
 		let mut workers_list = self.workers_list.write();
 		workers_list.insert(worker_id, worker);
 
 		let mut worker_stats = WorkerStats::default();
 		worker_stats.is_connected = true;
 		worker_stats.id = worker_id.to_string();
-		worker_stats.pow_difficulty = 1; // XXX TODO
+		worker_stats.pow_difficulty = share_difficulty;
 		stratum_stats.worker_stats.push(worker_stats);
 		stratum_stats.num_workers = workers_list.len();
 		worker_id
@@ -721,14 +1069,14 @@ impl WorkersList {
 			.write()
 			.remove(&worker_id)
 			.expect("Stratum: no such addr in map");
-		self.stratum_stats.write().num_workers = self.workers_list.read().len();
+		let num_workers = self.workers_list.read().len();
+		self.stratum_stats.write().num_workers = num_workers;
 	}
 
 	pub fn login(&self, worker_id: usize, login: String, agent: String) -> Result<(), RpcError> {
 		let mut wl = self.workers_list.write();
 		let mut worker = wl.get_mut(&worker_id).ok_or(RpcError::internal_error())?;
 		worker.login = Some(login);
-		// XXX TODO Future - Validate password?
 		worker.agent = agent;
 		worker.authenticated = true;
 		Ok(())
@@ -764,6 +1112,62 @@ impl WorkersList {
 		f(&mut stratum_stats.worker_stats[worker_id]);
 	}
 
+	pub fn ids(&self) -> Vec<usize> {
+		self.workers_list.read().keys().cloned().collect()
+	}
+
+	pub fn add_vardiff_share(&self, worker_id: usize) {
+		if let Some(worker) = self.workers_list.write().get_mut(&worker_id) {
+			worker.vardiff_window_shares += 1;
+		}
+	}
+
+	/// Retarget the share difficulty of the workers whose vardiff window has
+	/// elapsed, based on the share rate they achieved over that window.
+	/// Returns the ids of the workers whose difficulty changed.
+	pub fn retarget(
+		&self,
+		target_shares_per_minute: f64,
+		retarget_time: u64,
+		minimum: u64,
+		maximum: u64,
+	) -> Vec<usize> {
+		let mut retargeted = vec![];
+		let mut workers_list = self.workers_list.write();
+		for worker in workers_list.values_mut() {
+			let elapsed = worker.vardiff_window_start.elapsed();
+			if elapsed < Duration::from_secs(retarget_time) {
+				continue;
+			}
+			let difficulty = vardiff_retarget(
+				worker.share_difficulty,
+				worker.vardiff_window_shares,
+				elapsed,
+				target_shares_per_minute,
+				minimum,
+				maximum,
+			);
+			worker.vardiff_window_start = Instant::now();
+			worker.vardiff_window_shares = 0;
+			if difficulty != worker.share_difficulty {
+				debug!(
+					"Worker {} share difficulty retargeted from {} to {}",
+					worker.id, worker.share_difficulty, difficulty
+				);
+				worker.prev_share_difficulty = worker.share_difficulty;
+				worker.share_difficulty = difficulty;
+				retargeted.push((worker.id, difficulty));
+			}
+		}
+		// Released before locking the stats, which add_worker locks first
+		drop(workers_list);
+		let mut stratum_stats = self.stratum_stats.write();
+		for (worker_id, difficulty) in retargeted.iter() {
+			stratum_stats.worker_stats[*worker_id].pow_difficulty = *difficulty;
+		}
+		retargeted.into_iter().map(|(id, _)| id).collect()
+	}
+
 	pub fn send_to(&self, worker_id: usize, msg: String) {
 		let _ = self
 			.workers_list
@@ -806,18 +1210,40 @@ pub struct StratumServer {
 	verifier_cache: Arc<RwLock<dyn VerifierCache>>,
 	sync_state: Arc<SyncState>,
 	stratum_stats: Arc<RwLock<StratumStats>>,
+	store: Option<Arc<StratumStore>>,
+	local_coinbase: Option<Arc<LocalCoinbase>>,
+	stop_state: Arc<StopState>,
+	// logins allowed to connect, if authentication is enabled
+	credentials: Option<StratumCredentials>,
+	// accepts the connections of the TLS listener, if configured
+	tls_acceptor: Option<TlsAcceptor>,
 }
 
 impl StratumServer {
-	/// Creates a new Stratum Server.
+	/// Creates a new Stratum Server. Fails if the credentials file or the TLS
+	/// certificate configured can't be loaded, rather than running without
+	/// them.
 	pub fn new(
 		config: StratumServerConfig,
 		chain: Arc<chain::Chain>,
 		tx_pool: Arc<RwLock<pool::TransactionPool>>,
 		verifier_cache: Arc<RwLock<dyn VerifierCache>>,
 		stratum_stats: Arc<RwLock<StratumStats>>,
-	) -> StratumServer {
-		StratumServer {
+		store: Option<Arc<StratumStore>>,
+		local_coinbase: Option<Arc<LocalCoinbase>>,
+		stop_state: Arc<StopState>,
+	) -> Result<StratumServer, Error> {
+		let credentials = match config.stratum_credentials_file {
+			Some(ref path) => Some(StratumCredentials::from_file(path)?),
+			None => None,
+		};
+		let tls_acceptor = match config.stratum_server_tls_addr {
+			Some(ref addr) => Some(StratumServer::tls_acceptor(&config).map_err(|e| {
+				Error::Configuration(format!("Stratum TLS listener on {}: {}", addr, e))
+			})?),
+			None => None,
+		};
+		Ok(StratumServer {
 			id: String::from("0"),
 			config,
 			chain,
@@ -825,7 +1251,12 @@ impl StratumServer {
 			verifier_cache,
 			sync_state: Arc::new(SyncState::new()),
 			stratum_stats: stratum_stats,
-		}
+			store: store,
+			local_coinbase: local_coinbase,
+			stop_state,
+			credentials,
+			tls_acceptor,
+		})
 	}
 
 	/// "main()" - Starts the stratum-server.  Creates a thread to Listens for
@@ -841,20 +1272,28 @@ impl StratumServer {
 
 		self.sync_state = sync_state;
 
-		let listen_addr = self
-			.config
-			.stratum_server_addr
-			.clone()
-			.unwrap()
-			.parse()
-			.expect("Stratum: Incorrect address ");
-
 		let handler = Arc::new(Handler::from_stratum(&self));
-		let h = handler.clone();
 
-		let _listener_th = thread::spawn(move || {
-			accept_connections(listen_addr, h);
-		});
+		if let Some(addr) = self.config.stratum_server_addr.clone() {
+			let listen_addr: SocketAddr = addr.parse().expect("Stratum: Incorrect address ");
+			let h = handler.clone();
+			let _listener_th = thread::spawn(move || {
+				accept_connections(listen_addr, None, h);
+			});
+			warn!("Stratum server started on {}", addr);
+		}
+
+		if let (Some(addr), Some(acceptor)) = (
+			self.config.stratum_server_tls_addr.clone(),
+			self.tls_acceptor.clone(),
+		) {
+			let listen_addr: SocketAddr = addr.parse().expect("Stratum: Incorrect TLS address ");
+			let h = handler.clone();
+			let _tls_listener_th = thread::spawn(move || {
+				accept_connections(listen_addr, Some(acceptor), h);
+			});
+			warn!("Stratum TLS server started on {}", addr);
+		}
 
 		// We have started
 		{
@@ -863,20 +1302,70 @@ impl StratumServer {
 			stratum_stats.edge_bits = edge_bits as u16;
 		}
 
-		warn!(
-			"Stratum server started on {}",
-			self.config.stratum_server_addr.clone().unwrap()
-		);
-
 		// Initial Loop. Waiting node complete syncing
-		while self.sync_state.is_syncing() {
+		while self.sync_state.is_syncing() && !self.stop_state.is_stopped() {
 			thread::sleep(Duration::from_millis(50));
 		}
 
-		handler.run(&self.config, &self.tx_pool, self.verifier_cache.clone());
+		handler.run(
+			&self.config,
+			&self.tx_pool,
+			self.verifier_cache.clone(),
+			&self.stop_state,
+		);
+		self.stratum_stats.write().is_running = false;
+		info!("(Server ID: {}) Stratum server exit.", self.id);
 	} // fn run_loop()
+
+	// Build the TLS acceptor for the stratum TLS listener from the configured
+	// certificate and private key
+	fn tls_acceptor(config: &StratumServerConfig) -> Result<TlsAcceptor, String> {
+		let certificate = config
+			.tls_certificate_file
+			.clone()
+			.ok_or("TLS certificate file is not set".to_string())?;
+		let private_key = config
+			.tls_certificate_key
+			.clone()
+			.ok_or("Private key for certificate is not set".to_string())?;
+		let server_config = TLSConfig::new(certificate, private_key)
+			.build_server_config()
+			.map_err(|e| e.to_string())?;
+		Ok(TlsAcceptor::from(server_config))
+	}
 } // StratumServer
 
+// Bound a worker share difficulty by the minimum share difficulty and the
+// difficulty of the block being mined.
+fn clamp_share_difficulty(difficulty: u64, minimum: u64, maximum: u64) -> u64 {
+	cmp::max(minimum, cmp::min(difficulty, maximum))
+}
+
+// Compute the new share difficulty of a worker that submitted `shares` valid
+// shares over `elapsed`, so that it gets close to the target share rate.
+fn vardiff_retarget(
+	difficulty: u64,
+	shares: u64,
+	elapsed: Duration,
+	target_shares_per_minute: f64,
+	minimum: u64,
+	maximum: u64,
+) -> u64 {
+	let minutes = elapsed.as_secs_f64() / 60.0;
+	if minutes <= 0.0 || target_shares_per_minute <= 0.0 {
+		return clamp_share_difficulty(difficulty, minimum, maximum);
+	}
+	let ratio = shares as f64 / minutes / target_shares_per_minute;
+	if (ratio - 1.0).abs() <= VARDIFF_VARIANCE {
+		return clamp_share_difficulty(difficulty, minimum, maximum);
+	}
+	let ratio = ratio
+		.max(1.0 / VARDIFF_MAX_ADJUSTMENT)
+		.min(VARDIFF_MAX_ADJUSTMENT);
+	let new_difficulty = (difficulty as f64 * ratio).round() as u64;
+	clamp_share_difficulty(cmp::max(1, new_difficulty), minimum, maximum)
+}
+
 // Utility function to parse a JSON RPC parameter object, returning a proper
 // error if things go wrong.
 fn parse_params<T>(params: Option<Value>) -> Result<T, RpcError>
@@ -1031,4 +1520,24 @@ mod tests {
 
 		assert_eq!(expected_deserialized, actual_deserialized);
 	}
+
+	/// Tests the vardiff share difficulty retarget calculation.
+	#[test]
+	fn test_vardiff_retarget() {
+		let minute = Duration::from_secs(60);
+		// on target, no change
+		assert_eq!(vardiff_retarget(100, 6, minute, 6.0, 1, 10_000), 100);
+		// within the variance, no change
+		assert_eq!(vardiff_retarget(100, 7, minute, 6.0, 1, 10_000), 100);
+		// twice too many shares, double the difficulty
+		assert_eq!(vardiff_retarget(100, 12, minute, 6.0, 1, 10_000), 200);
+		// half the shares, halve the difficulty
+		assert_eq!(vardiff_retarget(100, 6, minute * 2, 6.0, 1, 10_000), 50);
+		// adjustment is bounded in both directions
+		assert_eq!(vardiff_retarget(100, 600, minute, 6.0, 1, 10_000), 400);
+		assert_eq!(vardiff_retarget(100, 0, minute, 6.0, 1, 10_000), 25);
+		// and so is the resulting difficulty
+		assert_eq!(vardiff_retarget(100, 600, minute, 6.0, 1, 150), 150);
+		assert_eq!(vardiff_retarget(100, 0, minute, 6.0, 60, 10_000), 60);
+	}
 }
@@ -27,6 +27,7 @@ use crate::core::core::hash::{Hash, Hashed};
 use crate::core::core::verifier_cache::VerifierCache;
 use crate::core::core::{Block, BlockHeader};
 use crate::core::global;
+use crate::mining::local_coinbase::LocalCoinbase;
 use crate::mining::mine_block;
 use crate::pool;
 use crate::util::StopState;
@@ -41,6 +42,7 @@ pub struct Miner {
 	verifier_cache: Arc<RwLock<dyn VerifierCache>>,
 	stop_state: Arc<StopState>,
 	sync_state: Arc<SyncState>,
+	local_coinbase: Option<Arc<LocalCoinbase>>,
 	// Just to hold the port we're on, so this miner can be identified
 	// while watching debug output
 	debug_output_id: String,
@@ -56,6 +58,7 @@ impl Miner {
 		verifier_cache: Arc<RwLock<dyn VerifierCache>>,
 		stop_state: Arc<StopState>,
 		sync_state: Arc<SyncState>,
+		local_coinbase: Option<Arc<LocalCoinbase>>,
 	) -> Miner {
 		Miner {
 			config,
@@ -65,6 +68,7 @@ impl Miner {
 			debug_output_id: String::from("none"),
 			stop_state,
 			sync_state,
+			local_coinbase,
 		}
 	}
 
@@ -161,6 +165,7 @@ impl Miner {
 				self.verifier_cache.clone(),
 				key_id.clone(),
 				wallet_listener_url.clone(),
+				self.local_coinbase.clone(),
 			);
 
 			let sol = self.inner_mining_loop(
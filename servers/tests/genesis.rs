@@ -0,0 +1,99 @@
+// Copyright 2020 The Kepler Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use kepler_chain as chain;
+use kepler_core as core;
+use kepler_keychain as keychain;
+use kepler_servers as servers;
+use kepler_util as util;
+
+use chrono::{TimeZone, Utc};
+use std::fs::{self, File};
+use std::io::BufReader;
+use std::sync::Arc;
+
+use crate::core::core::hash::Hashed;
+use crate::core::core::verifier_cache::LruVerifierCache;
+use crate::core::core::Block;
+use crate::core::global::{self, ChainParams};
+use crate::core::{consensus, pow, ser};
+use crate::keychain::{ExtKeychain, Keychain};
+use crate::servers::genesis::{self, GenesisParams};
+use crate::util::RwLock;
+
+// The chain type is global, so this is the only test of this file
+#[test]
+fn custom_chain_genesis_roundtrip() {
+	let params = ChainParams {
+		edge_bits: global::AUTOMATED_TESTING_MIN_EDGE_BITS,
+		proof_size: global::AUTOMATED_TESTING_PROOF_SIZE,
+		initial_difficulty: 1,
+		genesis_reward: 100 * consensus::KEPLER_BASE,
+		p2p_magic: [3, 4],
+		..ChainParams::default()
+	};
+	params.validate().unwrap();
+	global::set_custom_chain(params);
+	global::set_mining_mode(global::ChainTypes::Custom);
+
+	let test_dir = ".kepler_custom_genesis";
+	let _ = fs::remove_dir_all(test_dir);
+	fs::create_dir_all(test_dir).unwrap();
+	{
+		let keychain = ExtKeychain::from_random_seed(false).unwrap();
+		let key_file = format!("{}/genesis_key", test_dir);
+		fs::write(&key_file, keychain.master.to_string()).unwrap();
+
+		let genesis_params = GenesisParams {
+			timestamp: Utc.ymd(2020, 6, 1).and_hms(0, 0, 0),
+			message: "custom chain genesis test".to_owned(),
+			key_file,
+		};
+		let mut gen = genesis_params.build().unwrap();
+		genesis::mine(&mut gen).unwrap();
+		genesis::validate(&gen).unwrap();
+
+		let genesis_file = format!("{}/genesis.bin", test_dir);
+		genesis::write(&gen, &genesis_file).unwrap();
+		assert!(fs::metadata(format!("{}.json", genesis_file)).is_ok());
+
+		// read back as a node does on startup
+		let mut reader = BufReader::new(File::open(&genesis_file).unwrap());
+		let read: Block = ser::deserialize_default(&mut reader).unwrap();
+		assert_eq!(read.hash(), gen.hash());
+		assert_eq!(read.header.height, 0);
+		assert_eq!(
+			read.header.prev_root,
+			"custom chain genesis test".as_bytes().to_vec().hash()
+		);
+		assert_eq!(read.outputs().len(), 1);
+		assert_eq!(read.kernels().len(), 1);
+
+		// and a node of the custom chain initializes from it
+		let chain = chain::Chain::init(
+			format!("{}/chain", test_dir),
+			Arc::new(chain::types::NoopAdapter {}),
+			read,
+			pow::verify_size,
+			Arc::new(RwLock::new(LruVerifierCache::new())),
+			false,
+		)
+		.unwrap();
+		let head = chain.head().unwrap();
+		assert_eq!(head.height, 0);
+		assert_eq!(head.last_block_h, gen.hash());
+		chain.validate(false).unwrap();
+	}
+	let _ = fs::remove_dir_all(test_dir);
+}
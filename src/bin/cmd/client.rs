@@ -12,54 +12,429 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-/// Kepler client commands processing
+/// Kepler client commands processing, over the v2 Owner and Foreign APIs
+use std::fmt;
+use std::fs;
 use std::net::SocketAddr;
+use std::sync::mpsc;
 
 use clap::ArgMatches;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
 
-use crate::api;
+use crate::api::{
+	self, BlockHeaderPrintable, BlockPrintable, CoinbaseKey, LocatedTxKernel, Logs, OutputListing,
+	OutputPrintable, StratumLoginStats, Tip, TxKernelPrintable, Version,
+};
 use crate::config::GlobalConfig;
-use crate::p2p;
-use crate::servers::ServerConfig;
+use crate::core::core::{amount_to_hr_string, Transaction};
+use crate::core::ser::{self, ProtocolVersion};
+use crate::p2p::types::PeerInfoDisplay;
+use crate::p2p::PeerData;
+use crate::pool::PoolEntry;
+use crate::tui::remote::RemoteNode;
+use crate::tui::ui;
+use crate::util;
 use crate::util::file::get_first_line;
+use kepler_util::logger::LogEntry;
 use term;
 
-pub fn client_command(client_args: &ArgMatches<'_>, global_config: GlobalConfig) -> i32 {
+pub fn client_command(
+	client_args: &ArgMatches<'_>,
+	global_config: GlobalConfig,
+	logs_tx: Option<mpsc::SyncSender<LogEntry>>,
+	logs_rx: Option<mpsc::Receiver<LogEntry>>,
+) -> i32 {
 	// just get defaults from the global config
 	let server_config = global_config.members.unwrap().server;
 	let api_secret = get_first_line(server_config.api_secret_path.clone());
+	let foreign_api_secret = get_first_line(server_config.foreign_api_secret_path.clone());
+	let (name, sub_args) = client_args.subcommand();
+	// Global arguments can be provided before or after the subcommand
+	let api_url = match sub_args
+		.and_then(|a| a.value_of("api_address"))
+		.or_else(|| client_args.value_of("api_address"))
+	{
+		Some(addr) => api_url(addr, false),
+		None => api_url(
+			&server_config.api_http_addr,
+			server_config.tls_certificate_file.is_some(),
+		),
+	};
+	let json = client_args.is_present("json") || sub_args.map_or(false, |a| a.is_present("json"));
 
-	match client_args.subcommand() {
-		("status", Some(_)) => {
-			show_status(&server_config, api_secret);
+	let result = match (name, sub_args) {
+		("tui", Some(_)) => {
+			return remote_tui(
+				&api_url,
+				api_secret,
+				foreign_api_secret,
+				logs_tx.unwrap(),
+				logs_rx.unwrap(),
+			);
 		}
-		("listconnectedpeers", Some(_)) => {
-			list_connected_peers(&server_config, api_secret);
+		(name, Some(args)) => {
+			let client = NodeClient::new(&api_url, api_secret, foreign_api_secret);
+			run_command(&client, name, args, json)
 		}
-		("ban", Some(peer_args)) => {
-			let peer = peer_args.value_of("peer").unwrap();
-
-			if let Ok(addr) = peer.parse() {
-				ban_peer(&server_config, &addr, api_secret);
+		_ => Err(Error::Argument(
+			"Unknown client command, use 'kepler help client' for details".to_owned(),
+		)),
+	};
+	match result {
+		Ok(()) => 0,
+		Err(e) => {
+			if json {
+				println!("{}", json!({ "error": e.to_string() }));
 			} else {
-				panic!("Invalid peer address format");
+				eprintln!("{}", e);
 			}
+			1
 		}
-		("unban", Some(peer_args)) => {
-			let peer = peer_args.value_of("peer").unwrap();
+	}
+}
 
-			if let Ok(addr) = peer.parse() {
-				unban_peer(&server_config, &addr, api_secret);
-			} else {
-				panic!("Invalid peer address format");
-			}
+fn run_command(
+	client: &NodeClient,
+	name: &str,
+	args: &ArgMatches<'_>,
+	json: bool,
+) -> Result<(), Error> {
+	match name {
+		"status" => {
+			let status: api::Status = client.owner("get_status", json!([]))?;
+			output(json, &status, show_status)
+		}
+		"version" => {
+			let version: Version = client.foreign("get_version", json!([]))?;
+			output(json, &version, |v| {
+				println!("Node version: {}", v.node_version);
+				println!("Block header version: {}", v.block_header_version);
+			})
+		}
+		"tip" => {
+			let tip: Tip = client.foreign("get_tip", json!([]))?;
+			output(json, &tip, show_tip)
+		}
+		"validate" => {
+			client.owner::<()>("validate_chain", json!([]))?;
+			done(json, "Chain validated")
+		}
+		"compact" => {
+			client.owner::<()>("compact_chain", json!([]))?;
+			done(json, "Chain compacted")
+		}
+		"listconnectedpeers" => {
+			let peers: Vec<PeerInfoDisplay> = client.owner("get_connected_peers", json!([]))?;
+			output(json, &peers, |peers| {
+				for (index, peer) in peers.iter().enumerate() {
+					println!("Peer {}:", index);
+					println!("Capabilities: {:?}", peer.capabilities);
+					println!("User agent: {}", peer.user_agent);
+					println!("Version: {:?}", peer.version);
+					println!("Peer address: {}", peer.addr);
+					println!("Height: {}", peer.height);
+					println!("Total difficulty: {}", peer.total_difficulty);
+					println!("Direction: {:?}", peer.direction);
+					println!();
+				}
+			})
+		}
+		"listpeers" => {
+			let addr = args.value_of("peer").map(parse_addr).transpose()?;
+			let peers: Vec<PeerData> = client.owner("get_peers", json!([addr]))?;
+			output(json, &peers, |peers| {
+				for peer in peers {
+					println!(
+						"{} {:?} {} (last connected {}, capabilities {:?})",
+						peer.addr,
+						peer.flags,
+						peer.user_agent,
+						peer.last_connected,
+						peer.capabilities
+					);
+				}
+			})
+		}
+		"ban" => {
+			let addr = parse_addr(args.value_of("peer").unwrap())?;
+			client.owner::<()>("ban_peer", json!([addr]))?;
+			done(json, &format!("Successfully banned peer {}", addr))
+		}
+		"unban" => {
+			let addr = parse_addr(args.value_of("peer").unwrap())?;
+			client.owner::<()>("unban_peer", json!([addr]))?;
+			done(json, &format!("Successfully unbanned peer {}", addr))
+		}
+		"connect" => {
+			let addr = parse_addr(args.value_of("peer").unwrap())?;
+			let preferred = args.is_present("preferred");
+			client.owner::<()>("connect_peer", json!([addr, preferred]))?;
+			done(json, &format!("Successfully connected to peer {}", addr))
+		}
+		"disconnect" => {
+			let addr = parse_addr(args.value_of("peer").unwrap())?;
+			client.owner::<()>("disconnect_peer", json!([addr]))?;
+			done(
+				json,
+				&format!("Successfully disconnected from peer {}", addr),
+			)
+		}
+		"header" => {
+			let header: BlockHeaderPrintable = client.foreign("get_header", block_params(args)?)?;
+			output(json, &header, show_header)
+		}
+		"block" => {
+			let block: BlockPrintable = client.foreign("get_block", block_params(args)?)?;
+			output(json, &block, |block| {
+				show_header(&block.header);
+				println!("Inputs ({}):", block.inputs.len());
+				for input in &block.inputs {
+					println!("  {}", input);
+				}
+				println!("Outputs ({}):", block.outputs.len());
+				for o in &block.outputs {
+					show_output(o);
+				}
+				println!("Kernels ({}):", block.kernels.len());
+				for k in &block.kernels {
+					show_kernel(k);
+				}
+			})
+		}
+		"kernel" => {
+			let kernel: LocatedTxKernel = client.foreign(
+				"get_kernel",
+				json!([
+					args.value_of("excess").unwrap(),
+					parse_opt_u64(args, "min_height")?,
+					parse_opt_u64(args, "max_height")?
+				]),
+			)?;
+			output(json, &kernel, |k| {
+				println!("Height: {}", k.height);
+				println!("MMR index: {}", k.mmr_index);
+				show_kernel(&TxKernelPrintable::from_txkernel(&k.tx_kernel));
+			})
+		}
+		"outputs" => {
+			let commits: Option<Vec<&str>> = args.values_of("commit").map(|c| c.collect());
+			let outputs: Vec<OutputPrintable> = client.foreign(
+				"get_outputs",
+				json!([
+					commits,
+					parse_opt_u64(args, "start_height")?,
+					parse_opt_u64(args, "end_height")?,
+					args.is_present("proof"),
+					args.is_present("merkle_proof")
+				]),
+			)?;
+			output(json, &outputs, |outputs| {
+				for o in outputs {
+					show_output(o);
+				}
+			})
+		}
+		"unspentoutputs" => {
+			let listing: OutputListing = client.foreign(
+				"get_unspent_outputs",
+				json!([
+					parse_opt_u64(args, "start_index")?.unwrap_or(1),
+					parse_opt_u64(args, "end_index")?,
+					parse_opt_u64(args, "max")?.unwrap_or(100),
+					args.is_present("proof")
+				]),
+			)?;
+			output(json, &listing, show_listing)
 		}
-		_ => panic!("Unknown client command, use 'kepler help client' for details"),
+		"pmmrindices" => {
+			let listing: OutputListing = client.foreign(
+				"get_pmmr_indices",
+				json!([
+					parse_opt_u64(args, "start_height")?.unwrap_or(0),
+					parse_opt_u64(args, "end_height")?
+				]),
+			)?;
+			output(json, &listing, show_listing)
+		}
+		"pool" => {
+			let pool_size: usize = client.foreign("get_pool_size", json!([]))?;
+			let stempool_size: usize = client.foreign("get_stempool_size", json!([]))?;
+			let entries: Vec<PoolEntry> =
+				client.foreign("get_unconfirmed_transactions", json!([]))?;
+			let info = json!({
+				"pool_size": pool_size,
+				"stempool_size": stempool_size,
+				"entries": &entries,
+			});
+			output(json, &info, |_| {
+				println!("Transaction pool size: {}", pool_size);
+				println!("Stem pool size: {}", stempool_size);
+				for e in &entries {
+					println!(
+						"  {} {:?} kernels {} fee {}",
+						e.tx_at.to_rfc3339(),
+						e.src,
+						e.tx.kernels().len(),
+						amount_to_hr_string(e.tx.fee(), true)
+					);
+				}
+			})
+		}
+		"pushtx" => {
+			let tx = read_transaction(args.value_of("file").unwrap())?;
+			client.foreign::<()>("push_transaction", json!([tx, args.is_present("fluff")]))?;
+			done(json, "Transaction pushed")
+		}
+		"stratumlogins" => {
+			let logins: Vec<StratumLoginStats> =
+				client.owner("get_stratum_logins", json!([args.value_of("login")]))?;
+			output(json, &logins, |logins| {
+				for l in logins {
+					println!(
+						"{}: accepted {}, rejected {}, stale {}, blocks found {}, last share {}",
+						l.login,
+						l.num_accepted,
+						l.num_rejected,
+						l.num_stale,
+						l.num_blocks_found,
+						l.last_share
+					);
+				}
+			})
+		}
+		"coinbasekeys" => {
+			let keys: Vec<CoinbaseKey> = client.owner(
+				"get_coinbase_keys",
+				json!([parse_opt_u64(args, "min_height")?]),
+			)?;
+			output(json, &keys, |keys| {
+				for k in keys {
+					println!(
+						"{} height {} commit {} value {}",
+						k.key_id,
+						k.height,
+						k.commit,
+						amount_to_hr_string(k.value, true)
+					);
+				}
+			})
+		}
+		"logs" => {
+			let logs: Logs = client.owner("get_logs", json!([parse_opt_u64(args, "after")?]))?;
+			output(json, &logs, |logs| {
+				for entry in &logs.entries {
+					print!("{}", entry.log);
+				}
+			})
+		}
+		_ => Err(Error::Argument(
+			"Unknown client command, use 'kepler help client' for details".to_owned(),
+		)),
+	}
+}
+
+/// Base URL of the node API listening at the provided address, which may
+/// start with its scheme. Defaults to https if the node serves its API over
+/// TLS, to http otherwise.
+fn api_url(api_addr: &str, tls: bool) -> String {
+	let api_addr = api_addr.trim().trim_end_matches('/');
+	if api_addr.starts_with("http://") || api_addr.starts_with("https://") {
+		api_addr.to_owned()
+	} else if tls {
+		format!("https://{}", api_addr)
+	} else {
+		format!("http://{}", api_addr)
+	}
+}
+
+/// Runs the TUI against the node API at the provided base URL
+pub fn remote_tui(
+	api_url: &str,
+	api_secret: Option<String>,
+	foreign_api_secret: Option<String>,
+	logs_tx: mpsc::SyncSender<LogEntry>,
+	logs_rx: mpsc::Receiver<LogEntry>,
+) -> i32 {
+	let client = NodeClient::new(api_url, api_secret.clone(), foreign_api_secret.clone());
+	if let Err(e) = client.owner::<api::Status>("get_status", json!([])) {
+		println!("Unable to reach the node API at {}: {}", api_url, e);
+		return 1;
 	}
+
+	let node = RemoteNode::new(api_url, api_secret, foreign_api_secret, logs_tx);
+	let controller = ui::Controller::new(logs_rx).unwrap_or_else(|e| {
+		panic!("Error loading UI controller: {}", e);
+	});
+	controller.run(node);
 	0
 }
 
-pub fn show_status(config: &ServerConfig, api_secret: Option<String>) {
+/// Node reached over its v2 JSON-RPC endpoints
+struct NodeClient {
+	owner_url: String,
+	foreign_url: String,
+	api_secret: Option<String>,
+	foreign_api_secret: Option<String>,
+}
+
+impl NodeClient {
+	fn new(
+		api_url: &str,
+		api_secret: Option<String>,
+		foreign_api_secret: Option<String>,
+	) -> NodeClient {
+		NodeClient {
+			owner_url: format!("{}/v2/owner", api_url),
+			foreign_url: format!("{}/v2/foreign", api_url),
+			api_secret,
+			foreign_api_secret,
+		}
+	}
+
+	fn owner<OUT>(&self, method: &str, params: Value) -> Result<OUT, Error>
+	where
+		for<'de> OUT: Deserialize<'de>,
+	{
+		api::client::rpc(&self.owner_url, self.api_secret.clone(), method, &params)
+			.map_err(Error::API)
+	}
+
+	fn foreign<OUT>(&self, method: &str, params: Value) -> Result<OUT, Error>
+	where
+		for<'de> OUT: Deserialize<'de>,
+	{
+		api::client::rpc(
+			&self.foreign_url,
+			self.foreign_api_secret.clone(),
+			method,
+			&params,
+		)
+		.map_err(Error::API)
+	}
+}
+
+/// Prints the value as JSON when requested, in a human readable form
+/// otherwise
+fn output<T: Serialize, F: FnOnce(&T)>(json: bool, value: &T, human: F) -> Result<(), Error> {
+	if json {
+		let value = serde_json::to_string_pretty(value)
+			.map_err(|e| Error::Argument(format!("Unable to serialize the response: {}", e)))?;
+		println!("{}", value);
+	} else {
+		human(value);
+	}
+	Ok(())
+}
+
+/// Outcome of a command without any response from the node
+fn done(json: bool, message: &str) -> Result<(), Error> {
+	output(json, &json!({ "result": "ok" }), |_| {
+		println!("{}", message)
+	})
+}
+
+pub fn show_status(status: &api::Status) {
 	println!();
 	let title = format!("Kepler Server Status");
 	if term::stdout().is_none() {
@@ -67,99 +442,120 @@ pub fn show_status(config: &ServerConfig, api_secret: Option<String>) {
 		return;
 	}
 	let mut t = term::stdout().unwrap();
-	let mut e = term::stdout().unwrap();
 	t.fg(term::color::MAGENTA).unwrap();
 	writeln!(t, "{}", title).unwrap();
 	writeln!(t, "--------------------------").unwrap();
 	t.reset().unwrap();
-	match get_status_from_node(config, api_secret) {
-		Ok(status) => {
-			writeln!(e, "Protocol version: {:?}", status.protocol_version).unwrap();
-			writeln!(e, "User agent: {}", status.user_agent).unwrap();
-			writeln!(e, "Connections: {}", status.connections).unwrap();
-			writeln!(e, "Chain height: {}", status.tip.height).unwrap();
-			writeln!(e, "Last block hash: {}", status.tip.last_block_pushed).unwrap();
-			writeln!(e, "Previous block hash: {}", status.tip.prev_block_to_last).unwrap();
-			writeln!(e, "Total difficulty: {}", status.tip.total_difficulty).unwrap();
-		}
-		Err(_) => writeln!(
-			e,
-			"WARNING: Client failed to get data. Is your `kepler server` offline or broken?"
-		)
-		.unwrap(),
-	};
-	e.reset().unwrap();
+	println!("Protocol version: {:?}", status.protocol_version);
+	println!("User agent: {}", status.user_agent);
+	println!("Connections: {}", status.connections);
+	println!("Sync status: {}", status.sync_status);
+	show_tip(&status.tip);
 	println!()
 }
 
-pub fn ban_peer(config: &ServerConfig, peer_addr: &SocketAddr, api_secret: Option<String>) {
-	let params = "";
-	let mut e = term::stdout().unwrap();
-	let url = format!(
-		"http://{}/v1/peers/{}/ban",
-		config.api_http_addr,
-		peer_addr.to_string()
+fn show_tip(tip: &Tip) {
+	println!("Chain height: {}", tip.height);
+	println!("Last block hash: {}", tip.last_block_pushed);
+	println!("Previous block hash: {}", tip.prev_block_to_last);
+	println!("Total difficulty: {}", tip.total_difficulty);
+}
+
+fn show_header(header: &BlockHeaderPrintable) {
+	println!("Height: {}", header.height);
+	println!("Hash: {}", header.hash);
+	println!("Previous: {}", header.previous);
+	println!("Timestamp: {}", header.timestamp);
+	println!("Version: {}", header.version);
+	println!("Output root: {}", header.output_root);
+	println!("Range proof root: {}", header.range_proof_root);
+	println!("Kernel root: {}", header.kernel_root);
+	println!("Total difficulty: {}", header.total_difficulty);
+	println!("Secondary scaling: {}", header.secondary_scaling);
+	println!("Edge bits: {}", header.edge_bits);
+	println!("Nonce: {}", header.nonce);
+	println!("Total kernel offset: {}", header.total_kernel_offset);
+}
+
+fn show_output(output: &OutputPrintable) {
+	println!(
+		"  {} {:?} height {} mmr index {}{}",
+		util::to_hex(output.commit.0.to_vec()),
+		output.output_type,
+		output
+			.block_height
+			.map_or("-".to_owned(), |h| h.to_string()),
+		output.mmr_index,
+		if output.spent { " (spent)" } else { "" }
 	);
-	match api::client::post_no_ret(url.as_str(), api_secret, &params).map_err(|e| Error::API(e)) {
-		Ok(_) => writeln!(e, "Successfully banned peer {}", peer_addr.to_string()).unwrap(),
-		Err(_) => writeln!(e, "Failed to ban peer {}", peer_addr).unwrap(),
-	};
-	e.reset().unwrap();
 }
 
-pub fn unban_peer(config: &ServerConfig, peer_addr: &SocketAddr, api_secret: Option<String>) {
-	let params = "";
-	let mut e = term::stdout().unwrap();
-	let url = format!(
-		"http://{}/v1/peers/{}/unban",
-		config.api_http_addr,
-		peer_addr.to_string()
+fn show_kernel(kernel: &TxKernelPrintable) {
+	println!(
+		"  {} {} fee {} lock height {}",
+		kernel.excess,
+		kernel.features,
+		amount_to_hr_string(kernel.fee, true),
+		kernel.lock_height
 	);
-	let res: Result<(), api::Error>;
-	res = api::client::post_no_ret(url.as_str(), api_secret, &params);
+}
 
-	match res.map_err(|e| Error::API(e)) {
-		Ok(_) => writeln!(e, "Successfully unbanned peer {}", peer_addr).unwrap(),
-		Err(_) => writeln!(e, "Failed to unban peer {}", peer_addr).unwrap(),
-	};
-	e.reset().unwrap();
-}
-
-pub fn list_connected_peers(config: &ServerConfig, api_secret: Option<String>) {
-	let mut e = term::stdout().unwrap();
-	let url = format!("http://{}/v1/peers/connected", config.api_http_addr);
-	// let peers_info: Result<Vec<p2p::PeerInfoDisplay>, api::Error>;
-
-	let peers_info = api::client::get::<Vec<p2p::types::PeerInfoDisplay>>(url.as_str(), api_secret);
-
-	match peers_info.map_err(|e| Error::API(e)) {
-		Ok(connected_peers) => {
-			let mut index = 0;
-			for connected_peer in connected_peers {
-				writeln!(e, "Peer {}:", index).unwrap();
-				writeln!(e, "Capabilities: {:?}", connected_peer.capabilities).unwrap();
-				writeln!(e, "User agent: {}", connected_peer.user_agent).unwrap();
-				writeln!(e, "Version: {:?}", connected_peer.version).unwrap();
-				writeln!(e, "Peer address: {}", connected_peer.addr).unwrap();
-				writeln!(e, "Height: {}", connected_peer.height).unwrap();
-				writeln!(e, "Total difficulty: {}", connected_peer.total_difficulty).unwrap();
-				writeln!(e, "Direction: {:?}", connected_peer.direction).unwrap();
-				println!();
-				index = index + 1;
-			}
-		}
-		Err(_) => writeln!(e, "Failed to get connected peers").unwrap(),
-	};
+fn show_listing(listing: &OutputListing) {
+	println!(
+		"Highest index: {}, last retrieved index: {}",
+		listing.highest_index, listing.last_retrieved_index
+	);
+	for o in &listing.outputs {
+		show_output(o);
+	}
+}
 
-	e.reset().unwrap();
+fn parse_addr(addr: &str) -> Result<SocketAddr, Error> {
+	addr.parse()
+		.map_err(|_| Error::Argument(format!("Invalid peer address format {}", addr)))
 }
 
-fn get_status_from_node(
-	config: &ServerConfig,
-	api_secret: Option<String>,
-) -> Result<api::Status, Error> {
-	let url = format!("http://{}/v1/status", config.api_http_addr);
-	api::client::get::<api::Status>(url.as_str(), api_secret).map_err(|e| Error::API(e))
+fn parse_opt_u64(args: &ArgMatches<'_>, name: &str) -> Result<Option<u64>, Error> {
+	args.value_of(name)
+		.map(|v| {
+			v.parse()
+				.map_err(|_| Error::Argument(format!("Invalid {} {}", name, v)))
+		})
+		.transpose()
+}
+
+/// Parameters of `get_header` and `get_block`, from a height, a hash or an
+/// output commitment
+fn block_params(args: &ArgMatches<'_>) -> Result<Value, Error> {
+	if let Some(commit) = args.value_of("commit") {
+		return Ok(json!([null, null, commit]));
+	}
+	let id = args.value_of("id").ok_or_else(|| {
+		Error::Argument("Either a height, a hash or an output commitment is required".to_owned())
+	})?;
+	Ok(match id.parse::<u64>() {
+		Ok(height) => json!([height, null, null]),
+		Err(_) => json!([null, id, null]),
+	})
+}
+
+/// Reads a transaction from a file, either as JSON or as the hex of its
+/// binary serialization
+fn read_transaction(path: &str) -> Result<Transaction, Error> {
+	let content = fs::read_to_string(path)
+		.map_err(|e| Error::Argument(format!("Unable to read {}: {}", path, e)))?;
+	parse_transaction(content.trim())
+}
+
+fn parse_transaction(content: &str) -> Result<Transaction, Error> {
+	if content.starts_with('{') {
+		return serde_json::from_str(content)
+			.map_err(|e| Error::Argument(format!("Invalid transaction JSON: {}", e)));
+	}
+	let bytes = util::from_hex(content.to_owned())
+		.map_err(|_| Error::Argument("Transaction is neither JSON nor hex".to_owned()))?;
+	ser::deserialize(&mut &bytes[..], ProtocolVersion::local())
+		.map_err(|e| Error::Argument(format!("Invalid transaction: {}", e)))
 }
 
 /// Error type wrapping underlying module errors.
@@ -167,4 +563,48 @@ fn get_status_from_node(
 enum Error {
 	/// Error originating from HTTP API calls.
 	API(api::Error),
+	/// Invalid command argument or input file
+	Argument(String),
+}
+
+impl fmt::Display for Error {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			Error::API(e) => write!(
+				f,
+				"Request to the node failed, is your `kepler server` running? {}",
+				e
+			),
+			Error::Argument(e) => write!(f, "{}", e),
+		}
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	#[test]
+	fn test_parse_transaction() {
+		let tx = Transaction::empty();
+		let json = serde_json::to_string(&tx).unwrap();
+		assert_eq!(parse_transaction(&json).unwrap(), tx);
+		let hex = util::to_hex(ser::ser_vec(&tx, ProtocolVersion::local()).unwrap());
+		assert_eq!(parse_transaction(&hex).unwrap(), tx);
+		assert!(parse_transaction("not a tx").is_err());
+	}
+
+	#[test]
+	fn test_api_url() {
+		assert_eq!(api_url("127.0.0.1:7413", false), "http://127.0.0.1:7413");
+		assert_eq!(api_url("127.0.0.1:7413", true), "https://127.0.0.1:7413");
+		assert_eq!(
+			api_url("https://node.example:7413/", false),
+			"https://node.example:7413"
+		);
+		assert_eq!(
+			api_url("http://127.0.0.1:7413", true),
+			"http://127.0.0.1:7413"
+		);
+	}
 }
@@ -0,0 +1,86 @@
+// Copyright 2020 The Kepler Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+/// Kepler genesis command processing
+use chrono::{DateTime, SubsecRound, Utc};
+use clap::ArgMatches;
+
+use crate::core::core::hash::Hashed;
+use crate::servers::genesis::{self, GenesisParams};
+
+pub fn genesis_command(args: &ArgMatches<'_>) -> i32 {
+	let timestamp = match args.value_of("timestamp") {
+		Some(t) => match DateTime::parse_from_rfc3339(t) {
+			Ok(t) => t.with_timezone(&Utc),
+			Err(e) => {
+				println!("Invalid timestamp {}: {}", t, e);
+				return 1;
+			}
+		},
+		None => Utc::now().trunc_subsecs(0),
+	};
+	let params = GenesisParams {
+		timestamp,
+		message: args.value_of("message").unwrap_or("").to_owned(),
+		key_file: args.value_of("key_file").unwrap().to_owned(),
+	};
+	let mut gen = match params.build() {
+		Ok(gen) => gen,
+		Err(e) => {
+			println!("Unable to build the genesis block: {:?}", e);
+			return 1;
+		}
+	};
+
+	if args.is_present("pre_pow") {
+		println!("{}", genesis::pre_pow(&gen));
+		return 0;
+	}
+	if let (Some(nonce), Some(proof)) = (args.value_of("nonce"), args.value_of("proof")) {
+		let nonce = nonce.parse::<u64>();
+		let nonces = proof
+			.split(',')
+			.map(|n| n.trim().parse::<u64>())
+			.collect::<Result<Vec<_>, _>>();
+		match (nonce, nonces) {
+			(Ok(nonce), Ok(nonces)) => genesis::set_proof(&mut gen, nonce, nonces),
+			_ => {
+				println!("Invalid proof, a nonce and comma separated cycle nonces are expected");
+				return 1;
+			}
+		}
+	} else {
+		println!("Mining the genesis block...");
+		if let Err(e) = genesis::mine(&mut gen) {
+			println!("Unable to mine the genesis block: {:?}", e);
+			return 1;
+		}
+	}
+	if let Err(e) = genesis::validate(&gen) {
+		println!("Invalid genesis block: {:?}", e);
+		return 1;
+	}
+
+	let path = args.value_of("file").unwrap();
+	match genesis::write(&gen, path) {
+		Ok(_) => {
+			println!("Genesis block {} written to {}", gen.hash(), path);
+			0
+		}
+		Err(e) => {
+			println!("Unable to write the genesis block: {:?}", e);
+			1
+		}
+	}
+}
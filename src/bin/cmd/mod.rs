@@ -14,8 +14,10 @@
 
 mod client;
 mod config;
+mod genesis;
 mod server;
 
 pub use self::client::client_command;
 pub use self::config::config_command_server;
+pub use self::genesis::genesis_command;
 pub use self::server::server_command;
@@ -13,6 +13,7 @@
 // limitations under the License.
 
 /// Kepler server commands processing
+use std::io;
 use std::process::exit;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
@@ -50,7 +51,7 @@ fn start_server_tui(config: servers::ServerConfig, logs_rx: Option<mpsc::Receive
 			config,
 			logs_rx,
 			|serv: servers::Server, logs_rx: Option<mpsc::Receiver<LogEntry>>| {
-				let mut controller = ui::Controller::new(logs_rx.unwrap()).unwrap_or_else(|e| {
+				let controller = ui::Controller::new(logs_rx.unwrap()).unwrap_or_else(|e| {
 					panic!("Error loading UI controller: {}", e);
 				});
 				controller.run(serv);
@@ -128,6 +129,10 @@ pub fn server_command(
 			server_config.p2p_config.seeding_type = Seeding::List;
 			server_config.p2p_config.seeds = Some(PeerAddrs { peers });
 		}
+
+		if let Some(headers_file) = a.value_of("headers_file") {
+			server_config.headers_file = Some(headers_file.to_string());
+		}
 	}
 
 	if let Some(a) = server_args {
@@ -135,6 +140,26 @@ pub fn server_command(
 			("run", _) => {
 				start_server(server_config, logs_rx);
 			}
+			("export_headers", Some(args)) => {
+				let path = args.value_of("file").unwrap();
+				match servers::Server::export_headers(&server_config, path) {
+					Ok(count) => println!("Exported {} headers to {}", count, path),
+					Err(e) => {
+						println!("Unable to export headers: {:?}", e);
+						return 1;
+					}
+				}
+			}
+			("stratum_credentials", Some(args)) => {
+				let login = args.value_of("login").unwrap();
+				let mut password = String::new();
+				if let Err(e) = io::stdin().read_line(&mut password) {
+					println!("Unable to read password: {}", e);
+					return 1;
+				}
+				let password = password.trim_end_matches(|c| c == '\r' || c == '\n');
+				println!("{}", servers::stratum_credentials_entry(login, password));
+			}
 			("", _) => {
 				println!("Subcommand required, use 'kepler help server' for details");
 			}
@@ -28,6 +28,7 @@ use kepler_chain as chain;
 use kepler_config as config;
 use kepler_core as core;
 use kepler_p2p as p2p;
+use kepler_pool as pool;
 use kepler_servers as servers;
 use kepler_util as util;
 use kepler_util::logger::LogEntry;
@@ -76,7 +77,7 @@ fn real_main() -> i32 {
 	let args = App::from_yaml(yml)
 		.version(built_info::PKG_VERSION)
 		.get_matches();
-	let node_config;
+	let mut node_config;
 
 	// Temporary wallet warning message
 	match args.subcommand() {
@@ -96,6 +97,8 @@ fn real_main() -> i32 {
 		global::ChainTypes::Floonet
 	} else if args.is_present("usernet") {
 		global::ChainTypes::UserTesting
+	} else if args.is_present("chain") {
+		global::ChainTypes::Custom
 	} else {
 		global::ChainTypes::Mainnet
 	};
@@ -105,7 +108,11 @@ fn real_main() -> i32 {
 		("server", Some(server_args)) => {
 			// If it's just a server config command, do it and exit
 			if let ("config", Some(_)) = server_args.subcommand() {
-				cmd::config_command_server(&chain_type, SERVER_CONFIG_FILE_NAME);
+				cmd::config_command_server(
+					&chain_type,
+					args.value_of("chain"),
+					SERVER_CONFIG_FILE_NAME,
+				);
 				return 0;
 			}
 		}
@@ -138,9 +145,28 @@ fn real_main() -> i32 {
 		}
 	}
 
+	// A chain file on the command line takes precedence over the config file
+	if let Some(chain_file) = args.value_of("chain") {
+		let server_config = &mut node_config
+			.as_mut()
+			.unwrap()
+			.members
+			.as_mut()
+			.unwrap()
+			.server;
+		server_config.chain_type = global::ChainTypes::Custom;
+		server_config.custom_chain = Some(chain_file.to_owned());
+	}
+
 	let mut config = node_config.clone().unwrap();
 	let mut logging_config = config.members.as_mut().unwrap().logging.clone().unwrap();
 	logging_config.tui_running = config.members.as_mut().unwrap().server.run_tui;
+	// The remote TUI needs the logs channel even if the local one is disabled
+	if let ("client", Some(client_args)) = args.subcommand() {
+		if client_args.subcommand_name() == Some("tui") {
+			logging_config.tui_running = Some(true);
+		}
+	}
 
 	let (logs_tx, logs_rx) = if logging_config.tui_running.unwrap() {
 		let (logs_tx, logs_rx) = mpsc::sync_channel::<LogEntry>(200);
@@ -148,9 +174,28 @@ fn real_main() -> i32 {
 	} else {
 		(None, None)
 	};
-	init_logger(Some(logging_config), logs_tx);
-
-	global::set_mining_mode(config.members.unwrap().server.clone().chain_type);
+	init_logger(Some(logging_config), logs_tx.clone());
+
+	let server_config = config.members.unwrap().server;
+	if server_config.chain_type == global::ChainTypes::Custom {
+		let chain_file = match server_config.custom_chain {
+			Some(chain_file) => chain_file,
+			None => {
+				error!("A chain file is required to run a custom chain");
+				return 1;
+			}
+		};
+		let params = match config::load_chain_params(&chain_file) {
+			Ok(params) => params,
+			Err(e) => {
+				error!("Error loading chain file {}: {}", chain_file, e);
+				return 1;
+			}
+		};
+		info!("Running custom chain from {}: {:?}", chain_file, params);
+		global::set_custom_chain(params);
+	}
+	global::set_mining_mode(server_config.chain_type);
 
 	if let Some(file_path) = &config.config_file_path {
 		info!(
@@ -171,7 +216,12 @@ fn real_main() -> i32 {
 		}
 
 		// client commands and options
-		("client", Some(client_args)) => cmd::client_command(client_args, node_config.unwrap()),
+		("client", Some(client_args)) => {
+			cmd::client_command(client_args, node_config.unwrap(), logs_tx, logs_rx)
+		}
+
+		// genesis command
+		("genesis", Some(genesis_args)) => cmd::genesis_command(genesis_args),
 
 		// clean command
 		("clean", _) => {
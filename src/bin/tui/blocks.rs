@@ -0,0 +1,239 @@
+// Copyright 2020 The Kepler Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Block explorer view definition
+
+use std::cmp::Ordering;
+
+use cursive::direction::Orientation;
+use cursive::event::Key;
+use cursive::traits::{Boxable, Identifiable, Scrollable};
+use cursive::view::View;
+use cursive::views::{BoxView, Dialog, EditView, LinearLayout, OnEventView, TextView};
+use cursive::Cursive;
+
+use crate::core::core::amount_to_hr_string;
+use crate::servers::{BlockDetails, BlockStats, ServerStats};
+use crate::tui::constants::{MAIN_MENU, TABLE_BLOCKS, VIEW_BLOCKS};
+use crate::tui::table::{TableView, TableViewItem};
+use crate::tui::types::TUIStatusListener;
+use crate::tui::ui::{send_controller, ControllerMessage};
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash)]
+pub enum BlockColumn {
+	Height,
+	Hash,
+	Time,
+	Difficulty,
+	Inputs,
+	Outputs,
+	Kernels,
+	Fees,
+}
+
+impl BlockColumn {
+	fn _as_str(&self) -> &str {
+		match *self {
+			BlockColumn::Height => "Height",
+			BlockColumn::Hash => "Hash",
+			BlockColumn::Time => "Time",
+			BlockColumn::Difficulty => "Difficulty",
+			BlockColumn::Inputs => "Inputs",
+			BlockColumn::Outputs => "Outputs",
+			BlockColumn::Kernels => "Kernels",
+			BlockColumn::Fees => "Fees",
+		}
+	}
+}
+
+impl TableViewItem<BlockColumn> for BlockStats {
+	fn to_column(&self, column: BlockColumn) -> String {
+		match column {
+			BlockColumn::Height => self.height.to_string(),
+			BlockColumn::Hash => self.hash.to_string(),
+			BlockColumn::Time => self.time.format("%Y-%m-%d %H:%M:%S").to_string(),
+			BlockColumn::Difficulty => self.difficulty.to_string(),
+			BlockColumn::Inputs => self.num_inputs.to_string(),
+			BlockColumn::Outputs => self.num_outputs.to_string(),
+			BlockColumn::Kernels => self.num_kernels.to_string(),
+			BlockColumn::Fees => amount_to_hr_string(self.fees, true),
+		}
+	}
+
+	fn cmp(&self, other: &Self, column: BlockColumn) -> Ordering
+	where
+		Self: Sized,
+	{
+		let sort_by_height = || self.height.cmp(&other.height);
+
+		match column {
+			BlockColumn::Height => sort_by_height(),
+			BlockColumn::Hash => self.hash.cmp(&other.hash),
+			BlockColumn::Time => self.time.cmp(&other.time).then(sort_by_height()),
+			BlockColumn::Difficulty => self
+				.difficulty
+				.cmp(&other.difficulty)
+				.then(sort_by_height()),
+			BlockColumn::Inputs => self
+				.num_inputs
+				.cmp(&other.num_inputs)
+				.then(sort_by_height()),
+			BlockColumn::Outputs => self
+				.num_outputs
+				.cmp(&other.num_outputs)
+				.then(sort_by_height()),
+			BlockColumn::Kernels => self
+				.num_kernels
+				.cmp(&other.num_kernels)
+				.then(sort_by_height()),
+			BlockColumn::Fees => self.fees.cmp(&other.fees).then(sort_by_height()),
+		}
+	}
+}
+
+pub struct TUIBlocksView;
+
+impl TUIBlocksView {
+	/// Prompt for a height or hash to jump to
+	fn show_jump_prompt(c: &mut Cursive) {
+		let submit = |c: &mut Cursive, query: &str| {
+			c.pop_layer();
+			if !query.trim().is_empty() {
+				send_controller(c, ControllerMessage::GetBlock(query.to_owned()));
+			}
+		};
+		c.add_layer(
+			Dialog::around(
+				EditView::new()
+					.on_submit(submit)
+					.with_id("blocks_jump_query")
+					.fixed_width(66),
+			)
+			.title("Jump to height or hash")
+			.button("Go", move |c| {
+				let query = c
+					.call_on_id("blocks_jump_query", |e: &mut EditView| e.get_content())
+					.unwrap_or_default();
+				submit(c, &query);
+			})
+			.dismiss_button("Cancel"),
+		);
+	}
+
+	/// Show the kernels and outputs of a block, or why it couldn't be found
+	pub fn show_details(c: &mut Cursive, details: Result<BlockDetails, String>) {
+		let details = match details {
+			Ok(details) => details,
+			Err(e) => {
+				c.add_layer(Dialog::info(format!("Block not found: {}", e)));
+				return;
+			}
+		};
+
+		let block = &details.stats;
+		let mut content = format!(
+			"Height:     {}\nHash:       {}\nTime:       {}\nDifficulty: {}\nFees:       {}\n",
+			block.height,
+			block.hash.to_hex(),
+			block.time,
+			block.difficulty,
+			amount_to_hr_string(block.fees, false),
+		);
+		content.push_str(&format!("\nKernels ({}):\n", details.kernels.len()));
+		for k in &details.kernels {
+			content.push_str(&format!("  {} {}", k.excess, k.features));
+			if k.fee > 0 {
+				content.push_str(&format!(" fee {}", amount_to_hr_string(k.fee, true)));
+			}
+			if k.lock_height > 0 {
+				content.push_str(&format!(" lock height {}", k.lock_height));
+			}
+			content.push('\n');
+		}
+		content.push_str(&format!("\nOutputs ({}):\n", details.outputs.len()));
+		for o in &details.outputs {
+			content.push_str(&format!("  {} {}\n", o.commit, o.features));
+		}
+		content.push_str(&format!("\nInputs ({}):\n", details.inputs.len()));
+		for i in &details.inputs {
+			content.push_str(&format!("  {}\n", i));
+		}
+
+		c.add_layer(
+			Dialog::around(TextView::new(content).scrollable().max_size((100, 40)))
+				.title(format!("Block {}", block.height))
+				.dismiss_button("Close"),
+		);
+	}
+}
+
+impl TUIStatusListener for TUIBlocksView {
+	fn create() -> Box<dyn View> {
+		let table_view = TableView::<BlockStats, BlockColumn>::new()
+			.column(BlockColumn::Height, "Height", |c| {
+				c.width_percent(8).ordering(Ordering::Greater)
+			})
+			.column(BlockColumn::Hash, "Hash", |c| c.width_percent(14))
+			.column(BlockColumn::Time, "Time", |c| c.width_percent(20))
+			.column(BlockColumn::Difficulty, "Difficulty", |c| {
+				c.width_percent(14)
+			})
+			.column(BlockColumn::Inputs, "Inputs", |c| c.width_percent(8))
+			.column(BlockColumn::Outputs, "Outputs", |c| c.width_percent(8))
+			.column(BlockColumn::Kernels, "Kernels", |c| c.width_percent(8))
+			.column(BlockColumn::Fees, "Fees", |c| c.width_percent(20))
+			.default_column(BlockColumn::Height)
+			.on_submit(|c, _row, index| {
+				let hash = c.call_on_id(
+					TABLE_BLOCKS,
+					|t: &mut TableView<BlockStats, BlockColumn>| {
+						t.borrow_item(index).map(|b| b.hash.to_hex())
+					},
+				);
+				if let Some(Some(hash)) = hash {
+					send_controller(c, ControllerMessage::GetBlock(hash));
+				}
+			});
+
+		let blocks_view = BoxView::with_full_screen(
+			LinearLayout::new(Orientation::Vertical)
+				.child(TextView::new(
+					"Enter: block details    /: jump to height or hash",
+				))
+				.child(TextView::new("   "))
+				.child(
+					Dialog::around(table_view.with_id(TABLE_BLOCKS).min_size((50, 20)))
+						.title("Recent Blocks"),
+				),
+		)
+		.with_id(VIEW_BLOCKS);
+
+		let blocks_view = OnEventView::new(blocks_view)
+			.on_pre_event(Key::Esc, move |c| {
+				let _ = c.focus_id(MAIN_MENU);
+			})
+			.on_event('/', TUIBlocksView::show_jump_prompt);
+
+		Box::new(blocks_view)
+	}
+
+	fn update(c: &mut Cursive, stats: &ServerStats) {
+		let _ = c.call_on_id(
+			TABLE_BLOCKS,
+			|t: &mut TableView<BlockStats, BlockColumn>| {
+				t.set_items(stats.block_stats.clone());
+			},
+		);
+	}
+}
@@ -28,8 +28,17 @@ pub const SUBMENU_MINING_BUTTON: &str = "mining_submenu_button";
 pub const TABLE_MINING_STATUS: &str = "mining_status_table";
 pub const TABLE_MINING_DIFF_STATUS: &str = "mining_diff_status_table";
 
+// Blocks View
+pub const VIEW_BLOCKS: &str = "blocks_view";
+pub const TABLE_BLOCKS: &str = "blocks_table";
+
+// Mempool View
+pub const VIEW_MEMPOOL: &str = "mempool_view";
+pub const TABLE_MEMPOOL: &str = "mempool_table";
+
 // Logs View
 pub const VIEW_LOGS: &str = "logs_view";
+pub const LOG_BUFFER: &str = "log_buffer_view";
 
 // Mining View
 pub const VIEW_VERSION: &str = "version_view";
@@ -12,55 +12,313 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+//! Logs view definition, with a filter bar and a scrollback that can be
+//! paused, scrolled through and exported
+
+use cursive::direction::{Direction, Orientation};
+use cursive::event::{Event, EventResult, Key};
 use cursive::theme::{BaseColor, Color, ColorStyle};
-use cursive::traits::Identifiable;
+use cursive::traits::{Boxable, Identifiable};
 use cursive::view::View;
-use cursive::views::BoxView;
-use cursive::{Cursive, Printer};
+use cursive::views::{BoxView, Dialog, EditView, LinearLayout, OnEventView, SelectView, TextView};
+use cursive::{Cursive, Printer, Vec2};
 
-use crate::tui::constants::VIEW_LOGS;
+use crate::tui::constants::{LOG_BUFFER, MAIN_MENU, VIEW_LOGS};
 use cursive::utils::lines::spans::{LinesIterator, Row};
 use cursive::utils::markup::StyledString;
 use kepler_util::logger::LogEntry;
 use log::Level;
+use regex::Regex;
 use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{self, Write};
+
+/// Number of entries kept in the scrollback
+const LOG_SCROLLBACK: usize = 10_000;
+
+/// Default file the scrollback is exported to
+const DEFAULT_EXPORT_PATH: &str = "kepler-tui-logs.txt";
 
 pub struct TUILogsView;
 
 impl TUILogsView {
 	pub fn create() -> Box<dyn View> {
-		let logs_view = BoxView::with_full_screen(LogBufferView::new(200).with_id("logs"));
-		Box::new(logs_view.with_id(VIEW_LOGS))
+		let mut levels = SelectView::new().popup();
+		for level in &[
+			Level::Trace,
+			Level::Debug,
+			Level::Info,
+			Level::Warn,
+			Level::Error,
+		] {
+			levels.add_item(format!("{:<5} and above", level.to_string()), *level);
+		}
+		let levels = levels.on_submit(|c, level: &Level| {
+			let level = *level;
+			c.call_on_id(LOG_BUFFER, |v: &mut LogBufferView| {
+				v.filter.level = level;
+				v.scroll = 0;
+			});
+			TUILogsView::refresh_status(c);
+		});
+		let module = EditView::new()
+			.on_edit(|c, module, _| {
+				let module = module.to_owned();
+				c.call_on_id(LOG_BUFFER, |v: &mut LogBufferView| {
+					v.filter.module = module;
+					v.scroll = 0;
+				});
+				TUILogsView::refresh_status(c);
+			})
+			.fixed_width(24);
+		let search = EditView::new()
+			.on_edit(|c, search, _| {
+				let search = search.to_owned();
+				c.call_on_id(LOG_BUFFER, |v: &mut LogBufferView| {
+					v.set_search(&search);
+				});
+				TUILogsView::refresh_status(c);
+			})
+			.fixed_width(32);
+
+		let filter_bar = LinearLayout::new(Orientation::Horizontal)
+			.child(TextView::new("Level: "))
+			.child(levels)
+			.child(TextView::new("  Module: "))
+			.child(module)
+			.child(TextView::new("  Search: "))
+			.child(search);
+
+		let logs_view = BoxView::with_full_screen(
+			LinearLayout::new(Orientation::Vertical)
+				.child(filter_bar)
+				.child(TextView::new("Following").with_id("logs_status"))
+				.child(TextView::new(
+					"p: pause/follow    Up/Down/PgUp/PgDn: scroll    End: follow    e: export    Tab: filters",
+				))
+				.child(BoxView::with_full_screen(
+					LogBufferView::new(LOG_SCROLLBACK).with_id(LOG_BUFFER),
+				)),
+		)
+		.with_id(VIEW_LOGS);
+
+		let logs_view = OnEventView::new(logs_view)
+			.on_pre_event(Key::Esc, move |c| {
+				let _ = c.focus_id(MAIN_MENU);
+			})
+			.on_event('p', |c| {
+				c.call_on_id(LOG_BUFFER, |v: &mut LogBufferView| {
+					v.toggle_pause();
+				});
+				TUILogsView::refresh_status(c);
+			})
+			.on_event('e', TUILogsView::show_export_prompt);
+
+		Box::new(logs_view)
 	}
 
 	pub fn update(c: &mut Cursive, entry: LogEntry) {
-		c.call_on_id("logs", |t: &mut LogBufferView| {
+		c.call_on_id(LOG_BUFFER, |t: &mut LogBufferView| {
 			t.update(entry);
 		});
+		TUILogsView::refresh_status(c);
+	}
+
+	/// Show whether the logs are paused, and why the search is ignored if
+	/// it isn't a valid regex
+	pub fn refresh_status(c: &mut Cursive) {
+		let status = c.call_on_id(LOG_BUFFER, |v: &mut LogBufferView| v.status());
+		if let Some(status) = status {
+			c.call_on_id("logs_status", |t: &mut TextView| {
+				t.set_content(status);
+			});
+		}
+	}
+
+	/// Prompt for the file the displayed entries are exported to
+	fn show_export_prompt(c: &mut Cursive) {
+		let submit = |c: &mut Cursive, path: &str| {
+			c.pop_layer();
+			let path = path.trim().to_owned();
+			let result = c.call_on_id(LOG_BUFFER, |v: &mut LogBufferView| v.export(&path));
+			let message = match result {
+				Some(Ok(count)) => format!("Exported {} entries to {}", count, path),
+				Some(Err(e)) => format!("Unable to export the logs to {}: {}", path, e),
+				None => return,
+			};
+			c.add_layer(Dialog::info(message));
+		};
+		c.add_layer(
+			Dialog::around(
+				EditView::new()
+					.content(DEFAULT_EXPORT_PATH)
+					.on_submit(submit)
+					.with_id("logs_export_path")
+					.fixed_width(66),
+			)
+			.title("Export the displayed entries to")
+			.button("Export", move |c| {
+				let path = c
+					.call_on_id("logs_export_path", |e: &mut EditView| e.get_content())
+					.unwrap_or_default();
+				submit(c, &path);
+			})
+			.dismiss_button("Cancel"),
+		);
+	}
+}
+
+/// Entries displayed, by level threshold, module and message
+struct LogFilter {
+	level: Level,
+	module: String,
+	search: Option<Regex>,
+}
+
+impl LogFilter {
+	fn matches(&self, entry: &LogEntry) -> bool {
+		entry.level <= self.level
+			&& entry.module.contains(self.module.as_str())
+			&& self
+				.search
+				.as_ref()
+				.map_or(true, |r| r.is_match(&entry.log))
 	}
 }
 
 struct LogBufferView {
-	buffer: VecDeque<LogEntry>,
+	/// Entries along with their sequence number, latest first
+	buffer: VecDeque<(u64, LogEntry)>,
+	size: usize,
+	last_id: u64,
+	filter: LogFilter,
+	/// Why the current search is ignored
+	search_error: Option<String>,
+	/// Last entry displayed while paused, the entries received since then
+	/// only being displayed once following again
+	paused_at: Option<u64>,
+	/// Number of displayed entries scrolled past, from the latest one
+	scroll: usize,
+	height: usize,
 }
 
 impl LogBufferView {
 	fn new(size: usize) -> Self {
-		let mut buffer = VecDeque::new();
-		buffer.resize(
+		LogBufferView {
+			buffer: VecDeque::with_capacity(size * 2),
 			size,
-			LogEntry {
-				log: String::new(),
-				level: Level::Info,
+			last_id: 0,
+			filter: LogFilter {
+				level: Level::Trace,
+				module: String::new(),
+				search: None,
 			},
-		);
-
-		LogBufferView { buffer }
+			search_error: None,
+			paused_at: None,
+			scroll: 0,
+			height: 0,
+		}
 	}
 
 	fn update(&mut self, entry: LogEntry) {
-		self.buffer.push_front(entry);
-		self.buffer.pop_back();
+		self.last_id += 1;
+		self.buffer.push_front((self.last_id, entry));
+		self.trim();
+	}
+
+	/// Drops the oldest entries beyond the buffer size. While paused, up to
+	/// a buffer size of entries received since are kept on top of the
+	/// displayed ones.
+	fn trim(&mut self) {
+		let received = match self.paused_at {
+			Some(paused_at) => ((self.last_id - paused_at) as usize).min(self.size),
+			None => 0,
+		};
+		self.buffer.truncate(self.size + received);
+	}
+
+	fn set_search(&mut self, search: &str) {
+		self.scroll = 0;
+		if search.is_empty() {
+			self.filter.search = None;
+			self.search_error = None;
+			return;
+		}
+		match Regex::new(search) {
+			Ok(regex) => {
+				self.filter.search = Some(regex);
+				self.search_error = None;
+			}
+			Err(e) => {
+				self.filter.search = None;
+				self.search_error = Some(e.to_string());
+			}
+		}
+	}
+
+	/// Entries matching the filter, latest first
+	fn displayed(&self) -> impl Iterator<Item = &LogEntry> {
+		let last_id = self.paused_at.unwrap_or(self.last_id);
+		let filter = &self.filter;
+		self.buffer
+			.iter()
+			.filter(move |(id, entry)| *id <= last_id && filter.matches(entry))
+			.map(|(_, entry)| entry)
+	}
+
+	fn toggle_pause(&mut self) {
+		if self.paused_at.is_some() {
+			self.follow();
+		} else {
+			self.paused_at = Some(self.last_id);
+		}
+	}
+
+	fn follow(&mut self) {
+		self.paused_at = None;
+		self.scroll = 0;
+		self.trim();
+	}
+
+	/// Scrolls back in time for a positive delta, pausing the logs so the
+	/// entries don't move while being read
+	fn scroll_by(&mut self, delta: isize) {
+		if delta > 0 && self.paused_at.is_none() {
+			self.paused_at = Some(self.last_id);
+		}
+		let max = self.displayed().count().saturating_sub(1) as isize;
+		self.scroll = (self.scroll as isize + delta).max(0).min(max) as usize;
+	}
+
+	fn status(&self) -> String {
+		let mut status = match self.paused_at {
+			Some(paused_at) => format!(
+				"Paused, {} new entries",
+				self.buffer
+					.iter()
+					.take_while(|(id, _)| *id > paused_at)
+					.count()
+			),
+			None => "Following".to_string(),
+		};
+		if let Some(ref e) = self.search_error {
+			status.push_str(&format!("    Invalid search, ignored: {}", e));
+		}
+		status
+	}
+
+	/// Writes the displayed entries to a file, oldest first
+	fn export(&self, path: &str) -> io::Result<usize> {
+		let mut entries: Vec<&LogEntry> = self.displayed().collect();
+		entries.reverse();
+		let mut file = File::create(path)?;
+		for entry in &entries {
+			file.write_all(entry.log.as_bytes())?;
+			if !entry.log.ends_with('\n') {
+				file.write_all(b"\n")?;
+			}
+		}
+		Ok(entries.len())
 	}
 
 	fn color(level: Level) -> ColorStyle {
@@ -87,7 +345,10 @@ impl LogBufferView {
 impl View for LogBufferView {
 	fn draw(&self, printer: &Printer) {
 		let mut i = 0;
-		for entry in self.buffer.iter().take(printer.size.y) {
+		for entry in self.displayed().skip(self.scroll) {
+			if i >= printer.size.y {
+				break;
+			}
 			printer.with_color(LogBufferView::color(entry.level), |p| {
 				let log_message = StyledString::plain(entry.log.as_str());
 				let mut rows: Vec<Row> = LinesIterator::new(&log_message, printer.size.x).collect();
@@ -101,4 +362,75 @@ impl View for LogBufferView {
 			});
 		}
 	}
+
+	fn layout(&mut self, size: Vec2) {
+		self.height = size.y;
+	}
+
+	fn take_focus(&mut self, _: Direction) -> bool {
+		true
+	}
+
+	fn on_event(&mut self, event: Event) -> EventResult {
+		let page = self.height.max(1) as isize;
+		match event {
+			Event::Key(Key::Up) => self.scroll_by(1),
+			Event::Key(Key::Down) => self.scroll_by(-1),
+			Event::Key(Key::PageUp) => self.scroll_by(page),
+			Event::Key(Key::PageDown) => self.scroll_by(-page),
+			Event::Key(Key::End) => self.follow(),
+			_ => return EventResult::Ignored,
+		}
+		EventResult::with_cb(TUILogsView::refresh_status)
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	fn entry(log: &str, level: Level, module: &str) -> LogEntry {
+		LogEntry {
+			log: log.to_owned(),
+			level,
+			module: module.to_owned(),
+		}
+	}
+
+	fn displayed(view: &LogBufferView) -> Vec<String> {
+		view.displayed().map(|e| e.log.clone()).collect()
+	}
+
+	#[test]
+	fn test_log_buffer_view() {
+		let mut view = LogBufferView::new(3);
+		view.update(entry("a", Level::Debug, "kepler_p2p::peer"));
+		view.update(entry("b", Level::Info, "kepler_chain::chain"));
+		view.update(entry("c", Level::Warn, "kepler_p2p::peers"));
+		view.update(entry("d block 12", Level::Info, "kepler_servers::sync"));
+		assert_eq!(displayed(&view), vec!["d block 12", "c", "b"]);
+
+		view.filter.level = Level::Warn;
+		assert_eq!(displayed(&view), vec!["c"]);
+		view.filter.level = Level::Trace;
+		view.filter.module = "p2p".to_owned();
+		assert_eq!(displayed(&view), vec!["c"]);
+		view.filter.module = String::new();
+		view.set_search(r"block \d+");
+		assert_eq!(displayed(&view), vec!["d block 12"]);
+		view.set_search("(");
+		assert!(view.search_error.is_some());
+		assert_eq!(displayed(&view).len(), 3);
+
+		// Entries received while paused are only displayed once following
+		view.toggle_pause();
+		view.update(entry("e", Level::Info, "kepler_servers::sync"));
+		assert_eq!(displayed(&view), vec!["d block 12", "c", "b"]);
+		assert!(view.status().starts_with("Paused, 1 new entries"));
+		view.scroll_by(10);
+		assert_eq!(view.scroll, 2);
+		view.toggle_pause();
+		assert_eq!(view.scroll, 0);
+		assert_eq!(displayed(&view), vec!["e", "d block 12", "c"]);
+	}
 }
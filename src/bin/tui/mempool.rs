@@ -0,0 +1,239 @@
+// Copyright 2020 The Kepler Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Transaction pool view definition
+
+use std::cmp::Ordering;
+
+use chrono::prelude::Utc;
+use cursive::direction::Orientation;
+use cursive::event::Key;
+use cursive::traits::{Boxable, Identifiable};
+use cursive::view::View;
+use cursive::views::{BoxView, Dialog, LinearLayout, OnEventView, TextView};
+use cursive::Cursive;
+
+use crate::core::core::amount_to_hr_string;
+use crate::servers::{PoolEntryStats, ServerStats};
+use crate::tui::constants::{MAIN_MENU, TABLE_MEMPOOL, VIEW_MEMPOOL};
+use crate::tui::table::{TableView, TableViewItem};
+use crate::tui::types::TUIStatusListener;
+
+/// Upper bounds of the fee-per-weight histogram buckets, the last bucket
+/// holding everything above
+const FEE_BUCKETS: [(u64, &str); 6] = [
+	(1_000, "< 1k"),
+	(10_000, "< 10k"),
+	(100_000, "< 100k"),
+	(1_000_000, "< 1M"),
+	(10_000_000, "< 10M"),
+	(100_000_000, "< 100M"),
+];
+
+/// Width of the longest histogram bar
+const HISTOGRAM_WIDTH: usize = 40;
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash)]
+pub enum PoolColumn {
+	Pool,
+	Source,
+	Age,
+	Weight,
+	Fee,
+	FeeToWeight,
+	Excess,
+}
+
+impl PoolColumn {
+	fn _as_str(&self) -> &str {
+		match *self {
+			PoolColumn::Pool => "Pool",
+			PoolColumn::Source => "Source",
+			PoolColumn::Age => "Age",
+			PoolColumn::Weight => "Weight",
+			PoolColumn::Fee => "Fee",
+			PoolColumn::FeeToWeight => "Fee/Weight",
+			PoolColumn::Excess => "Kernel Excess",
+		}
+	}
+}
+
+impl TableViewItem<PoolColumn> for PoolEntryStats {
+	fn to_column(&self, column: PoolColumn) -> String {
+		match column {
+			PoolColumn::Pool => if self.stem { "Stem" } else { "Tx" }.to_string(),
+			PoolColumn::Source => self.source.clone(),
+			PoolColumn::Age => format!("{}s", (Utc::now() - self.received).num_seconds()),
+			PoolColumn::Weight => self.weight.to_string(),
+			PoolColumn::Fee => amount_to_hr_string(self.fee, true),
+			PoolColumn::FeeToWeight => self.fee_to_weight.to_string(),
+			PoolColumn::Excess => match self.kernel_excesses.len() {
+				0 => "-".to_string(),
+				1 => self.kernel_excesses[0].clone(),
+				n => format!("{} (+{})", self.kernel_excesses[0], n - 1),
+			},
+		}
+	}
+
+	fn cmp(&self, other: &Self, column: PoolColumn) -> Ordering
+	where
+		Self: Sized,
+	{
+		let sort_by_received = || self.received.cmp(&other.received);
+
+		match column {
+			PoolColumn::Pool => self.stem.cmp(&other.stem).then(sort_by_received()),
+			PoolColumn::Source => self.source.cmp(&other.source).then(sort_by_received()),
+			// Ascending age is descending reception time
+			PoolColumn::Age => other.received.cmp(&self.received),
+			PoolColumn::Weight => self.weight.cmp(&other.weight).then(sort_by_received()),
+			PoolColumn::Fee => self.fee.cmp(&other.fee).then(sort_by_received()),
+			PoolColumn::FeeToWeight => self
+				.fee_to_weight
+				.cmp(&other.fee_to_weight)
+				.then(sort_by_received()),
+			PoolColumn::Excess => self.kernel_excesses.cmp(&other.kernel_excesses),
+		}
+	}
+}
+
+/// Min, median and max fee per weight, followed by a histogram of the
+/// entries per fee-per-weight bucket
+fn fee_summary(entries: &[PoolEntryStats]) -> String {
+	if entries.is_empty() {
+		return "No transactions".to_string();
+	}
+	let mut rates: Vec<u64> = entries.iter().map(|e| e.fee_to_weight).collect();
+	rates.sort();
+	let mut summary = format!(
+		"Fee/Weight: min {}, median {}, max {}\n",
+		rates[0],
+		rates[rates.len() / 2],
+		rates[rates.len() - 1],
+	);
+
+	let histogram = fee_histogram(&rates);
+	let max_count = histogram.iter().map(|(_, n)| *n).max().unwrap_or(0).max(1);
+	for (label, count) in histogram {
+		let bar = "#".repeat((count * HISTOGRAM_WIDTH + max_count - 1) / max_count);
+		summary.push_str(&format!("{:>8} | {} {}\n", label, bar, count));
+	}
+	summary
+}
+
+/// Number of entries per bucket, from the lowest to the highest non-empty
+/// bucket
+fn fee_histogram(rates: &[u64]) -> Vec<(&'static str, usize)> {
+	let mut counts = vec![0; FEE_BUCKETS.len() + 1];
+	for rate in rates {
+		let bucket = FEE_BUCKETS
+			.iter()
+			.position(|(bound, _)| rate < bound)
+			.unwrap_or(FEE_BUCKETS.len());
+		counts[bucket] += 1;
+	}
+	let labels = FEE_BUCKETS
+		.iter()
+		.map(|(_, label)| *label)
+		.chain(Some(">= 100M"));
+	let buckets: Vec<(&str, usize)> = labels.zip(counts).collect();
+	let first = buckets.iter().position(|(_, n)| *n > 0).unwrap_or(0);
+	let last = buckets.iter().rposition(|(_, n)| *n > 0).unwrap_or(0);
+	buckets[first..=last].to_vec()
+}
+
+pub struct TUIMempoolView;
+
+impl TUIStatusListener for TUIMempoolView {
+	fn create() -> Box<dyn View> {
+		let table_view = TableView::<PoolEntryStats, PoolColumn>::new()
+			.column(PoolColumn::Pool, "Pool", |c| c.width_percent(6))
+			.column(PoolColumn::Source, "Source", |c| c.width_percent(12))
+			.column(PoolColumn::Age, "Age", |c| c.width_percent(8))
+			.column(PoolColumn::Weight, "Weight", |c| c.width_percent(8))
+			.column(PoolColumn::Fee, "Fee", |c| c.width_percent(12))
+			.column(PoolColumn::FeeToWeight, "Fee/Weight", |c| {
+				c.width_percent(10).ordering(Ordering::Greater)
+			})
+			.column(PoolColumn::Excess, "Kernel Excess", |c| c.width_percent(44))
+			.default_column(PoolColumn::FeeToWeight);
+
+		let mempool_view = BoxView::with_full_screen(
+			LinearLayout::new(Orientation::Vertical)
+				.child(TextView::new("  ").with_id("mempool_totals"))
+				.child(TextView::new("   "))
+				.child(TextView::new("  ").with_id("mempool_fees"))
+				.child(
+					Dialog::around(table_view.with_id(TABLE_MEMPOOL).min_size((50, 20)))
+						.title("Pool Transactions"),
+				),
+		)
+		.with_id(VIEW_MEMPOOL);
+
+		let mempool_view = OnEventView::new(mempool_view).on_pre_event(Key::Esc, move |c| {
+			let _ = c.focus_id(MAIN_MENU);
+		});
+
+		Box::new(mempool_view)
+	}
+
+	fn update(c: &mut Cursive, stats: &ServerStats) {
+		// The pool may have been busy, keep the previous content until the next
+		// update in that case
+		let tx_stats = match stats.tx_stats {
+			Some(ref tx_stats) => tx_stats,
+			None => return,
+		};
+		let totals = format!(
+			"Transaction Pool: {} txs ({} kernels)    Stem Pool: {} txs ({} kernels)",
+			tx_stats.tx_pool_size,
+			tx_stats.tx_pool_kernels,
+			tx_stats.stem_pool_size,
+			tx_stats.stem_pool_kernels,
+		);
+		let fees = fee_summary(&tx_stats.entries);
+		let _ = c.call_on_id("mempool_totals", |t: &mut TextView| {
+			t.set_content(totals);
+		});
+		let _ = c.call_on_id("mempool_fees", |t: &mut TextView| {
+			t.set_content(fees);
+		});
+		let _ = c.call_on_id(
+			TABLE_MEMPOOL,
+			|t: &mut TableView<PoolEntryStats, PoolColumn>| {
+				t.set_items(tx_stats.entries.clone());
+			},
+		);
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	#[test]
+	fn test_fee_histogram() {
+		assert_eq!(fee_histogram(&[5]), vec![("< 1k", 1)]);
+		assert_eq!(
+			fee_histogram(&[20_000, 50_000, 2_000_000, 500_000_000]),
+			vec![
+				("< 100k", 2),
+				("< 1M", 0),
+				("< 10M", 1),
+				("< 100M", 0),
+				(">= 100M", 1)
+			]
+		);
+	}
+}
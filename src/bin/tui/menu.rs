@@ -25,8 +25,9 @@ use cursive::views::{
 use cursive::Cursive;
 
 use crate::tui::constants::{
-	MAIN_MENU, ROOT_STACK, SUBMENU_MINING_BUTTON, VIEW_BASIC_STATUS, VIEW_LOGS, VIEW_MINING,
-	VIEW_PEER_SYNC, VIEW_VERSION,
+	LOG_BUFFER, MAIN_MENU, ROOT_STACK, SUBMENU_MINING_BUTTON, TABLE_BLOCKS, TABLE_MEMPOOL,
+	VIEW_BASIC_STATUS, VIEW_BLOCKS, VIEW_LOGS, VIEW_MEMPOOL, VIEW_MINING, VIEW_PEER_SYNC,
+	VIEW_VERSION,
 };
 
 pub fn create() -> Box<dyn View> {
@@ -37,6 +38,8 @@ pub fn create() -> Box<dyn View> {
 	main_menu
 		.get_mut()
 		.add_item("Peers and Sync", VIEW_PEER_SYNC);
+	main_menu.get_mut().add_item("Blocks", VIEW_BLOCKS);
+	main_menu.get_mut().add_item("Mempool", VIEW_MEMPOOL);
 	main_menu.get_mut().add_item("Mining", VIEW_MINING);
 	main_menu.get_mut().add_item("Logs", VIEW_LOGS);
 	main_menu.get_mut().add_item("Version Info", VIEW_VERSION);
@@ -58,6 +61,15 @@ pub fn create() -> Box<dyn View> {
 			if v == VIEW_MINING {
 				let _ = c.focus_id(SUBMENU_MINING_BUTTON);
 			}
+			if v == VIEW_BLOCKS {
+				let _ = c.focus_id(TABLE_BLOCKS);
+			}
+			if v == VIEW_MEMPOOL {
+				let _ = c.focus_id(TABLE_MEMPOOL);
+			}
+			if v == VIEW_LOGS {
+				let _ = c.focus_id(LOG_BUFFER);
+			}
 		});
 	let main_menu = OnEventView::new(main_menu)
 		.on_pre_event('j', move |c| {
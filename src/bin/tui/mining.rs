@@ -22,7 +22,7 @@ use cursive::event::Key;
 use cursive::traits::{Boxable, Identifiable};
 use cursive::view::View;
 use cursive::views::{
-	BoxView, Button, Dialog, LinearLayout, OnEventView, Panel, StackView, TextView,
+	BoxView, Button, Dialog, EditView, LinearLayout, OnEventView, Panel, StackView, TextView,
 };
 use cursive::Cursive;
 use std::time;
@@ -34,6 +34,62 @@ use crate::tui::types::TUIStatusListener;
 
 use crate::servers::{DiffBlock, ServerStats, WorkerStats};
 use crate::tui::table::{TableView, TableViewItem};
+use crate::tui::ui::{send_controller, ControllerMessage};
+
+/// Sparkline levels, from the lowest value to the highest
+const SPARK_LEVELS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+
+/// Maximum width of a chart, blocks being averaged together beyond it
+const CHART_WIDTH: usize = 96;
+
+/// Values as a sparkline at most `width` characters wide, consecutive values
+/// being averaged together when there are more of them than characters
+fn sparkline(values: &[f64], width: usize) -> String {
+	if values.is_empty() || width == 0 {
+		return String::new();
+	}
+	let columns = values.len().min(width);
+	let points: Vec<f64> = (0..columns)
+		.map(|i| {
+			let bucket = &values[i * values.len() / columns..(i + 1) * values.len() / columns];
+			bucket.iter().sum::<f64>() / bucket.len() as f64
+		})
+		.collect();
+	let min = points.iter().cloned().fold(f64::INFINITY, f64::min);
+	let max = points.iter().cloned().fold(f64::NEG_INFINITY, f64::max);
+	let top = (SPARK_LEVELS.len() - 1) as f64;
+	points
+		.iter()
+		.map(|p| {
+			let level = if max > min {
+				((p - min) / (max - min) * top).round() as usize
+			} else {
+				SPARK_LEVELS.len() / 2
+			};
+			SPARK_LEVELS[level]
+		})
+		.collect()
+}
+
+/// Min, average and max of the values followed by their sparkline
+fn chart(values: &[f64], precision: usize) -> String {
+	if values.is_empty() {
+		return "No blocks".to_string();
+	}
+	let min = values.iter().cloned().fold(f64::INFINITY, f64::min);
+	let max = values.iter().cloned().fold(f64::NEG_INFINITY, f64::max);
+	let avg = values.iter().sum::<f64>() / values.len() as f64;
+	format!(
+		"min {:.*}    avg {:.*}    max {:.*}\n{}",
+		precision,
+		min,
+		precision,
+		avg,
+		precision,
+		max,
+		sparkline(values, CHART_WIDTH)
+	)
+}
 
 #[derive(Copy, Clone, PartialEq, Eq, Hash)]
 enum StratumWorkerColumn {
@@ -165,6 +221,78 @@ impl TableViewItem<DiffColumn> for DiffBlock {
 /// Mining status view
 pub struct TUIMiningView;
 
+impl TUIMiningView {
+	/// Prompt for the number of blocks to chart
+	fn show_chart_window_prompt(c: &mut Cursive) {
+		let submit = |c: &mut Cursive, window: &str| match window.trim().parse::<u64>() {
+			Ok(window) => {
+				c.pop_layer();
+				send_controller(c, ControllerMessage::SetChartWindow(window));
+			}
+			Err(_) => c.add_layer(Dialog::info(format!("Invalid number of blocks {}", window))),
+		};
+		c.add_layer(
+			Dialog::around(
+				EditView::new()
+					.on_submit(submit)
+					.with_id("chart_window_query")
+					.fixed_width(20),
+			)
+			.title("Number of blocks to chart")
+			.button("Set", move |c| {
+				let window = c
+					.call_on_id("chart_window_query", |e: &mut EditView| e.get_content())
+					.unwrap_or_default();
+				submit(c, &window);
+			})
+			.dismiss_button("Cancel"),
+		);
+	}
+
+	/// Redraw the charts from the provided blocks, oldest first
+	pub fn update_charts(c: &mut Cursive, blocks: Result<Vec<DiffBlock>, String>) {
+		let blocks = match blocks {
+			Ok(blocks) => blocks,
+			Err(e) => {
+				c.call_on_id("chart_window", |t: &mut TextView| {
+					t.set_content(format!("Unable to load the chart data: {}", e));
+				});
+				return;
+			}
+		};
+		let window = match (blocks.first(), blocks.last()) {
+			(Some(first), Some(last)) => format!(
+				"Last {} blocks, heights {} to {}",
+				blocks.len(),
+				first.block_height,
+				last.block_height
+			),
+			_ => "No blocks yet".to_string(),
+		};
+		let series = |f: &dyn Fn(&DiffBlock) -> f64| blocks.iter().map(f).collect::<Vec<f64>>();
+		let block_time = chart(&series(&|b| b.duration as f64), 0);
+		let difficulty = chart(&series(&|b| b.difficulty as f64), 0);
+		let scaling = chart(&series(&|b| b.secondary_scaling as f64), 0);
+		let hashrate = chart(&series(&|b| b.network_hashrate()), 2);
+
+		c.call_on_id("chart_window", |t: &mut TextView| {
+			t.set_content(window);
+		});
+		c.call_on_id("chart_block_time", |t: &mut TextView| {
+			t.set_content(block_time);
+		});
+		c.call_on_id("chart_difficulty", |t: &mut TextView| {
+			t.set_content(difficulty);
+		});
+		c.call_on_id("chart_secondary_scaling", |t: &mut TextView| {
+			t.set_content(scaling);
+		});
+		c.call_on_id("chart_network_hashrate", |t: &mut TextView| {
+			t.set_content(hashrate);
+		});
+	}
+}
+
 impl TUIStatusListener for TUIMiningView {
 	/// Create the mining view
 	fn create() -> Box<dyn View> {
@@ -181,9 +309,16 @@ impl TUIStatusListener for TUIMiningView {
 				sv.move_to_front(pos);
 			});
 		});
+		let charts_button = Button::new_raw("Charts", |s| {
+			let _ = s.call_on_id("mining_stack_view", |sv: &mut StackView| {
+				let pos = sv.find_layer_from_id("mining_charts_view").unwrap();
+				sv.move_to_front(pos);
+			});
+		});
 		let mining_submenu = LinearLayout::new(Orientation::Horizontal)
 			.child(Panel::new(devices_button))
-			.child(Panel::new(difficulty_button));
+			.child(Panel::new(difficulty_button))
+			.child(Panel::new(charts_button));
 
 		let table_view = TableView::<WorkerStats, StratumWorkerColumn>::new()
 			.column(StratumWorkerColumn::Id, "Worker ID", |c| c.width_percent(8))
@@ -237,6 +372,14 @@ impl TUIStatusListener for TUIMiningView {
 			.child(
 				LinearLayout::new(Orientation::Horizontal)
 					.child(TextView::new("  ").with_id("stratum_edge_bits_status")),
+			)
+			.child(
+				LinearLayout::new(Orientation::Horizontal)
+					.child(TextView::new("  ").with_id("cpu_miner_status")),
+			)
+			.child(
+				LinearLayout::new(Orientation::Horizontal)
+					.child(TextView::new("  ").with_id("cpu_miner_hashrate")),
 			);
 
 		let mining_device_view = LinearLayout::new(Orientation::Vertical)
@@ -294,7 +437,31 @@ impl TUIStatusListener for TUIMiningView {
 			))
 			.with_id("mining_difficulty_view");
 
+		let mining_charts_view = LinearLayout::new(Orientation::Vertical)
+			.child(TextView::new("  ").with_id("chart_window"))
+			.child(TextView::new(
+				"+: more blocks    -: fewer blocks    n: set the number of blocks",
+			))
+			.child(
+				Dialog::around(TextView::new("").with_id("chart_block_time"))
+					.title("Block Time (s)"),
+			)
+			.child(
+				Dialog::around(TextView::new("").with_id("chart_difficulty"))
+					.title("Network Difficulty"),
+			)
+			.child(
+				Dialog::around(TextView::new("").with_id("chart_secondary_scaling"))
+					.title("Secondary Scaling"),
+			)
+			.child(
+				Dialog::around(TextView::new("").with_id("chart_network_hashrate"))
+					.title("Estimated Network Hashrate (graphs/s)"),
+			)
+			.with_id("mining_charts_view");
+
 		let view_stack = StackView::new()
+			.layer(mining_charts_view)
 			.layer(mining_difficulty_view)
 			.layer(mining_device_view)
 			.with_id("mining_stack_view");
@@ -303,9 +470,17 @@ impl TUIStatusListener for TUIMiningView {
 			.child(mining_submenu)
 			.child(view_stack);
 
-		let mining_view = OnEventView::new(mining_view).on_pre_event(Key::Esc, move |c| {
-			let _ = c.focus_id(MAIN_MENU);
-		});
+		let mining_view = OnEventView::new(mining_view)
+			.on_pre_event(Key::Esc, move |c| {
+				let _ = c.focus_id(MAIN_MENU);
+			})
+			.on_event('+', |c| {
+				send_controller(c, ControllerMessage::ResizeChartWindow { larger: true });
+			})
+			.on_event('-', |c| {
+				send_controller(c, ControllerMessage::ResizeChartWindow { larger: false });
+			})
+			.on_event('n', TUIMiningView::show_chart_window_prompt);
 
 		Box::new(mining_view.with_id(VIEW_MINING))
 	}
@@ -372,6 +547,30 @@ impl TUIStatusListener for TUIMiningView {
 		c.call_on_id("stratum_edge_bits_status", |t: &mut TextView| {
 			t.set_content(stratum_edge_bits);
 		});
+
+		let cpu_miner_stats = &stats.cpu_miner_stats;
+		let cpu_miner_status = if cpu_miner_stats.is_enabled {
+			format!(
+				"CPU miner running:     {} ({} threads, Cuckatoo{})",
+				cpu_miner_stats.is_running, cpu_miner_stats.num_threads, cpu_miner_stats.edge_bits
+			)
+		} else {
+			"CPU miner enabled:     false".to_string()
+		};
+		let cpu_miner_hashrate = if cpu_miner_stats.is_enabled {
+			format!(
+				"CPU miner hashrate:    {:.*} graphs/s, {} blocks found",
+				2, cpu_miner_stats.graphs_per_sec, cpu_miner_stats.num_blocks_found
+			)
+		} else {
+			String::new()
+		};
+		c.call_on_id("cpu_miner_status", |t: &mut TextView| {
+			t.set_content(cpu_miner_status);
+		});
+		c.call_on_id("cpu_miner_hashrate", |t: &mut TextView| {
+			t.set_content(cpu_miner_hashrate);
+		});
 		let _ = c.call_on_id(
 			TABLE_MINING_STATUS,
 			|t: &mut TableView<WorkerStats, StratumWorkerColumn>| {
@@ -380,3 +579,20 @@ impl TUIStatusListener for TUIMiningView {
 		);
 	}
 }
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	#[test]
+	fn test_sparkline() {
+		assert_eq!(sparkline(&[], 10), "");
+		assert_eq!(
+			sparkline(&[1.0, 2.0, 3.0, 4.0, 5.0, 6.0, 7.0, 8.0], 10),
+			"▁▂▃▄▅▆▇█"
+		);
+		assert_eq!(sparkline(&[5.0, 5.0, 5.0], 10), "▅▅▅");
+		// Pairs of values averaged together
+		assert_eq!(sparkline(&[0.0, 2.0, 10.0, 10.0], 2), "▁█");
+	}
+}
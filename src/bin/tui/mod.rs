@@ -16,11 +16,15 @@
 use chrono;
 use humansize;
 //
+mod blocks;
 mod constants;
 mod logs;
+mod mempool;
 mod menu;
 mod mining;
+pub mod node;
 mod peers;
+pub mod remote;
 mod status;
 pub mod table;
 mod types;
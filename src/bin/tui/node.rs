@@ -0,0 +1,104 @@
+// Copyright 2020 The Kepler Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Node the TUI displays, either the server running in the same process or
+//! a remote one attached to over its APIs
+
+use std::net::SocketAddr;
+use std::sync::mpsc;
+use std::thread;
+
+use crate::p2p::{PeerAddr, PeerData};
+use crate::servers::{BlockDetails, DiffBlock, PeerDetails, Server, ServerStats};
+use crate::tui::types::UIMessage;
+
+/// Everything the controller needs from the node it displays. Errors are
+/// returned as messages ready to be shown to the user.
+pub trait NodeSource {
+	/// Stats refreshing all the views
+	fn get_server_stats(&mut self) -> Result<ServerStats, String>;
+	/// Forwards the log entries received since the last call to the logs
+	/// view, if the node logs aren't sent to it directly
+	fn update_logs(&mut self) {}
+	/// Full content of a block, looked up by height or by hash
+	fn get_block_details(&self, height_or_hash: &str) -> Result<BlockDetails, String>;
+	/// Difficulty data of the last `count` blocks, oldest first
+	fn get_diff_blocks(&mut self, count: u64) -> Result<Vec<DiffBlock>, String>;
+	/// Stored data, live stats and ban history of a peer
+	fn get_peer_details(&self, addr: &str) -> Result<PeerDetails, String>;
+	/// All the peers currently banned
+	fn banned_peers(&self) -> Result<Vec<PeerData>, String>;
+	fn ban_peer(&self, addr: &str) -> Result<(), String>;
+	fn unban_peer(&self, addr: &str) -> Result<(), String>;
+	fn disconnect_peer(&self, addr: &str) -> Result<(), String>;
+	/// Connects to a peer without blocking the UI, sending the outcome to it
+	/// once known
+	fn connect_peer(&self, addr: String, ui_tx: mpsc::Sender<UIMessage>);
+	/// Called when the user quits the TUI
+	fn stop(self);
+}
+
+impl NodeSource for Server {
+	fn get_server_stats(&mut self) -> Result<ServerStats, String> {
+		Server::get_server_stats(self).map_err(|e| format!("{:?}", e))
+	}
+
+	fn get_block_details(&self, height_or_hash: &str) -> Result<BlockDetails, String> {
+		Server::get_block_details(self, height_or_hash).map_err(|e| format!("{:?}", e))
+	}
+
+	fn get_diff_blocks(&mut self, count: u64) -> Result<Vec<DiffBlock>, String> {
+		Server::get_diff_blocks(self, count).map_err(|e| format!("{:?}", e))
+	}
+
+	fn get_peer_details(&self, addr: &str) -> Result<PeerDetails, String> {
+		Server::get_peer_details(self, addr).map_err(|e| format!("{:?}", e))
+	}
+
+	fn banned_peers(&self) -> Result<Vec<PeerData>, String> {
+		Ok(Server::banned_peers(self))
+	}
+
+	fn ban_peer(&self, addr: &str) -> Result<(), String> {
+		Server::ban_peer(self, addr).map_err(|e| format!("{:?}", e))
+	}
+
+	fn unban_peer(&self, addr: &str) -> Result<(), String> {
+		Server::unban_peer(self, addr).map_err(|e| format!("{:?}", e))
+	}
+
+	fn disconnect_peer(&self, addr: &str) -> Result<(), String> {
+		Server::disconnect_peer(self, addr).map_err(|e| format!("{:?}", e))
+	}
+
+	fn connect_peer(&self, addr: String, ui_tx: mpsc::Sender<UIMessage>) {
+		let p2p = self.p2p.clone();
+		let _ = thread::Builder::new()
+			.name("tui_connect_peer".to_string())
+			.spawn(move || {
+				let result = match addr.parse::<SocketAddr>() {
+					Ok(socket_addr) => p2p
+						.connect(PeerAddr(socket_addr))
+						.map(|_| format!("Connected to peer {}", addr))
+						.map_err(|e| format!("{:?}", e)),
+					Err(_) => Err(format!("Invalid peer address {}", addr)),
+				};
+				let _ = ui_tx.send(UIMessage::PeerActionResult(result));
+			});
+	}
+
+	fn stop(self) {
+		Server::stop(self)
+	}
+}
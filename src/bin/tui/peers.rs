@@ -16,21 +16,23 @@
 
 use std::cmp::Ordering;
 
-use crate::servers::{PeerStats, ServerStats};
+use crate::p2p::PeerData;
+use crate::servers::{PeerDetails, PeerStats, ServerStats};
 
 use crate::tui::humansize::{file_size_opts::CONVENTIONAL, FileSize};
 use chrono::prelude::*;
 
 use cursive::direction::Orientation;
 use cursive::event::Key;
-use cursive::traits::{Boxable, Identifiable};
+use cursive::traits::{Boxable, Identifiable, Scrollable};
 use cursive::view::View;
-use cursive::views::{BoxView, Dialog, LinearLayout, OnEventView, TextView};
+use cursive::views::{BoxView, Dialog, EditView, LinearLayout, OnEventView, SelectView, TextView};
 use cursive::Cursive;
 
 use crate::tui::constants::{MAIN_MENU, TABLE_PEER_STATUS, VIEW_PEER_SYNC};
 use crate::tui::table::{TableView, TableViewItem};
 use crate::tui::types::TUIStatusListener;
+use crate::tui::ui::{send_controller, ControllerMessage};
 
 #[derive(Copy, Clone, PartialEq, Eq, Hash)]
 pub enum PeerColumn {
@@ -120,8 +122,182 @@ impl TableViewItem<PeerColumn> for PeerStats {
 	}
 }
 
+/// Formats a timestamp stored in the peer database, 0 meaning never
+fn format_time(timestamp: i64) -> String {
+	if timestamp == 0 {
+		return "never".to_string();
+	}
+	Utc.timestamp(timestamp, 0)
+		.format("%Y-%m-%d %H:%M:%S")
+		.to_string()
+}
+
 pub struct TUIPeerView;
 
+impl TUIPeerView {
+	/// Actions available on a connected peer
+	fn show_actions(c: &mut Cursive, addr: String) {
+		let details_addr = addr.clone();
+		let disconnect_addr = addr.clone();
+		let ban_addr = addr.clone();
+		c.add_layer(
+			Dialog::text(format!("Peer {}", addr))
+				.title("Peer Actions")
+				.button("Details", move |c| {
+					c.pop_layer();
+					send_controller(c, ControllerMessage::GetPeerDetails(details_addr.clone()));
+				})
+				.button("Disconnect", move |c| {
+					c.pop_layer();
+					send_controller(
+						c,
+						ControllerMessage::DisconnectPeer(disconnect_addr.clone()),
+					);
+				})
+				.button("Ban", move |c| {
+					c.pop_layer();
+					TUIPeerView::confirm_ban(c, ban_addr.clone());
+				})
+				.dismiss_button("Cancel"),
+		);
+	}
+
+	fn confirm_ban(c: &mut Cursive, addr: String) {
+		c.add_layer(
+			Dialog::text(format!(
+				"Ban peer {}? It will be disconnected and refused until unbanned.",
+				addr
+			))
+			.title("Ban Peer")
+			.button("Ban", move |c| {
+				c.pop_layer();
+				send_controller(c, ControllerMessage::BanPeer(addr.clone()));
+			})
+			.dismiss_button("Cancel"),
+		);
+	}
+
+	/// Prompt for the address of a peer to connect to
+	fn show_connect_prompt(c: &mut Cursive) {
+		let submit = |c: &mut Cursive, addr: &str| {
+			c.pop_layer();
+			if !addr.trim().is_empty() {
+				send_controller(c, ControllerMessage::ConnectPeer(addr.trim().to_owned()));
+			}
+		};
+		c.add_layer(
+			Dialog::around(
+				EditView::new()
+					.on_submit(submit)
+					.with_id("peers_connect_addr")
+					.fixed_width(48),
+			)
+			.title("Connect to peer (ip:port)")
+			.button("Connect", move |c| {
+				let addr = c
+					.call_on_id("peers_connect_addr", |e: &mut EditView| e.get_content())
+					.unwrap_or_default();
+				submit(c, &addr);
+			})
+			.dismiss_button("Cancel"),
+		);
+	}
+
+	/// Stored data, live stats and ban history of a peer
+	pub fn show_details(c: &mut Cursive, details: Result<PeerDetails, String>) {
+		let details = match details {
+			Ok(details) => details,
+			Err(e) => {
+				c.add_layer(Dialog::info(format!("Peer not found: {}", e)));
+				return;
+			}
+		};
+
+		let data = &details.data;
+		let mut content = format!(
+			"Address:        {}\nUser Agent:     {}\nCapabilities:   {:?}\nState:          {:?}\nLast Connected: {}\n",
+			data.addr,
+			data.user_agent,
+			data.capabilities,
+			data.flags,
+			format_time(data.last_connected),
+		);
+		if let Some(ref stats) = details.stats {
+			content.push_str(&format!(
+				"\nDirection:      {}\nProtocol:       {}\nTotal Diff.:    {} @ {} H\nBandwidth:      {}\n",
+				stats.direction,
+				stats.version,
+				stats.total_difficulty,
+				stats.height,
+				stats.to_column(PeerColumn::UsedBandwidth),
+			));
+		}
+		content.push_str(&format!("\nBans ({}):\n", details.ban_history.len()));
+		for ban in &details.ban_history {
+			content.push_str(&format!("  {} {:?}\n", format_time(ban.time), ban.reason));
+		}
+
+		c.add_layer(
+			Dialog::around(TextView::new(content).scrollable().max_size((80, 30)))
+				.title(format!("Peer {}", data.addr))
+				.dismiss_button("Close"),
+		);
+	}
+
+	/// List of the banned peers, selecting one offers to unban it
+	pub fn show_banned(c: &mut Cursive, peers: Result<Vec<PeerData>, String>) {
+		let peers = match peers {
+			Ok(peers) => peers,
+			Err(e) => {
+				c.add_layer(Dialog::info(format!("Error: {}", e)));
+				return;
+			}
+		};
+		if peers.is_empty() {
+			c.add_layer(Dialog::info("No banned peers"));
+			return;
+		}
+		let mut select = SelectView::new();
+		for peer in peers {
+			let label = format!(
+				"{:<24} {:<20} {:?}",
+				peer.addr.to_string(),
+				format_time(peer.last_banned),
+				peer.ban_reason
+			);
+			select.add_item(label, peer.addr.to_string());
+		}
+		select.set_on_submit(|c, addr: &String| {
+			let addr = addr.clone();
+			c.add_layer(
+				Dialog::text(format!("Unban peer {}?", addr))
+					.title("Unban Peer")
+					.button("Unban", move |c| {
+						// Closes the confirmation and the list, which is
+						// outdated after the unban
+						c.pop_layer();
+						c.pop_layer();
+						send_controller(c, ControllerMessage::UnbanPeer(addr.clone()));
+					})
+					.dismiss_button("Cancel"),
+			);
+		});
+		c.add_layer(
+			Dialog::around(select.scrollable().max_size((80, 20)))
+				.title("Banned Peers")
+				.dismiss_button("Close"),
+		);
+	}
+
+	/// Outcome of a peer action
+	pub fn show_action_result(c: &mut Cursive, result: Result<String, String>) {
+		match result {
+			Ok(msg) => c.add_layer(Dialog::info(msg)),
+			Err(e) => c.add_layer(Dialog::info(format!("Error: {}", e))),
+		}
+	}
+}
+
 impl TUIStatusListener for TUIPeerView {
 	fn create() -> Box<dyn View> {
 		let table_view = TableView::<PeerStats, PeerColumn>::new()
@@ -135,9 +311,24 @@ impl TUIStatusListener for TUIPeerView {
 				c.width_percent(24)
 			})
 			.column(PeerColumn::Version, "Proto", |c| c.width_percent(6))
-			.column(PeerColumn::UserAgent, "User Agent", |c| c.width_percent(18));
+			.column(PeerColumn::UserAgent, "User Agent", |c| c.width_percent(18))
+			.on_submit(|c, _row, index| {
+				let addr = c.call_on_id(
+					TABLE_PEER_STATUS,
+					|t: &mut TableView<PeerStats, PeerColumn>| {
+						t.borrow_item(index).map(|p| p.addr.clone())
+					},
+				);
+				if let Some(Some(addr)) = addr {
+					TUIPeerView::show_actions(c, addr);
+				}
+			});
 		let peer_status_view = BoxView::with_full_screen(
 			LinearLayout::new(Orientation::Vertical)
+				.child(TextView::new(
+					"Enter: peer actions    a: connect to a peer    b: banned peers",
+				))
+				.child(TextView::new("   "))
 				.child(
 					LinearLayout::new(Orientation::Horizontal)
 						.child(TextView::new("  ").with_id("peers_total")),
@@ -155,9 +346,13 @@ impl TUIStatusListener for TUIPeerView {
 		)
 		.with_id(VIEW_PEER_SYNC);
 
-		let peer_status_view =
-			OnEventView::new(peer_status_view).on_pre_event(Key::Esc, move |c| {
+		let peer_status_view = OnEventView::new(peer_status_view)
+			.on_pre_event(Key::Esc, move |c| {
 				let _ = c.focus_id(MAIN_MENU);
+			})
+			.on_event('a', TUIPeerView::show_connect_prompt)
+			.on_event('b', |c| {
+				send_controller(c, ControllerMessage::GetBannedPeers)
 			});
 
 		Box::new(peer_status_view)
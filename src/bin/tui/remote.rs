@@ -0,0 +1,518 @@
+// Copyright 2020 The Kepler Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Remote node attached to over its v2 Owner and Foreign APIs. The stats
+//! the node doesn't expose over its APIs (stratum server, disk usage,
+//! bandwidth) are left empty.
+
+use std::collections::{HashMap, HashSet};
+use std::net::SocketAddr;
+use std::sync::mpsc;
+use std::thread;
+
+use chrono::prelude::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use crate::api::{self, BlockHeaderPrintable, BlockPrintable, Logs, OutputType, Status};
+use crate::chain::SyncStatus;
+use crate::core::consensus::{self, HeaderInfo};
+use crate::core::core::hash::Hash;
+use crate::core::global;
+use crate::core::pow::Difficulty;
+use crate::p2p::types::{Direction, PeerInfoDisplay};
+use crate::p2p::{PeerData, State};
+use crate::pool::PoolEntry;
+use crate::servers::{
+	BlockDetails, BlockKernelStats, BlockOutputStats, BlockStats, BodySyncStats, ChainStats,
+	CpuMinerStats, DiffBlock, DiffStats, PeerDetails, PeerStats, PoolEntryStats, ServerStats,
+	StratumStats, TxStats,
+};
+use crate::tui::node::NodeSource;
+use crate::tui::types::UIMessage;
+use crate::util;
+use kepler_util::logger::LogEntry;
+
+/// Number of blocks listed in the blocks view, as for a local node
+const RECENT_BLOCKS_COUNT: usize = 50;
+
+/// Node reached over its v2 APIs, caching the headers and blocks already
+/// fetched so each refresh only asks for the new ones.
+pub struct RemoteNode {
+	owner_url: String,
+	foreign_url: String,
+	api_secret: Option<String>,
+	foreign_api_secret: Option<String>,
+	logs_tx: mpsc::SyncSender<LogEntry>,
+	last_log_id: Option<u64>,
+	headers: HashMap<String, BlockHeaderPrintable>,
+	blocks: HashMap<String, BlockStats>,
+	/// Number of headers requested by the last chart refresh, kept cached
+	/// along with the ones needed by the stats
+	chart_headers: usize,
+}
+
+impl RemoteNode {
+	/// Attaches to the node API at the provided base URL, over http or
+	/// https. The node log entries are forwarded to the provided channel.
+	pub fn new(
+		api_url: &str,
+		api_secret: Option<String>,
+		foreign_api_secret: Option<String>,
+		logs_tx: mpsc::SyncSender<LogEntry>,
+	) -> RemoteNode {
+		RemoteNode {
+			owner_url: format!("{}/v2/owner", api_url),
+			foreign_url: format!("{}/v2/foreign", api_url),
+			api_secret,
+			foreign_api_secret,
+			logs_tx,
+			last_log_id: None,
+			headers: HashMap::new(),
+			blocks: HashMap::new(),
+			chart_headers: 0,
+		}
+	}
+
+	fn owner<IN, OUT>(&self, method: &str, params: &IN) -> Result<OUT, String>
+	where
+		IN: Serialize,
+		for<'de> OUT: Deserialize<'de>,
+	{
+		api::client::rpc(&self.owner_url, self.api_secret.clone(), method, params)
+			.map_err(|e| e.to_string())
+	}
+
+	fn foreign<IN, OUT>(&self, method: &str, params: &IN) -> Result<OUT, String>
+	where
+		IN: Serialize,
+		for<'de> OUT: Deserialize<'de>,
+	{
+		api::client::rpc(
+			&self.foreign_url,
+			self.foreign_api_secret.clone(),
+			method,
+			params,
+		)
+		.map_err(|e| e.to_string())
+	}
+
+	fn get_header(&mut self, hash: &str) -> Result<BlockHeaderPrintable, String> {
+		if let Some(header) = self.headers.get(hash) {
+			return Ok(header.clone());
+		}
+		let header: BlockHeaderPrintable =
+			self.foreign("get_header", &json!([null, hash, null]))?;
+		self.headers.insert(hash.to_owned(), header.clone());
+		Ok(header)
+	}
+
+	/// Headers from the provided one back, latest first, stopping at genesis
+	fn last_headers(
+		&mut self,
+		hash: &str,
+		count: usize,
+	) -> Result<Vec<BlockHeaderPrintable>, String> {
+		let mut headers = vec![self.get_header(hash)?];
+		while headers.len() < count {
+			let last = &headers[headers.len() - 1];
+			if last.height == 0 {
+				break;
+			}
+			let previous = last.previous.clone();
+			headers.push(self.get_header(&previous)?);
+		}
+		Ok(headers)
+	}
+
+	fn get_block_stats(
+		&mut self,
+		header: &BlockHeaderPrintable,
+		difficulty: u64,
+	) -> Result<BlockStats, String> {
+		if let Some(stats) = self.blocks.get(&header.hash) {
+			return Ok(stats.clone());
+		}
+		let block: BlockPrintable = self.foreign("get_block", &json!([null, header.hash, null]))?;
+		let stats = block_stats(&block, difficulty)?;
+		self.blocks.insert(header.hash.clone(), stats.clone());
+		Ok(stats)
+	}
+
+	fn get_tx_stats(&self) -> Result<TxStats, String> {
+		let entries: Vec<PoolEntry> = self.foreign("get_unconfirmed_transactions", &json!([]))?;
+		let stem_pool_size: usize = self.foreign("get_stempool_size", &json!([]))?;
+		Ok(TxStats {
+			tx_pool_size: entries.len(),
+			tx_pool_kernels: entries.iter().map(|e| e.tx.kernels().len()).sum(),
+			stem_pool_size,
+			// The stem pool transactions aren't exposed
+			stem_pool_kernels: 0,
+			entries: entries
+				.iter()
+				.map(|e| PoolEntryStats::from_entry(e, false))
+				.collect(),
+		})
+	}
+
+	fn connected_peers(&self) -> Result<Vec<PeerStats>, String> {
+		let peers: Vec<PeerInfoDisplay> = self.owner("get_connected_peers", &json!([]))?;
+		Ok(peers.iter().map(peer_stats).collect())
+	}
+}
+
+impl NodeSource for RemoteNode {
+	fn get_server_stats(&mut self) -> Result<ServerStats, String> {
+		let status: Status = self.owner("get_status", &json!([]))?;
+		let peer_stats = self.connected_peers()?;
+		// The pool is only shown when available, as for a local node
+		let tx_stats = self.get_tx_stats().ok();
+
+		let window = consensus::DIFFICULTY_ADJUST_WINDOW as usize + 1;
+		// One more header than needed, for the difficulty of the oldest one
+		let count = window.max(RECENT_BLOCKS_COUNT) + 1;
+		let headers = self.last_headers(&status.tip.last_block_pushed, count)?;
+
+		let infos: Vec<HeaderInfo> = headers
+			.iter()
+			.enumerate()
+			.map(|(i, h)| header_info(h, headers.get(i + 1)))
+			.collect::<Result<_, _>>()?;
+		let diff_stats = DiffStats::from_window(
+			global::difficulty_data_to_vector(infos.iter().take(window).cloned()),
+			status.tip.height,
+		);
+
+		// Stops early if a block body isn't available, as for a local node
+		let mut block_stats = vec![];
+		for (header, info) in headers.iter().zip(&infos).take(RECENT_BLOCKS_COUNT) {
+			match self.get_block_stats(header, info.difficulty.to_num()) {
+				Ok(stats) => block_stats.push(stats),
+				Err(_) => break,
+			}
+		}
+
+		// Only keep what the next refresh may need
+		let hashes: HashSet<String> = headers.iter().map(|h| h.hash.clone()).collect();
+		let chart_hashes: HashSet<String> = if self.chart_headers > count {
+			self.last_headers(&status.tip.last_block_pushed, self.chart_headers)?
+				.into_iter()
+				.map(|h| h.hash)
+				.collect()
+		} else {
+			HashSet::new()
+		};
+		self.headers
+			.retain(|hash, _| hashes.contains(hash) || chart_hashes.contains(hash));
+		self.blocks.retain(|hash, _| hashes.contains(hash));
+
+		let tip = &headers[0];
+		let chain_stats = ChainStats {
+			height: tip.height,
+			last_block_h: parse_hash(&status.tip.last_block_pushed)?,
+			total_difficulty: Difficulty::from_num(tip.total_difficulty),
+			latest_timestamp: parse_time(&tip.timestamp)?,
+		};
+
+		Ok(ServerStats {
+			peer_count: status.connections,
+			chain_stats,
+			header_stats: None,
+			sync_status: sync_status(&status.sync_status, status.sync_info.as_ref()),
+			stratum_stats: StratumStats::default(),
+			cpu_miner_stats: CpuMinerStats::default(),
+			peer_stats,
+			diff_stats,
+			tx_stats,
+			block_stats,
+			disk_usage_gb: "-".to_string(),
+			body_sync_stats: BodySyncStats::default(),
+		})
+	}
+
+	fn update_logs(&mut self) {
+		let logs: Logs = match self.owner("get_logs", &json!([self.last_log_id])) {
+			Ok(logs) => logs,
+			// Older nodes don't expose their logs
+			Err(_) => return,
+		};
+		self.last_log_id = Some(logs.last_id);
+		for entry in logs.entries {
+			let _ = self.logs_tx.try_send(entry);
+		}
+	}
+
+	fn get_block_details(&self, height_or_hash: &str) -> Result<BlockDetails, String> {
+		let height_or_hash = height_or_hash.trim();
+		let params = match height_or_hash.parse::<u64>() {
+			Ok(height) => json!([height, null, null]),
+			Err(_) => json!([null, height_or_hash, null]),
+		};
+		let block: BlockPrintable = self.foreign("get_block", &params)?;
+		let difficulty = if block.header.height == 0 {
+			block.header.total_difficulty
+		} else {
+			let prev: BlockHeaderPrintable =
+				self.foreign("get_header", &json!([null, block.header.previous, null]))?;
+			block.header.total_difficulty - prev.total_difficulty
+		};
+		block_details(&block, difficulty)
+	}
+
+	fn get_diff_blocks(&mut self, count: u64) -> Result<Vec<DiffBlock>, String> {
+		let status: Status = self.owner("get_status", &json!([]))?;
+		// One more header for the duration of the oldest block, and another
+		// one for its difficulty
+		self.chart_headers = count as usize + 2;
+		let headers = self.last_headers(&status.tip.last_block_pushed, self.chart_headers)?;
+		let mut infos: Vec<HeaderInfo> = headers
+			.iter()
+			.take(count as usize + 1)
+			.enumerate()
+			.map(|(i, h)| header_info(h, headers.get(i + 1)))
+			.collect::<Result<_, _>>()?;
+		infos.reverse();
+		Ok(DiffBlock::from_header_infos(&infos, status.tip.height))
+	}
+
+	fn get_peer_details(&self, addr: &str) -> Result<PeerDetails, String> {
+		let socket_addr = parse_addr(addr)?;
+		let peers: Vec<PeerData> = self.owner("get_peers", &json!([socket_addr]))?;
+		let data = peers
+			.into_iter()
+			.next()
+			.ok_or_else(|| format!("Unknown peer {}", addr))?;
+		let stats = self
+			.connected_peers()?
+			.into_iter()
+			.find(|p| p.addr == data.addr.to_string());
+		Ok(PeerDetails {
+			data,
+			stats,
+			// Not exposed by the node API
+			ban_history: vec![],
+		})
+	}
+
+	fn banned_peers(&self) -> Result<Vec<PeerData>, String> {
+		let peers: Vec<PeerData> = self.owner("get_peers", &json!([null]))?;
+		Ok(peers
+			.into_iter()
+			.filter(|p| p.flags == State::Banned)
+			.collect())
+	}
+
+	fn ban_peer(&self, addr: &str) -> Result<(), String> {
+		self.owner("ban_peer", &json!([parse_addr(addr)?]))
+	}
+
+	fn unban_peer(&self, addr: &str) -> Result<(), String> {
+		self.owner("unban_peer", &json!([parse_addr(addr)?]))
+	}
+
+	fn disconnect_peer(&self, addr: &str) -> Result<(), String> {
+		self.owner("disconnect_peer", &json!([parse_addr(addr)?]))
+	}
+
+	fn connect_peer(&self, addr: String, ui_tx: mpsc::Sender<UIMessage>) {
+		let owner_url = self.owner_url.clone();
+		let api_secret = self.api_secret.clone();
+		let _ = thread::Builder::new()
+			.name("tui_connect_peer".to_string())
+			.spawn(move || {
+				let result = parse_addr(&addr).and_then(|socket_addr| {
+					api::client::rpc::<_, ()>(
+						&owner_url,
+						api_secret,
+						"connect_peer",
+						&json!([socket_addr, false]),
+					)
+					.map(|_| format!("Connected to peer {}", addr))
+					.map_err(|e| e.to_string())
+				});
+				let _ = ui_tx.send(UIMessage::PeerActionResult(result));
+			});
+	}
+
+	/// The remote node keeps running
+	fn stop(self) {}
+}
+
+fn parse_addr(addr: &str) -> Result<SocketAddr, String> {
+	addr.trim()
+		.parse()
+		.map_err(|_| format!("Invalid peer address {}", addr))
+}
+
+fn parse_hash(hash: &str) -> Result<Hash, String> {
+	Hash::from_hex(hash).map_err(|_| format!("Invalid hash {}", hash))
+}
+
+fn parse_time(time: &str) -> Result<DateTime<Utc>, String> {
+	DateTime::parse_from_rfc3339(time)
+		.map(|t| t.with_timezone(&Utc))
+		.map_err(|_| format!("Invalid timestamp {}", time))
+}
+
+/// Difficulty data of a header, provided the previous header if any
+fn header_info(
+	header: &BlockHeaderPrintable,
+	prev: Option<&BlockHeaderPrintable>,
+) -> Result<HeaderInfo, String> {
+	let prev_difficulty = prev.map_or(0, |p| p.total_difficulty);
+	Ok(HeaderInfo::new(
+		parse_hash(&header.hash)?,
+		parse_time(&header.timestamp)?.timestamp() as u64,
+		Difficulty::from_num(header.total_difficulty - prev_difficulty),
+		header.secondary_scaling,
+		header.edge_bits == consensus::SECOND_POW_EDGE_BITS,
+	))
+}
+
+fn block_stats(block: &BlockPrintable, difficulty: u64) -> Result<BlockStats, String> {
+	Ok(BlockStats {
+		height: block.header.height,
+		hash: parse_hash(&block.header.hash)?,
+		time: parse_time(&block.header.timestamp)?,
+		difficulty,
+		num_inputs: block.inputs.len(),
+		num_outputs: block.outputs.len(),
+		num_kernels: block.kernels.len(),
+		fees: block.kernels.iter().map(|k| k.fee).sum(),
+	})
+}
+
+fn block_details(block: &BlockPrintable, difficulty: u64) -> Result<BlockDetails, String> {
+	Ok(BlockDetails {
+		stats: block_stats(block, difficulty)?,
+		inputs: block.inputs.clone(),
+		outputs: block
+			.outputs
+			.iter()
+			.map(|o| BlockOutputStats {
+				features: match o.output_type {
+					OutputType::Coinbase => "Coinbase".to_owned(),
+					OutputType::Transaction => "Plain".to_owned(),
+				},
+				commit: util::to_hex(o.commit.0.to_vec()),
+			})
+			.collect(),
+		kernels: block
+			.kernels
+			.iter()
+			.map(|k| BlockKernelStats {
+				features: k.features.clone(),
+				excess: k.excess.clone(),
+				fee: k.fee,
+				lock_height: k.lock_height,
+			})
+			.collect(),
+	})
+}
+
+fn peer_stats(peer: &PeerInfoDisplay) -> PeerStats {
+	PeerStats {
+		state: "Connected".to_string(),
+		addr: peer.addr.to_string(),
+		version: peer.version,
+		user_agent: peer.user_agent.clone(),
+		total_difficulty: peer.total_difficulty.to_num(),
+		height: peer.height,
+		direction: match peer.direction {
+			Direction::Inbound => "Inbound",
+			Direction::Outbound => "Outbound",
+		}
+		.to_string(),
+		// Not exposed by the node API
+		last_seen: Utc::now(),
+		sent_bytes_per_sec: 0,
+		received_bytes_per_sec: 0,
+		capabilities: peer.capabilities,
+	}
+}
+
+/// Sync status from its API representation, the download progress being
+/// the only information lost along the way
+fn sync_status(status: &str, info: Option<&Value>) -> SyncStatus {
+	let field = |name: &str| {
+		info.and_then(|i| i.get(name))
+			.and_then(|v| v.as_u64())
+			.unwrap_or(0)
+	};
+	match status {
+		"no_sync" => SyncStatus::NoSync,
+		"awaiting_peers" => SyncStatus::AwaitingPeers(false),
+		"header_sync" => SyncStatus::HeaderSync {
+			current_height: field("current_height"),
+			highest_height: field("highest_height"),
+		},
+		"txhashset_download" => {
+			let now = Utc::now();
+			SyncStatus::TxHashsetDownload {
+				start_time: now,
+				prev_update_time: now,
+				update_time: now,
+				prev_downloaded_size: field("downloaded_size"),
+				downloaded_size: field("downloaded_size"),
+				total_size: field("total_size"),
+			}
+		}
+		"txhashset_pibd" => SyncStatus::TxHashsetPibd {
+			recv_segments: field("recv_segments"),
+			total_segments: field("total_segments"),
+		},
+		"txhashset_rangeproofs_validation" => SyncStatus::TxHashsetRangeProofsValidation {
+			rproofs: field("rproofs"),
+			rproofs_total: field("rproofs_total"),
+		},
+		"txhashset_kernels_validation" => SyncStatus::TxHashsetKernelsValidation {
+			kernels: field("kernels"),
+			kernels_total: field("kernels_total"),
+		},
+		"body_sync" => SyncStatus::BodySync {
+			current_height: field("current_height"),
+			highest_height: field("highest_height"),
+		},
+		"shutdown" => SyncStatus::Shutdown,
+		_ => SyncStatus::Initial,
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	#[test]
+	fn test_sync_status() {
+		assert_eq!(sync_status("no_sync", None), SyncStatus::NoSync);
+		assert_eq!(
+			sync_status(
+				"body_sync",
+				Some(&json!({"current_height": 10, "highest_height": 20}))
+			),
+			SyncStatus::BodySync {
+				current_height: 10,
+				highest_height: 20
+			}
+		);
+		assert_eq!(
+			sync_status("header_sync", None),
+			SyncStatus::HeaderSync {
+				current_height: 0,
+				highest_height: 0
+			}
+		);
+		assert_eq!(sync_status("syncing", None), SyncStatus::Initial);
+	}
+}
@@ -25,7 +25,7 @@ use crate::tui::constants::VIEW_BASIC_STATUS;
 use crate::tui::types::TUIStatusListener;
 
 use crate::chain::SyncStatus;
-use crate::servers::ServerStats;
+use crate::servers::{BodySyncStats, ServerStats};
 
 const NANO_TO_MILLIS: f64 = 1.0 / 1_000_000.0;
 
@@ -81,6 +81,20 @@ impl TUIStatusView {
 					)
 				}
 			}
+			SyncStatus::TxHashsetPibd {
+				recv_segments,
+				total_segments,
+			} => {
+				let percent = if total_segments > 0 {
+					recv_segments * 100 / total_segments
+				} else {
+					0
+				};
+				format!(
+					"Sync step 2/7: Downloading chain state segments: {}/{} ({}%)",
+					recv_segments, total_segments, percent
+				)
+			}
 			SyncStatus::TxHashsetSetup => {
 				"Sync step 3/7: Preparing chain state for validation".to_string()
 			}
@@ -132,6 +146,37 @@ impl TUIStatusView {
 			SyncStatus::Shutdown => "Shutting down, closing connections".to_string(),
 		}
 	}
+
+	/// Block downloads per peer during body sync
+	fn body_sync_downloads(stats: &BodySyncStats) -> String {
+		if stats.peers.is_empty() {
+			return String::new();
+		}
+		let mut lines = vec![
+			"--------------------------------------------------------".to_string(),
+			format!(
+				"Block Downloads: {} in flight, window {} to {}",
+				stats.in_flight, stats.window_start, stats.window_end
+			),
+			format!(
+				"{:<24} {:>9} {:>9} {:>10} {:>9} {:>9}",
+				"Peer", "Blocks/s", "In Flight", "Requested", "Received", "Timeouts"
+			),
+		];
+		for peer in &stats.peers {
+			lines.push(format!(
+				"{:<24} {:>9} {:>9} {:>10} {:>9} {:>9}",
+				peer.addr,
+				peer.blocks_per_sec
+					.map_or("-".to_string(), |rate| format!("{:.1}", rate)),
+				peer.in_flight,
+				peer.requested,
+				peer.received,
+				peer.reassigned,
+			));
+		}
+		lines.join("\n")
+	}
 }
 
 impl TUIStatusListener for TUIStatusView {
@@ -241,6 +286,10 @@ impl TUIStatusListener for TUIStatusView {
 				.child(
 					LinearLayout::new(Orientation::Horizontal)
 						.child(TextView::new("  ").with_id("basic_network_info")),
+				)
+				.child(
+					LinearLayout::new(Orientation::Horizontal)
+						.child(TextView::new("").with_id("basic_body_sync")),
 				), //.child(logo_view)
 		);
 		Box::new(basic_status_view.with_id(VIEW_BASIC_STATUS))
@@ -258,6 +307,15 @@ impl TUIStatusListener for TUIStatusView {
 		c.call_on_id("disk_usage", |t: &mut TextView| {
 			t.set_content(stats.disk_usage_gb.clone());
 		});
+		let body_sync = match stats.sync_status {
+			SyncStatus::BodySync { .. } => {
+				TUIStatusView::body_sync_downloads(&stats.body_sync_stats)
+			}
+			_ => String::new(),
+		};
+		c.call_on_id("basic_body_sync", |t: &mut TextView| {
+			t.set_content(body_sync);
+		});
 		c.call_on_id("tip_hash", |t: &mut TextView| {
 			t.set_content(stats.chain_stats.last_block_h.to_string() + "...");
 		});
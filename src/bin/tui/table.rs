@@ -998,6 +998,7 @@ mod test {
 	use crate::tui::table::TableView;
 	use chrono::Utc;
 	use kepler_core::ser::ProtocolVersion;
+	use kepler_p2p::Capabilities;
 	use kepler_servers::PeerStats;
 	use std::cmp::Ordering;
 
@@ -1059,6 +1060,7 @@ mod test {
 				last_seen: Utc::now(),
 				sent_bytes_per_sec: 0,
 				received_bytes_per_sec: 0,
+				capabilities: Capabilities::FULL_NODE,
 			}
 		}
 	}
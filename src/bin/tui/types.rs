@@ -14,7 +14,8 @@
 
 //! Types specific to the UI module
 
-use crate::servers::ServerStats;
+use crate::p2p::PeerData;
+use crate::servers::{BlockDetails, DiffBlock, PeerDetails, ServerStats};
 use cursive::view::View;
 use cursive::Cursive;
 
@@ -22,6 +23,15 @@ use cursive::Cursive;
 /// the main process
 pub enum UIMessage {
 	UpdateStatus(ServerStats),
+	BlockDetails(Result<BlockDetails, String>),
+	PeerDetails(Result<PeerDetails, String>),
+	BannedPeers(Result<Vec<PeerData>, String>),
+	/// Outcome of a ban, unban, disconnect or connect request
+	PeerActionResult(Result<String, String>),
+	/// Blocks charted in the mining view, oldest first
+	DiffChart(Result<Vec<DiffBlock>, String>),
+	/// The node was stopped, quits the UI
+	Stop,
 }
 
 /// Trait for a UI element that receives status update messages
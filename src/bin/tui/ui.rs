@@ -15,7 +15,6 @@
 //! Basic TUI to better output the overall system status and status
 //! of various subsystems
 
-use chrono::prelude::Utc;
 use cursive::direction::Orientation;
 use cursive::theme::BaseColor::{Black, Blue, Cyan, White};
 use cursive::theme::Color::Dark;
@@ -28,15 +27,24 @@ use cursive::traits::Identifiable;
 use cursive::utils::markup::StyledString;
 use cursive::views::{CircularFocus, Dialog, LinearLayout, Panel, StackView, TextView, ViewBox};
 use cursive::Cursive;
-use std::sync::mpsc;
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::thread;
+use std::time::{Duration, Instant};
 
 use crate::built_info;
+use crate::core::consensus::DIFFICULTY_ADJUST_WINDOW;
 use crate::servers::Server;
 use crate::tui::constants::ROOT_STACK;
+use crate::tui::node::NodeSource;
 use crate::tui::types::{TUIStatusListener, UIMessage};
-use crate::tui::{logs, menu, mining, peers, status, version};
+use crate::tui::{blocks, logs, mempool, menu, mining, peers, status, version};
 use kepler_util::logger::LogEntry;
 
+/// Bounds of the number of blocks charted, the upper one keeping the
+/// charts quick to load from a remote node
+const MIN_CHART_WINDOW: u64 = 10;
+const MAX_CHART_WINDOW: u64 = 2880;
+
 pub struct UI {
 	cursive: Cursive,
 	ui_rx: mpsc::Receiver<UIMessage>,
@@ -76,6 +84,8 @@ impl UI {
 		let status_view = status::TUIStatusView::create();
 		let mining_view = mining::TUIMiningView::create();
 		let peer_view = peers::TUIPeerView::create();
+		let blocks_view = blocks::TUIBlocksView::create();
+		let mempool_view = mempool::TUIMempoolView::create();
 		let logs_view = logs::TUILogsView::create();
 		let version_view = version::TUIVersionView::create();
 
@@ -84,6 +94,8 @@ impl UI {
 		let root_stack = StackView::new()
 			.layer(version_view)
 			.layer(mining_view)
+			.layer(mempool_view)
+			.layer(blocks_view)
 			.layer(peer_view)
 			.layer(logs_view)
 			.layer(status_view)
@@ -114,6 +126,11 @@ impl UI {
 		kepler_ui.cursive.set_theme(theme);
 		kepler_ui.cursive.add_fullscreen_layer(main_layer);
 
+		// Lets the views send requests to the controller
+		kepler_ui
+			.cursive
+			.set_user_data(kepler_ui.controller_tx.clone());
+
 		// Configure a callback (shutdown, for the first test)
 		let controller_tx_clone = kepler_ui.controller_tx.clone();
 		kepler_ui.cursive.add_global_callback('q', move |c| {
@@ -147,8 +164,26 @@ impl UI {
 					status::TUIStatusView::update(&mut self.cursive, &update);
 					mining::TUIMiningView::update(&mut self.cursive, &update);
 					peers::TUIPeerView::update(&mut self.cursive, &update);
+					blocks::TUIBlocksView::update(&mut self.cursive, &update);
+					mempool::TUIMempoolView::update(&mut self.cursive, &update);
 					version::TUIVersionView::update(&mut self.cursive, &update);
 				}
+				UIMessage::BlockDetails(details) => {
+					blocks::TUIBlocksView::show_details(&mut self.cursive, details);
+				}
+				UIMessage::PeerDetails(details) => {
+					peers::TUIPeerView::show_details(&mut self.cursive, details);
+				}
+				UIMessage::BannedPeers(banned) => {
+					peers::TUIPeerView::show_banned(&mut self.cursive, banned);
+				}
+				UIMessage::PeerActionResult(result) => {
+					peers::TUIPeerView::show_action_result(&mut self.cursive, result);
+				}
+				UIMessage::DiffChart(blocks) => {
+					mining::TUIMiningView::update_charts(&mut self.cursive, blocks);
+				}
+				UIMessage::Stop => self.stop(),
 			}
 		}
 
@@ -170,6 +205,26 @@ pub struct Controller {
 
 pub enum ControllerMessage {
 	Shutdown,
+	/// Look up a block by height or hash
+	GetBlock(String),
+	GetPeerDetails(String),
+	GetBannedPeers,
+	BanPeer(String),
+	UnbanPeer(String),
+	DisconnectPeer(String),
+	/// Connect to a new peer, in the background as it can take a while
+	ConnectPeer(String),
+	/// Chart the last N blocks in the mining view
+	SetChartWindow(u64),
+	/// Chart twice as many blocks, or half as many if false
+	ResizeChartWindow {
+		larger: bool,
+	},
+}
+
+/// Sends a request to the controller from a view callback
+pub fn send_controller(c: &mut Cursive, message: ControllerMessage) {
+	let _ = c.with_user_data(|tx: &mut mpsc::Sender<ControllerMessage>| tx.send(message));
 }
 
 impl Controller {
@@ -182,29 +237,114 @@ impl Controller {
 		})
 	}
 
-	/// Run the controller
-	pub fn run(&mut self, server: Server) {
-		let stat_update_interval = 1;
-		let mut next_stat_update = Utc::now().timestamp() + stat_update_interval;
-		while self.ui.step() {
-			while let Some(message) = self.rx.try_iter().next() {
-				match message {
-					ControllerMessage::Shutdown => {
-						warn!("Shutdown in progress, please wait");
-						self.ui.stop();
-						server.stop();
-						return;
-					}
-				}
+	/// Run the controller. The node is queried from its own thread, so the
+	/// UI keeps responding while a slow node (e.g. a remote one loading a
+	/// large chart) answers.
+	pub fn run<N: NodeSource + Send + 'static>(self, node: N) {
+		let Controller { rx, mut ui } = self;
+		let ui_tx = ui.ui_tx.clone();
+		let node_thread = thread::Builder::new()
+			.name("tui_node".to_string())
+			.spawn(move || run_node(node, rx, ui_tx))
+			.expect("Unable to start the TUI node thread");
+		while ui.step() {}
+		let _ = node_thread.join();
+	}
+}
+
+/// Answers the controller requests and refreshes the stats every second,
+/// until the user quits
+fn run_node<N: NodeSource>(
+	mut node: N,
+	rx: mpsc::Receiver<ControllerMessage>,
+	ui_tx: mpsc::Sender<UIMessage>,
+) {
+	let stat_update_interval = Duration::from_secs(1);
+	let mut next_stat_update = Instant::now() + stat_update_interval;
+	let mut stats_error: Option<String> = None;
+	// Charts are refreshed when the tip or the number of blocks changes
+	let mut chart_window = DIFFICULTY_ADJUST_WINDOW;
+	let mut chart_tip: Option<u64> = None;
+	loop {
+		let timeout = next_stat_update.saturating_duration_since(Instant::now());
+		match rx.recv_timeout(timeout) {
+			Ok(ControllerMessage::Shutdown) | Err(RecvTimeoutError::Disconnected) => break,
+			Ok(ControllerMessage::GetBlock(query)) => {
+				let details = node.get_block_details(&query);
+				let _ = ui_tx.send(UIMessage::BlockDetails(details));
+			}
+			Ok(ControllerMessage::GetPeerDetails(addr)) => {
+				let details = node.get_peer_details(&addr);
+				let _ = ui_tx.send(UIMessage::PeerDetails(details));
+			}
+			Ok(ControllerMessage::GetBannedPeers) => {
+				let _ = ui_tx.send(UIMessage::BannedPeers(node.banned_peers()));
+			}
+			Ok(ControllerMessage::BanPeer(addr)) => {
+				let result = node
+					.ban_peer(&addr)
+					.map(|_| format!("Banned peer {}", addr));
+				let _ = ui_tx.send(UIMessage::PeerActionResult(result));
+			}
+			Ok(ControllerMessage::UnbanPeer(addr)) => {
+				let result = node
+					.unban_peer(&addr)
+					.map(|_| format!("Unbanned peer {}", addr));
+				let _ = ui_tx.send(UIMessage::PeerActionResult(result));
+			}
+			Ok(ControllerMessage::DisconnectPeer(addr)) => {
+				let result = node
+					.disconnect_peer(&addr)
+					.map(|_| format!("Disconnected peer {}", addr));
+				let _ = ui_tx.send(UIMessage::PeerActionResult(result));
 			}
+			Ok(ControllerMessage::ConnectPeer(addr)) => {
+				node.connect_peer(addr, ui_tx.clone());
+			}
+			Ok(ControllerMessage::SetChartWindow(window)) => {
+				chart_window = window.max(MIN_CHART_WINDOW).min(MAX_CHART_WINDOW);
+				chart_tip = None;
+			}
+			Ok(ControllerMessage::ResizeChartWindow { larger }) => {
+				let window = if larger {
+					chart_window * 2
+				} else {
+					chart_window / 2
+				};
+				chart_window = window.max(MIN_CHART_WINDOW).min(MAX_CHART_WINDOW);
+				chart_tip = None;
+			}
+			Err(RecvTimeoutError::Timeout) => {}
+		}
 
-			if Utc::now().timestamp() > next_stat_update {
-				next_stat_update = Utc::now().timestamp() + stat_update_interval;
-				if let Ok(stats) = server.get_server_stats() {
-					self.ui.ui_tx.send(UIMessage::UpdateStatus(stats)).unwrap();
+		if Instant::now() >= next_stat_update {
+			node.update_logs();
+			match node.get_server_stats() {
+				Ok(stats) => {
+					stats_error = None;
+					let tip = stats.chain_stats.height;
+					let _ = ui_tx.send(UIMessage::UpdateStatus(stats));
+					if chart_tip != Some(tip) {
+						chart_tip = Some(tip);
+						let blocks = node.get_diff_blocks(chart_window);
+						let _ = ui_tx.send(UIMessage::DiffChart(blocks));
+					}
+				}
+				// Only logged once per distinct error, the remote node
+				// may be unreachable for a while
+				Err(e) => {
+					if stats_error.as_ref() != Some(&e) {
+						warn!("Unable to update the node stats: {}", e);
+						stats_error = Some(e);
+					}
 				}
 			}
+			// Counted from the end of the refresh, a slow node being
+			// queried less often
+			next_stat_update = Instant::now() + stat_update_interval;
 		}
-		server.stop();
 	}
+	warn!("Shutdown in progress, please wait");
+	node.stop();
+	let _ = ui_tx.send(UIMessage::Stop);
 }
@@ -292,10 +292,41 @@ impl<T: PMMRable> PMMRBackend<T> {
 		self.prune_list.is_pruned_root(pos)
 	}
 
-	fn is_compacted(&self, pos: u64) -> bool {
+	/// Whether the position has been compacted away, only the root of a
+	/// pruned subtree being kept.
+	pub fn is_compacted(&self, pos: u64) -> bool {
 		self.is_pruned(pos) && !self.is_pruned_root(pos)
 	}
 
+	/// Prunes the provided leaves of an empty backend, as they would be once
+	/// removed and compacted, before filling it position by position with
+	/// `append_hash` and `append_leaf_data`. Used to rebuild a PMMR from
+	/// its segments.
+	pub fn init_prune_list(&mut self, leaves: impl Iterator<Item = u64>) -> io::Result<()> {
+		assert!(self.prunable, "Trying to prune a non-prunable PMMR");
+		assert_eq!(self.unpruned_size(), 0, "Trying to prune a non-empty PMMR");
+		for pos in leaves {
+			self.prune_list.add(pos);
+		}
+		self.prune_list.flush()
+	}
+
+	/// Appends the hash of the next position not compacted away.
+	pub fn append_hash(&mut self, hash: &Hash) -> io::Result<()> {
+		self.hash_file.append(hash)?;
+		Ok(())
+	}
+
+	/// Appends the data of the next leaf not compacted away, adding it to the
+	/// leaf_set unless it has been removed.
+	pub fn append_leaf_data(&mut self, pos: u64, data: &T::E, removed: bool) -> io::Result<()> {
+		self.data_file.append(data)?;
+		if self.prunable && !removed {
+			self.leaf_set.add(pos);
+		}
+		Ok(())
+	}
+
 	/// Number of hashes in the PMMR stored by this backend. Only produces the
 	/// fully sync'd size.
 	pub fn unpruned_size(&self) -> u64 {
@@ -33,6 +33,7 @@ use log4rs::encode::pattern::PatternEncoder;
 use log4rs::encode::writer::simple::SimpleWriter;
 use log4rs::encode::Encode;
 use log4rs::filter::{threshold::ThresholdFilter, Filter, Response};
+use std::collections::VecDeque;
 use std::error::Error;
 use std::sync::mpsc;
 use std::sync::mpsc::SyncSender;
@@ -45,6 +46,8 @@ lazy_static! {
 	static ref TUI_RUNNING: Mutex<bool> = Mutex::new(false);
 	/// Static Logging configuration, should only be set once, before first logging call
 	static ref LOGGING_CONFIG: Mutex<LoggingConfig> = Mutex::new(LoggingConfig::default());
+	/// Most recent log entries, with their sequence number, for remote consumers
+	static ref LOG_BUFFER: Mutex<LogBuffer> = Mutex::new(LogBuffer::default());
 }
 
 const LOGGING_PATTERN: &str = "{d(%Y%m%d %H:%M:%S%.3f)} {h({l})} {M} - {m}{n}";
@@ -52,13 +55,19 @@ const LOGGING_PATTERN: &str = "{d(%Y%m%d %H:%M:%S%.3f)} {h({l})} {M} - {m}{n}";
 /// 32 log files to rotate over by default
 const DEFAULT_ROTATE_LOG_FILES: u32 = 32 as u32;
 
+/// Number of log entries kept in memory for remote consumers
+const LOG_BUFFER_SIZE: usize = 1000;
+
 /// Log Entry
-#[derive(Clone, Serialize, Debug)]
+#[derive(Clone, Serialize, Deserialize, Debug)]
 pub struct LogEntry {
 	/// The log message
 	pub log: String,
 	/// The log levelO
 	pub level: Level,
+	/// Module the entry was logged from
+	#[serde(default)]
+	pub module: String,
 }
 
 /// Logging config
@@ -133,6 +142,7 @@ impl Append for ChannelAppender {
 		let _ = self.output.lock().try_send(LogEntry {
 			log,
 			level: record.level(),
+			module: record.module_path().unwrap_or_default().to_owned(),
 		});
 
 		Ok(())
@@ -141,6 +151,70 @@ impl Append for ChannelAppender {
 	fn flush(&self) {}
 }
 
+/// Entries kept in memory, numbered from 1
+#[derive(Default)]
+struct LogBuffer {
+	last_id: u64,
+	entries: VecDeque<LogEntry>,
+}
+
+impl LogBuffer {
+	fn push(&mut self, entry: LogEntry) {
+		if self.entries.len() == LOG_BUFFER_SIZE {
+			self.entries.pop_front();
+		}
+		self.entries.push_back(entry);
+		self.last_id += 1;
+	}
+
+	/// Entries after the provided sequence number. All of them are returned
+	/// if that entry isn't known, as the consumer is either too far behind or
+	/// saw a previous run of the node.
+	fn after(&self, after: Option<u64>) -> Vec<LogEntry> {
+		let first_id = self.last_id + 1 - self.entries.len() as u64;
+		let skip = match after {
+			Some(after) if after >= first_id && after <= self.last_id => {
+				(after + 1 - first_id) as usize
+			}
+			_ => 0,
+		};
+		self.entries.iter().skip(skip).cloned().collect()
+	}
+}
+
+/// Appender keeping the most recent entries in the log buffer
+#[derive(Debug)]
+struct BufferAppender {
+	encoder: Box<dyn Encode>,
+}
+
+impl Append for BufferAppender {
+	fn append(&self, record: &Record) -> Result<(), Box<dyn Error + Sync + Send>> {
+		let mut writer = SimpleWriter(Vec::new());
+		self.encoder.encode(&mut writer, record)?;
+
+		let log = String::from_utf8_lossy(writer.0.as_slice()).to_string();
+
+		LOG_BUFFER.lock().push(LogEntry {
+			log,
+			level: record.level(),
+			module: record.module_path().unwrap_or_default().to_owned(),
+		});
+
+		Ok(())
+	}
+
+	fn flush(&self) {}
+}
+
+/// Log entries recorded after the one with the provided sequence number (or
+/// all the entries still in memory), along with the sequence number of the
+/// last entry. Only entries at or above the stdout level are kept.
+pub fn recent_logs(after: Option<u64>) -> (u64, Vec<LogEntry>) {
+	let buffer = LOG_BUFFER.lock();
+	(buffer.last_id, buffer.after(after))
+}
+
 /// Initialize the logger with the given configuration
 pub fn init_logger(config: Option<LoggingConfig>, logs_tx: Option<mpsc::SyncSender<LogEntry>>) {
 	if let Some(c) = config {
@@ -196,6 +270,19 @@ pub fn init_logger(config: Option<LoggingConfig>, logs_tx: Option<mpsc::SyncSend
 			root = root.appender("stdout");
 		}
 
+		appenders.push(
+			Appender::builder()
+				.filter(Box::new(ThresholdFilter::new(level_stdout)))
+				.filter(Box::new(KeplerFilter))
+				.build(
+					"buffer",
+					Box::new(BufferAppender {
+						encoder: Box::new(PatternEncoder::new(&LOGGING_PATTERN)),
+					}),
+				),
+		);
+		root = root.appender("buffer");
+
 		if c.log_to_file {
 			// If maximum log size is specified, use rolling file appender
 			// or use basic one otherwise
@@ -351,3 +438,50 @@ fn send_panic_to_log() {
 		}
 	}));
 }
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	fn entry(log: &str) -> LogEntry {
+		LogEntry {
+			log: log.to_owned(),
+			level: Level::Info,
+			module: "kepler_util::logger".to_owned(),
+		}
+	}
+
+	fn logs(entries: Vec<LogEntry>) -> Vec<String> {
+		entries.into_iter().map(|e| e.log).collect()
+	}
+
+	#[test]
+	fn test_log_buffer() {
+		let mut buffer = LogBuffer::default();
+		assert!(buffer.after(None).is_empty());
+
+		for log in &["a", "b", "c"] {
+			buffer.push(entry(log));
+		}
+		assert_eq!(logs(buffer.after(None)), vec!["a", "b", "c"]);
+		assert_eq!(logs(buffer.after(Some(1))), vec!["b", "c"]);
+		assert!(buffer.after(Some(3)).is_empty());
+		// Unknown entries, from a previous run of the node
+		assert_eq!(logs(buffer.after(Some(10))), vec!["a", "b", "c"]);
+
+		for i in 0..LOG_BUFFER_SIZE {
+			buffer.push(entry(&i.to_string()));
+		}
+		assert_eq!(buffer.entries.len(), LOG_BUFFER_SIZE);
+		let after = buffer.after(Some(LOG_BUFFER_SIZE as u64 + 1));
+		assert_eq!(
+			logs(after),
+			vec![
+				(LOG_BUFFER_SIZE - 2).to_string(),
+				(LOG_BUFFER_SIZE - 1).to_string()
+			]
+		);
+		// Entries dropped from the buffer
+		assert_eq!(buffer.after(Some(2)).len(), LOG_BUFFER_SIZE);
+	}
+}
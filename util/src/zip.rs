@@ -22,7 +22,8 @@ use self::zip_rs::write::FileOptions;
 use zip as zip_rs;
 
 /// Create a zip archive from source dir and list of relative file paths.
-/// Permissions are set to 644 by default.
+/// Permissions are set to 644 by default and modification times are left
+/// to the zip epoch, so the same files always give the same archive.
 pub fn create_zip(dst_file: &File, src_dir: &Path, files: Vec<PathBuf>) -> io::Result<()> {
 	let mut writer = {
 		let zip = zip_rs::ZipWriter::new(dst_file);
@@ -31,6 +32,7 @@ pub fn create_zip(dst_file: &File, src_dir: &Path, files: Vec<PathBuf>) -> io::R
 
 	let options = FileOptions::default()
 		.compression_method(zip_rs::CompressionMethod::Stored)
+		.last_modified_time(zip_rs::DateTime::default())
 		.unix_permissions(0o644);
 
 	for x in &files {
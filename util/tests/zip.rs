@@ -18,6 +18,8 @@ use crate::util::zip;
 use std::fs::{self, File};
 use std::io::Write;
 use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::Duration;
 
 #[test]
 fn zip_unzip() {
@@ -93,3 +95,23 @@ fn zip_unzip() {
 		);
 	}
 }
+
+#[test]
+fn zip_deterministic() {
+	let root = Path::new("target/tmp");
+	let path = root.join("to_zip_twice");
+	fs::create_dir_all(&path).unwrap();
+	let mut file = File::create(path.join("foo.txt")).unwrap();
+	file.write_all(b"Hello, world!").unwrap();
+
+	// Archives of the same files are identical, whenever they're created
+	let mut zips = vec![];
+	for name in &["first.zip", "second.zip"] {
+		let zip_path = root.join(name);
+		let zip_file = File::create(&zip_path).unwrap();
+		zip::create_zip(&zip_file, &path, vec![PathBuf::from("foo.txt")]).unwrap();
+		zips.push(fs::read(zip_path).unwrap());
+		thread::sleep(Duration::from_secs(2));
+	}
+	assert_eq!(zips[0], zips[1]);
+}